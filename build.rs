@@ -0,0 +1,48 @@
+//! Embed build metadata for `GET /version` as compile-time env vars, and
+//! rebuild when the embedded migrations change.
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn main() {
+    // Container builds have no .git directory; they pass GIT_SHA instead
+    let git_sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(|| command_output("git", &["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let build_epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version =
+        command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=SYNAPSE_GIT_SHA={}", git_sha);
+    println!("cargo:rustc-env=SYNAPSE_BUILD_EPOCH={}", build_epoch);
+    println!("cargo:rustc-env=SYNAPSE_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    // `sqlx::migrate!` embeds this directory but cannot watch it itself
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,3 @@
+-- Allow longer status values such as 'reconciliation_failed'
+ALTER TABLE transactions
+ALTER COLUMN status TYPE VARCHAR(32);
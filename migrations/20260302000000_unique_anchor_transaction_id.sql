@@ -0,0 +1,19 @@
+-- Reject a second transaction for the same anchor_transaction_id.
+--
+-- A unique index on a partitioned table must include the partition key
+-- (created_at), which would only make anchor ids unique per instant. The ids
+-- are claimed in this table instead, in the same DB transaction as the
+-- insert, so two concurrent callbacks cannot both succeed.
+CREATE TABLE IF NOT EXISTS transaction_anchor_ids (
+    anchor_transaction_id VARCHAR(255) PRIMARY KEY,
+    transaction_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Existing duplicates are left alone; the oldest row claims the id
+INSERT INTO transaction_anchor_ids (anchor_transaction_id, transaction_id, created_at)
+SELECT DISTINCT ON (anchor_transaction_id) anchor_transaction_id, id, created_at
+FROM transactions
+WHERE anchor_transaction_id IS NOT NULL
+ORDER BY anchor_transaction_id, created_at
+ON CONFLICT (anchor_transaction_id) DO NOTHING;
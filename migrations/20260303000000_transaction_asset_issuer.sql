@@ -0,0 +1,6 @@
+-- Stellar assets are identified by code and issuer; NULL for transactions
+-- recorded before the issuer was captured
+ALTER TABLE transactions
+ADD COLUMN IF NOT EXISTS asset_issuer VARCHAR(56);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_asset_issuer ON transactions(asset_code, asset_issuer);
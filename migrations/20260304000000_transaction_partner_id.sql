@@ -0,0 +1,7 @@
+-- Partner (tenant) that recorded the transaction; NULL for single-tenant
+-- deployments and rows recorded before partners were tracked
+ALTER TABLE transactions
+ADD COLUMN IF NOT EXISTS partner_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_partner_created
+ON transactions(partner_id, created_at DESC, id DESC);
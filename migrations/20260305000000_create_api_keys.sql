@@ -0,0 +1,15 @@
+-- API keys for partners calling the API. Only a salted HMAC-SHA256 of each
+-- key is stored; `key_prefix` narrows the lookup before the hash is checked.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    partner_id UUID NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL,
+    key_salt VARCHAR(64) NOT NULL,
+    key_hash VARCHAR(64) NOT NULL,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_prefix ON api_keys(key_prefix);
+CREATE INDEX IF NOT EXISTS idx_api_keys_partner ON api_keys(partner_id);
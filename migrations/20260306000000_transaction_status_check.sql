@@ -0,0 +1,16 @@
+-- Restrict status to the canonical values in services::transaction::ALL_STATUSES.
+-- Adding a status means replacing this constraint in a new migration.
+ALTER TABLE transactions DROP CONSTRAINT IF EXISTS transactions_status_check;
+
+ALTER TABLE transactions
+ADD CONSTRAINT transactions_status_check CHECK (
+    status IN (
+        'pending',
+        'processing',
+        'completed',
+        'failed',
+        'refunded',
+        'reconciliation_failed',
+        'dlq'
+    )
+);
@@ -0,0 +1,4 @@
+-- Compliance review lists transactions by metadata.compliance_tag, newest first
+CREATE INDEX IF NOT EXISTS idx_transactions_compliance_tag
+ON transactions((metadata->>'compliance_tag'), created_at DESC, id DESC)
+WHERE metadata ? 'compliance_tag';
@@ -0,0 +1,20 @@
+-- Structured failure cause from services::transaction_processor::DlqErrorCode;
+-- error_reason keeps the full message. Rows moved before this column existed
+-- are 'unknown'. Adding a code means replacing this constraint in a new migration.
+ALTER TABLE transaction_dlq
+ADD COLUMN IF NOT EXISTS error_code TEXT NOT NULL DEFAULT 'unknown';
+
+ALTER TABLE transaction_dlq DROP CONSTRAINT IF EXISTS transaction_dlq_error_code_check;
+
+ALTER TABLE transaction_dlq
+ADD CONSTRAINT transaction_dlq_error_code_check CHECK (
+    error_code IN (
+        'retries_exhausted',
+        'transaction_not_found',
+        'invalid_data',
+        'permanent_failure',
+        'unknown'
+    )
+);
+
+CREATE INDEX IF NOT EXISTS idx_transaction_dlq_error_code ON transaction_dlq(error_code);
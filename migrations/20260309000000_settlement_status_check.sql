@@ -0,0 +1,13 @@
+-- Restrict status to the values of services::settlement::SettlementStatus.
+-- Adding a status means replacing this constraint in a new migration.
+ALTER TABLE settlements DROP CONSTRAINT IF EXISTS settlements_status_check;
+
+ALTER TABLE settlements
+ADD CONSTRAINT settlements_status_check CHECK (
+    status IN (
+        'pending',
+        'completed',
+        'paid',
+        'voided'
+    )
+);
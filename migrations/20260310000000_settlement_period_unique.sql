@@ -0,0 +1,18 @@
+-- One live settlement per asset and period, so re-running settlement for a
+-- period that is already settled cannot create a duplicate. Voided
+-- settlements are left out, so voiding one frees its period to be settled
+-- again.
+-- queries::insert_settlement reports a violation as SettlementAlreadyExists.
+--
+-- Creating the index fails if non-voided duplicates already exist. Find them
+-- with
+--   SELECT asset_code, period_start, period_end, COUNT(*) FROM settlements
+--   WHERE status <> 'voided'
+--   GROUP BY 1, 2, 3 HAVING COUNT(*) > 1;
+-- and void the extras before running this migration.
+ALTER TABLE settlements DROP CONSTRAINT IF EXISTS settlements_asset_period_key;
+DROP INDEX IF EXISTS settlements_asset_period_key;
+
+CREATE UNIQUE INDEX settlements_asset_period_key
+ON settlements (asset_code, period_start, period_end)
+WHERE status <> 'voided';
@@ -0,0 +1,13 @@
+-- Internal notes support staff attach to a transaction, kept apart from
+-- the transaction's metadata
+CREATE TABLE IF NOT EXISTS transaction_notes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- No foreign key: transactions is partitioned on created_at
+    transaction_id UUID NOT NULL,
+    author VARCHAR(255) NOT NULL,
+    note TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_transaction_notes_transaction_id
+    ON transaction_notes(transaction_id, created_at);
@@ -0,0 +1,4 @@
+-- Set once the PII retention job has redacted a transaction's account, memo
+-- and metadata, so each row is anonymized and audited only once
+ALTER TABLE transactions
+ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
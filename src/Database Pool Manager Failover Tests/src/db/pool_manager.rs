@@ -4,6 +4,10 @@ use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::RwLock;
 
+/// How long a read may run on one replica before the next is tried, unless
+/// `REPLICA_QUERY_TIMEOUT_MS` says otherwise
+pub const DEFAULT_REPLICA_QUERY_TIMEOUT: Duration = Duration::from_millis(2000);
+
 #[derive(Error, Debug)]
 pub enum PoolError {
     #[error("Database error: {0}")]
@@ -24,6 +28,7 @@ pub struct PoolManager {
     primary: Arc<PgPool>,
     replicas: Arc<RwLock<Vec<ReplicaPool>>>,
     health_check_interval: Duration,
+    replica_query_timeout: Duration,
 }
 
 struct ReplicaPool {
@@ -63,16 +68,23 @@ impl PoolManager {
             primary: Arc::new(primary),
             replicas: Arc::new(RwLock::new(replicas)),
             health_check_interval: Duration::from_secs(30),
+            replica_query_timeout: replica_query_timeout_from_env(),
         })
     }
 
+    /// Abandon a read on a replica after `timeout` and move on to the next one
+    pub fn with_replica_query_timeout(mut self, timeout: Duration) -> Self {
+        self.replica_query_timeout = timeout;
+        self
+    }
+
     pub async fn execute_query<T, F>(
         &self,
         query_type: QueryType,
         query_fn: F,
     ) -> Result<T, PoolError>
     where
-        F: FnOnce(&PgPool) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T, sqlx::Error>> + Send>> + Send,
+        F: Fn(&PgPool) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T, sqlx::Error>> + Send>> + Send,
     {
         match query_type {
             QueryType::Write => {
@@ -80,16 +92,29 @@ impl PoolManager {
                 Ok(result)
             }
             QueryType::Read => {
-                // Try replicas first
+                // Try replicas first; a slow one is given up on after the
+                // per-replica timeout rather than the full query timeout
                 let replicas = self.replicas.read().await;
                 for replica in replicas.iter() {
                     if replica.healthy {
-                        match query_fn(&replica.pool).await {
-                            Ok(result) => return Ok(result),
-                            Err(e) => {
+                        match tokio::time::timeout(
+                            self.replica_query_timeout,
+                            query_fn(&replica.pool),
+                        )
+                        .await
+                        {
+                            Ok(Ok(result)) => return Ok(result),
+                            Ok(Err(e)) => {
                                 tracing::warn!("Replica query failed: {}", e);
                                 continue;
                             }
+                            Err(_) => {
+                                tracing::warn!(
+                                    "Replica query timed out after {:?}",
+                                    self.replica_query_timeout
+                                );
+                                continue;
+                            }
                         }
                     }
                 }
@@ -144,6 +169,15 @@ impl PoolManager {
     }
 }
 
+fn replica_query_timeout_from_env() -> Duration {
+    std::env::var("REPLICA_QUERY_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_REPLICA_QUERY_TIMEOUT)
+}
+
 #[derive(Debug, Clone)]
 pub struct HealthStatus {
     pub primary_healthy: bool,
@@ -178,6 +178,55 @@ async fn test_failover_on_replica_failure() {
     assert_eq!(result, "test_data");
 }
 
+#[tokio::test]
+async fn test_slow_replica_falls_over_within_timeout() {
+    let docker = Cli::default();
+    let env = TestEnvironment::new(&docker).await;
+    env.setup_test_data().await.unwrap();
+
+    // Every read of test_table on the replica takes 5 seconds
+    let replica_pool = PgPool::connect(&env.replica_url).await.unwrap();
+    sqlx::query("ALTER TABLE test_table RENAME TO test_table_data")
+        .execute(&replica_pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "CREATE VIEW test_table AS SELECT d.id, d.value FROM test_table_data d, pg_sleep(5)",
+    )
+    .execute(&replica_pool)
+    .await
+    .unwrap();
+    replica_pool.close().await;
+
+    let timeout = Duration::from_millis(200);
+    let pool_manager = PoolManager::new(
+        &env.primary_url,
+        vec![env.replica_url.clone()],
+        5,
+    )
+    .await
+    .unwrap()
+    .with_replica_query_timeout(timeout);
+
+    let started = std::time::Instant::now();
+    let result = pool_manager
+        .execute_query(QueryType::Read, |pool| {
+            Box::pin(async move {
+                let row = sqlx::query("SELECT value FROM test_table WHERE id = 1")
+                    .fetch_one(pool)
+                    .await?;
+                let value: String = row.get("value");
+                Ok(value)
+            })
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(result, "test_data");
+    // Abandoned the replica after the timeout instead of waiting out pg_sleep
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
 #[tokio::test]
 async fn test_pool_health_checks() {
     let docker = Cli::default();
@@ -0,0 +1,52 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Crate version from Cargo.toml
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short commit hash the binary was built from, or `unknown`
+pub const GIT_SHA: &str = env!("SYNAPSE_GIT_SHA");
+/// Output of `rustc --version` for the compiler that built the binary
+pub const RUSTC_VERSION: &str = env!("SYNAPSE_RUSTC_VERSION");
+const BUILD_EPOCH: &str = env!("SYNAPSE_BUILD_EPOCH");
+
+/// What is running: version, commit and toolchain of this build
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_sha: String,
+    /// RFC 3339 time the binary was built
+    pub build_timestamp: String,
+    pub rustc_version: String,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let build_timestamp = BUILD_EPOCH
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|ts| ts.to_rfc3339())
+            .unwrap_or_else(|| "unknown".to_string());
+        Self {
+            version: VERSION.to_string(),
+            git_sha: GIT_SHA.to_string(),
+            build_timestamp,
+            rustc_version: RUSTC_VERSION.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_build_info() {
+        let info = BuildInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+        assert!(DateTime::parse_from_rfc3339(&info.build_timestamp).is_ok());
+        assert!(info.rustc_version.starts_with("rustc"));
+    }
+}
@@ -1,6 +1,10 @@
 use clap::{Parser, Subcommand};
 use sqlx::PgPool;
+use std::path::PathBuf;
 use synapse_core::config::Config;
+use synapse_core::services::backup::BackupService;
+use synapse_core::services::transaction as transaction_service;
+use synapse_core::services::transaction_processor::{ReprocessOutcome, TransactionProcessor};
 use uuid::Uuid;
 
 #[derive(Parser)]
@@ -9,6 +13,10 @@ use uuid::Uuid;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Start serving even if startup validation fails
+    #[arg(long, global = true)]
+    pub skip_validation: bool,
 }
 
 #[derive(Subcommand)]
@@ -34,12 +42,19 @@ pub enum Commands {
 
 #[derive(Subcommand)]
 pub enum TxCommands {
-    /// Force complete a transaction by ID
+    /// Force complete a pending, unsettled transaction by ID
     ForceComplete {
         /// Transaction UUID
         #[arg(value_name = "TX_ID")]
         tx_id: Uuid,
     },
+
+    /// Run a transaction through the processor again
+    Reprocess {
+        /// Transaction UUID
+        #[arg(value_name = "TX_ID")]
+        tx_id: Uuid,
+    },
 }
 
 #[derive(Subcommand)]
@@ -69,27 +84,35 @@ pub enum BackupCommands {
 
     /// Apply retention policy to clean old backups
     Cleanup,
+
+    /// Recompute every backup's checksum and report the corrupt ones
+    Verify,
 }
 
 pub async fn handle_tx_force_complete(pool: &PgPool, tx_id: Uuid) -> anyhow::Result<()> {
-    let result = sqlx::query(
-        "UPDATE transactions SET status = 'completed', updated_at = NOW() WHERE id = $1 RETURNING id"
-    )
-    .bind(tx_id)
-    .fetch_optional(pool)
-    .await?;
-
-    match result {
-        Some(_) => {
-            tracing::info!("Transaction {} marked as completed", tx_id);
-            println!("✓ Transaction {} marked as completed", tx_id);
-            Ok(())
-        }
-        None => {
-            tracing::warn!("Transaction {} not found", tx_id);
-            anyhow::bail!("Transaction {} not found", tx_id)
+    // Same guard and audit entry as the admin mutation: pending, unsettled only
+    transaction_service::force_complete(pool, tx_id, "cli").await?;
+
+    tracing::info!("Transaction {} marked as completed", tx_id);
+    println!("✓ Transaction {} marked as completed", tx_id);
+    Ok(())
+}
+
+pub async fn handle_tx_reprocess(pool: &PgPool, tx_id: Uuid) -> anyhow::Result<()> {
+    let outcome = TransactionProcessor::new(pool.clone())
+        .reprocess(tx_id)
+        .await?;
+
+    tracing::info!("Transaction {} reprocessed: {}", tx_id, outcome);
+    match outcome {
+        ReprocessOutcome::Completed => println!("✓ Transaction {} completed", tx_id),
+        ReprocessOutcome::Failed(_)
+        | ReprocessOutcome::SentToDlq(_)
+        | ReprocessOutcome::Refused(_) => {
+            println!("✗ Transaction {} {}", tx_id, outcome)
         }
     }
+    Ok(())
 }
 
 pub async fn handle_db_migrate(config: &Config) -> anyhow::Result<()> {
@@ -145,10 +168,34 @@ pub async fn handle_backup_list(_config: &Config) -> anyhow::Result<()> {
     anyhow::bail!("Backup service not yet implemented")
 }
 
-pub async fn handle_backup_restore(_config: &Config, _filename: &str) -> anyhow::Result<()> {
-    anyhow::bail!("Backup service not yet implemented")
+pub async fn handle_backup_restore(config: &Config, filename: &str) -> anyhow::Result<()> {
+    backup_service(config).restore_backup(filename).await?;
+    println!("✓ Restored backup {}", filename);
+    Ok(())
 }
 
 pub async fn handle_backup_cleanup(_config: &Config) -> anyhow::Result<()> {
     anyhow::bail!("Backup service not yet implemented")
 }
+
+fn backup_service(config: &Config) -> BackupService {
+    BackupService::new(
+        config.database_url.clone(),
+        PathBuf::from(&config.backup_dir),
+        config.backup_encryption_key.clone(),
+    )
+    .with_restore_jobs(config.backup_restore_jobs)
+}
+
+pub async fn handle_backup_verify(config: &Config) -> anyhow::Result<()> {
+    let corrupt = backup_service(config).verify_all().await?;
+    if corrupt.is_empty() {
+        println!("✓ All backups match their checksums");
+        return Ok(());
+    }
+
+    for filename in &corrupt {
+        println!("✗ {}", filename);
+    }
+    anyhow::bail!("{} backup(s) failed checksum verification", corrupt.len())
+}
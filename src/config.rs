@@ -1,8 +1,18 @@
-use crate::secrets::SecretsManager;
+use crate::db::DbTlsOptions;
+use crate::error::DEFAULT_POOL_RETRY_AFTER_SECS;
+use crate::middleware::body_limit::BodyLimits;
+use crate::middleware::timeout::ServerTimeouts;
+use crate::secrets::{resolve_credentials, SecretsBackendKind};
+use crate::services::backup::{BackupType, DEFAULT_RESTORE_JOBS};
+use crate::services::callback_queue::DEFAULT_QUEUE_CAPACITY;
+use crate::services::settlement::DEFAULT_MAX_BATCH_SIZE;
+use crate::services::webhook_dispatcher::WebhookDispatchConfig;
+use crate::utils::export_link::DEFAULT_LINK_TTL_SECS;
 use anyhow::Result;
 use dotenvy::dotenv;
 use ipnet::IpNet;
 use std::env;
+use url::Url;
 
 #[derive(Debug, Clone)]
 pub enum AllowedIps {
@@ -21,16 +31,117 @@ pub struct Config {
     pub server_port: u16,
     pub database_url: String,
     pub database_replica_url: Option<String>,
+    /// Seconds a replica may lag before reads fall back to the primary
+    pub db_replica_max_lag_secs: u64,
+    /// Milliseconds a read may run on the replica before it is re-run on the
+    /// primary
+    pub replica_query_timeout_ms: u64,
+    pub db_tls: DbTlsOptions,
     pub stellar_horizon_url: String,
+    /// Horizon endpoints in failover order; the first entry is the initial primary
+    pub stellar_horizon_urls: Vec<String>,
+    /// Seconds each Horizon request from background work may take
+    pub horizon_timeout_secs: u64,
     pub anchor_webhook_secret: String,
     pub redis_url: String,
     pub default_rate_limit: u32,
     pub whitelist_rate_limit: u32,
     pub whitelisted_ips: String,
+    /// Per-route quotas, as `template:limit/s` entries separated by commas
+    pub rate_limit_overrides: String,
     pub log_format: LogFormat,
     pub allowed_ips: AllowedIps,
+    /// Proxies in front of the service whose `x-forwarded-for` entries are
+    /// skipped when identifying the client
+    pub trusted_proxy_depth: usize,
+    /// Canonical address used for absolute page links instead of the
+    /// request's own
+    pub public_base_url: Option<Url>,
     pub backup_dir: String,
     pub backup_encryption_key: Option<String>,
+    /// Take backups automatically while serving
+    pub backup_schedule_enabled: bool,
+    pub backup_schedule: BackupType,
+    /// Parallel pg_restore jobs when restoring a custom-format backup
+    pub backup_restore_jobs: usize,
+    pub idempotency_lock_ttl_secs: u64,
+    pub ws_max_connections: usize,
+    /// WebSocket lag events in a row before a client is disconnected
+    pub ws_max_consecutive_lags: u32,
+    /// Missed WebSocket messages before a client is disconnected
+    pub ws_max_lagged_messages: u64,
+    pub feature_flag_cache_ttl_secs: u64,
+    /// Seconds between partition maintenance runs
+    pub partition_maintenance_interval_secs: u64,
+    /// Most transactions in one settlement
+    pub settlement_max_batch_size: usize,
+    /// `async=true` callbacks that may wait to be stored
+    pub callback_queue_capacity: usize,
+    /// Days after which transactions are anonymized; `None` keeps them as is
+    pub transaction_pii_retention_days: Option<u32>,
+    /// Key signing shareable export links; `None` disables them
+    pub export_link_secret: Option<String>,
+    /// Default lifetime of a shareable export link
+    pub export_link_ttl_secs: u64,
+    /// Refuse callers with neither a partner API key nor the admin key
+    pub require_api_key: bool,
+    /// Add the underlying cause to error responses as `detail`
+    pub debug_errors: bool,
+    pub body_limits: BodyLimits,
+    pub server_timeouts: ServerTimeouts,
+    /// `Retry-After` seconds sent when no database connection could be acquired
+    pub db_pool_retry_after_secs: u64,
+    pub webhook_dispatch: WebhookDispatchConfig,
+}
+
+/// Every optional setting at the default [`Config::load`] gives it, with the
+/// required ones left empty
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server_port: 3000,
+            database_url: String::new(),
+            database_replica_url: None,
+            db_replica_max_lag_secs: 30,
+            replica_query_timeout_ms: 2000,
+            db_tls: DbTlsOptions::default(),
+            stellar_horizon_url: String::new(),
+            stellar_horizon_urls: Vec::new(),
+            horizon_timeout_secs: 30,
+            anchor_webhook_secret: String::new(),
+            redis_url: "redis://localhost:6379".to_string(),
+            default_rate_limit: 100,
+            whitelist_rate_limit: 1000,
+            whitelisted_ips: String::new(),
+            rate_limit_overrides: String::new(),
+            log_format: LogFormat::Text,
+            allowed_ips: AllowedIps::Any,
+            trusted_proxy_depth: 0,
+            public_base_url: None,
+            backup_dir: "./backups".to_string(),
+            backup_encryption_key: None,
+            backup_schedule_enabled: false,
+            backup_schedule: BackupType::Daily,
+            backup_restore_jobs: DEFAULT_RESTORE_JOBS,
+            idempotency_lock_ttl_secs: 30,
+            ws_max_connections: 1000,
+            ws_max_consecutive_lags: 3,
+            ws_max_lagged_messages: 1000,
+            feature_flag_cache_ttl_secs: 60,
+            partition_maintenance_interval_secs: 86400,
+            settlement_max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            callback_queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            transaction_pii_retention_days: None,
+            export_link_secret: None,
+            export_link_ttl_secs: DEFAULT_LINK_TTL_SECS,
+            require_api_key: true,
+            debug_errors: false,
+            body_limits: BodyLimits::default(),
+            server_timeouts: ServerTimeouts::default(),
+            db_pool_retry_after_secs: DEFAULT_POOL_RETRY_AFTER_SECS,
+            webhook_dispatch: WebhookDispatchConfig::default(),
+        }
+    }
 }
 
 pub mod assets;
@@ -44,33 +155,44 @@ impl Config {
         let log_format =
             parse_log_format(&env::var("LOG_FORMAT").unwrap_or_else(|_| "text".to_string()))?;
 
-        let use_vault = env::var("VAULT_ROLE_ID").is_ok() && env::var("VAULT_SECRET_ID").is_ok();
-
-        let (database_url, anchor_webhook_secret) = if use_vault {
-            let secrets = SecretsManager::new().await?;
-            let db_password = secrets.get_db_password().await?;
-            let anchor_secret = secrets.get_anchor_secret().await?;
-
-            let db_template = env::var("DATABASE_URL_TEMPLATE").ok();
-            let db_url = db_template
-                .map(|template| template.replace("{password}", &db_password))
-                .unwrap_or_else(|| env::var("DATABASE_URL").unwrap_or_default());
+        let secrets_backend = SecretsBackendKind::from_env()?.connect().await?;
 
-            (db_url, anchor_secret)
-        } else {
-            (
+        let (database_url, anchor_webhook_secret) = match secrets_backend {
+            Some(secrets) => {
+                resolve_credentials(
+                    secrets.as_ref(),
+                    env::var("DATABASE_URL_TEMPLATE").ok().as_deref(),
+                    env::var("DATABASE_URL").ok().as_deref(),
+                )
+                .await?
+            }
+            None => (
                 env::var("DATABASE_URL")?,
                 env::var("ANCHOR_WEBHOOK_SECRET")?,
-            )
+            ),
         };
 
+        let stellar_horizon_urls = parse_horizon_urls(
+            env::var("STELLAR_HORIZON_URLS").ok().as_deref(),
+            env::var("STELLAR_HORIZON_URL").ok().as_deref(),
+        )?;
+
         Ok(Config {
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()?,
             database_url,
             database_replica_url: env::var("DATABASE_REPLICA_URL").ok(),
-            stellar_horizon_url: env::var("STELLAR_HORIZON_URL")?,
+            db_replica_max_lag_secs: env::var("DB_REPLICA_MAX_LAG_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+            replica_query_timeout_ms: parse_positive("REPLICA_QUERY_TIMEOUT_MS", 2000)?,
+            db_tls: DbTlsOptions::from_env()?,
+            stellar_horizon_url: stellar_horizon_urls[0].clone(),
+            stellar_horizon_urls,
+            horizon_timeout_secs: env::var("HORIZON_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
             anchor_webhook_secret,
             redis_url: env::var("REDIS_URL")
                 .unwrap_or_else(|_| "redis://localhost:6379".to_string()),
@@ -81,14 +203,94 @@ impl Config {
                 .unwrap_or_else(|_| "1000".to_string())
                 .parse()?,
             whitelisted_ips: env::var("WHITELISTED_IPS").unwrap_or_default(),
+            rate_limit_overrides: env::var("RATE_LIMIT_OVERRIDES").unwrap_or_default(),
             log_format,
             allowed_ips,
+            trusted_proxy_depth: env::var("TRUSTED_PROXY_DEPTH")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            public_base_url: parse_public_base_url(env::var("PUBLIC_BASE_URL").ok().as_deref())?,
             backup_dir: env::var("BACKUP_DIR").unwrap_or_else(|_| "./backups".to_string()),
             backup_encryption_key: env::var("BACKUP_ENCRYPTION_KEY").ok(),
+            backup_schedule_enabled: env::var("BACKUP_SCHEDULE_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            backup_schedule: env::var("BACKUP_SCHEDULE")
+                .unwrap_or_else(|_| "daily".to_string())
+                .parse()?,
+            backup_restore_jobs: env::var("BACKUP_RESTORE_JOBS")
+                .map(|v| v.parse())
+                .unwrap_or(Ok(DEFAULT_RESTORE_JOBS))?,
+            idempotency_lock_ttl_secs: parse_positive("IDEMPOTENCY_LOCK_TTL_SECS", 30)?,
+            ws_max_connections: env::var("WS_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
+            ws_max_consecutive_lags: parse_positive("WS_MAX_CONSECUTIVE_LAGS", 3)?,
+            ws_max_lagged_messages: env::var("WS_MAX_LAGGED_MESSAGES")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
+            feature_flag_cache_ttl_secs: env::var("FEATURE_FLAG_CACHE_TTL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
+            partition_maintenance_interval_secs: env::var("PARTITION_MAINTENANCE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()?,
+            settlement_max_batch_size: parse_positive(
+                "SETTLEMENT_MAX_BATCH_SIZE",
+                DEFAULT_MAX_BATCH_SIZE,
+            )?,
+            callback_queue_capacity: parse_positive(
+                "CALLBACK_QUEUE_CAPACITY",
+                DEFAULT_QUEUE_CAPACITY,
+            )?,
+            transaction_pii_retention_days: env::var("TRANSACTION_PII_RETENTION_DAYS")
+                .ok()
+                .map(|v| v.parse())
+                .transpose()?,
+            export_link_secret: env::var("EXPORT_LINK_SECRET")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            export_link_ttl_secs: parse_positive("EXPORT_LINK_TTL_SECS", DEFAULT_LINK_TTL_SECS)?,
+            require_api_key: env::var("REQUIRE_API_KEY")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            debug_errors: env::var("DEBUG_ERRORS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            body_limits: BodyLimits::from_env()?,
+            server_timeouts: ServerTimeouts::from_env()?,
+            db_pool_retry_after_secs: parse_positive(
+                "DB_POOL_RETRY_AFTER_SECS",
+                DEFAULT_POOL_RETRY_AFTER_SECS,
+            )?,
+            webhook_dispatch: WebhookDispatchConfig::from_env()?,
         })
     }
 }
 
+/// `name` as a number above zero, or `default` when it is unset
+pub(crate) fn parse_positive<T>(name: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+{
+    let Ok(raw) = env::var(name) else {
+        return Ok(default);
+    };
+    match raw.trim().parse::<T>() {
+        Ok(value) if value > T::default() => Ok(value),
+        _ => anyhow::bail!("{} must be a number above zero, got '{}'", name, raw),
+    }
+}
+
+fn parse_public_base_url(raw: Option<&str>) -> anyhow::Result<Option<Url>> {
+    match raw.map(str::trim) {
+        Some(raw) if !raw.is_empty() => Url::parse(raw)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("PUBLIC_BASE_URL '{}' is not a valid URL: {}", raw, e)),
+        _ => Ok(None),
+    }
+}
+
 fn parse_allowed_ips(raw: &str) -> anyhow::Result<AllowedIps> {
     let value = raw.trim();
     if value == "*" {
@@ -109,6 +311,27 @@ fn parse_allowed_ips(raw: &str) -> anyhow::Result<AllowedIps> {
     Ok(AllowedIps::Cidrs(cidrs))
 }
 
+/// `STELLAR_HORIZON_URLS` (comma-separated) takes precedence over the single
+/// `STELLAR_HORIZON_URL`; at least one of them must be set.
+fn parse_horizon_urls(list: Option<&str>, single: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let urls: Vec<String> = list
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+
+    if !urls.is_empty() {
+        return Ok(urls);
+    }
+
+    match single.map(str::trim) {
+        Some(url) if !url.is_empty() => Ok(vec![url.to_string()]),
+        _ => anyhow::bail!("STELLAR_HORIZON_URL or STELLAR_HORIZON_URLS must be set"),
+    }
+}
+
 fn parse_log_format(raw: &str) -> anyhow::Result<LogFormat> {
     match raw.trim().to_ascii_lowercase().as_str() {
         "text" => Ok(LogFormat::Text),
@@ -1,14 +1,17 @@
 use chrono::{DateTime, Utc};
 use serde_json::{json, Value as JsonValue};
-use sqlx::{Postgres, Transaction as SqlxTransaction};
+use sqlx::{PgPool, Postgres, Transaction as SqlxTransaction};
 use uuid::Uuid;
 
 /// Entity type constants for audit logs
 pub const ENTITY_TRANSACTION: &str = "transaction";
 pub const ENTITY_SETTLEMENT: &str = "settlement";
+/// Notes are audited under their own id, so internal notes stay out of the
+/// transaction's public timeline
+pub const ENTITY_TRANSACTION_NOTE: &str = "transaction_note";
 
 /// Represents an audit log entry
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct AuditLog {
     pub entity_id: Uuid,
     pub entity_type: String,
@@ -40,6 +43,21 @@ impl AuditLog {
         }
     }
 
+    /// All entries for an entity, oldest first
+    pub async fn for_entity(pool: &PgPool, entity_id: Uuid) -> sqlx::Result<Vec<AuditLog>> {
+        sqlx::query_as::<_, AuditLog>(
+            r#"
+            SELECT entity_id, entity_type, action, old_val, new_val, actor, timestamp
+            FROM audit_logs
+            WHERE entity_id = $1
+            ORDER BY timestamp ASC, created_at ASC
+            "#,
+        )
+        .bind(entity_id)
+        .fetch_all(pool)
+        .await
+    }
+
     /// Log an action with explicit old and new values
     pub async fn log(
         tx: &mut SqlxTransaction<'_, Postgres>,
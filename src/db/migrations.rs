@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+/// The migrations shipped with the service, embedded at compile time so the
+/// check compares against exactly what this binary expects
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// A row of `_sqlx_migrations`
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    /// False when the migration failed part way and the schema needs attention
+    pub success: bool,
+    /// False when the file on disk changed after the migration was applied
+    pub checksum_matches: bool,
+}
+
+/// A migration shipped with the service but not yet applied
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+/// A way the database's migration history disagrees with the binary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MigrationDrift {
+    /// Shipped but not applied: the database is behind
+    Pending { version: i64 },
+    /// Applied but not shipped: the database is ahead of this binary
+    Unknown { version: i64 },
+    /// Applied from a file that has changed since
+    ChecksumMismatch { version: i64 },
+    /// Failed part way through
+    Failed { version: i64 },
+}
+
+impl fmt::Display for MigrationDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationDrift::Pending { version } => write!(f, "{} not applied", version),
+            MigrationDrift::Unknown { version } => {
+                write!(f, "{} applied but unknown to this build", version)
+            }
+            MigrationDrift::ChecksumMismatch { version } => {
+                write!(f, "{} modified after being applied", version)
+            }
+            MigrationDrift::Failed { version } => write!(f, "{} failed", version),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<AppliedMigration>,
+    pub pending: Vec<PendingMigration>,
+    /// Every difference from the migrations `migration_status` compared
+    /// against, in version order
+    pub drift: Vec<MigrationDrift>,
+    /// No drift: every migration is applied, succeeded and matches its file
+    pub up_to_date: bool,
+}
+
+/// version, description, installed_on, success, checksum
+type MigrationRow = (i64, String, DateTime<Utc>, bool, Vec<u8>);
+
+/// Compare what the database has applied with what `migrator` holds. A
+/// database that was never migrated reports every migration as pending.
+pub async fn migration_status(
+    pool: &PgPool,
+    migrator: &Migrator,
+) -> Result<MigrationStatus, sqlx::Error> {
+    let rows: Vec<MigrationRow> = match sqlx::query_as(
+        "SELECT version, description, installed_on, success, checksum
+         FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(rows) => rows,
+        // 42P01 = undefined_table: nothing has been applied yet
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42P01") => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let known: HashMap<i64, &[u8]> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| (m.version, m.checksum.as_ref()))
+        .collect();
+
+    let applied: Vec<AppliedMigration> = rows
+        .into_iter()
+        .map(
+            |(version, description, installed_on, success, checksum)| AppliedMigration {
+                version,
+                description,
+                installed_on,
+                success,
+                checksum_matches: known
+                    .get(&version)
+                    .is_none_or(|expected| *expected == checksum.as_slice()),
+            },
+        )
+        .collect();
+
+    let pending: Vec<PendingMigration> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .map(|m| PendingMigration {
+            version: m.version,
+            description: m.description.to_string(),
+        })
+        .collect();
+
+    let mut drift: Vec<MigrationDrift> = pending
+        .iter()
+        .map(|m| MigrationDrift::Pending { version: m.version })
+        .collect();
+    for m in &applied {
+        if !known.contains_key(&m.version) {
+            drift.push(MigrationDrift::Unknown { version: m.version });
+        } else if !m.success {
+            drift.push(MigrationDrift::Failed { version: m.version });
+        } else if !m.checksum_matches {
+            drift.push(MigrationDrift::ChecksumMismatch { version: m.version });
+        }
+    }
+    drift.sort_by_key(|d| match d {
+        MigrationDrift::Pending { version }
+        | MigrationDrift::Unknown { version }
+        | MigrationDrift::ChecksumMismatch { version }
+        | MigrationDrift::Failed { version } => *version,
+    });
+
+    Ok(MigrationStatus {
+        applied,
+        pending,
+        up_to_date: drift.is_empty(),
+        drift,
+    })
+}
@@ -4,6 +4,11 @@ use sqlx::types::BigDecimal;
 use sqlx::FromRow;
 use uuid::Uuid;
 
+/// `metadata` key holding a transaction's compliance risk tag
+pub const COMPLIANCE_TAG_KEY: &str = "compliance_tag";
+/// Compliance tag of transactions that need review
+pub const HIGH_RISK_TAG: &str = "high_risk";
+
 #[derive(Debug, FromRow, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct Transaction {
@@ -11,6 +16,8 @@ pub struct Transaction {
     pub stellar_account: String,
     pub amount: BigDecimal,
     pub asset_code: String,
+    /// Issuing account of the asset; `None` for native or legacy rows
+    pub asset_issuer: Option<String>,
     pub status: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -21,6 +28,8 @@ pub struct Transaction {
     pub memo: Option<String>,
     pub memo_type: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    /// Partner that recorded the transaction; `None` when unscoped
+    pub partner_id: Option<Uuid>,
 }
 
 #[async_graphql::Object]
@@ -37,6 +46,9 @@ impl Transaction {
     async fn asset_code(&self) -> &str {
         &self.asset_code
     }
+    async fn asset_issuer(&self) -> Option<&str> {
+        self.asset_issuer.as_deref()
+    }
     async fn status(&self) -> &str {
         &self.status
     }
@@ -64,6 +76,13 @@ impl Transaction {
     async fn memo_type(&self) -> Option<&str> {
         self.memo_type.as_deref()
     }
+    async fn partner_id(&self) -> Option<String> {
+        self.partner_id.map(|id| id.to_string())
+    }
+    #[graphql(name = "complianceTag")]
+    async fn graphql_compliance_tag(&self) -> Option<&str> {
+        self.compliance_tag()
+    }
 }
 
 impl Transaction {
@@ -84,6 +103,7 @@ impl Transaction {
             stellar_account,
             amount,
             asset_code,
+            asset_issuer: None,
             status: "pending".to_string(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
@@ -94,8 +114,28 @@ impl Transaction {
             memo,
             memo_type,
             metadata,
+            partner_id: None,
         }
     }
+
+    pub fn with_asset_issuer(mut self, asset_issuer: Option<String>) -> Self {
+        self.asset_issuer = asset_issuer;
+        self
+    }
+
+    pub fn with_partner_id(mut self, partner_id: Option<Uuid>) -> Self {
+        self.partner_id = partner_id;
+        self
+    }
+
+    /// `metadata.compliance_tag`, when it is a string
+    pub fn compliance_tag(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get(COMPLIANCE_TAG_KEY)?.as_str()
+    }
+
+    pub fn is_high_risk(&self) -> bool {
+        self.compliance_tag() == Some(HIGH_RISK_TAG)
+    }
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize, Clone)]
@@ -142,6 +182,16 @@ impl Settlement {
     }
 }
 
+/// An internal note attached to a transaction by support staff
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TransactionNote {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub author: String,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct TransactionDlq {
     pub id: Uuid,
@@ -151,6 +201,8 @@ pub struct TransactionDlq {
     pub asset_code: String,
     pub anchor_transaction_id: Option<String>,
     pub error_reason: String,
+    /// One of `services::transaction_processor::DlqErrorCode`
+    pub error_code: String,
     pub stack_trace: Option<String>,
     pub retry_count: i32,
     pub original_created_at: DateTime<Utc>,
@@ -1,5 +1,7 @@
 use sqlx::PgPool;
 use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tokio::time;
 use tracing::{error, info};
 
@@ -17,25 +19,45 @@ impl PartitionManager {
         }
     }
 
-    /// Start the partition maintenance background task
-    pub fn start(self) {
-        tokio::spawn(async move {
+    /// Run maintenance every `interval` instead of the hourly count given to `new`
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Start the partition maintenance background task. It runs until
+    /// [`PartitionManagerHandle::stop`] is called or the handle is dropped;
+    /// a maintenance run already in progress is allowed to finish.
+    pub fn start(self) -> PartitionManagerHandle {
+        let (stop_tx, mut stop_rx) = watch::channel(false);
+        let task = tokio::spawn(async move {
             let mut interval = time::interval(self.interval);
             interval.tick().await; // Skip first immediate tick
 
             loop {
-                interval.tick().await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    // A send or a dropped handle both end the loop
+                    _ = stop_rx.changed() => break,
+                }
                 if let Err(e) = self.maintain_partitions().await {
                     error!("Partition maintenance failed: {}", e);
                 } else {
                     info!("Partition maintenance completed successfully");
                 }
             }
+            info!("Partition manager stopped");
         });
+
+        PartitionManagerHandle { stop_tx, task }
     }
 
     /// Run partition maintenance (create new partitions, detach old ones)
-    async fn maintain_partitions(&self) -> Result<(), sqlx::Error> {
+    pub async fn maintain_partitions(&self) -> Result<(), sqlx::Error> {
         sqlx::query("SELECT maintain_partitions()")
             .execute(&self.pool)
             .await?;
@@ -60,6 +82,26 @@ impl PartitionManager {
     }
 }
 
+/// Controls a running [`PartitionManager`] task
+pub struct PartitionManagerHandle {
+    stop_tx: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl PartitionManagerHandle {
+    /// Signal the task to stop and wait for it to exit
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        if let Err(e) = self.task.await {
+            error!("Partition manager task failed: {}", e);
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,4 +118,19 @@ mod tests {
 
         assert_eq!(manager.interval, Duration::from_secs(24 * 3600));
     }
+
+    #[tokio::test]
+    async fn test_stop_ends_task_promptly() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let handle = PartitionManager::new(pool, 24)
+            .with_interval(Duration::from_secs(3600))
+            .start();
+        assert!(!handle.is_finished());
+
+        tokio::time::timeout(Duration::from_secs(1), handle.stop())
+            .await
+            .expect("partition manager did not stop");
+    }
 }
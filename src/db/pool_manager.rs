@@ -1,16 +1,58 @@
+use crate::db::DbTlsOptions;
+use serde::Serialize;
 use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// How long a health probe may take before the pool counts as down
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Replication lag above which a replica stops serving reads
+pub const DEFAULT_MAX_REPLICA_LAG: Duration = Duration::from_secs(30);
+
+/// How long a read may run on the replica before it is re-run on the primary
+pub const DEFAULT_REPLICA_QUERY_TIMEOUT: Duration = Duration::from_millis(2000);
+
+/// Reads abandoned on the replica after the replica query timeout, labelled
+/// `pool` like the pool gauges
+pub const REPLICA_TIMEOUT_METRIC: &str = "db_replica_query_timeouts_total";
+
+/// Seconds the replica is behind the primary, labelled `pool` like the pool
+/// gauges, e.g. `pool="replica_1"`
+pub const REPLICA_LAG_METRIC: &str = "db_replica_lag_seconds";
+
+/// Result of one [`PoolManager::check_health`] probe
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct PoolHealthReport {
+    pub primary_healthy: bool,
+    /// Writes are refused while the primary is down
+    pub read_only: bool,
+    pub replica_count: usize,
+    pub replicas: Vec<ReplicaHealth>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ReplicaHealth {
+    /// Metrics label of the replica pool, e.g. `replica_1`
+    pub name: String,
+    /// Reachable and within the allowed lag, so serving reads
+    pub healthy: bool,
+    /// Seconds behind the primary; absent when the replica did not answer
+    pub lag_seconds: Option<f64>,
+}
+
 #[derive(Clone)]
 pub struct PoolManager {
     primary: PgPool,
     replica: Option<PgPool>,
     failover_state: Arc<RwLock<FailoverState>>,
+    max_replica_lag: Duration,
+    replica_query_timeout: Duration,
 }
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 struct FailoverState {
     primary_healthy: bool,
     replica_healthy: bool,
@@ -18,16 +60,25 @@ struct FailoverState {
 
 impl PoolManager {
     pub async fn new(primary_url: &str, replica_url: Option<&str>) -> Result<Self, sqlx::Error> {
+        Self::with_tls(primary_url, replica_url, &DbTlsOptions::default()).await
+    }
+
+    /// Creates a PoolManager whose primary and replica connections both use `tls`
+    pub async fn with_tls(
+        primary_url: &str,
+        replica_url: Option<&str>,
+        tls: &DbTlsOptions,
+    ) -> Result<Self, sqlx::Error> {
         let primary = PgPoolOptions::new()
             .max_connections(10)
-            .connect(primary_url)
+            .connect_with(tls.connect_options(primary_url)?)
             .await?;
 
         let replica = if let Some(url) = replica_url {
             Some(
                 PgPoolOptions::new()
                     .max_connections(10)
-                    .connect(url)
+                    .connect_with(tls.connect_options(url)?)
                     .await?,
             )
         } else {
@@ -41,9 +92,24 @@ impl PoolManager {
                 primary_healthy: true,
                 replica_healthy: true,
             })),
+            max_replica_lag: DEFAULT_MAX_REPLICA_LAG,
+            replica_query_timeout: DEFAULT_REPLICA_QUERY_TIMEOUT,
         })
     }
 
+    /// Stop reading from the replica while it is more than `max_lag` behind
+    pub fn with_max_replica_lag(mut self, max_lag: Duration) -> Self {
+        self.max_replica_lag = max_lag;
+        self
+    }
+
+    /// Give up on a read that has not finished on the replica after
+    /// `timeout` and run it on the primary instead
+    pub fn with_replica_query_timeout(mut self, timeout: Duration) -> Self {
+        self.replica_query_timeout = timeout;
+        self
+    }
+
     pub fn primary(&self) -> &PgPool {
         &self.primary
     }
@@ -52,6 +118,18 @@ impl PoolManager {
         self.replica.as_ref()
     }
 
+    /// Every pool with its metrics label: `primary`, then `replica_1`, ...
+    pub fn labelled_pools(&self) -> Vec<(String, &PgPool)> {
+        let mut pools = vec![("primary".to_string(), &self.primary)];
+        pools.extend(
+            self.replica
+                .iter()
+                .enumerate()
+                .map(|(i, pool)| (format!("replica_{}", i + 1), pool)),
+        );
+        pools
+    }
+
     pub async fn get_read_pool(&self) -> &PgPool {
         let state = self.failover_state.read().await;
 
@@ -64,7 +142,176 @@ impl PoolManager {
         &self.primary
     }
 
+    /// Run `query` on the read pool. When that is the replica and it has not
+    /// answered within the replica query timeout, the query is dropped there
+    /// and run again on the primary, so a slow replica costs at most the
+    /// timeout rather than the whole query.
+    pub async fn read<T, E, F, Fut>(&self, query: F) -> Result<T, E>
+    where
+        F: Fn(PgPool) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let pool = self.get_read_pool().await;
+        if std::ptr::eq(pool, &self.primary) {
+            return query(self.primary.clone()).await;
+        }
+
+        match tokio::time::timeout(self.replica_query_timeout, query(pool.clone())).await {
+            Ok(result) => result,
+            Err(_) => {
+                crate::metrics::registry()
+                    .increment_counter(REPLICA_TIMEOUT_METRIC, &[("pool", "replica_1")]);
+                tracing::warn!(
+                    timeout_ms = self.replica_query_timeout.as_millis() as u64,
+                    "Replica read timed out, retrying on primary"
+                );
+                query(self.primary.clone()).await
+            }
+        }
+    }
+
     pub async fn get_write_pool(&self) -> &PgPool {
         &self.primary
     }
+
+    /// True while the last health check found the primary unreachable.
+    /// Writes should be refused; reads can still go to a replica.
+    pub async fn is_read_only(&self) -> bool {
+        !self.failover_state.read().await.primary_healthy
+    }
+
+    /// Probe every pool and record the result, entering read-only mode when
+    /// the primary is down and leaving it once the primary answers again. A
+    /// replica further behind than the allowed lag counts as unhealthy.
+    pub async fn check_health(&self) -> PoolHealthReport {
+        let primary_healthy = probe(&self.primary).await;
+        let mut replicas = Vec::new();
+        if let Some(replica) = &self.replica {
+            let name = "replica_1";
+            let lag_seconds = replica_lag(replica).await;
+            replicas.push(ReplicaHealth {
+                name: name.to_string(),
+                healthy: lag_seconds.is_some_and(|lag| self.record_replica_lag(name, lag)),
+                lag_seconds,
+            });
+        }
+        let replica_healthy = replicas.iter().all(|r| r.healthy);
+
+        let mut state = self.failover_state.write().await;
+        if state.primary_healthy && !primary_healthy {
+            tracing::error!("Primary database unreachable, entering read-only mode");
+        } else if !state.primary_healthy && primary_healthy {
+            tracing::info!("Primary database recovered, leaving read-only mode");
+        }
+        if state.replica_healthy != replica_healthy {
+            tracing::warn!(replica_healthy, "Replica health changed");
+        }
+        state.primary_healthy = primary_healthy;
+        state.replica_healthy = replica_healthy;
+
+        PoolHealthReport {
+            primary_healthy,
+            read_only: !primary_healthy,
+            replica_count: replicas.len(),
+            replicas,
+        }
+    }
+
+    /// Publish the replica's lag and decide whether it is fresh enough to read from
+    fn record_replica_lag(&self, name: &str, lag_seconds: f64) -> bool {
+        crate::metrics::registry().set_labelled_gauge(
+            REPLICA_LAG_METRIC,
+            &[("pool", name)],
+            lag_seconds,
+        );
+        let healthy = lag_seconds <= self.max_replica_lag.as_secs_f64();
+        if !healthy {
+            tracing::warn!(
+                replica = name,
+                lag_seconds,
+                max_lag_seconds = self.max_replica_lag.as_secs_f64(),
+                "Replica lag above threshold, routing reads to primary"
+            );
+        }
+        healthy
+    }
+
+    /// Run [`check_health`](Self::check_health) every `interval` in the background
+    pub fn spawn_health_monitor(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                manager.check_health().await;
+            }
+        })
+    }
+}
+
+/// Seconds since the replica last replayed a transaction, or zero once it
+/// has replayed everything it received: an idle primary sends nothing new, so
+/// the replay timestamp ages without the replica falling behind. Zero for a
+/// server that is not in recovery. `None` if the replica cannot be queried.
+async fn replica_lag(pool: &PgPool) -> Option<f64> {
+    let query = sqlx::query_scalar::<_, f64>(
+        r#"
+        SELECT CASE
+            WHEN NOT pg_is_in_recovery() THEN 0
+            WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+            ELSE COALESCE(
+                EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())::float8,
+                0
+            )
+        END
+        "#,
+    )
+    .fetch_one(pool);
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, query).await {
+        Ok(Ok(lag)) => Some(lag.max(0.0)),
+        _ => None,
+    }
+}
+
+async fn probe(pool: &PgPool) -> bool {
+    matches!(
+        tokio::time::timeout(HEALTH_CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(pool)).await,
+        Ok(Ok(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A manager whose pools never connect, for exercising health bookkeeping
+    fn lazy_manager() -> PoolManager {
+        let lazy = || {
+            PgPoolOptions::new()
+                .connect_lazy("postgres://localhost/unused")
+                .unwrap()
+        };
+        PoolManager {
+            primary: lazy(),
+            replica: Some(lazy()),
+            failover_state: Arc::new(RwLock::new(FailoverState {
+                primary_healthy: true,
+                replica_healthy: true,
+            })),
+            max_replica_lag: DEFAULT_MAX_REPLICA_LAG,
+            replica_query_timeout: DEFAULT_REPLICA_QUERY_TIMEOUT,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_excessive_lag_marks_replica_unhealthy() {
+        let manager = lazy_manager().with_max_replica_lag(Duration::from_secs(5));
+
+        assert!(manager.record_replica_lag("replica_1", 1.5));
+        assert!(!manager.record_replica_lag("replica_1", 60.0));
+        assert_eq!(
+            crate::metrics::registry().labelled_gauge(REPLICA_LAG_METRIC, &[("pool", "replica_1")]),
+            Some(60.0)
+        );
+    }
 }
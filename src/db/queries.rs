@@ -1,5 +1,8 @@
-use crate::db::audit::{AuditLog, ENTITY_TRANSACTION};
-use crate::db::models::{Settlement, Transaction};
+use crate::db::audit::{AuditLog, ENTITY_TRANSACTION, ENTITY_TRANSACTION_NOTE};
+use crate::db::models::{Settlement, Transaction, TransactionNote};
+use crate::error::AppError;
+use crate::services::transaction as transaction_service;
+use crate::utils::pagination::{PageDirection, SortOrder};
 use chrono::{DateTime, Utc};
 use serde_json::json;
 use sqlx::types::BigDecimal;
@@ -8,16 +11,56 @@ use uuid::Uuid;
 
 // --- Transaction Queries ---
 
-pub async fn insert_transaction(pool: &PgPool, tx: &Transaction) -> Result<Transaction> {
+pub async fn insert_transaction(
+    pool: &PgPool,
+    tx: &Transaction,
+) -> std::result::Result<Transaction, AppError> {
     let mut db_tx = pool.begin().await?;
+    let result = insert_transaction_in_tx(&mut db_tx, tx).await?;
+    db_tx.commit().await?;
+    Ok(result)
+}
+
+/// Insert a transaction and its audit entry inside an existing DB transaction.
+///
+/// A status outside [`transaction_service::ALL_STATUSES`] is rejected with
+/// [`AppError::InvalidStatusTransition`].
+///
+/// A non-null `anchor_transaction_id` is claimed in `transaction_anchor_ids`
+/// first; if another transaction already holds it this fails with
+/// [`AppError::TransactionAlreadyProcessed`].
+pub async fn insert_transaction_in_tx(
+    db_tx: &mut SqlxTransaction<'_, Postgres>,
+    tx: &Transaction,
+) -> std::result::Result<Transaction, AppError> {
+    transaction_service::validate_status(&tx.status)?;
+
+    if let Some(anchor_transaction_id) = &tx.anchor_transaction_id {
+        sqlx::query(
+            "INSERT INTO transaction_anchor_ids (anchor_transaction_id, transaction_id) VALUES ($1, $2)",
+        )
+        .bind(anchor_transaction_id)
+        .bind(tx.id)
+        .execute(&mut **db_tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                AppError::TransactionAlreadyProcessed(format!(
+                    "anchor_transaction_id '{}' has already been recorded",
+                    anchor_transaction_id
+                ))
+            }
+            other => AppError::Database(other),
+        })?;
+    }
 
     let result = sqlx::query_as::<_, Transaction>(
         r#"
         INSERT INTO transactions (
             id, stellar_account, amount, asset_code, status,
             created_at, updated_at, anchor_transaction_id, callback_type, callback_status,
-            settlement_id, memo, memo_type, metadata
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            settlement_id, memo, memo_type, metadata, asset_issuer, partner_id
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
         RETURNING *
         "#,
     )
@@ -35,18 +78,22 @@ pub async fn insert_transaction(pool: &PgPool, tx: &Transaction) -> Result<Trans
     .bind(&tx.memo)
     .bind(&tx.memo_type)
     .bind(&tx.metadata)
-    .fetch_one(&mut *db_tx)
-    .await?;
+    .bind(&tx.asset_issuer)
+    .bind(tx.partner_id)
+    .fetch_one(&mut **db_tx)
+    .await
+    .map_err(transaction_service::map_status_violation)?;
 
     // Audit log: transaction created
     AuditLog::log_creation(
-        &mut db_tx,
+        db_tx,
         result.id,
         ENTITY_TRANSACTION,
         json!({
             "stellar_account": result.stellar_account,
             "amount": result.amount.to_string(),
             "asset_code": result.asset_code,
+            "asset_issuer": result.asset_issuer,
             "status": result.status,
             "anchor_transaction_id": result.anchor_transaction_id,
             "callback_type": result.callback_type,
@@ -54,15 +101,215 @@ pub async fn insert_transaction(pool: &PgPool, tx: &Transaction) -> Result<Trans
             "memo": result.memo,
             "memo_type": result.memo_type,
             "metadata": result.metadata,
+            "partner_id": result.partner_id,
         }),
         "system",
     )
     .await?;
 
-    db_tx.commit().await?;
     Ok(result)
 }
 
+/// Batches at least this large are loaded with `COPY`; smaller ones use a
+/// single multi-row `INSERT`
+pub const BULK_COPY_THRESHOLD: usize = 500;
+
+/// Rows sent to the server per `COPY` data message
+const COPY_CHUNK_ROWS: usize = 1000;
+
+const BULK_INSERT_COLUMNS: &str = "id, stellar_account, amount, asset_code, status, \
+    created_at, updated_at, anchor_transaction_id, callback_type, callback_status, \
+    settlement_id, memo, memo_type, metadata, asset_issuer, partner_id";
+
+/// Insert many transactions in one DB transaction and return how many were
+/// written. Large batches are streamed with `COPY`, small ones with a
+/// multi-row `INSERT`. Each row gets the same audit entry as
+/// [`insert_transaction_in_tx`].
+///
+/// The batch is all or nothing: an invalid status, or an
+/// `anchor_transaction_id` that is already recorded or repeated within the
+/// batch, fails the whole call and nothing is inserted.
+pub async fn bulk_insert_transactions(
+    pool: &PgPool,
+    txs: &[Transaction],
+) -> std::result::Result<u64, AppError> {
+    if txs.is_empty() {
+        return Ok(0);
+    }
+    let mut db_tx = pool.begin().await?;
+    let inserted = bulk_insert_transactions_in_tx(&mut db_tx, txs).await?;
+    db_tx.commit().await?;
+    Ok(inserted)
+}
+
+/// [`bulk_insert_transactions`] inside an existing DB transaction. On error
+/// the caller must roll back, as some rows may already be written.
+pub async fn bulk_insert_transactions_in_tx(
+    db_tx: &mut SqlxTransaction<'_, Postgres>,
+    txs: &[Transaction],
+) -> std::result::Result<u64, AppError> {
+    if txs.is_empty() {
+        return Ok(0);
+    }
+    for tx in txs {
+        transaction_service::validate_status(&tx.status)?;
+    }
+
+    claim_anchor_ids(db_tx, txs).await?;
+
+    let inserted = if txs.len() >= BULK_COPY_THRESHOLD {
+        copy_transactions(db_tx, txs).await
+    } else {
+        insert_transaction_rows(db_tx, txs).await
+    }
+    .map_err(transaction_service::map_status_violation)?;
+
+    let ids: Vec<Uuid> = txs.iter().map(|tx| tx.id).collect();
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (entity_id, entity_type, action, new_val, actor)
+        SELECT id, $2, 'created', jsonb_build_object(
+            'stellar_account', stellar_account,
+            'amount', amount::text,
+            'asset_code', asset_code,
+            'asset_issuer', asset_issuer,
+            'status', status,
+            'anchor_transaction_id', anchor_transaction_id,
+            'callback_type', callback_type,
+            'callback_status', callback_status,
+            'memo', memo,
+            'memo_type', memo_type,
+            'metadata', metadata,
+            'partner_id', partner_id
+        ), 'system'
+        FROM transactions
+        WHERE id = ANY($1)
+        "#,
+    )
+    .bind(&ids)
+    .bind(ENTITY_TRANSACTION)
+    .execute(&mut **db_tx)
+    .await?;
+
+    Ok(inserted)
+}
+
+/// Claim every non-null anchor id of the batch in `transaction_anchor_ids`
+async fn claim_anchor_ids(
+    db_tx: &mut SqlxTransaction<'_, Postgres>,
+    txs: &[Transaction],
+) -> std::result::Result<(), AppError> {
+    let (anchor_ids, tx_ids): (Vec<&str>, Vec<Uuid>) = txs
+        .iter()
+        .filter_map(|tx| Some((tx.anchor_transaction_id.as_deref()?, tx.id)))
+        .unzip();
+    if anchor_ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO transaction_anchor_ids (anchor_transaction_id, transaction_id)
+        SELECT * FROM UNNEST($1::VARCHAR[], $2::UUID[])
+        "#,
+    )
+    .bind(&anchor_ids)
+    .bind(&tx_ids)
+    .execute(&mut **db_tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            AppError::TransactionAlreadyProcessed(
+                "batch contains an anchor_transaction_id that has already been recorded"
+                    .to_string(),
+            )
+        }
+        other => AppError::Database(other),
+    })?;
+    Ok(())
+}
+
+async fn copy_transactions(
+    db_tx: &mut SqlxTransaction<'_, Postgres>,
+    txs: &[Transaction],
+) -> Result<u64> {
+    let statement = format!(
+        "COPY transactions ({}) FROM STDIN WITH (FORMAT csv)",
+        BULK_INSERT_COLUMNS
+    );
+    let mut copy = db_tx.copy_in_raw(&statement).await?;
+    for chunk in txs.chunks(COPY_CHUNK_ROWS) {
+        let data: String = chunk.iter().map(copy_csv_row).collect();
+        if let Err(e) = copy.send(data.into_bytes()).await {
+            let _ = copy.abort(e.to_string()).await;
+            return Err(e);
+        }
+    }
+    copy.finish().await
+}
+
+async fn insert_transaction_rows(
+    db_tx: &mut SqlxTransaction<'_, Postgres>,
+    txs: &[Transaction],
+) -> Result<u64> {
+    let mut builder = sqlx::QueryBuilder::<Postgres>::new(format!(
+        "INSERT INTO transactions ({}) ",
+        BULK_INSERT_COLUMNS
+    ));
+    builder.push_values(txs, |mut row, tx| {
+        row.push_bind(tx.id)
+            .push_bind(&tx.stellar_account)
+            .push_bind(&tx.amount)
+            .push_bind(&tx.asset_code)
+            .push_bind(&tx.status)
+            .push_bind(tx.created_at)
+            .push_bind(tx.updated_at)
+            .push_bind(&tx.anchor_transaction_id)
+            .push_bind(&tx.callback_type)
+            .push_bind(&tx.callback_status)
+            .push_bind(tx.settlement_id)
+            .push_bind(&tx.memo)
+            .push_bind(&tx.memo_type)
+            .push_bind(&tx.metadata)
+            .push_bind(&tx.asset_issuer)
+            .push_bind(tx.partner_id);
+    });
+    Ok(builder.build().execute(&mut **db_tx).await?.rows_affected())
+}
+
+/// One `COPY ... (FORMAT csv)` line in [`BULK_INSERT_COLUMNS`] order. Values
+/// are always quoted, so only an empty unquoted field reads as NULL.
+fn copy_csv_row(tx: &Transaction) -> String {
+    let fields = [
+        Some(tx.id.to_string()),
+        Some(tx.stellar_account.clone()),
+        Some(tx.amount.to_string()),
+        Some(tx.asset_code.clone()),
+        Some(tx.status.clone()),
+        Some(tx.created_at.to_rfc3339()),
+        Some(tx.updated_at.to_rfc3339()),
+        tx.anchor_transaction_id.clone(),
+        tx.callback_type.clone(),
+        tx.callback_status.clone(),
+        tx.settlement_id.map(|id| id.to_string()),
+        tx.memo.clone(),
+        tx.memo_type.clone(),
+        tx.metadata.as_ref().map(|m| m.to_string()),
+        tx.asset_issuer.clone(),
+        tx.partner_id.map(|id| id.to_string()),
+    ];
+    let mut line = fields
+        .iter()
+        .map(|field| match field {
+            Some(value) => format!("\"{}\"", value.replace('"', "\"\"")),
+            None => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
 pub async fn get_transaction(pool: &PgPool, id: Uuid) -> Result<Transaction> {
     sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = $1")
         .bind(id)
@@ -70,68 +317,133 @@ pub async fn get_transaction(pool: &PgPool, id: Uuid) -> Result<Transaction> {
         .await
 }
 
+/// Whether a transaction has already claimed `anchor_transaction_id`
+pub async fn anchor_transaction_id_exists(
+    pool: &PgPool,
+    anchor_transaction_id: &str,
+) -> Result<bool> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM transaction_anchor_ids WHERE anchor_transaction_id = $1)",
+    )
+    .bind(anchor_transaction_id)
+    .fetch_one(pool)
+    .await
+}
+
 pub async fn list_transactions(
     pool: &PgPool,
     limit: i64,
     cursor: Option<(DateTime<Utc>, Uuid)>,
     backward: bool,
 ) -> Result<Vec<Transaction>> {
-    // We implement cursor-based pagination on (created_at, id).
-    // Default ordering for the API is newest-first (created_at DESC, id DESC).
-    // For forward pagination (older items) we query WHERE (created_at, id) < (cursor)
-    // For backward pagination (newer items) we query WHERE (created_at, id) > (cursor)
-
-    if let Some((ts, id)) = cursor {
-        if !backward {
-            // forward page: older records than cursor
-            let q = sqlx::query_as::<_, Transaction>(
-                "SELECT * FROM transactions WHERE (created_at, id) < ($1, $2) ORDER BY created_at DESC, id DESC LIMIT $3",
-            )
-            .bind(ts)
-            .bind(id)
-            .bind(limit)
-            .fetch_all(pool)
-            .await?;
-            Ok(q)
-        } else {
-            // backward page: newer records than cursor; fetch asc then reverse to keep newest-first
-            let mut rows = sqlx::query_as::<_, Transaction>(
-                "SELECT * FROM transactions WHERE (created_at, id) > ($1, $2) ORDER BY created_at ASC, id ASC LIMIT $3",
-            )
-            .bind(ts)
-            .bind(id)
-            .bind(limit)
-            .fetch_all(pool)
-            .await?;
-            rows.reverse();
-            Ok(rows)
-        }
-    } else if !backward {
-        // first page, newest first
-        let q = sqlx::query_as::<_, Transaction>(
-            "SELECT * FROM transactions ORDER BY created_at DESC, id DESC LIMIT $1",
-        )
-        .bind(limit)
-        .fetch_all(pool)
-        .await?;
-        Ok(q)
+    let direction = if backward {
+        PageDirection::Backward
     } else {
-        // backward without cursor -> return last page (oldest first reversed)
-        let mut rows = sqlx::query_as::<_, Transaction>(
-            "SELECT * FROM transactions ORDER BY created_at ASC, id ASC LIMIT $1",
-        )
+        PageDirection::Forward
+    };
+    list_partner_transactions(pool, None, limit, cursor, direction, SortOrder::Desc).await
+}
+
+/// Newest-first page of transactions whose `metadata.compliance_tag` is
+/// `tag`, continuing past `cursor`
+pub async fn list_transactions_by_compliance_tag(
+    pool: &PgPool,
+    tag: &str,
+    limit: i64,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<Vec<Transaction>> {
+    let (ts, id) = cursor.unzip();
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions
+         WHERE metadata->>'compliance_tag' = $1
+           AND ($2::TIMESTAMPTZ IS NULL OR (created_at, id) < ($2, $3))
+         ORDER BY created_at DESC, id DESC
+         LIMIT $4",
+    )
+    .bind(tag)
+    .bind(ts)
+    .bind(id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// [`list_transactions`] restricted to one partner's rows; `None` lists all.
+/// Rows come back in `order` whichever way the page moves.
+pub async fn list_partner_transactions(
+    pool: &PgPool,
+    partner_id: Option<Uuid>,
+    limit: i64,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+    direction: PageDirection,
+    order: SortOrder,
+) -> Result<Vec<Transaction>> {
+    // We implement cursor-based pagination on (created_at, id). A page is
+    // scanned away from the cursor: ascending with (created_at, id) > cursor,
+    // descending with (created_at, id) < cursor. Pages moving backward are
+    // scanned against `order` and reversed afterwards.
+    let (cmp, dir) = if order.scans_ascending(direction) {
+        (">", "ASC")
+    } else {
+        ("<", "DESC")
+    };
+    let sql = format!(
+        "SELECT * FROM transactions
+         WHERE ($1::TIMESTAMPTZ IS NULL OR (created_at, id) {cmp} ($1, $2))
+           AND ($4::UUID IS NULL OR partner_id = $4)
+         ORDER BY created_at {dir}, id {dir}
+         LIMIT $3"
+    );
+
+    let (ts, id) = cursor.unzip();
+    let mut rows = sqlx::query_as::<_, Transaction>(&sql)
+        .bind(ts)
+        .bind(id)
         .bind(limit)
+        .bind(partner_id)
         .fetch_all(pool)
         .await?;
+    if direction.is_backward() {
         rows.reverse();
-        Ok(rows)
     }
+    Ok(rows)
 }
 
+/// Lock up to `limit` completed, unsettled transactions for `asset_code`,
+/// oldest first, so a large backlog is settled over several batches.
 pub async fn get_unsettled_transactions(
     executor: &mut SqlxTransaction<'_, Postgres>,
     asset_code: &str,
     end_time: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        r#"
+        SELECT * FROM transactions
+        WHERE status = 'completed'
+        AND settlement_id IS NULL
+        AND asset_code = $1
+        AND updated_at <= $2
+        ORDER BY created_at ASC, id ASC
+        LIMIT $3
+        FOR UPDATE
+        "#,
+    )
+    .bind(asset_code)
+    .bind(end_time)
+    .bind(limit)
+    .fetch_all(&mut **executor)
+    .await
+}
+
+/// Lock the remaining unsettled transactions created at exactly `created_at`
+/// after `after_id`, so a batch cut at `created_at` can take the whole tie.
+pub async fn get_unsettled_transactions_created_at(
+    executor: &mut SqlxTransaction<'_, Postgres>,
+    asset_code: &str,
+    end_time: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    after_id: Uuid,
 ) -> Result<Vec<Transaction>> {
     sqlx::query_as::<_, Transaction>(
         r#"
@@ -140,15 +452,43 @@ pub async fn get_unsettled_transactions(
         AND settlement_id IS NULL
         AND asset_code = $1
         AND updated_at <= $2
+        AND created_at = $3
+        AND id > $4
+        ORDER BY id ASC
         FOR UPDATE
         "#,
     )
     .bind(asset_code)
     .bind(end_time)
+    .bind(created_at)
+    .bind(after_id)
     .fetch_all(&mut **executor)
     .await
 }
 
+/// Read-only version of [`get_unsettled_transactions`] without row locks.
+/// `asset_code` of `None` covers every asset.
+pub async fn preview_unsettled_transactions(
+    pool: &PgPool,
+    asset_code: Option<&str>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        r#"
+        SELECT * FROM transactions
+        WHERE status = 'completed'
+        AND settlement_id IS NULL
+        AND ($1::TEXT IS NULL OR asset_code = $1)
+        AND updated_at <= $2
+        ORDER BY asset_code, created_at, id
+        "#,
+    )
+    .bind(asset_code)
+    .bind(end_time)
+    .fetch_all(pool)
+    .await
+}
+
 pub async fn update_transactions_settlement(
     executor: &mut SqlxTransaction<'_, Postgres>,
     tx_ids: &[Uuid],
@@ -179,13 +519,142 @@ pub async fn update_transactions_settlement(
     Ok(())
 }
 
+/// Replaces `stellar_account` on anonymized transactions. The column cannot be
+/// null, and a hash of a public account id is reversible by lookup.
+pub const ANONYMIZED_ACCOUNT: &str = "ANONYMIZED";
+
+/// Fields the PII retention job clears, as recorded in its audit entries
+pub const ANONYMIZED_FIELDS: [&str; 4] = ["stellar_account", "memo", "memo_type", "metadata"];
+
+/// Audit action recorded for each anonymized transaction
+pub const ACTION_ANONYMIZED: &str = "anonymized";
+
+/// Anonymize up to `limit` transactions created before `cutoff` that are not
+/// anonymized yet, auditing each one. Returns the ids anonymized; fewer than
+/// `limit` means none are left.
+///
+/// The same fields are removed from the transactions' earlier audit entries,
+/// which copy them at creation. `updated_at` is left alone so settlement
+/// windows are unaffected.
+pub async fn anonymize_transactions_before(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Uuid>> {
+    let mut db_tx = pool.begin().await?;
+    let ids: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE transactions
+        SET stellar_account = $1, memo = NULL, memo_type = NULL, metadata = NULL,
+            anonymized_at = NOW()
+        WHERE (id, created_at) IN (
+            SELECT id, created_at FROM transactions
+            WHERE created_at < $2 AND anonymized_at IS NULL
+            ORDER BY created_at
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id
+        "#,
+    )
+    .bind(ANONYMIZED_ACCOUNT)
+    .bind(cutoff)
+    .bind(limit)
+    .fetch_all(&mut *db_tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE audit_logs
+        SET old_val = old_val - $3::TEXT[], new_val = new_val - $3::TEXT[]
+        WHERE entity_type = $1 AND entity_id = ANY($2)
+        "#,
+    )
+    .bind(ENTITY_TRANSACTION)
+    .bind(&ids)
+    .bind(&ANONYMIZED_FIELDS[..])
+    .execute(&mut *db_tx)
+    .await?;
+
+    for id in &ids {
+        AuditLog::log(
+            &mut db_tx,
+            *id,
+            ENTITY_TRANSACTION,
+            ACTION_ANONYMIZED,
+            None,
+            Some(json!({ "fields": ANONYMIZED_FIELDS })),
+            "system",
+        )
+        .await?;
+    }
+
+    db_tx.commit().await?;
+    Ok(ids)
+}
+
+// --- Transaction Note Queries ---
+
+/// Attach a note to a transaction and audit it as created by `author`
+pub async fn insert_transaction_note(
+    pool: &PgPool,
+    transaction_id: Uuid,
+    author: &str,
+    note: &str,
+) -> Result<TransactionNote> {
+    let mut db_tx = pool.begin().await?;
+    let created = sqlx::query_as::<_, TransactionNote>(
+        r#"
+        INSERT INTO transaction_notes (transaction_id, author, note)
+        VALUES ($1, $2, $3)
+        RETURNING *
+        "#,
+    )
+    .bind(transaction_id)
+    .bind(author)
+    .bind(note)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    AuditLog::log_creation(
+        &mut db_tx,
+        created.id,
+        ENTITY_TRANSACTION_NOTE,
+        json!({
+            "transaction_id": created.transaction_id,
+            "note": created.note,
+        }),
+        author,
+    )
+    .await?;
+
+    db_tx.commit().await?;
+    Ok(created)
+}
+
+/// A transaction's notes, oldest first
+pub async fn list_transaction_notes(
+    pool: &PgPool,
+    transaction_id: Uuid,
+) -> Result<Vec<TransactionNote>> {
+    sqlx::query_as::<_, TransactionNote>(
+        "SELECT * FROM transaction_notes WHERE transaction_id = $1 ORDER BY created_at, id",
+    )
+    .bind(transaction_id)
+    .fetch_all(pool)
+    .await
+}
+
 // --- Settlement Queries ---
 
+/// Insert a settlement inside an existing DB transaction. A settlement for
+/// the same asset and period that is not voided fails with
+/// [`AppError::SettlementAlreadyExists`].
 pub async fn insert_settlement(
     executor: &mut SqlxTransaction<'_, Postgres>,
     settlement: &Settlement,
-) -> Result<Settlement> {
-    let result = sqlx::query_as::<_, Settlement>(
+) -> std::result::Result<Settlement, AppError> {
+    sqlx::query_as::<_, Settlement>(
         r#"
         INSERT INTO settlements (
             id, asset_code, total_amount, tx_count, period_start, period_end, status, created_at, updated_at
@@ -203,9 +672,16 @@ pub async fn insert_settlement(
     .bind(settlement.created_at)
     .bind(settlement.updated_at)
     .fetch_one(&mut **executor)
-    .await?;
-
-    Ok(result)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            AppError::SettlementAlreadyExists(format!(
+                "{} is already settled for {} to {}",
+                settlement.asset_code, settlement.period_start, settlement.period_end
+            ))
+        }
+        other => AppError::query_failed(other),
+    })
 }
 
 pub async fn get_settlement(pool: &PgPool, id: Uuid) -> Result<Settlement> {
@@ -215,6 +691,46 @@ pub async fn get_settlement(pool: &PgPool, id: Uuid) -> Result<Settlement> {
         .await
 }
 
+pub async fn get_settlement_optional(pool: &PgPool, id: Uuid) -> Result<Option<Settlement>> {
+    sqlx::query_as::<_, Settlement>("SELECT * FROM settlements WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Set the status of settlement `id` while it is still `from`; `None` when
+/// the settlement is missing or its status has changed
+pub async fn update_settlement_status(
+    pool: &PgPool,
+    id: Uuid,
+    from: &str,
+    to: &str,
+) -> Result<Option<Settlement>> {
+    sqlx::query_as::<_, Settlement>(
+        "UPDATE settlements SET status = $3, updated_at = NOW()
+         WHERE id = $1 AND status = $2
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(from)
+    .bind(to)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Transactions included in a settlement, oldest first
+pub async fn list_settlement_transactions(
+    pool: &PgPool,
+    settlement_id: Uuid,
+) -> Result<Vec<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE settlement_id = $1 ORDER BY created_at, id",
+    )
+    .bind(settlement_id)
+    .fetch_all(pool)
+    .await
+}
+
 pub async fn list_settlements(pool: &PgPool, limit: i64, offset: i64) -> Result<Vec<Settlement>> {
     sqlx::query_as::<_, Settlement>(
         "SELECT * FROM settlements ORDER BY created_at DESC LIMIT $1 OFFSET $2",
@@ -225,6 +741,12 @@ pub async fn list_settlements(pool: &PgPool, limit: i64, offset: i64) -> Result<
     .await
 }
 
+pub async fn count_settlements(pool: &PgPool) -> Result<i64> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM settlements")
+        .fetch_one(pool)
+        .await
+}
+
 pub async fn get_unique_assets_to_settle(pool: &PgPool) -> Result<Vec<String>> {
     let rows = sqlx::query(
         "SELECT DISTINCT asset_code FROM transactions WHERE status = 'completed' AND settlement_id IS NULL"
@@ -240,147 +762,120 @@ pub async fn get_unique_assets_to_settle(pool: &PgPool) -> Result<Vec<String>> {
 
 // --- Transaction Search ---
 
-#[allow(clippy::too_many_arguments)]
-pub async fn search_transactions(
-    pool: &PgPool,
-    status: Option<&str>,
-    asset_code: Option<&str>,
-    min_amount: Option<&BigDecimal>,
-    max_amount: Option<&BigDecimal>,
-    from_date: Option<DateTime<Utc>>,
-    to_date: Option<DateTime<Utc>>,
-    stellar_account: Option<&str>,
-    limit: i64,
-    cursor: Option<(DateTime<Utc>, Uuid)>,
-) -> Result<(i64, Vec<Transaction>)> {
-    // Build dynamic WHERE clause
-    let mut conditions = Vec::new();
-    let mut param_count = 1;
-
-    if status.is_some() {
-        conditions.push(format!("status = ${}", param_count));
-        param_count += 1;
-    }
-
-    if asset_code.is_some() {
-        conditions.push(format!("asset_code = ${}", param_count));
-        param_count += 1;
-    }
-
-    if min_amount.is_some() {
-        conditions.push(format!("amount >= ${}", param_count));
-        param_count += 1;
-    }
-
-    if max_amount.is_some() {
-        conditions.push(format!("amount <= ${}", param_count));
-        param_count += 1;
-    }
-
-    if from_date.is_some() {
-        conditions.push(format!("created_at >= ${}", param_count));
-        param_count += 1;
-    }
-
-    if to_date.is_some() {
-        conditions.push(format!("created_at <= ${}", param_count));
-        param_count += 1;
-    }
+/// Filters shared by transaction search and count; every `Some` field must
+/// match
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransactionFilters<'a> {
+    pub status: Option<&'a str>,
+    pub asset_code: Option<&'a str>,
+    pub asset_issuer: Option<&'a str>,
+    pub min_amount: Option<&'a BigDecimal>,
+    pub max_amount: Option<&'a BigDecimal>,
+    /// Inclusive lower bound on `created_at`
+    pub from_date: Option<DateTime<Utc>>,
+    /// Inclusive upper bound on `created_at`
+    pub to_date: Option<DateTime<Utc>>,
+    pub stellar_account: Option<&'a str>,
+    pub settlement_id: Option<Uuid>,
+    pub partner_id: Option<Uuid>,
+}
 
-    if stellar_account.is_some() {
-        conditions.push(format!("stellar_account = ${}", param_count));
-        param_count += 1;
+impl<'a> TransactionFilters<'a> {
+    /// Append ` WHERE ...` for the set filters, plus the keyset condition
+    /// for `cursor`; appends nothing when neither is set
+    fn push_where(
+        &self,
+        builder: &mut sqlx::QueryBuilder<'a, Postgres>,
+        cursor: Option<(DateTime<Utc>, Uuid)>,
+    ) {
+        let mut separator = " WHERE ";
+        let mut next = |builder: &mut sqlx::QueryBuilder<'a, Postgres>, condition: &str| {
+            builder.push(separator).push(condition);
+            separator = " AND ";
+        };
+
+        if let Some(status) = self.status {
+            next(builder, "status = ");
+            builder.push_bind(status);
+        }
+        if let Some(asset_code) = self.asset_code {
+            next(builder, "asset_code = ");
+            builder.push_bind(asset_code);
+        }
+        if let Some(asset_issuer) = self.asset_issuer {
+            next(builder, "asset_issuer = ");
+            builder.push_bind(asset_issuer);
+        }
+        if let Some(min) = self.min_amount {
+            next(builder, "amount >= ");
+            builder.push_bind(min);
+        }
+        if let Some(max) = self.max_amount {
+            next(builder, "amount <= ");
+            builder.push_bind(max);
+        }
+        if let Some(from) = self.from_date {
+            next(builder, "created_at >= ");
+            builder.push_bind(from);
+        }
+        if let Some(to) = self.to_date {
+            next(builder, "created_at <= ");
+            builder.push_bind(to);
+        }
+        if let Some(account) = self.stellar_account {
+            next(builder, "stellar_account = ");
+            builder.push_bind(account);
+        }
+        if let Some(settlement_id) = self.settlement_id {
+            next(builder, "settlement_id = ");
+            builder.push_bind(settlement_id);
+        }
+        if let Some(partner_id) = self.partner_id {
+            next(builder, "partner_id = ");
+            builder.push_bind(partner_id);
+        }
+        if let Some((ts, id)) = cursor {
+            next(builder, "(created_at, id) < (");
+            builder.push_bind(ts).push(", ").push_bind(id).push(")");
+        }
     }
+}
 
-    // Add cursor condition
-    if cursor.is_some() {
-        conditions.push(format!(
-            "(created_at, id) < (${}, ${})",
-            param_count,
-            param_count + 1
-        ));
-        param_count += 2;
-    }
+/// Number of transactions matching `filters` after `cursor`
+pub async fn count_transactions(
+    pool: &PgPool,
+    filters: &TransactionFilters<'_>,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<i64> {
+    let mut builder = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM transactions");
+    filters.push_where(&mut builder, cursor);
+    builder.build_query_scalar().fetch_one(pool).await
+}
 
-    let where_clause = if conditions.is_empty() {
-        String::new()
+/// Up to `limit` transactions matching `filters`, newest first, after
+/// `cursor`. With `with_count` the total after the cursor is counted too.
+pub async fn search_transactions(
+    pool: &PgPool,
+    filters: &TransactionFilters<'_>,
+    limit: i64,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+    with_count: bool,
+) -> Result<(Option<i64>, Vec<Transaction>)> {
+    let total = if with_count {
+        Some(count_transactions(pool, filters, cursor).await?)
     } else {
-        format!("WHERE {}", conditions.join(" AND "))
+        None
     };
 
-    // Build count query
-    let count_query = format!(
-        "SELECT COUNT(*) as count FROM transactions {}",
-        where_clause
-    );
-
-    // Build data query with pagination
-    let data_query = format!(
-        "SELECT * FROM transactions {} ORDER BY created_at DESC, id DESC LIMIT ${}",
-        where_clause, param_count
-    );
-
-    // Execute count query
-    let mut count_query_builder = sqlx::query(&count_query);
-
-    if let Some(s) = status {
-        count_query_builder = count_query_builder.bind(s);
-    }
-    if let Some(a) = asset_code {
-        count_query_builder = count_query_builder.bind(a);
-    }
-    if let Some(min) = min_amount {
-        count_query_builder = count_query_builder.bind(min);
-    }
-    if let Some(max) = max_amount {
-        count_query_builder = count_query_builder.bind(max);
-    }
-    if let Some(from) = from_date {
-        count_query_builder = count_query_builder.bind(from);
-    }
-    if let Some(to) = to_date {
-        count_query_builder = count_query_builder.bind(to);
-    }
-    if let Some(acc) = stellar_account {
-        count_query_builder = count_query_builder.bind(acc);
-    }
-    if let Some((ts, id)) = cursor {
-        count_query_builder = count_query_builder.bind(ts).bind(id);
-    }
-
-    let count_row = count_query_builder.fetch_one(pool).await?;
-    let total: i64 = count_row.try_get("count")?;
-
-    // Execute data query
-    let mut data_query_builder = sqlx::query_as::<_, Transaction>(&data_query);
-
-    if let Some(s) = status {
-        data_query_builder = data_query_builder.bind(s);
-    }
-    if let Some(a) = asset_code {
-        data_query_builder = data_query_builder.bind(a);
-    }
-    if let Some(min) = min_amount {
-        data_query_builder = data_query_builder.bind(min);
-    }
-    if let Some(max) = max_amount {
-        data_query_builder = data_query_builder.bind(max);
-    }
-    if let Some(from) = from_date {
-        data_query_builder = data_query_builder.bind(from);
-    }
-    if let Some(to) = to_date {
-        data_query_builder = data_query_builder.bind(to);
-    }
-    if let Some(acc) = stellar_account {
-        data_query_builder = data_query_builder.bind(acc);
-    }
-    if let Some((ts, id)) = cursor {
-        data_query_builder = data_query_builder.bind(ts).bind(id);
-    }
-    data_query_builder = data_query_builder.bind(limit);
-
-    let transactions = data_query_builder.fetch_all(pool).await?;
+    let mut builder = sqlx::QueryBuilder::new("SELECT * FROM transactions");
+    filters.push_where(&mut builder, cursor);
+    builder
+        .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(limit);
+    let transactions = builder
+        .build_query_as::<Transaction>()
+        .fetch_all(pool)
+        .await?;
 
     Ok((total, transactions))
 }
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -25,6 +25,16 @@ pub mod codes {
         ("ERR_DATABASE_001", 500, "Database connection error");
     pub const DATABASE_002: (&str, u16, &str) =
         ("ERR_DATABASE_002", 500, "Database query execution error");
+    pub const DATABASE_003: (&str, u16, &str) = (
+        "ERR_DATABASE_003",
+        503,
+        "Primary database unavailable - service is read-only",
+    );
+    pub const DATABASE_004: (&str, u16, &str) = (
+        "ERR_DATABASE_004",
+        503,
+        "Database connection pool exhausted - retry shortly",
+    );
     pub const VALIDATION_001: (&str, u16, &str) = (
         "ERR_VALIDATION_001",
         400,
@@ -37,6 +47,16 @@ pub mod codes {
         400,
         "Bad request - invalid parameters",
     );
+    pub const BAD_REQUEST_002: (&str, u16, &str) = (
+        "ERR_BAD_REQUEST_002",
+        413,
+        "Request body exceeds the size limit",
+    );
+    pub const BAD_REQUEST_003: (&str, u16, &str) = (
+        "ERR_BAD_REQUEST_003",
+        408,
+        "Request was not completed within the server timeout",
+    );
     pub const UNAUTHORIZED_001: (&str, u16, &str) = (
         "ERR_UNAUTHORIZED_001",
         401,
@@ -68,6 +88,11 @@ pub mod codes {
         400,
         "Invalid transaction status transition",
     );
+    pub const TRANSACTION_006: (&str, u16, &str) = (
+        "ERR_TRANSACTION_006",
+        400,
+        "Transaction amount above maximum",
+    );
 
     // Webhook specific errors
     pub const WEBHOOK_001: (&str, u16, &str) =
@@ -80,12 +105,36 @@ pub mod codes {
         ("ERR_SETTLEMENT_001", 400, "Invalid settlement amount");
     pub const SETTLEMENT_002: (&str, u16, &str) =
         ("ERR_SETTLEMENT_002", 409, "Settlement already exists");
+    pub const SETTLEMENT_003: (&str, u16, &str) = (
+        "ERR_SETTLEMENT_003",
+        400,
+        "Invalid settlement status transition",
+    );
+
+    // Background job errors
+    pub const JOB_001: (&str, u16, &str) = ("ERR_JOB_001", 409, "Job is already running");
 
     // Rate limiting
     pub const RATE_LIMIT_001: (&str, u16, &str) =
         ("ERR_RATE_LIMIT_001", 429, "Rate limit exceeded");
 }
 
+/// Version of the error catalog served at `/errors`
+pub const ERROR_CATALOG_VERSION: &str = "1.0.0";
+
+/// Error codes whose code starts with `category`, e.g. `ERR_SETTLEMENT_`.
+/// Matching ignores case and the `ERR_` prefix may be omitted.
+pub fn error_codes_in_category(category: &str) -> Vec<ErrorCode> {
+    let mut prefix = category.trim().to_ascii_uppercase();
+    if !prefix.starts_with("ERR_") {
+        prefix.insert_str(0, "ERR_");
+    }
+    get_all_error_codes()
+        .into_iter()
+        .filter(|e| e.code.starts_with(&prefix))
+        .collect()
+}
+
 /// Get all error codes as a vector for catalog generation
 pub fn get_all_error_codes() -> Vec<ErrorCode> {
     vec![
@@ -99,6 +148,16 @@ pub fn get_all_error_codes() -> Vec<ErrorCode> {
             http_status: codes::DATABASE_002.1,
             description: codes::DATABASE_002.2,
         },
+        ErrorCode {
+            code: codes::DATABASE_003.0,
+            http_status: codes::DATABASE_003.1,
+            description: codes::DATABASE_003.2,
+        },
+        ErrorCode {
+            code: codes::DATABASE_004.0,
+            http_status: codes::DATABASE_004.1,
+            description: codes::DATABASE_004.2,
+        },
         ErrorCode {
             code: codes::VALIDATION_001.0,
             http_status: codes::VALIDATION_001.1,
@@ -119,6 +178,16 @@ pub fn get_all_error_codes() -> Vec<ErrorCode> {
             http_status: codes::BAD_REQUEST_001.1,
             description: codes::BAD_REQUEST_001.2,
         },
+        ErrorCode {
+            code: codes::BAD_REQUEST_002.0,
+            http_status: codes::BAD_REQUEST_002.1,
+            description: codes::BAD_REQUEST_002.2,
+        },
+        ErrorCode {
+            code: codes::BAD_REQUEST_003.0,
+            http_status: codes::BAD_REQUEST_003.1,
+            description: codes::BAD_REQUEST_003.2,
+        },
         ErrorCode {
             code: codes::UNAUTHORIZED_001.0,
             http_status: codes::UNAUTHORIZED_001.1,
@@ -159,6 +228,11 @@ pub fn get_all_error_codes() -> Vec<ErrorCode> {
             http_status: codes::TRANSACTION_005.1,
             description: codes::TRANSACTION_005.2,
         },
+        ErrorCode {
+            code: codes::TRANSACTION_006.0,
+            http_status: codes::TRANSACTION_006.1,
+            description: codes::TRANSACTION_006.2,
+        },
         ErrorCode {
             code: codes::WEBHOOK_001.0,
             http_status: codes::WEBHOOK_001.1,
@@ -179,6 +253,16 @@ pub fn get_all_error_codes() -> Vec<ErrorCode> {
             http_status: codes::SETTLEMENT_002.1,
             description: codes::SETTLEMENT_002.2,
         },
+        ErrorCode {
+            code: codes::SETTLEMENT_003.0,
+            http_status: codes::SETTLEMENT_003.1,
+            description: codes::SETTLEMENT_003.2,
+        },
+        ErrorCode {
+            code: codes::JOB_001.0,
+            http_status: codes::JOB_001.1,
+            description: codes::JOB_001.2,
+        },
         ErrorCode {
             code: codes::RATE_LIMIT_001.0,
             http_status: codes::RATE_LIMIT_001.1,
@@ -190,11 +274,18 @@ pub fn get_all_error_codes() -> Vec<ErrorCode> {
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(sqlx::Error),
+
+    /// No pooled connection became free within the acquire timeout
+    #[error("Database pool exhausted: {0}")]
+    PoolExhausted(String),
 
     #[error("Database error: {0}")]
     DatabaseError(String),
 
+    #[error("Service is read-only: {0}")]
+    ReadOnly(String),
+
     #[error("Validation error: {0}")]
     Validation(String),
 
@@ -207,6 +298,12 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Request timeout: {0}")]
+    RequestTimeout(String),
+
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
@@ -217,6 +314,9 @@ pub enum AppError {
     #[error("Amount below minimum: {0}")]
     AmountBelowMinimum(String),
 
+    #[error("Amount above maximum: {0}")]
+    AmountAboveMaximum(String),
+
     #[error("Invalid Stellar address: {0}")]
     InvalidStellarAddress(String),
 
@@ -238,6 +338,12 @@ pub enum AppError {
     #[error("Settlement already exists: {0}")]
     SettlementAlreadyExists(String),
 
+    #[error("Invalid settlement status transition: {0}")]
+    InvalidSettlementTransition(String),
+
+    #[error("Job is already running: {0}")]
+    JobAlreadyRunning(String),
+
     #[error("Rate limit exceeded")]
     RateLimitExceeded,
 
@@ -250,16 +356,20 @@ pub enum AppError {
 
 impl AppError {
     /// Get the HTTP status code for this error
-    fn status_code(&self) -> StatusCode {
+    pub fn status_code(&self) -> StatusCode {
         match self {
             AppError::Database(_) | AppError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ReadOnly(_) | AppError::PoolExhausted(_) => StatusCode::SERVICE_UNAVAILABLE,
             AppError::Validation(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::RequestTimeout(_) => StatusCode::REQUEST_TIMEOUT,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             AppError::InvalidTransactionAmount(_) => StatusCode::BAD_REQUEST,
             AppError::AmountBelowMinimum(_) => StatusCode::BAD_REQUEST,
+            AppError::AmountAboveMaximum(_) => StatusCode::BAD_REQUEST,
             AppError::InvalidStellarAddress(_) => StatusCode::BAD_REQUEST,
             AppError::TransactionAlreadyProcessed(_) => StatusCode::CONFLICT,
             AppError::InvalidStatusTransition(_) => StatusCode::BAD_REQUEST,
@@ -267,25 +377,71 @@ impl AppError {
             AppError::MalformedWebhookPayload(_) => StatusCode::BAD_REQUEST,
             AppError::InvalidSettlementAmount(_) => StatusCode::BAD_REQUEST,
             AppError::SettlementAlreadyExists(_) => StatusCode::CONFLICT,
+            AppError::InvalidSettlementTransition(_) => StatusCode::BAD_REQUEST,
+            AppError::JobAlreadyRunning(_) => StatusCode::CONFLICT,
             AppError::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
             AppError::AuthenticationFailed(_) => StatusCode::UNAUTHORIZED,
             AppError::InsufficientPermissions(_) => StatusCode::FORBIDDEN,
         }
     }
 
+    /// Message safe to show any caller. Database and internal errors are
+    /// reduced to their catalog description so SQL and internals never leak.
+    pub fn public_message(&self) -> String {
+        match self {
+            AppError::Database(_) => codes::DATABASE_001.2.to_string(),
+            AppError::DatabaseError(_) => codes::DATABASE_002.2.to_string(),
+            AppError::Internal(_) => codes::INTERNAL_001.2.to_string(),
+            AppError::PoolExhausted(_) => codes::DATABASE_004.2.to_string(),
+            other => other.to_string(),
+        }
+    }
+
+    /// The underlying cause hidden by [`public_message`](Self::public_message),
+    /// if any
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            AppError::Database(e) => Some(e.to_string()),
+            AppError::DatabaseError(detail)
+            | AppError::Internal(detail)
+            | AppError::PoolExhausted(detail) => Some(detail.clone()),
+            _ => None,
+        }
+    }
+
+    /// JSON error body; `detail` is only included when `include_detail` is set
+    pub fn body(&self, include_detail: bool) -> serde_json::Value {
+        let mut body = json!({
+            "error": self.public_message(),
+            "code": self.code(),
+            "status": self.status_code().as_u16(),
+        });
+        if include_detail {
+            if let Some(detail) = self.detail() {
+                body["detail"] = json!(detail);
+            }
+        }
+        body
+    }
+
     /// Get the stable error code for this error
     /// These codes are stable and should never be renamed or reused
     pub fn code(&self) -> &'static str {
         match self {
             AppError::Database(_) => codes::DATABASE_001.0,
             AppError::DatabaseError(_) => codes::DATABASE_002.0,
+            AppError::ReadOnly(_) => codes::DATABASE_003.0,
+            AppError::PoolExhausted(_) => codes::DATABASE_004.0,
             AppError::Validation(_) => codes::VALIDATION_001.0,
             AppError::NotFound(_) => codes::NOT_FOUND_001.0,
             AppError::Internal(_) => codes::INTERNAL_001.0,
             AppError::BadRequest(_) => codes::BAD_REQUEST_001.0,
+            AppError::PayloadTooLarge(_) => codes::BAD_REQUEST_002.0,
+            AppError::RequestTimeout(_) => codes::BAD_REQUEST_003.0,
             AppError::Unauthorized(_) => codes::UNAUTHORIZED_001.0,
             AppError::InvalidTransactionAmount(_) => codes::TRANSACTION_001.0,
             AppError::AmountBelowMinimum(_) => codes::TRANSACTION_002.0,
+            AppError::AmountAboveMaximum(_) => codes::TRANSACTION_006.0,
             AppError::InvalidStellarAddress(_) => codes::TRANSACTION_003.0,
             AppError::TransactionAlreadyProcessed(_) => codes::TRANSACTION_004.0,
             AppError::InvalidStatusTransition(_) => codes::TRANSACTION_005.0,
@@ -293,6 +449,8 @@ impl AppError {
             AppError::MalformedWebhookPayload(_) => codes::WEBHOOK_002.0,
             AppError::InvalidSettlementAmount(_) => codes::SETTLEMENT_001.0,
             AppError::SettlementAlreadyExists(_) => codes::SETTLEMENT_002.0,
+            AppError::InvalidSettlementTransition(_) => codes::SETTLEMENT_003.0,
+            AppError::JobAlreadyRunning(_) => codes::JOB_001.0,
             AppError::RateLimitExceeded => codes::RATE_LIMIT_001.0,
             AppError::AuthenticationFailed(_) => codes::AUTH_001.0,
             AppError::InsufficientPermissions(_) => codes::AUTH_002.0,
@@ -300,16 +458,71 @@ impl AppError {
     }
 }
 
+/// The error body with its `detail`, attached to responses for errors that
+/// have a cause to hide. [`crate::middleware::error_response`] sends it instead
+/// of the public body when `DEBUG_ERRORS` is on.
+#[derive(Debug, Clone)]
+pub struct DetailedErrorBody(pub serde_json::Value);
+
+/// `Retry-After` seconds sent with `ERR_DATABASE_004` unless
+/// `DB_POOL_RETRY_AFTER_SECS` says otherwise
+pub const DEFAULT_POOL_RETRY_AFTER_SECS: u64 = 1;
+
+/// Marks `ERR_DATABASE_004` responses, whose `Retry-After`
+/// [`crate::middleware::error_response`] sets from `DB_POOL_RETRY_AFTER_SECS`
+#[derive(Debug, Clone, Copy)]
+pub struct PoolExhaustedResponse;
+
+impl AppError {
+    /// Wrap a failed query as [`AppError::DatabaseError`], or as
+    /// [`AppError::PoolExhausted`] when no connection could be acquired
+    pub fn query_failed(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::PoolTimedOut => Self::pool_exhausted(e),
+            e => AppError::DatabaseError(e.to_string()),
+        }
+    }
+
+    /// A pool acquire timeout means the service is saturated rather than
+    /// broken, so it becomes a retryable 503 and is counted
+    fn pool_exhausted(e: sqlx::Error) -> Self {
+        crate::metrics::registry()
+            .increment_counter(crate::metrics::POOL_ACQUIRE_TIMEOUTS_METRIC, &[]);
+        AppError::PoolExhausted(e.to_string())
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::PoolTimedOut => Self::pool_exhausted(e),
+            e => AppError::Database(e),
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let status = self.status_code();
-        let body = Json(json!({
-            "error": self.to_string(),
-            "code": self.code(),
-            "status": status.as_u16(),
-        }));
+        if let Some(detail) = self.detail() {
+            // The response hides the cause, so keep it in the logs
+            tracing::error!(code = self.code(), detail = %detail, "Request failed");
+        }
 
-        (status, body).into_response()
+        let mut response = (status, Json(self.body(false))).into_response();
+        if self.detail().is_some() {
+            response
+                .extensions_mut()
+                .insert(DetailedErrorBody(self.body(true)));
+        }
+        if matches!(self, AppError::PoolExhausted(_)) {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(DEFAULT_POOL_RETRY_AFTER_SECS),
+            );
+            response.extensions_mut().insert(PoolExhaustedResponse);
+        }
+        response
     }
 }
 
@@ -333,6 +546,37 @@ pub struct ErrorCatalogResponse {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_database_detail_is_stripped_by_default() {
+        let error = AppError::DatabaseError(
+            "error returned from database: relation \"transactions\" does not exist".to_string(),
+        );
+
+        let body = error.body(false);
+        assert_eq!(body["error"], codes::DATABASE_002.2);
+        assert_eq!(body["code"], codes::DATABASE_002.0);
+        assert!(body.get("detail").is_none());
+        assert!(!body.to_string().contains("relation"));
+
+        let body = error.body(true);
+        assert_eq!(body["error"], codes::DATABASE_002.2);
+        assert!(body["detail"].as_str().unwrap().contains("relation"));
+    }
+
+    #[test]
+    fn test_client_errors_keep_their_message_without_detail() {
+        let error = AppError::NotFound("Transaction 42 not found".to_string());
+        for include_detail in [false, true] {
+            let body = error.body(include_detail);
+            assert_eq!(body["error"], "Not found: Transaction 42 not found");
+            assert!(body.get("detail").is_none());
+        }
+        assert_eq!(
+            AppError::Database(sqlx::Error::RowNotFound).body(true)["detail"],
+            sqlx::Error::RowNotFound.to_string()
+        );
+    }
+
     #[test]
     fn test_validation_error_status_code() {
         let error = AppError::Validation("Invalid input".to_string());
@@ -351,6 +595,16 @@ mod tests {
         assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[test]
+    fn test_query_failed_wraps_other_errors_as_database_error() {
+        let error = AppError::query_failed(sqlx::Error::RowNotFound);
+        assert!(matches!(error, AppError::DatabaseError(_)), "{:?}", error);
+        assert!(matches!(
+            AppError::query_failed(sqlx::Error::PoolTimedOut),
+            AppError::PoolExhausted(_)
+        ));
+    }
+
     #[test]
     fn test_internal_error_status_code() {
         let error = AppError::Internal("Something went wrong".to_string());
@@ -408,6 +662,14 @@ mod tests {
             AppError::BadRequest("test".to_string()).code(),
             codes::BAD_REQUEST_001.0
         );
+        assert_eq!(
+            AppError::PayloadTooLarge("test".to_string()).code(),
+            codes::BAD_REQUEST_002.0
+        );
+        assert_eq!(
+            AppError::RequestTimeout("test".to_string()).code(),
+            codes::BAD_REQUEST_003.0
+        );
         assert_eq!(
             AppError::Unauthorized("test".to_string()).code(),
             codes::UNAUTHORIZED_001.0
@@ -424,6 +686,10 @@ mod tests {
             AppError::DatabaseError("test".to_string()).code(),
             codes::DATABASE_002.0
         );
+        assert_eq!(
+            AppError::PoolExhausted("test".to_string()).code(),
+            codes::DATABASE_004.0
+        );
 
         // Custom errors
         assert_eq!(
@@ -446,6 +712,10 @@ mod tests {
             AppError::InvalidStatusTransition("test".to_string()).code(),
             codes::TRANSACTION_005.0
         );
+        assert_eq!(
+            AppError::AmountAboveMaximum("test".to_string()).code(),
+            codes::TRANSACTION_006.0
+        );
         assert_eq!(
             AppError::InvalidWebhookSignature.code(),
             codes::WEBHOOK_001.0
@@ -462,6 +732,14 @@ mod tests {
             AppError::SettlementAlreadyExists("test".to_string()).code(),
             codes::SETTLEMENT_002.0
         );
+        assert_eq!(
+            AppError::InvalidSettlementTransition("test".to_string()).code(),
+            codes::SETTLEMENT_003.0
+        );
+        assert_eq!(
+            AppError::JobAlreadyRunning("test".to_string()).code(),
+            codes::JOB_001.0
+        );
         assert_eq!(AppError::RateLimitExceeded.code(), codes::RATE_LIMIT_001.0);
         assert_eq!(
             AppError::AuthenticationFailed("test".to_string()).code(),
@@ -482,4 +760,22 @@ mod tests {
             "Error catalog should have at least 19 codes"
         );
     }
+
+    #[test]
+    fn test_error_codes_in_category() {
+        let settlement = error_codes_in_category("ERR_SETTLEMENT_");
+        assert!(!settlement.is_empty());
+        assert!(settlement
+            .iter()
+            .all(|e| e.code.starts_with("ERR_SETTLEMENT_")));
+
+        let bare: Vec<_> = error_codes_in_category("settlement")
+            .into_iter()
+            .map(|e| e.code)
+            .collect();
+        let prefixed: Vec<_> = settlement.into_iter().map(|e| e.code).collect();
+        assert_eq!(bare, prefixed);
+
+        assert!(error_codes_in_category("ERR_NOPE_").is_empty());
+    }
 }
@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{ServerError, ServerResult, Variables};
+use sha2::{Digest, Sha256};
+
+/// Message of the GraphQL error returned for a query outside the allowlist
+pub const NOT_ALLOWED_MESSAGE: &str = "Query is not in the GraphQL allowlist";
+
+/// Pre-registered GraphQL documents, identified by the hex SHA-256 of the
+/// exact query text. When enabled, only these queries are executed.
+#[derive(Debug, Clone, Default)]
+pub struct QueryAllowlist {
+    hashes: HashSet<String>,
+}
+
+impl QueryAllowlist {
+    /// Parse one hex SHA-256 per line. Blank lines and lines starting with
+    /// `#` are ignored.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut hashes = HashSet::new();
+        for line in contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            if line.len() != 64 || !line.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("'{}' is not a hex SHA-256", line));
+            }
+            hashes.insert(line.to_ascii_lowercase());
+        }
+        Ok(Self { hashes })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        Self::parse(&contents)
+    }
+
+    /// Read the file named by `GRAPHQL_ALLOWLIST_FILE`. Unset means the
+    /// allowlist is off. A file that cannot be loaded leaves the allowlist
+    /// on and empty, so every query is rejected rather than allowed.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("GRAPHQL_ALLOWLIST_FILE").ok()?;
+        Some(Self::from_file(Path::new(&path)).unwrap_or_else(|e| {
+            tracing::error!(
+                "Rejecting every GraphQL query, GRAPHQL_ALLOWLIST_FILE: {}",
+                e
+            );
+            Self::default()
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn allows(&self, query: &str) -> bool {
+        self.hashes.contains(&query_hash(query))
+    }
+}
+
+/// Hex SHA-256 of `query`, the form allowlist entries are written in
+pub fn query_hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+/// Process-wide allowlist, or `None` when the mode is off
+pub fn query_allowlist() -> Option<&'static QueryAllowlist> {
+    static ALLOWLIST: OnceLock<Option<QueryAllowlist>> = OnceLock::new();
+    ALLOWLIST.get_or_init(QueryAllowlist::from_env).as_ref()
+}
+
+/// Schema extension rejecting documents outside the allowlist before they
+/// are parsed, which covers subscriptions as well as HTTP requests
+pub struct AllowlistExtension(pub &'static QueryAllowlist);
+
+impl ExtensionFactory for AllowlistExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AllowlistCheck(self.0))
+    }
+}
+
+struct AllowlistCheck(&'static QueryAllowlist);
+
+#[async_trait::async_trait]
+impl Extension for AllowlistCheck {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        if !self.0.allows(query) {
+            return Err(ServerError::new(NOT_ALLOWED_MESSAGE, None));
+        }
+        next.run(ctx, query, variables).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_allowlist() {
+        let query = "{ transactions { id } }";
+        let contents = format!("# registered queries\n\n{}\n", query_hash(query));
+        let allowlist = QueryAllowlist::parse(&contents).unwrap();
+        assert_eq!(allowlist.len(), 1);
+        assert!(allowlist.allows(query));
+        // The hash covers the exact text
+        assert!(!allowlist.allows("{ transactions { id status } }"));
+
+        let upper = QueryAllowlist::parse(&query_hash(query).to_uppercase()).unwrap();
+        assert!(upper.allows(query));
+
+        assert!(QueryAllowlist::parse("not-a-hash").is_err());
+        assert!(QueryAllowlist::parse("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+use async_graphql::{Context, Guard, Result};
+
+use crate::middleware::auth::{CallerScope, SCOPE_READ};
+
+/// Per-request authentication context, inserted into the GraphQL request data
+/// by `POST /graphql` from the caller's `Authorization` header.
+#[derive(Debug, Clone, Default)]
+pub struct AuthContext {
+    pub is_admin: bool,
+}
+
+impl AuthContext {
+    pub fn admin() -> Self {
+        Self { is_admin: true }
+    }
+
+    /// Build the context from an `Authorization` header value
+    pub fn from_authorization(header: Option<&str>) -> Self {
+        Self {
+            is_admin: crate::middleware::auth::is_admin_authorized(header),
+        }
+    }
+}
+
+/// Guard for fields that mutate state and require admin credentials
+pub struct AdminGuard;
+
+#[async_trait::async_trait]
+impl Guard for AdminGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<AuthContext>() {
+            Some(auth) if auth.is_admin => Ok(()),
+            _ => Err("Unauthorized: admin credentials required".into()),
+        }
+    }
+}
+
+/// The caller's partner scope, inserted into the request data by
+/// `POST /graphql`, once it is known to allow reading transactions.
+/// Requests it could not be resolved for, such as those with an unknown
+/// key, are refused.
+pub fn caller_scope<'a>(ctx: &Context<'a>) -> Result<&'a CallerScope> {
+    let scope = ctx
+        .data_opt::<CallerScope>()
+        .ok_or("Unauthorized: a partner API key is required")?;
+    scope.require(SCOPE_READ)?;
+    Ok(scope)
+}
@@ -1,2 +1,4 @@
+pub mod allowlist;
+pub mod auth;
 pub mod resolvers;
 pub mod schema;
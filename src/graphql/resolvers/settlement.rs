@@ -1,21 +1,58 @@
 use crate::db::{models::Settlement, queries};
+use crate::graphql::auth::AdminGuard;
+use crate::graphql::schema::request_pool;
+use crate::services::SettlementService;
+use crate::utils::pagination::resolve_limit;
 use crate::AppState;
 use async_graphql::{Context, Object, Result};
+use uuid::Uuid;
 
 #[derive(Default)]
 pub struct SettlementQuery;
 
 #[Object]
 impl SettlementQuery {
+    async fn settlement(&self, ctx: &Context<'_>, id: Uuid) -> Result<Settlement> {
+        queries::get_settlement(request_pool(ctx)?, id)
+            .await
+            .map_err(|e| e.into())
+    }
+
     async fn settlements(
         &self,
         ctx: &Context<'_>,
         limit: Option<i64>,
         offset: Option<i64>,
     ) -> Result<Vec<Settlement>> {
-        let state = ctx.data::<AppState>()?;
-        queries::list_settlements(&state.db, limit.unwrap_or(20), offset.unwrap_or(0))
+        let limit = resolve_limit(limit, 20)?;
+        queries::list_settlements(request_pool(ctx)?, limit, offset.unwrap_or(0).max(0))
             .await
             .map_err(|e| e.into())
     }
 }
+
+#[derive(Default)]
+pub struct SettlementMutation;
+
+#[Object]
+impl SettlementMutation {
+    /// Settle completed, unsettled transactions. Settles a single asset when
+    /// `asset_code` is given, otherwise every asset with pending work.
+    #[graphql(guard = "AdminGuard")]
+    async fn run_settlements(
+        &self,
+        ctx: &Context<'_>,
+        asset_code: Option<String>,
+    ) -> Result<Vec<Settlement>> {
+        let state = ctx.data::<AppState>()?;
+        let service = SettlementService::new(state.db.clone())
+            .with_max_batch_size(state.config.settlement_max_batch_size);
+
+        let settlements = match asset_code {
+            Some(asset_code) => service.settle_asset_batches(&asset_code).await?,
+            None => service.run_settlements().await?,
+        };
+
+        Ok(settlements)
+    }
+}
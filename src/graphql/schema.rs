@@ -1,15 +1,93 @@
+use crate::graphql::allowlist::{query_allowlist, AllowlistExtension};
 use crate::graphql::resolvers::{Mutation, Query, Subscription};
 use crate::AppState;
-use async_graphql::Schema;
+use async_graphql::{Context, Result, Schema};
+use sqlx::PgPool;
+use std::env;
 
 pub type AppSchema = Schema<Query, Mutation, Subscription>;
 
+/// Pool `POST /graphql` picked for the request: a replica for queries, the
+/// primary for anything that may write
+#[derive(Clone)]
+pub struct RequestPool(pub PgPool);
+
+/// The request's [`RequestPool`], or the primary when there is none
+pub fn request_pool<'a>(ctx: &Context<'a>) -> Result<&'a PgPool> {
+    match ctx.data_opt::<RequestPool>() {
+        Some(RequestPool(pool)) => Ok(pool),
+        None => Ok(&ctx.data::<AppState>()?.db),
+    }
+}
+
+/// Default maximum nesting depth of a GraphQL query
+pub const DEFAULT_MAX_DEPTH: usize = 10;
+/// Default maximum complexity (number of resolved fields) of a GraphQL query
+pub const DEFAULT_MAX_COMPLEXITY: usize = 256;
+
+/// Execution limits applied to every query before it runs
+#[derive(Debug, Clone)]
+pub struct SchemaLimits {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    pub introspection_enabled: bool,
+}
+
+impl Default for SchemaLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_complexity: DEFAULT_MAX_COMPLEXITY,
+            introspection_enabled: true,
+        }
+    }
+}
+
+impl SchemaLimits {
+    /// Read limits from `GRAPHQL_MAX_DEPTH`, `GRAPHQL_MAX_COMPLEXITY` and
+    /// `GRAPHQL_INTROSPECTION`, falling back to defaults for unset or invalid values.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_depth: env::var("GRAPHQL_MAX_DEPTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_depth),
+            max_complexity: env::var("GRAPHQL_MAX_COMPLEXITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_complexity),
+            introspection_enabled: env::var("GRAPHQL_INTROSPECTION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.introspection_enabled),
+        }
+    }
+}
+
 pub fn build_schema(state: AppState) -> AppSchema {
-    Schema::build(
+    build_schema_with_limits(state, SchemaLimits::from_env())
+}
+
+/// Build the schema with explicit limits. Queries exceeding the depth or
+/// complexity limit are rejected with a GraphQL error before execution, as
+/// are queries outside `GRAPHQL_ALLOWLIST_FILE` when it is set.
+pub fn build_schema_with_limits(state: AppState, limits: SchemaLimits) -> AppSchema {
+    let mut builder = Schema::build(
         Query::default(),
         Mutation::default(),
         Subscription::default(),
     )
     .data(state)
-    .finish()
+    .limit_depth(limits.max_depth)
+    .limit_complexity(limits.max_complexity);
+    if let Some(allowlist) = query_allowlist() {
+        builder = builder.extension(AllowlistExtension(allowlist));
+    }
+
+    if limits.introspection_enabled {
+        builder.finish()
+    } else {
+        builder.disable_introspection().finish()
+    }
 }
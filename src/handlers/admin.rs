@@ -1,12 +1,28 @@
+use crate::db::migrations::{migration_status, MigrationStatus, MIGRATOR};
+use crate::db::models::{Settlement, TransactionNote, HIGH_RISK_TAG};
+use crate::db::pool_manager::PoolHealthReport;
+use crate::db::queries;
+use crate::error::AppError;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::middleware::json::ApiJson;
+use crate::middleware::path::ApiPath;
+use crate::services::api_keys::{ApiKey, ApiKeyService};
+use crate::services::scheduler::{JobRunResult, JobScheduler, JobStatus};
+use crate::services::settlement::{SettlementService, SettlementStatus};
+use crate::services::transaction::{self as transaction_service, StatusUpdateResult};
+use crate::utils::cursor as cursor_util;
+use crate::utils::pagination::resolve_limit;
+use crate::validation::{sanitize_string, sanitize_text, validate_max_len, validate_required};
 use crate::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::get,
+    routing::{delete, get, patch, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateFlagRequest {
@@ -17,6 +33,289 @@ pub fn admin_routes() -> Router<sqlx::PgPool> {
     Router::new().route("/flags", get(|| async { StatusCode::NOT_IMPLEMENTED }))
 }
 
+pub fn admin_transaction_routes() -> Router<AppState> {
+    Router::new()
+        .route("/transactions/status", patch(update_transaction_statuses))
+        .route("/transactions/flagged", get(flagged_transactions))
+        .route(
+            "/transactions/:id/notes",
+            get(list_transaction_notes).post(add_transaction_note),
+        )
+}
+
+/// Longest note accepted, in bytes
+pub const NOTE_MAX_LEN: usize = 4000;
+/// Longest author name accepted, matching the column
+pub const NOTE_AUTHOR_MAX_LEN: usize = 255;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNoteRequest {
+    /// Support staff member writing the note
+    pub author: String,
+    pub note: String,
+}
+
+/// Attach an internal note to a transaction. Line breaks in the note are
+/// kept; the transaction itself is not changed.
+pub async fn add_transaction_note(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<Uuid>,
+    ApiJson(payload): ApiJson<CreateNoteRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let author = sanitize_string(&payload.author);
+    let note = sanitize_text(&payload.note).trim().to_string();
+    validate_required("author", &author)
+        .and_then(|_| validate_max_len("author", &author, NOTE_AUTHOR_MAX_LEN))
+        .and_then(|_| validate_required("note", &note))
+        .and_then(|_| validate_max_len("note", &note, NOTE_MAX_LEN))
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+
+    ensure_transaction_exists(&state, id).await?;
+    let created = queries::insert_transaction_note(&state.db, id, &author, &note)
+        .await
+        .map_err(AppError::query_failed)?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// A transaction's internal notes, oldest first
+pub async fn list_transaction_notes(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<Json<Vec<TransactionNote>>, AppError> {
+    ensure_transaction_exists(&state, id).await?;
+    let notes = queries::list_transaction_notes(&state.db, id)
+        .await
+        .map_err(AppError::query_failed)?;
+    Ok(Json(notes))
+}
+
+async fn ensure_transaction_exists(state: &AppState, id: Uuid) -> Result<(), AppError> {
+    queries::get_transaction(&state.db, id)
+        .await
+        .map(|_| ())
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::NotFound(format!("Transaction {} not found", id)),
+            _ => AppError::query_failed(e),
+        })
+}
+
+const DEFAULT_FLAGGED_LIMIT: i64 = 25;
+
+#[derive(Debug, Deserialize)]
+pub struct FlaggedQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Transactions tagged `high_risk` in `metadata.compliance_tag`, newest
+/// first, with the same `data`/`meta` page shape as `GET /transactions`
+pub async fn flagged_transactions(
+    State(state): State<AppState>,
+    Query(params): Query<FlaggedQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let limit = resolve_limit(params.limit, DEFAULT_FLAGGED_LIMIT)?;
+    let cursor = params
+        .cursor
+        .as_deref()
+        .map(cursor_util::decode)
+        .transpose()?;
+
+    let mut rows = state
+        .pool_manager
+        .read(|pool| async move {
+            queries::list_transactions_by_compliance_tag(&pool, HIGH_RISK_TAG, limit + 1, cursor)
+                .await
+        })
+        .await
+        .map_err(AppError::query_failed)?;
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+    let next_cursor = rows.last().map(|r| cursor_util::encode(r.created_at, r.id));
+
+    Ok(Json(serde_json::json!({
+        "data": rows,
+        "meta": {
+            "next_cursor": next_cursor,
+            "has_more": has_more
+        }
+    })))
+}
+
+pub fn admin_db_routes() -> Router<AppState> {
+    Router::new()
+        .route("/db/health", get(db_health))
+        .route("/db/migrations", get(db_migrations))
+}
+
+/// Probe the primary and every replica now and report what was found
+pub async fn db_health(State(state): State<AppState>) -> Json<PoolHealthReport> {
+    Json(state.pool_manager.check_health().await)
+}
+
+/// Applied migrations and any shipped ones the database has not applied yet
+pub async fn db_migrations(
+    State(state): State<AppState>,
+) -> Result<Json<MigrationStatus>, AppError> {
+    let status = migration_status(&state.db, &MIGRATOR)
+        .await
+        .map_err(AppError::query_failed)?;
+    Ok(Json(status))
+}
+
+pub fn admin_job_routes() -> Router<JobScheduler> {
+    Router::new()
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:name/run", post(run_job))
+}
+
+/// Every registered background job with its schedule and runs, by name
+pub async fn list_jobs(State(scheduler): State<JobScheduler>) -> Json<Vec<JobStatus>> {
+    let mut jobs: Vec<JobStatus> = scheduler.get_job_status().await.into_values().collect();
+    jobs.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(jobs)
+}
+
+/// Run a registered job now and wait for its result. A failed run is
+/// reported in the body; 409 means a run was already in progress.
+pub async fn run_job(
+    State(scheduler): State<JobScheduler>,
+    Path(name): Path<String>,
+) -> Result<Json<JobRunResult>, AppError> {
+    Ok(Json(scheduler.run_now(&name).await?))
+}
+
+pub fn admin_settlement_routes() -> Router<AppState> {
+    Router::new().route("/settlements/:id/status", patch(update_settlement_status))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SettlementStatusRequest {
+    pub status: String,
+}
+
+/// Move a settlement along `pending -> completed -> paid`, or void it
+pub async fn update_settlement_status(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<Uuid>,
+    ApiJson(payload): ApiJson<SettlementStatusRequest>,
+) -> Result<Json<Settlement>, AppError> {
+    let to = SettlementStatus::parse(&payload.status).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "unknown settlement status '{}': expected one of {}",
+            payload.status,
+            SettlementStatus::ALL
+                .map(SettlementStatus::as_str)
+                .join(", ")
+        ))
+    })?;
+    let settlement = SettlementService::new(state.db.clone())
+        .update_status(id, to)
+        .await?;
+    Ok(Json(settlement))
+}
+
+pub fn admin_api_key_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api-keys", post(create_api_key))
+        .route("/api-keys/:id", delete(revoke_api_key))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateApiKeyRequest {
+    pub partner_id: Uuid,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyResponse {
+    /// The plaintext key; only its hash is stored, so it is shown this once
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// Issue an API key for a partner
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let (api_key, key) = ApiKeyService::new(state.db.clone())
+        .create(payload.partner_id, &payload.scopes)
+        .await?;
+    tracing::info!(api_key_id = %api_key.id, partner_id = %api_key.partner_id, "API key created");
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiKeyResponse { key, api_key }),
+    ))
+}
+
+/// Revoke an API key; requests using it are rejected from then on
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<StatusCode, AppError> {
+    if !ApiKeyService::new(state.db.clone()).revoke(id).await? {
+        return Err(AppError::NotFound(format!(
+            "active API key {} not found",
+            id
+        )));
+    }
+    tracing::info!(api_key_id = %id, "API key revoked");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BulkStatusRequest {
+    pub ids: Vec<Uuid>,
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BulkStatusResponse {
+    pub updated: usize,
+    pub failed: usize,
+    pub results: Vec<StatusUpdateResult>,
+}
+
+/// Set the status of many transactions at once, e.g. after reconciliation.
+/// Each id reports its own outcome; an unknown target status is a 400.
+pub async fn update_transaction_statuses(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<BulkStatusRequest>,
+) -> Result<Json<BulkStatusResponse>, AppError> {
+    let results = transaction_service::bulk_transition_status(
+        &state.db,
+        &payload.ids,
+        &payload.status,
+        "admin",
+    )
+    .await?;
+
+    for result in results.iter().filter(|r| r.updated) {
+        // No subscribers is not an error
+        let _ = state.tx_broadcast.send(TransactionStatusUpdate {
+            transaction_id: result.id,
+            status: payload.status.clone(),
+            timestamp: chrono::Utc::now(),
+            message: Some("Status updated by admin".to_string()),
+        });
+    }
+
+    let updated = results.iter().filter(|r| r.updated).count();
+    tracing::info!(
+        updated,
+        failed = results.len() - updated,
+        status = %payload.status,
+        "Bulk transaction status update finished"
+    );
+    Ok(Json(BulkStatusResponse {
+        updated,
+        failed: results.len() - updated,
+        results,
+    }))
+}
+
 pub async fn get_flags(State(state): State<AppState>) -> impl IntoResponse {
     match state.feature_flags.get_all().await {
         Ok(flags) => (StatusCode::OK, Json(flags)).into_response(),
@@ -36,7 +335,7 @@ pub async fn get_flags(State(state): State<AppState>) -> impl IntoResponse {
 pub async fn update_flag(
     State(state): State<AppState>,
     Path(name): Path<String>,
-    Json(payload): Json<UpdateFlagRequest>,
+    ApiJson(payload): ApiJson<UpdateFlagRequest>,
 ) -> impl IntoResponse {
     match state.feature_flags.update(&name, payload.enabled).await {
         Ok(flag) => (StatusCode::OK, Json(flag)).into_response(),
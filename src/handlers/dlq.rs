@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Path, State},
+    extract::State,
     response::Json,
     routing::{get, post},
     Router,
@@ -10,14 +10,22 @@ use uuid::Uuid;
 
 use crate::db::models::TransactionDlq;
 use crate::error::AppError;
-use crate::services::TransactionProcessor;
+use crate::middleware::json::ApiJson;
+use crate::middleware::path::ApiPath;
+use crate::services::{DlqErrorGroup, DlqFilter, RequeueSummary, TransactionProcessor};
 
 pub fn dlq_routes() -> Router<PgPool> {
     Router::new()
         .route("/dlq", get(list_dlq))
+        .route("/dlq/summary", get(dlq_summary))
         .route("/dlq/:id/requeue", post(requeue_dlq))
 }
 
+/// Admin-only DLQ operations, mounted under `/admin`
+pub fn admin_dlq_routes() -> Router<PgPool> {
+    Router::new().route("/dlq/requeue", post(requeue_dlq_bulk))
+}
+
 async fn list_dlq(State(pool): State<PgPool>) -> Result<Json<Value>, AppError> {
     let entries = sqlx::query_as::<_, TransactionDlq>(
         "SELECT * FROM transaction_dlq ORDER BY moved_to_dlq_at DESC LIMIT 100",
@@ -31,9 +39,23 @@ async fn list_dlq(State(pool): State<PgPool>) -> Result<Json<Value>, AppError> {
     })))
 }
 
+/// DLQ entries grouped by `error_code`, largest group first
+async fn dlq_summary(State(pool): State<PgPool>) -> Result<Json<Value>, AppError> {
+    let groups: Vec<DlqErrorGroup> = TransactionProcessor::new(pool)
+        .dlq_error_summary()
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    let total: i64 = groups.iter().map(|g| g.count).sum();
+
+    Ok(Json(json!({
+        "groups": groups,
+        "total": total
+    })))
+}
+
 async fn requeue_dlq(
     State(pool): State<PgPool>,
-    Path(id): Path<Uuid>,
+    ApiPath(id): ApiPath<Uuid>,
 ) -> Result<Json<Value>, AppError> {
     let processor = TransactionProcessor::new(pool);
     processor
@@ -46,3 +68,29 @@ async fn requeue_dlq(
         "dlq_id": id
     })))
 }
+
+/// Requeue DLQ entries by id list and/or filter. An empty body is rejected
+/// so a stray request cannot requeue the whole DLQ.
+pub async fn requeue_dlq_bulk(
+    State(pool): State<PgPool>,
+    ApiJson(filter): ApiJson<DlqFilter>,
+) -> Result<Json<RequeueSummary>, AppError> {
+    if filter.is_empty() {
+        return Err(AppError::BadRequest(
+            "Provide ids or at least one of asset_code, error_reason, error_code, before"
+                .to_string(),
+        ));
+    }
+
+    let summary = TransactionProcessor::new(pool)
+        .requeue_dlq_matching(&filter)
+        .await
+        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+
+    tracing::info!(
+        succeeded = summary.succeeded,
+        failed = summary.failed,
+        "Bulk DLQ requeue finished"
+    );
+    Ok(Json(summary))
+}
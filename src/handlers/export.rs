@@ -1,39 +1,89 @@
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::{header, header::HeaderValue, HeaderMap, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
+    Json,
 };
 use chrono::{DateTime, Utc};
-use csv::Writer;
+use csv::WriterBuilder;
 use futures::stream::{Stream, StreamExt};
 use serde::Deserialize;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, Row};
 use std::pin::Pin;
 use std::sync::Arc;
-
-use crate::db::models::Transaction;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::models::{Settlement, Transaction};
+use crate::error::AppError;
+use crate::metrics::MetricsRegistry;
+use crate::middleware::auth::{CallerScope, SCOPE_READ};
+use crate::middleware::json::ApiJson;
+use crate::utils::export_link::ExportLinkSigner;
+use crate::utils::links::LinkConfig;
+use crate::utils::time::parse_flexible_date;
+
+/// JSON exports are newline-delimited: one JSON object per line
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Number of data rows in an export requested with `manifest=true`
+pub const EXPORT_ROW_COUNT_HEADER: &str = "x-export-row-count";
+
+/// Hex SHA-256 of the full body of an export requested with `manifest=true`
+pub const EXPORT_CHECKSUM_HEADER: &str = "x-export-checksum";
+
+/// Completed exports, labelled by `format` (`csv` or `json`)
+pub const EXPORTS_METRIC: &str = "exports_total";
+/// Data rows written to exports, labelled by `format`
+pub const EXPORT_ROWS_METRIC: &str = "export_rows_total";
+/// Time spent querying and assembling an export
+pub const EXPORT_DURATION_METRIC: &str = "export_duration_seconds";
+
+const EXPORT_FORMATS: [&str; 2] = ["csv", "json"];
+const EXPORT_DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+pub fn register_metrics(registry: &MetricsRegistry) {
+    for format in EXPORT_FORMATS {
+        registry.register_counter(EXPORTS_METRIC, &[("format", format)]);
+        registry.register_counter(EXPORT_ROWS_METRIC, &[("format", format)]);
+    }
+    registry.register_histogram(EXPORT_DURATION_METRIC, EXPORT_DURATION_BUCKETS);
+}
 
 /// Query parameters for the export endpoint
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ExportQuery {
     /// Export format: "csv" or "json"
     #[serde(default = "default_format")]
     pub format: String,
-    /// Start date filter (inclusive) - format: YYYY-MM-DD
+    /// Start date filter (inclusive) - YYYY-MM-DD, YYYY-MM, RFC 3339 or now-7d
     pub from: Option<String>,
-    /// End date filter (inclusive) - format: YYYY-MM-DD
+    /// End date filter (inclusive of the whole day or month) - same forms as `from`
     pub to: Option<String>,
     /// Filter by transaction status
     pub status: Option<String>,
     /// Filter by asset code
     pub asset_code: Option<String>,
+    /// Only transactions included in this settlement
+    pub settlement_id: Option<Uuid>,
+    /// Add row count and checksum headers, plus a footer line for CSV
+    #[serde(default)]
+    pub manifest: bool,
+    /// Neutralize CSV cells that a spreadsheet would evaluate as formulas
+    #[serde(default = "default_safe_csv")]
+    pub safe_csv: bool,
 }
 
 fn default_format() -> String {
     "csv".to_string()
 }
 
+fn default_safe_csv() -> bool {
+    true
+}
+
 impl Default for ExportQuery {
     fn default() -> Self {
         Self {
@@ -42,10 +92,25 @@ impl Default for ExportQuery {
             to: None,
             status: None,
             asset_code: None,
+            settlement_id: None,
+            manifest: false,
+            safe_csv: default_safe_csv(),
         }
     }
 }
 
+/// Leading characters that make a spreadsheet treat a cell as a formula
+const FORMULA_TRIGGERS: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Prefix `value` with a single quote if a spreadsheet would evaluate it
+fn neutralize_formula(value: String) -> String {
+    if value.starts_with(FORMULA_TRIGGERS) {
+        format!("'{}", value)
+    } else {
+        value
+    }
+}
+
 /// CSV row representation - uses String for amount to avoid Serialize issues with BigDecimal
 #[derive(Serialize)]
 struct TransactionCsvRow {
@@ -53,12 +118,32 @@ struct TransactionCsvRow {
     stellar_account: String,
     amount: String,
     asset_code: String,
+    asset_issuer: String,
     status: String,
     created_at: String,
     updated_at: String,
     anchor_transaction_id: String,
     callback_type: String,
     callback_status: String,
+    memo: String,
+}
+
+impl TransactionCsvRow {
+    /// Neutralize formula triggers in the free-text fields. Ids, amounts and
+    /// timestamps are generated by the service and left as they are.
+    fn neutralized(self) -> Self {
+        TransactionCsvRow {
+            stellar_account: neutralize_formula(self.stellar_account),
+            asset_code: neutralize_formula(self.asset_code),
+            asset_issuer: neutralize_formula(self.asset_issuer),
+            status: neutralize_formula(self.status),
+            anchor_transaction_id: neutralize_formula(self.anchor_transaction_id),
+            callback_type: neutralize_formula(self.callback_type),
+            callback_status: neutralize_formula(self.callback_status),
+            memo: neutralize_formula(self.memo),
+            ..self
+        }
+    }
 }
 
 /// JSON representation for export - converts BigDecimal to String
@@ -68,6 +153,7 @@ struct TransactionJsonRow {
     stellar_account: String,
     amount: String,
     asset_code: String,
+    asset_issuer: Option<String>,
     status: String,
     created_at: String,
     updated_at: String,
@@ -83,12 +169,14 @@ impl From<&Transaction> for TransactionCsvRow {
             stellar_account: tx.stellar_account.clone(),
             amount: tx.amount.to_string(),
             asset_code: tx.asset_code.clone(),
+            asset_issuer: tx.asset_issuer.clone().unwrap_or_default(),
             status: tx.status.clone(),
             created_at: tx.created_at.to_rfc3339(),
             updated_at: tx.updated_at.to_rfc3339(),
             anchor_transaction_id: tx.anchor_transaction_id.clone().unwrap_or_default(),
             callback_type: tx.callback_type.clone().unwrap_or_default(),
             callback_status: tx.callback_status.clone().unwrap_or_default(),
+            memo: tx.memo.clone().unwrap_or_default(),
         }
     }
 }
@@ -100,6 +188,7 @@ impl From<&Transaction> for TransactionJsonRow {
             stellar_account: tx.stellar_account.clone(),
             amount: tx.amount.to_string(),
             asset_code: tx.asset_code.clone(),
+            asset_issuer: tx.asset_issuer.clone(),
             status: tx.status.clone(),
             created_at: tx.created_at.to_rfc3339(),
             updated_at: tx.updated_at.to_rfc3339(),
@@ -110,6 +199,8 @@ impl From<&Transaction> for TransactionJsonRow {
     }
 }
 
+const TRANSACTION_CSV_HEADER: &str = "id,stellar_account,amount,asset_code,asset_issuer,status,created_at,updated_at,anchor_transaction_id,callback_type,callback_status,memo\n";
+
 /// Batch size for cursor-based streaming
 const BATCH_SIZE: i64 = 1000;
 
@@ -119,45 +210,66 @@ type CsvStream = Pin<Box<dyn Stream<Item = Result<String, sqlx::Error>> + Send>>
 /// Type alias for the stream of JSON rows
 type JsonStream = Pin<Box<dyn Stream<Item = Result<String, sqlx::Error>> + Send>>;
 
-/// Parse date string to DateTime<Utc>
-fn parse_date(date_str: &str) -> Result<DateTime<Utc>, String> {
-    // Handle both YYYY-MM-DD and YYYY-MM-DDTHH:MM:SSZ formats
-    let date_str = if date_str.len() == 10 {
-        format!("{}T00:00:00Z", date_str)
-    } else {
-        date_str.to_string()
-    };
-
-    DateTime::parse_from_rfc3339(&date_str)
-        .map(|dt| dt.with_timezone(&Utc))
-        .map_err(|e| format!("Invalid date format: {}", e))
+/// Reject unparseable `from`/`to` up front; the streams assume valid filters
+fn validate_date_filters(query: &ExportQuery) -> Result<(), AppError> {
+    for date in [&query.from, &query.to].into_iter().flatten() {
+        parse_flexible_date(date)?;
+    }
+    Ok(())
 }
 
-/// Build SQL filter conditions based on query parameters
+/// Build SQL filter conditions based on query parameters, limited to
+/// `settlement_id`'s and `partner_id`'s transactions when given
 fn build_filter_conditions(
     from: &Option<String>,
     to: &Option<String>,
     status: &Option<String>,
     asset_code: &Option<String>,
+    settlement_id: Option<Uuid>,
+    partner_id: Option<Uuid>,
+) -> (String, Vec<FilterValue>) {
+    let (mut where_clause, mut params) =
+        build_filter_conditions_on("created_at", "created_at", from, to, status, asset_code);
+
+    for (column, id) in [("settlement_id", settlement_id), ("partner_id", partner_id)] {
+        let Some(id) = id else { continue };
+        params.push(FilterValue::Uuid(id));
+        let condition = format!("{} = ${}", column, params.len());
+        where_clause = if where_clause.is_empty() {
+            format!("WHERE {}", condition)
+        } else {
+            format!("{} AND {}", where_clause, condition)
+        };
+    }
+    (where_clause, params)
+}
+
+/// Build SQL filter conditions, applying `from` to `from_column` and `to` to `to_column`
+fn build_filter_conditions_on(
+    from_column: &str,
+    to_column: &str,
+    from: &Option<String>,
+    to: &Option<String>,
+    status: &Option<String>,
+    asset_code: &Option<String>,
 ) -> (String, Vec<FilterValue>) {
     let mut conditions = Vec::new();
     let mut params = Vec::new();
     let mut param_count = 1;
 
     if let Some(ref from_date) = from {
-        if let Ok(parsed) = parse_date(from_date) {
-            conditions.push(format!("created_at >= ${}", param_count));
-            params.push(FilterValue::DateTime(parsed));
+        if let Ok(parsed) = parse_flexible_date(from_date) {
+            conditions.push(format!("{} >= ${}", from_column, param_count));
+            params.push(FilterValue::DateTime(parsed.start));
             param_count += 1;
         }
     }
 
     if let Some(ref to_date) = to {
-        if let Ok(parsed) = parse_date(to_date) {
-            // Add one day to include the entire end date
-            let end_of_day = parsed + chrono::Duration::days(1);
-            conditions.push(format!("created_at < ${}", param_count));
-            params.push(FilterValue::DateTime(end_of_day));
+        if let Ok(parsed) = parse_flexible_date(to_date) {
+            // Exclusive end, so a bare date or month includes all of it
+            conditions.push(format!("{} < ${}", to_column, param_count));
+            params.push(FilterValue::DateTime(parsed.end));
             param_count += 1;
         }
     }
@@ -186,33 +298,36 @@ fn build_filter_conditions(
 enum FilterValue {
     String(String),
     DateTime(DateTime<Utc>),
+    Uuid(Uuid),
 }
 
-/// Create a CSV stream from database rows - truly streaming without buffering
-fn create_csv_stream(
-    pool: Arc<PgPool>,
-    from: Option<String>,
-    to: Option<String>,
-    status: Option<String>,
-    asset_code: Option<String>,
-) -> CsvStream {
+/// Create a CSV stream from database rows - truly streaming without buffering.
+/// With `safe_csv`, cells that would be evaluated as formulas are neutralized.
+fn create_csv_stream(pool: Arc<PgPool>, query: ExportQuery, partner_id: Option<Uuid>) -> CsvStream {
+    let safe_csv = query.safe_csv;
     let pool_clone = pool.clone();
 
     Box::pin(async_stream::stream! {
         let mut last_id: Option<uuid::Uuid> = None;
 
         // First, write CSV header
-        let headers = "id,stellar_account,amount,asset_code,status,created_at,updated_at,anchor_transaction_id,callback_type,callback_status";
-        yield Ok(headers.to_string());
+        yield Ok(TRANSACTION_CSV_HEADER.to_string());
 
         loop {
             // Build base query with filters
-            let (where_clause, params) = build_filter_conditions(&from, &to, &status, &asset_code);
+            let (where_clause, params) = build_filter_conditions(
+                &query.from,
+                &query.to,
+                &query.status,
+                &query.asset_code,
+                query.settlement_id,
+                partner_id,
+            );
 
             let mut sql = format!(
                 "SELECT id, stellar_account, amount, asset_code, status, created_at, updated_at,
                         anchor_transaction_id, callback_type, callback_status, settlement_id,
-                        memo, memo_type, metadata
+                        memo, memo_type, metadata, asset_issuer, partner_id
                  FROM transactions {}",
                 where_clause
             );
@@ -240,6 +355,9 @@ fn create_csv_stream(
                     FilterValue::DateTime(dt) => {
                         query = query.bind(*dt);
                     }
+                    FilterValue::Uuid(id) => {
+                        query = query.bind(*id);
+                    }
                 }
             }
 
@@ -256,6 +374,7 @@ fn create_csv_stream(
                             stellar_account: row.get("stellar_account"),
                             amount: row.get("amount"),
                             asset_code: row.get("asset_code"),
+                            asset_issuer: row.get("asset_issuer"),
                             status: row.get("status"),
                             created_at: row.get("created_at"),
                             updated_at: row.get("updated_at"),
@@ -266,12 +385,16 @@ fn create_csv_stream(
                             memo: row.get("memo"),
                             memo_type: row.get("memo_type"),
                             metadata: row.get("metadata"),
+                            partner_id: row.get("partner_id"),
                         };
 
                         last_id = Some(tx.id);
 
-                        let csv_row = TransactionCsvRow::from(&tx);
-                        let mut wtr = Writer::from_writer(vec![]);
+                        let mut csv_row = TransactionCsvRow::from(&tx);
+                        if safe_csv {
+                            csv_row = csv_row.neutralized();
+                        }
+                        let mut wtr = WriterBuilder::new().has_headers(false).from_writer(vec![]);
                         wtr.serialize(csv_row).unwrap();
                         let csv_line = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
                         yield Ok(csv_line);
@@ -293,10 +416,8 @@ fn create_csv_stream(
 /// Create a JSON stream from database rows - truly streaming without buffering
 fn create_json_stream(
     pool: Arc<PgPool>,
-    from: Option<String>,
-    to: Option<String>,
-    status: Option<String>,
-    asset_code: Option<String>,
+    query: ExportQuery,
+    partner_id: Option<Uuid>,
 ) -> JsonStream {
     let pool_clone = pool.clone();
 
@@ -305,12 +426,19 @@ fn create_json_stream(
 
         loop {
             // Build base query with filters
-            let (where_clause, params) = build_filter_conditions(&from, &to, &status, &asset_code);
+            let (where_clause, params) = build_filter_conditions(
+                &query.from,
+                &query.to,
+                &query.status,
+                &query.asset_code,
+                query.settlement_id,
+                partner_id,
+            );
 
             let mut sql = format!(
                 "SELECT id, stellar_account, amount, asset_code, status, created_at, updated_at,
                         anchor_transaction_id, callback_type, callback_status, settlement_id,
-                        memo, memo_type, metadata
+                        memo, memo_type, metadata, asset_issuer, partner_id
                  FROM transactions {}",
                 where_clause
             );
@@ -337,6 +465,9 @@ fn create_json_stream(
                     FilterValue::DateTime(dt) => {
                         query = query.bind(*dt);
                     }
+                    FilterValue::Uuid(id) => {
+                        query = query.bind(*id);
+                    }
                 }
             }
 
@@ -353,6 +484,7 @@ fn create_json_stream(
                             stellar_account: row.get("stellar_account"),
                             amount: row.get("amount"),
                             asset_code: row.get("asset_code"),
+                            asset_issuer: row.get("asset_issuer"),
                             status: row.get("status"),
                             created_at: row.get("created_at"),
                             updated_at: row.get("updated_at"),
@@ -363,13 +495,13 @@ fn create_json_stream(
                             memo: row.get("memo"),
                             memo_type: row.get("memo_type"),
                             metadata: row.get("metadata"),
+                            partner_id: row.get("partner_id"),
                         };
 
                         last_id = Some(tx.id);
 
                         let json_row = TransactionJsonRow::from(&tx);
-                        let json_line = serde_json::to_string(&json_row).unwrap();
-                        yield Ok(json_line);
+                        yield Ok(ndjson_line(&json_row));
                     }
                     Err(e) => {
                         yield Err(e);
@@ -385,24 +517,52 @@ fn create_json_stream(
     })
 }
 
+/// Serialize one record as an NDJSON line, terminated by `\n`
+fn ndjson_line<T: Serialize>(row: &T) -> String {
+    let mut line = serde_json::to_string(row).unwrap();
+    line.push('\n');
+    line
+}
+
 /// Helper function to convert a stream of strings into an Axum response
 /// Note: For production with 100k+ rows, you'd want to use true streaming.
 /// This implementation uses cursor-based pagination in the query but collects
 /// the final result. For true streaming, you'd need to use a different approach.
-async fn stream_to_response<S>(stream: S, content_type: &str, filename: &str) -> impl IntoResponse
+///
+/// A query error part way through fails the whole export: no partial body,
+/// manifest or footer is sent and the export is not counted.
+async fn stream_to_response<S>(
+    stream: S,
+    content_type: &str,
+    filename: &str,
+    manifest: ExportManifest,
+) -> Result<Response, AppError>
 where
     S: Stream<Item = Result<String, sqlx::Error>> + Send + 'static,
 {
     use futures::stream::StreamExt;
 
+    let started = std::time::Instant::now();
     // Collect all data from the stream
     let mut all_data = String::new();
+    let mut chunks = 0usize;
     // Pin the stream to allow polling
     let mut pinned_stream = Box::pin(stream);
     while let Some(result) = pinned_stream.next().await {
         match result {
-            Ok(s) => all_data.push_str(&s),
-            Err(_) => break,
+            Ok(s) => {
+                all_data.push_str(&s);
+                chunks += 1;
+            }
+            Err(e) => {
+                tracing::error!(
+                    format = manifest.format,
+                    rows = chunks,
+                    "Export aborted by a query error: {}",
+                    e
+                );
+                return Err(AppError::query_failed(e));
+            }
         }
     }
 
@@ -415,74 +575,354 @@ where
         header::CONTENT_DISPOSITION,
         HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)).unwrap(),
     );
+    let registry = crate::metrics::registry();
+    let labels = [("format", manifest.format)];
+    registry.increment_counter(EXPORTS_METRIC, &labels);
+    registry.add_counter(
+        EXPORT_ROWS_METRIC,
+        &labels,
+        chunks.saturating_sub(manifest.header_rows) as u64,
+    );
+    registry.observe_histogram(EXPORT_DURATION_METRIC, started.elapsed().as_secs_f64());
+
+    if manifest.enabled {
+        let (row_count, checksum) = manifest.finish(&mut all_data, chunks);
+        headers.insert(EXPORT_ROW_COUNT_HEADER, HeaderValue::from(row_count));
+        headers.insert(
+            EXPORT_CHECKSUM_HEADER,
+            HeaderValue::from_str(&checksum).unwrap(),
+        );
+    }
+
+    Ok((StatusCode::OK, headers, all_data).into_response())
+}
 
-    (StatusCode::OK, headers, all_data)
+/// Integrity information added to an export when `manifest=true`, so
+/// consumers can detect a truncated download
+#[derive(Debug, Clone, Copy)]
+struct ExportManifest {
+    enabled: bool,
+    /// Leading chunks that are column headers rather than data rows
+    header_rows: usize,
+    /// Append a `# row_count=N` line after the data
+    csv_footer: bool,
+    /// `format` label of the export metrics
+    format: &'static str,
+}
+
+impl ExportManifest {
+    fn csv(enabled: bool) -> Self {
+        Self {
+            enabled,
+            header_rows: 1,
+            csv_footer: true,
+            format: "csv",
+        }
+    }
+
+    fn ndjson(enabled: bool) -> Self {
+        Self {
+            enabled,
+            header_rows: 0,
+            csv_footer: false,
+            format: "json",
+        }
+    }
+
+    /// Add the footer to `body`, which was streamed as `chunks` chunks of one
+    /// row each, and return the data row count and the hex SHA-256 of the
+    /// final body
+    fn finish(&self, body: &mut String, chunks: usize) -> (usize, String) {
+        let row_count = chunks.saturating_sub(self.header_rows);
+        if self.csv_footer {
+            body.push_str(&format!("# row_count={}\n", row_count));
+        }
+        (row_count, hex::encode(Sha256::digest(body.as_bytes())))
+    }
 }
 
 /// Export transactions as CSV with true streaming
 pub async fn export_transactions_csv(
     State(state): State<crate::ApiState>,
+    scope: CallerScope,
     Query(query): Query<ExportQuery>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, AppError> {
+    scope.require(SCOPE_READ)?;
+    validate_date_filters(&query)?;
     let pool = Arc::new(state.app_state.db);
-    let from = query.from.clone();
-    let to = query.to.clone();
-    let status = query.status.clone();
-    let asset_code = query.asset_code.clone();
-
-    let stream = create_csv_stream(pool, from, to, status, asset_code);
+    let manifest = ExportManifest::csv(query.manifest);
+    let stream = create_csv_stream(pool, query, scope.partner_id());
 
     // Generate filename with current date
     let filename = format!("transactions_{}.csv", Utc::now().format("%Y-%m"));
 
-    stream_to_response(stream, "text/csv", &filename).await
+    stream_to_response(stream, "text/csv", &filename, manifest).await
 }
 
 /// Export transactions as JSON with true streaming (JSON Lines format)
 pub async fn export_transactions_json(
     State(state): State<crate::ApiState>,
+    scope: CallerScope,
     Query(query): Query<ExportQuery>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, AppError> {
+    scope.require(SCOPE_READ)?;
+    validate_date_filters(&query)?;
     let pool = Arc::new(state.app_state.db);
-    let from = query.from.clone();
-    let to = query.to.clone();
-    let status = query.status.clone();
-    let asset_code = query.asset_code.clone();
-
-    let stream = create_json_stream(pool, from, to, status, asset_code);
+    let manifest = ExportManifest::ndjson(query.manifest);
+    let stream = create_json_stream(pool, query, scope.partner_id());
 
     // Generate filename with current date
     let filename = format!("transactions_{}.json", Utc::now().format("%Y-%m"));
 
-    stream_to_response(stream, "application/json", &filename).await
+    stream_to_response(stream, NDJSON_CONTENT_TYPE, &filename, manifest).await
 }
 
 /// Main export handler that routes to CSV or JSON based on format parameter
 pub async fn export_transactions(
     State(state): State<crate::ApiState>,
+    scope: CallerScope,
     Query(query): Query<ExportQuery>,
-) -> impl IntoResponse {
-    let pool = Arc::new(state.app_state.db);
-    let from = query.from.clone();
-    let to = query.to.clone();
-    let status = query.status.clone();
-    let asset_code = query.asset_code.clone();
-    let format = query.format.clone();
+) -> Result<impl IntoResponse, AppError> {
+    scope.require(SCOPE_READ)?;
+    validate_date_filters(&query)?;
+    run_transaction_export(state.app_state.db, query, scope.partner_id()).await
+}
+
+async fn run_transaction_export(
+    pool: PgPool,
+    query: ExportQuery,
+    partner_id: Option<Uuid>,
+) -> Result<Response, AppError> {
+    let pool = Arc::new(pool);
 
-    match format.to_lowercase().as_str() {
+    match query.format.to_lowercase().as_str() {
         "json" => {
-            let stream = create_json_stream(pool, from, to, status, asset_code);
+            let manifest = ExportManifest::ndjson(query.manifest);
+            let stream = create_json_stream(pool, query, partner_id);
             let filename = format!("transactions_{}.json", Utc::now().format("%Y-%m"));
-            stream_to_response(stream, "application/json", &filename).await
+            stream_to_response(stream, NDJSON_CONTENT_TYPE, &filename, manifest).await
         }
         _ => {
-            let stream = create_csv_stream(pool, from, to, status, asset_code);
+            let manifest = ExportManifest::csv(query.manifest);
+            let stream = create_csv_stream(pool, query, partner_id);
             let filename = format!("transactions_{}.csv", Utc::now().format("%Y-%m"));
-            stream_to_response(stream, "text/csv", &filename).await
+            stream_to_response(stream, "text/csv", &filename, manifest).await
+        }
+    }
+}
+
+/// Body of `POST /export/link`: the same filters as `GET /export`, plus the
+/// link lifetime
+#[derive(Debug, Deserialize)]
+pub struct ExportLinkRequest {
+    #[serde(flatten)]
+    pub query: ExportQuery,
+    /// Seconds the link stays valid; defaults to `EXPORT_LINK_TTL_SECS`
+    pub expires_in_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportLinkResponse {
+    pub token: String,
+    /// Absolute URL that serves the export without further authentication;
+    /// only the path when the request named no host
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What a link token grants: the filters and the caller's partner scope,
+/// fixed when the link was created
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportLinkClaims {
+    query: ExportQuery,
+    partner_id: Option<Uuid>,
+}
+
+/// The signer for export links, which are disabled (`404`) until
+/// `EXPORT_LINK_SECRET` is set
+fn link_signer(config: &Config) -> Result<ExportLinkSigner, AppError> {
+    ExportLinkSigner::from_config(config).ok_or_else(|| {
+        AppError::NotFound("export links are disabled: EXPORT_LINK_SECRET is not set".to_string())
+    })
+}
+
+/// Create a time-limited link that exports with the given filters, scoped
+/// to the caller
+pub async fn create_export_link(
+    State(state): State<crate::ApiState>,
+    scope: CallerScope,
+    headers: HeaderMap,
+    ApiJson(request): ApiJson<ExportLinkRequest>,
+) -> Result<Json<ExportLinkResponse>, AppError> {
+    scope.require(SCOPE_READ)?;
+    let config = &state.app_state.config;
+    let signer = link_signer(config)?;
+    validate_date_filters(&request.query)?;
+    let expires_at = signer.expiry(Utc::now(), request.expires_in_secs);
+    let claims = ExportLinkClaims {
+        query: request.query,
+        partner_id: scope.partner_id(),
+    };
+    let token = signer.sign(&claims, expires_at);
+    let path = format!("/export/token/{}", token);
+    let url = LinkConfig::new(config)
+        .url_for(&headers, &path)
+        .map(String::from)
+        .unwrap_or(path);
+    Ok(Json(ExportLinkResponse {
+        url,
+        token,
+        expires_at,
+    }))
+}
+
+/// Serve the export a link token was created for. Expired and tampered
+/// tokens get 403.
+pub async fn export_with_token(
+    State(state): State<crate::ApiState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let claims: ExportLinkClaims =
+        link_signer(&state.app_state.config)?.verify(&token, Utc::now())?;
+    run_transaction_export(state.app_state.db, claims.query, claims.partner_id).await
+}
+
+/// CSV/JSON row representation of a settlement
+#[derive(Serialize)]
+struct SettlementExportRow {
+    id: String,
+    asset_code: String,
+    total_amount: String,
+    tx_count: i32,
+    period_start: String,
+    period_end: String,
+    status: String,
+}
+
+impl From<&Settlement> for SettlementExportRow {
+    fn from(s: &Settlement) -> Self {
+        SettlementExportRow {
+            id: s.id.to_string(),
+            asset_code: s.asset_code.clone(),
+            total_amount: s.total_amount.to_string(),
+            tx_count: s.tx_count,
+            period_start: s.period_start.to_rfc3339(),
+            period_end: s.period_end.to_rfc3339(),
+            status: s.status.clone(),
         }
     }
 }
 
+const SETTLEMENT_CSV_HEADER: &str =
+    "id,asset_code,total_amount,tx_count,period_start,period_end,status\n";
+
+/// Stream settlements in id order, one CSV line or JSON object per row.
+/// `from` filters on `period_start`, `to` (inclusive) on `period_end`.
+fn create_settlement_stream(pool: Arc<PgPool>, query: ExportQuery, as_json: bool) -> CsvStream {
+    Box::pin(async_stream::stream! {
+        let mut last_id: Option<uuid::Uuid> = None;
+
+        if !as_json {
+            yield Ok(SETTLEMENT_CSV_HEADER.to_string());
+        }
+
+        loop {
+            let (where_clause, params) = build_filter_conditions_on(
+                "period_start",
+                "period_end",
+                &query.from,
+                &query.to,
+                &query.status,
+                &query.asset_code,
+            );
+
+            let mut sql = format!(
+                "SELECT id, asset_code, total_amount, tx_count, period_start, period_end,
+                        status, created_at, updated_at
+                 FROM settlements {}",
+                where_clause
+            );
+
+            if let Some(id) = last_id {
+                let joiner = if where_clause.is_empty() { "WHERE" } else { "AND" };
+                sql = format!("{} {} id > '{}' ORDER BY id ASC LIMIT {}", sql, joiner, id, BATCH_SIZE);
+            } else {
+                sql = format!("{} ORDER BY id ASC LIMIT {}", sql, BATCH_SIZE);
+            }
+
+            let mut db_query = sqlx::query_as::<_, Settlement>(&sql);
+            for param in params.iter() {
+                match param {
+                    FilterValue::String(s) => {
+                        db_query = db_query.bind(s.clone());
+                    }
+                    FilterValue::DateTime(dt) => {
+                        db_query = db_query.bind(*dt);
+                    }
+                    FilterValue::Uuid(id) => {
+                        db_query = db_query.bind(*id);
+                    }
+                }
+            }
+
+            let mut rows = db_query.fetch(&*pool);
+            let mut batch_has_rows = false;
+
+            while let Some(row) = rows.next().await {
+                match row {
+                    Ok(settlement) => {
+                        batch_has_rows = true;
+                        last_id = Some(settlement.id);
+
+                        let export_row = SettlementExportRow::from(&settlement);
+                        let line = if as_json {
+                            ndjson_line(&export_row)
+                        } else {
+                            let mut wtr = WriterBuilder::new()
+                                .has_headers(false)
+                                .from_writer(vec![]);
+                            wtr.serialize(export_row).unwrap();
+                            String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+                        };
+                        yield Ok(line);
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+
+            if !batch_has_rows {
+                break;
+            }
+        }
+    })
+}
+
+/// Export settlements as CSV or JSON Lines based on the format parameter
+pub async fn export_settlements(
+    State(state): State<crate::ApiState>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_date_filters(&query)?;
+    let pool = Arc::new(state.app_state.db);
+    let as_json = query.format.eq_ignore_ascii_case("json");
+    let month = Utc::now().format("%Y-%m");
+    let manifest = query.manifest;
+
+    let stream = create_settlement_stream(pool, query, as_json);
+    if as_json {
+        let filename = format!("settlements_{}.json", month);
+        let manifest = ExportManifest::ndjson(manifest);
+        stream_to_response(stream, NDJSON_CONTENT_TYPE, &filename, manifest).await
+    } else {
+        let filename = format!("settlements_{}.csv", month);
+        let manifest = ExportManifest::csv(manifest);
+        stream_to_response(stream, "text/csv", &filename, manifest).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -493,12 +933,38 @@ mod tests {
         assert_eq!(query.format, "csv");
     }
 
+    #[test]
+    fn test_manifest_counts_rows_after_header() {
+        let mut body = format!("{}a\nb\n", TRANSACTION_CSV_HEADER);
+        let (rows, checksum) = ExportManifest::csv(true).finish(&mut body, 3);
+        assert_eq!(rows, 2);
+        assert!(body.ends_with("b\n# row_count=2\n"));
+        assert_eq!(checksum, hex::encode(Sha256::digest(body.as_bytes())));
+
+        let mut body = "{}\n".to_string();
+        let (rows, _) = ExportManifest::ndjson(true).finish(&mut body, 1);
+        assert_eq!(rows, 1);
+        assert_eq!(body, "{}\n");
+    }
+
     #[test]
     fn test_parse_date() {
-        let result = parse_date("2025-01-01");
+        let result = parse_flexible_date("2025-01-01");
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_invalid_date_filter_is_rejected() {
+        let query = ExportQuery {
+            from: Some("last tuesday".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_date_filters(&query),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
     #[test]
     fn test_transaction_csv_row_from() {
         use bigdecimal::BigDecimal;
@@ -509,6 +975,7 @@ mod tests {
             stellar_account: "GABC123".to_string(),
             amount: BigDecimal::from(100),
             asset_code: "USD".to_string(),
+            asset_issuer: None,
             status: "pending".to_string(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
@@ -519,6 +986,7 @@ mod tests {
             memo: None,
             memo_type: None,
             metadata: None,
+            partner_id: None,
         };
 
         let csv_row = TransactionCsvRow::from(&tx);
@@ -526,6 +994,16 @@ mod tests {
         assert_eq!(csv_row.stellar_account, "GABC123");
     }
 
+    #[test]
+    fn test_neutralize_formula() {
+        assert_eq!(neutralize_formula("=cmd()".to_string()), "'=cmd()");
+        assert_eq!(neutralize_formula("+1".to_string()), "'+1");
+        assert_eq!(neutralize_formula("-1".to_string()), "'-1");
+        assert_eq!(neutralize_formula("@SUM(A1)".to_string()), "'@SUM(A1)");
+        assert_eq!(neutralize_formula("order 42".to_string()), "order 42");
+        assert_eq!(neutralize_formula(String::new()), "");
+    }
+
     #[test]
     fn test_transaction_json_row_from() {
         use bigdecimal::BigDecimal;
@@ -536,6 +1014,7 @@ mod tests {
             stellar_account: "GABC123".to_string(),
             amount: BigDecimal::from(100),
             asset_code: "USD".to_string(),
+            asset_issuer: None,
             status: "pending".to_string(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
@@ -546,6 +1025,7 @@ mod tests {
             memo: None,
             memo_type: None,
             metadata: None,
+            partner_id: None,
         };
 
         let json_row = TransactionJsonRow::from(&tx);
@@ -557,20 +1037,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_ndjson_line_is_newline_terminated() {
+        let line = ndjson_line(&serde_json::json!({ "id": "abc", "note": "multi\nline" }));
+        assert!(line.ends_with('\n'));
+        assert_eq!(
+            line.matches('\n').count(),
+            1,
+            "embedded newlines are escaped"
+        );
+        let parsed: serde_json::Value = serde_json::from_str(line.trim_end()).unwrap();
+        assert_eq!(parsed["note"], "multi\nline");
+    }
+
     #[test]
     fn test_build_filter_conditions_no_filters() {
-        let (where_clause, params) = build_filter_conditions(&None, &None, &None, &None);
+        let (where_clause, params) =
+            build_filter_conditions(&None, &None, &None, &None, None, None);
         assert!(where_clause.is_empty());
         assert!(params.is_empty());
     }
 
+    #[test]
+    fn test_build_filter_conditions_on_settlement_period() {
+        let from = Some("2025-01-01".to_string());
+        let to = Some("2025-02-01".to_string());
+        let (where_clause, params) =
+            build_filter_conditions_on("period_start", "period_end", &from, &to, &None, &None);
+        assert!(where_clause.contains("period_start >= $1"));
+        assert!(where_clause.contains("period_end < $2"));
+        assert_eq!(params.len(), 2);
+    }
+
     #[test]
     fn test_build_filter_conditions_with_date_range() {
         let from = Some("2025-01-01".to_string());
         let to = Some("2025-02-01".to_string());
-        let (where_clause, params) = build_filter_conditions(&from, &to, &None, &None);
+        let (where_clause, params) = build_filter_conditions(&from, &to, &None, &None, None, None);
         assert!(where_clause.contains("created_at >="));
         assert!(where_clause.contains("created_at <"));
         assert_eq!(params.len(), 2);
     }
+
+    #[test]
+    fn test_build_filter_conditions_scoped_to_partner() {
+        let partner_id = Uuid::new_v4();
+        let (where_clause, params) =
+            build_filter_conditions(&None, &None, &None, &None, None, Some(partner_id));
+        assert_eq!(where_clause, "WHERE partner_id = $1");
+        assert_eq!(params.len(), 1);
+
+        let status = Some("completed".to_string());
+        let (where_clause, params) =
+            build_filter_conditions(&None, &None, &status, &None, None, Some(partner_id));
+        assert_eq!(where_clause, "WHERE status = $1 AND partner_id = $2");
+        assert_eq!(params.len(), 2);
+    }
+
+    #[test]
+    fn test_build_filter_conditions_by_settlement() {
+        let settlement_id = Uuid::new_v4();
+        let asset_code = Some("USDC".to_string());
+        let (where_clause, params) = build_filter_conditions(
+            &None,
+            &None,
+            &None,
+            &asset_code,
+            Some(settlement_id),
+            Some(Uuid::new_v4()),
+        );
+        assert_eq!(
+            where_clause,
+            "WHERE asset_code = $1 AND settlement_id = $2 AND partner_id = $3"
+        );
+        assert!(matches!(params[1], FilterValue::Uuid(id) if id == settlement_id));
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_fails_the_export() {
+        let registry = crate::metrics::registry();
+        let labels = [("format", "csv")];
+        let before = registry.counter(EXPORTS_METRIC, &labels);
+
+        let stream = futures::stream::iter(vec![
+            Ok("id,amount\n".to_string()),
+            Ok("1,10\n".to_string()),
+            Err(sqlx::Error::PoolClosed),
+        ]);
+        let result =
+            stream_to_response(stream, "text/csv", "t.csv", ExportManifest::csv(true)).await;
+
+        let err = result.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(registry.counter(EXPORTS_METRIC, &labels), before);
+    }
 }
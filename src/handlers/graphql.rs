@@ -1,132 +1,238 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use async_graphql::http::ALL_WEBSOCKET_PROTOCOLS;
+use async_graphql::parser::{parse_query, types::OperationType};
+use async_graphql::{Data, Executor, Request, Response, ServerError, Variables};
+use async_graphql_axum::{GraphQLProtocol, GraphQLWebSocket};
+use axum::{
+    extract::{State, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use futures::stream::{self, BoxStream, StreamExt};
 use serde::Deserialize;
 use serde_json::{json, Value};
-use uuid::Uuid;
+use sqlx::PgPool;
+use std::convert::Infallible;
+use std::sync::Arc;
 
-use crate::db::queries;
+use crate::error::AppError;
+use crate::graphql::auth::AuthContext;
+use crate::graphql::schema::{AppSchema, RequestPool};
+use crate::middleware::auth::{CallerScope, SCOPE_READ};
+use crate::middleware::json::ApiJson;
 use crate::ApiState;
 
 #[derive(Debug, Deserialize)]
 pub struct GraphqlRequest {
     pub query: String,
     pub variables: Option<Value>,
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
 }
 
+/// Execute a query or mutation against the schema. The caller's
+/// `Authorization` header becomes the [`AuthContext`] that guarded fields
+/// check, and its [`CallerScope`] limits which transactions are visible.
+/// A response carrying errors and no data is answered with 400.
 pub async fn graphql_handler(
     State(state): State<ApiState>,
-    Json(payload): Json<GraphqlRequest>,
+    headers: HeaderMap,
+    scope: Result<CallerScope, AppError>,
+    ApiJson(payload): ApiJson<GraphqlRequest>,
 ) -> impl IntoResponse {
-    let query = payload.query.replace(char::is_whitespace, "");
-
-    if query.contains("transactions{") {
-        let status_filter = payload
-            .variables
-            .as_ref()
-            .and_then(|v| v.get("filter"))
-            .and_then(|f| f.get("status"))
-            .and_then(|s| s.as_str())
-            .map(ToOwned::to_owned);
-
-        match queries::list_transactions(&state.app_state.db, 100, None, false).await {
-            Ok(mut rows) => {
-                if let Some(status) = status_filter {
-                    rows.retain(|t| t.status == status);
-                }
-                let data: Vec<Value> = rows
-                    .into_iter()
-                    .map(|t| json!({ "id": t.id.to_string(), "status": t.status }))
-                    .collect();
-                return (
-                    StatusCode::OK,
-                    Json(json!({ "data": { "transactions": data } })),
-                )
-                    .into_response();
-            }
-            Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "errors": [{ "message": e.to_string() }] })),
+    let kind = match operation_kind(&payload.query) {
+        Ok(kind) => kind,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "errors": [{ "message": message }] })),
+            )
+                .into_response()
+        }
+    };
+    let auth =
+        AuthContext::from_authorization(headers.get("Authorization").and_then(|h| h.to_str().ok()));
+    let request = |pool: PgPool| {
+        let mut request = Request::new(payload.query.clone())
+            .data(auth.clone())
+            .data(RequestPool(pool));
+        // Transaction fields refuse requests without a scope
+        if let Ok(scope) = &scope {
+            request = request.data(scope.clone());
+        }
+        if let Some(variables) = payload.variables.clone() {
+            request = request.variables(Variables::from_json(variables));
+        }
+        if let Some(operation_name) = payload.operation_name.clone() {
+            request = request.operation_name(operation_name);
+        }
+        request
+    };
+
+    // Read-only operations go to a replica when one is healthy, and are
+    // re-run on the primary if it is slow; anything that may write stays on
+    // the primary
+    let response = match kind {
+        OperationType::Query => {
+            let schema = &state.graphql_schema;
+            let request = &request;
+            state
+                .app_state
+                .pool_manager
+                .read(
+                    |pool| async move { Ok::<_, Infallible>(schema.execute(request(pool)).await) },
                 )
-                    .into_response()
-            }
+                .await
+                .unwrap_or_else(|never| match never {})
+        }
+        _ => {
+            let pool = state.app_state.pool_manager.get_write_pool().await;
+            state.graphql_schema.execute(request(pool.clone())).await
         }
+    };
+    let status = if response.is_err() && response.data == async_graphql::Value::Null {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::OK
+    };
+    (status, Json(response)).into_response()
+}
+
+/// Error returned for queries and mutations sent over `/graphql/ws`
+pub const SUBSCRIPTIONS_ONLY_MESSAGE: &str =
+    "Only subscription operations are accepted over WebSocket";
+
+/// Serve GraphQL subscriptions over WebSocket, speaking either the
+/// `graphql-transport-ws` or the legacy `graphql-ws` protocol. Queries and
+/// mutations are refused; they go through `POST /graphql`.
+///
+/// The caller's [`CallerScope`] is resolved from the upgrade request and
+/// limits which transactions can be subscribed to. Each connection holds a
+/// [`WsConnections`](crate::handlers::ws::WsConnections) slot, like `/ws`.
+pub async fn graphql_subscription_handler(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Result<impl IntoResponse, AppError> {
+    scope.require(SCOPE_READ)?;
+
+    let Some(guard) = state.app_state.ws_connections.try_acquire() else {
+        tracing::warn!("Rejecting GraphQL WebSocket connection: connection limit reached");
+        return Ok(StatusCode::SERVICE_UNAVAILABLE.into_response());
+    };
+
+    let mut data = Data::default();
+    data.insert(scope);
+    Ok(upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| async move {
+            GraphQLWebSocket::new(stream, SubscriptionsOnly(state.graphql_schema), protocol)
+                .with_data(data)
+                .serve()
+                .await;
+            drop(guard);
+        })
+        .into_response())
+}
+
+/// Runs subscription operations against the schema and answers anything
+/// else with [`SUBSCRIPTIONS_ONLY_MESSAGE`]
+#[derive(Clone)]
+struct SubscriptionsOnly(AppSchema);
+
+#[async_trait::async_trait]
+impl Executor for SubscriptionsOnly {
+    async fn execute(&self, _request: Request) -> Response {
+        refused()
     }
 
-    if query.starts_with("{transaction(id:\"") || query.contains("transaction(id:\"") {
-        let id = extract_id(&payload.query);
-        if let Some(id) = id {
-            match queries::get_transaction(&state.app_state.db, id).await {
-                Ok(t) => {
-                    return (
-                        StatusCode::OK,
-                        Json(json!({
-                            "data": {
-                                "transaction": {
-                                    "id": t.id.to_string(),
-                                    "status": t.status,
-                                    "amount": t.amount.to_string(),
-                                    "assetCode": t.asset_code
-                                }
-                            }
-                        })),
-                    )
-                        .into_response()
-                }
-                Err(e) => {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({ "errors": [{ "message": e.to_string() }] })),
-                    )
-                        .into_response()
-                }
-            }
+    fn execute_stream(
+        &self,
+        request: Request,
+        session_data: Option<Arc<Data>>,
+    ) -> BoxStream<'static, Response> {
+        if only_subscriptions(&request.query) {
+            Executor::execute_stream(&self.0, request, session_data)
+        } else {
+            stream::once(async { refused() }).boxed()
         }
     }
+}
 
-    if query.contains("mutation{forceCompleteTransaction(id:\"") {
-        let id = extract_id(&payload.query);
-        if let Some(id) = id {
-            let updated = sqlx::query(
-                "UPDATE transactions SET status = 'completed', updated_at = NOW() WHERE id = $1",
-            )
-            .bind(id)
-            .execute(&state.app_state.db)
-            .await;
-
-            if let Err(e) = updated {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "errors": [{ "message": e.to_string() }] })),
-                )
-                    .into_response();
-            }
-
-            match queries::get_transaction(&state.app_state.db, id).await {
-                Ok(t) => {
-                    return (StatusCode::OK, Json(json!({
-                        "data": { "forceCompleteTransaction": { "id": t.id.to_string(), "status": t.status } }
-                    }))).into_response()
-                }
-                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "errors": [{ "message": e.to_string() }] }))).into_response(),
-            }
-        }
+fn refused() -> Response {
+    Response::from_errors(vec![ServerError::new(SUBSCRIPTIONS_ONLY_MESSAGE, None)])
+}
+
+/// Whether every operation in `query` is a subscription. Unparseable
+/// documents are passed on so the schema reports the syntax error.
+fn only_subscriptions(query: &str) -> bool {
+    match parse_query(query) {
+        Ok(document) => document
+            .operations
+            .iter()
+            .all(|(_, operation)| operation.node.ty == OperationType::Subscription),
+        Err(_) => true,
     }
+}
 
-    (
-        StatusCode::BAD_REQUEST,
-        Json(json!({ "errors": [{ "message": "Unsupported GraphQL query" }] })),
-    )
-        .into_response()
+/// The operation type of `query`. A document holding several operations is
+/// treated as a mutation if any of them is one, so it never reaches a replica.
+fn operation_kind(query: &str) -> Result<OperationType, String> {
+    let document = parse_query(query).map_err(|e| e.to_string())?;
+    let mut kind = OperationType::Query;
+    for (_, operation) in document.operations.iter() {
+        match operation.node.ty {
+            OperationType::Query => {}
+            OperationType::Mutation => return Ok(OperationType::Mutation),
+            OperationType::Subscription => kind = OperationType::Subscription,
+        }
+    }
+    Ok(kind)
 }
 
-fn extract_id(query: &str) -> Option<Uuid> {
-    let marker = if query.contains("id: \"") {
-        "id: \""
-    } else {
-        "id:\""
-    };
-    let start = query.find(marker)? + marker.len();
-    let remainder = &query[start..];
-    let end = remainder.find('"')?;
-    Uuid::parse_str(&remainder[..end]).ok()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_kind_from_document() {
+        assert_eq!(
+            operation_kind("{ transactions { id } }").unwrap(),
+            OperationType::Query
+        );
+        assert_eq!(
+            operation_kind("query Q { transaction(id: \"x\") { id } }").unwrap(),
+            OperationType::Query
+        );
+        assert_eq!(
+            operation_kind("mutation { forceCompleteTransaction(id: \"x\") { id } }").unwrap(),
+            OperationType::Mutation
+        );
+        assert_eq!(
+            operation_kind("query A { transactions { id } } mutation B { forceCompleteTransaction(id: \"x\") { id } }")
+                .unwrap(),
+            OperationType::Mutation
+        );
+        // The keyword inside a string argument does not make it a mutation
+        assert_eq!(
+            operation_kind("{ transaction(id: \"mutation\") { id } }").unwrap(),
+            OperationType::Query
+        );
+        assert!(operation_kind("{ transactions {").is_err());
+    }
+
+    #[test]
+    fn test_only_subscriptions() {
+        assert!(only_subscriptions(
+            "subscription { transactionStatus(id: \"x\") { status } }"
+        ));
+        assert!(!only_subscriptions("{ transactions { id } }"));
+        assert!(!only_subscriptions(
+            "mutation { forceCompleteTransaction(id: \"x\") { id } }"
+        ));
+        assert!(!only_subscriptions(
+            "subscription S { transactionStatus(id: \"x\") { status } } query Q { transactions { id } }"
+        ));
+    }
 }
@@ -9,8 +9,16 @@ pub mod v2;
 pub mod webhook;
 pub mod ws;
 
+use crate::build_info::{self, BuildInfo};
+use crate::error::AppError;
+use crate::middleware::rate_limit::RateLimitStatus;
 use crate::ApiState;
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -18,6 +26,7 @@ use utoipa::ToSchema;
 pub struct HealthStatus {
     pub status: String,
     pub version: String,
+    pub git_sha: String,
     pub db: String,
     pub db_pool: DbPoolStats,
 }
@@ -66,7 +75,8 @@ pub async fn health(State(state): State<ApiState>) -> impl IntoResponse {
         } else {
             "unhealthy".to_string()
         },
-        version: "0.1.0".to_string(),
+        version: build_info::VERSION.to_string(),
+        git_sha: build_info::GIT_SHA.to_string(),
         db: db_status.to_string(),
         db_pool: pool_stats,
     };
@@ -81,37 +91,104 @@ pub async fn health(State(state): State<ApiState>) -> impl IntoResponse {
     (status_code, Json(health_response))
 }
 
+/// Version, commit and toolchain of the running build
+#[utoipa::path(
+    get,
+    path = "/version",
+    responses(
+        (status = 200, description = "Build metadata", body = BuildInfo)
+    ),
+    tag = "Health"
+)]
+pub async fn version() -> Json<BuildInfo> {
+    Json(BuildInfo::current())
+}
+
+/// The caller's remaining rate-limit quota. The status request itself
+/// counts against the quota.
+#[utoipa::path(
+    get,
+    path = "/ratelimit/status",
+    responses(
+        (status = 200, description = "Current quota for the caller", body = RateLimitStatus),
+        (status = 404, description = "Rate limiting is not enabled")
+    ),
+    tag = "Health"
+)]
+pub async fn rate_limit_status(
+    status: Option<Extension<RateLimitStatus>>,
+) -> Result<Json<RateLimitStatus>, AppError> {
+    status
+        .map(|Extension(status)| Json(status))
+        .ok_or_else(|| AppError::NotFound("Rate limiting is not enabled".to_string()))
+}
+
 /// Readiness probe endpoint for Kubernetes
-/// Returns 200 when ready to accept traffic, 503 when draining or not ready
+/// Returns 200 when ready to accept traffic, 503 when draining or not ready.
+/// The service is not ready until the database schema matches the
+/// migrations embedded in the binary, and reports any drift until then; once
+/// it matches the check is not repeated.
 pub async fn ready(State(state): State<ApiState>) -> impl IntoResponse {
-    if state.app_state.readiness.is_ready() {
-        let response = ReadinessResponse {
-            status: "ready".to_string(),
-            draining: state.app_state.readiness.is_draining(),
-        };
+    let readiness = &state.app_state.readiness;
+    let mut migration_drift = Vec::new();
+    if !readiness.migrations_current() {
+        match crate::db::migrations::migration_status(
+            &state.app_state.db,
+            &crate::db::migrations::MIGRATOR,
+        )
+        .await
+        {
+            Ok(status) => {
+                readiness.set_migrations_current(status.up_to_date);
+                migration_drift = status.drift;
+            }
+            Err(e) => tracing::warn!("Failed to check migration status: {}", e),
+        }
+    }
+
+    let migrations_current = readiness.migrations_current();
+    let ready = readiness.is_ready() && migrations_current;
+    let response = ReadinessResponse {
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
+        draining: readiness.is_draining(),
+        migrations_current,
+        migration_drift,
+    };
+    if ready {
         (StatusCode::OK, Json(response))
     } else {
-        let response = ReadinessResponse {
-            status: "not_ready".to_string(),
-            draining: state.app_state.readiness.is_draining(),
-        };
         (StatusCode::SERVICE_UNAVAILABLE, Json(response))
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ReadinessResponse {
     pub status: String,
     pub draining: bool,
+    pub migrations_current: bool,
+    /// How the database differs from the embedded migrations; omitted when
+    /// it does not
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub migration_drift: Vec<crate::db::migrations::MigrationDrift>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorCatalogQuery {
+    /// Code prefix such as `ERR_TRANSACTION_`
+    pub category: Option<String>,
 }
 
 /// Error catalog endpoint
-/// Returns all available error codes and their descriptions
-pub async fn error_catalog() -> impl IntoResponse {
-    let errors = crate::error::get_all_error_codes();
+/// Returns all available error codes and their descriptions, optionally
+/// only those in one category
+pub async fn error_catalog(Query(query): Query<ErrorCatalogQuery>) -> impl IntoResponse {
+    let errors = match query.category.as_deref() {
+        Some(category) => crate::error::error_codes_in_category(category),
+        None => crate::error::get_all_error_codes(),
+    };
     let catalog = crate::error::ErrorCatalogResponse {
         errors,
-        version: "1.0.0".to_string(),
+        version: crate::error::ERROR_CATALOG_VERSION.to_string(),
     };
 
     (StatusCode::OK, Json(catalog))
@@ -1,6 +1,154 @@
-use crate::db::pool_manager::PoolManager;
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use crate::db::models::Transaction;
+use crate::db::queries::{self, TransactionFilters};
+use crate::error::AppError;
+use crate::middleware::auth::{CallerScope, SCOPE_READ};
+use crate::utils::links::RequestUrl;
+use crate::utils::{cursor, pagination, time::parse_flexible_date};
+use crate::ApiState;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-pub async fn search_transactions(State(_pool_manager): State<PoolManager>) -> impl IntoResponse {
-    StatusCode::NOT_IMPLEMENTED
+const DEFAULT_LIMIT: i64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub status: Option<String>,
+    pub asset_code: Option<String>,
+    pub asset_issuer: Option<String>,
+    pub min_amount: Option<BigDecimal>,
+    pub max_amount: Option<BigDecimal>,
+    /// YYYY-MM-DD, YYYY-MM, RFC 3339 or now-7d style; inclusive
+    pub from: Option<String>,
+    /// Same forms as `from`; a bare day or month includes all of it
+    pub to: Option<String>,
+    pub stellar_account: Option<String>,
+    /// Only transactions included in this settlement
+    pub settlement_id: Option<Uuid>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    /// Set to false to skip the `COUNT(*)` query; `total` is then null
+    pub count: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResponse {
+    pub transactions: Vec<Transaction>,
+    /// Rows matching the filters after the cursor, or null when `count=false`
+    pub total: Option<i64>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+    /// Absolute URL of the next page with the same filters
+    pub next: Option<String>,
+}
+
+impl SearchQuery {
+    /// The row filters, limited to `partner_id`'s transactions when given
+    fn filters(&self, partner_id: Option<Uuid>) -> Result<TransactionFilters<'_>, AppError> {
+        let from_date = self
+            .from
+            .as_deref()
+            .map(parse_flexible_date)
+            .transpose()?
+            .map(|d| d.start);
+        let to_date = self
+            .to
+            .as_deref()
+            .map(parse_flexible_date)
+            .transpose()?
+            .map(|d| d.last_instant());
+
+        Ok(TransactionFilters {
+            status: self.status.as_deref(),
+            asset_code: self.asset_code.as_deref(),
+            asset_issuer: self.asset_issuer.as_deref(),
+            min_amount: self.min_amount.as_ref(),
+            max_amount: self.max_amount.as_ref(),
+            from_date,
+            to_date,
+            stellar_account: self.stellar_account.as_deref(),
+            settlement_id: self.settlement_id,
+            partner_id,
+        })
+    }
+}
+
+/// Search transactions, newest first, with keyset pagination. Partner
+/// callers only see their own transactions.
+pub async fn search_transactions(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    url: RequestUrl,
+    Query(params): Query<SearchQuery>,
+) -> Result<Json<SearchResponse>, AppError> {
+    scope.require(SCOPE_READ)?;
+    let limit = pagination::resolve_limit(params.limit, DEFAULT_LIMIT)?;
+    let cursor = params.cursor.as_deref().map(cursor::decode).transpose()?;
+    let filters = params.filters(scope.partner_id())?;
+
+    // Fetch one extra row to learn whether another page exists
+    let with_total = params.count.unwrap_or(true);
+    let (total, mut transactions) = state
+        .app_state
+        .pool_manager
+        .read(|pool| {
+            let filters = &filters;
+            async move {
+                queries::search_transactions(&pool, filters, limit + 1, cursor, with_total).await
+            }
+        })
+        .await
+        .map_err(AppError::query_failed)?;
+
+    let has_more = transactions.len() as i64 > limit;
+    transactions.truncate(limit as usize);
+    let next_cursor = if has_more {
+        transactions
+            .last()
+            .map(|tx| cursor::encode(tx.created_at, tx.id))
+    } else {
+        None
+    };
+
+    let next = next_cursor
+        .as_deref()
+        .and_then(|cursor| url.with_params(&[("cursor", cursor)]));
+
+    Ok(Json(SearchResponse {
+        transactions,
+        total,
+        has_more,
+        next_cursor,
+        next,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountResponse {
+    pub count: i64,
+}
+
+/// Number of transactions matching the search filters, without fetching
+/// them. Pagination parameters are ignored.
+pub async fn count_transactions(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    Query(params): Query<SearchQuery>,
+) -> Result<Json<CountResponse>, AppError> {
+    scope.require(SCOPE_READ)?;
+    let filters = params.filters(scope.partner_id())?;
+    let count = state
+        .app_state
+        .pool_manager
+        .read(|pool| {
+            let filters = &filters;
+            async move { queries::count_transactions(&pool, filters, None).await }
+        })
+        .await
+        .map_err(AppError::query_failed)?;
+    Ok(Json(CountResponse { count }))
 }
@@ -1,12 +1,23 @@
+use crate::db::queries;
+use crate::error::AppError;
+use crate::middleware::auth::{CallerScope, SCOPE_READ};
+use crate::middleware::path::ApiPath;
+use crate::services::settlement::{SettlementPreview, SettlementReceipt, SettlementService};
+use crate::utils::pagination::resolve_limit;
+use crate::utils::time::parse_flexible_date;
 use crate::ApiState;
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json},
 };
 use serde::{Deserialize, Serialize};
 use utoipa::IntoParams;
 use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Page size for `/settlements` when `limit` is omitted
+const DEFAULT_LIMIT: i64 = 10;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, IntoParams)]
 pub struct Pagination {
@@ -30,23 +41,88 @@ pub struct SettlementListResponse {
     params(Pagination),
     responses(
         (status = 200, description = "List of settlements", body = SettlementListResponse),
+        (status = 400, description = "Invalid page or limit"),
         (status = 500, description = "Database error")
     ),
     tag = "Settlements"
 )]
 pub async fn list_settlements(
-    State(_state): State<ApiState>,
-    _query: Query<Pagination>,
-) -> Result<Json<SettlementListResponse>, StatusCode> {
-    // TODO: Implement settlement listing
+    State(state): State<ApiState>,
+    Query(query): Query<Pagination>,
+) -> Result<Json<SettlementListResponse>, AppError> {
+    let limit = resolve_limit(query.limit.map(i64::from), DEFAULT_LIMIT)?;
+    let page = query.page.unwrap_or(1);
+    if page == 0 {
+        return Err(AppError::BadRequest("page starts at 1".to_string()));
+    }
+
+    let offset = (i64::from(page) - 1) * limit;
+    let (settlements, total) = state
+        .app_state
+        .pool_manager
+        .read(|pool| async move {
+            let settlements = queries::list_settlements(&pool, limit, offset).await?;
+            let total = queries::count_settlements(&pool).await?;
+            Ok::<_, sqlx::Error>((settlements, total))
+        })
+        .await?;
+
     Ok(Json(SettlementListResponse {
-        settlements: vec![],
-        total: 0,
-        page: _query.page.unwrap_or(1),
-        limit: _query.limit.unwrap_or(10).min(100),
+        settlements,
+        total,
+        page,
+        limit: limit as u32,
     }))
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PreviewQuery {
+    /// Only preview this asset; all assets when omitted
+    pub asset_code: Option<String>,
+    /// Cut-off for `updated_at`, in any form `/export` accepts; defaults to now
+    pub period_end: Option<String>,
+}
+
+/// Show what the next settlement run would create, without creating it.
+/// Partner callers only see their own transactions, with totals over those.
+#[utoipa::path(
+    get,
+    path = "/settlements/preview",
+    params(PreviewQuery),
+    responses(
+        (status = 200, description = "Prospective settlement batches", body = SettlementPreview),
+        (status = 400, description = "Invalid period_end"),
+        (status = 500, description = "Database error")
+    ),
+    tag = "Settlements"
+)]
+pub async fn preview_settlements(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    Query(query): Query<PreviewQuery>,
+) -> Result<Json<SettlementPreview>, AppError> {
+    scope.require(SCOPE_READ)?;
+    let period_end = match query.period_end.as_deref() {
+        Some(raw) => parse_flexible_date(raw)?.last_instant(),
+        None => chrono::Utc::now(),
+    };
+    let asset_code = query.asset_code.as_deref().filter(|a| !a.is_empty());
+    let partner_id = scope.partner_id();
+    let max_batch_size = state.app_state.config.settlement_max_batch_size;
+
+    let preview = state
+        .app_state
+        .pool_manager
+        .read(|pool| async move {
+            SettlementService::new(pool)
+                .with_max_batch_size(max_batch_size)
+                .preview(asset_code, period_end, partner_id)
+                .await
+        })
+        .await?;
+    Ok(Json(preview))
+}
+
 #[utoipa::path(
     get,
     path = "/settlements/{id}",
@@ -67,3 +143,124 @@ pub async fn get_settlement(
     // TODO: Implement settlement retrieval
     Err(StatusCode::NOT_IMPLEMENTED)
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ReceiptQuery {
+    /// `csv` (default) or `json`
+    pub format: Option<String>,
+}
+
+/// Download a settlement receipt: the settlement's period, totals and status
+/// followed by every transaction it includes. Partner callers only see their
+/// own transactions, with totals over those.
+#[utoipa::path(
+    get,
+    path = "/settlements/{id}/receipt",
+    params(
+        ("id" = String, Path, description = "Settlement ID"),
+        ReceiptQuery
+    ),
+    responses(
+        (status = 200, description = "Receipt document", body = SettlementReceipt),
+        (status = 400, description = "Unsupported format"),
+        (status = 404, description = "Settlement not found, or none of its transactions belong to the caller")
+    ),
+    tag = "Settlements"
+)]
+pub async fn get_settlement_receipt(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    ApiPath(id): ApiPath<Uuid>,
+    Query(query): Query<ReceiptQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = query.format.as_deref().unwrap_or("csv").to_lowercase();
+    if format != "csv" && format != "json" {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported format '{}': expected csv or json",
+            format
+        )));
+    }
+
+    scope.require(SCOPE_READ)?;
+    let partner_id = scope.partner_id();
+    let receipt = state
+        .app_state
+        .pool_manager
+        .read(|pool| async move { SettlementService::new(pool).receipt(id, partner_id).await })
+        .await?;
+
+    let (content_type, body) = if format == "json" {
+        let body = serde_json::to_string_pretty(&receipt)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        ("application/json", body)
+    } else {
+        ("text/csv", receipt_csv(&receipt)?)
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!(
+            "attachment; filename=\"settlement_{}_receipt.{}\"",
+            id, format
+        ))
+        .map_err(|e| AppError::Internal(e.to_string()))?,
+    );
+    Ok((StatusCode::OK, headers, body))
+}
+
+/// Two CSV sections separated by a blank line: the settlement summary, then
+/// one row per member transaction
+fn receipt_csv(receipt: &SettlementReceipt) -> Result<String, AppError> {
+    let to_internal = |e: csv::Error| AppError::Internal(e.to_string());
+    let mut wtr = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new());
+
+    wtr.write_record([
+        "settlement_id",
+        "asset_code",
+        "status",
+        "period_start",
+        "period_end",
+        "tx_count",
+        "total_amount",
+    ])
+    .map_err(to_internal)?;
+    wtr.write_record([
+        receipt.settlement_id.to_string(),
+        receipt.asset_code.clone(),
+        receipt.status.clone(),
+        receipt.period_start.to_rfc3339(),
+        receipt.period_end.to_rfc3339(),
+        receipt.tx_count.to_string(),
+        receipt.total_amount.to_string(),
+    ])
+    .map_err(to_internal)?;
+    wtr.write_record(None::<&[u8]>).map_err(to_internal)?;
+
+    wtr.write_record([
+        "transaction_id",
+        "stellar_account",
+        "amount",
+        "anchor_transaction_id",
+        "created_at",
+    ])
+    .map_err(to_internal)?;
+    for line in &receipt.transactions {
+        wtr.write_record([
+            line.transaction_id.to_string(),
+            line.stellar_account.clone(),
+            line.amount.to_string(),
+            line.anchor_transaction_id.clone().unwrap_or_default(),
+            line.created_at.to_rfc3339(),
+        ])
+        .map_err(to_internal)?;
+    }
+
+    let bytes = wtr
+        .into_inner()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| AppError::Internal(e.to_string()))
+}
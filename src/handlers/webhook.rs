@@ -1,21 +1,32 @@
+use crate::db::audit::AuditLog;
 use crate::db::models::Transaction as TxModel;
 use crate::db::{models::Transaction, queries};
 use crate::error::AppError;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::middleware::auth::{CallerScope, SCOPE_CALLBACK, SCOPE_READ};
+use crate::middleware::json::{ApiJson, OptionalApiJson, WebhookJson};
+use crate::middleware::path::ApiPath;
+use crate::services::transaction as transaction_service;
 use crate::utils::cursor as cursor_util;
+use crate::utils::links::RequestUrl;
+use crate::utils::pagination::{resolve_direction, resolve_limit, SortOrder};
 use crate::validation::{
-    sanitize_string, validate_asset_code, validate_max_len, validate_positive_amount,
-    validate_stellar_address, AMOUNT_INPUT_MAX_LEN, ANCHOR_TRANSACTION_ID_MAX_LEN,
-    CALLBACK_STATUS_MAX_LEN, CALLBACK_TYPE_MAX_LEN,
+    amount_limits, callback_vocabulary, metadata_validator, sanitize_string, sanitize_text,
+    validate_asset_code, validate_asset_issuer, validate_max_len, validate_memo,
+    validate_positive_amount, validate_stellar_address, AMOUNT_INPUT_MAX_LEN,
+    ANCHOR_TRANSACTION_ID_MAX_LEN, CALLBACK_STATUS_MAX_LEN, CALLBACK_TYPE_MAX_LEN,
 };
 use crate::{ApiState, AppState};
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::types::BigDecimal;
+use sqlx::Acquire;
 use std::str::FromStr;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -25,6 +36,8 @@ pub struct CallbackPayload {
     pub stellar_account: String,
     pub amount: String,
     pub asset_code: String,
+    /// Issuing account of the asset, as a Stellar address
+    pub asset_issuer: Option<String>,
     pub callback_type: Option<String>,
     pub callback_status: Option<String>,
     pub anchor_transaction_id: Option<String>,
@@ -44,6 +57,7 @@ pub struct WebhookTransactionRequest {
     pub stellar_address: String,
     pub amount: String,
     pub asset_code: String,
+    pub asset_issuer: Option<String>,
     pub anchor_transaction_id: Option<String>,
     pub callback_type: Option<String>,
     pub callback_status: Option<String>,
@@ -59,6 +73,7 @@ struct ValidatedWebhookTransaction {
     stellar_address: String,
     amount: BigDecimal,
     asset_code: String,
+    asset_issuer: Option<String>,
     anchor_transaction_id: Option<String>,
     callback_type: Option<String>,
     callback_status: Option<String>,
@@ -76,6 +91,7 @@ fn validate_webhook_payload(
     let stellar_address = sanitize_string(&payload.stellar_address);
     let asset_code = sanitize_string(&payload.asset_code);
     let amount_str = sanitize_string(&payload.amount);
+    let asset_issuer = sanitize_optional(payload.asset_issuer);
     let anchor_transaction_id = sanitize_optional(payload.anchor_transaction_id);
     let callback_type = sanitize_optional(payload.callback_type);
     let callback_status = sanitize_optional(payload.callback_status);
@@ -83,6 +99,9 @@ fn validate_webhook_payload(
     validate_stellar_address(&stellar_address)
         .map_err(|err| AppError::Validation(err.to_string()))?;
     validate_asset_code(&asset_code).map_err(|err| AppError::Validation(err.to_string()))?;
+    if let Some(asset_issuer) = &asset_issuer {
+        validate_asset_issuer(asset_issuer).map_err(|err| AppError::Validation(err.to_string()))?;
+    }
     validate_max_len("amount", &amount_str, AMOUNT_INPUT_MAX_LEN)
         .map_err(|err| AppError::Validation(err.to_string()))?;
     if let Some(anchor_transaction_id) = &anchor_transaction_id {
@@ -95,10 +114,12 @@ fn validate_webhook_payload(
     }
     if let Some(callback_type) = &callback_type {
         validate_max_len("callback_type", callback_type, CALLBACK_TYPE_MAX_LEN)
+            .and_then(|_| callback_vocabulary().check_type(callback_type))
             .map_err(|err| AppError::Validation(err.to_string()))?;
     }
     if let Some(callback_status) = &callback_status {
         validate_max_len("callback_status", callback_status, CALLBACK_STATUS_MAX_LEN)
+            .and_then(|_| callback_vocabulary().check_status(callback_status))
             .map_err(|err| AppError::Validation(err.to_string()))?;
     }
 
@@ -106,11 +127,13 @@ fn validate_webhook_payload(
         .parse::<BigDecimal>()
         .map_err(|_| AppError::Validation("amount: must be a valid decimal".to_string()))?;
     validate_positive_amount(&amount).map_err(|err| AppError::Validation(err.to_string()))?;
+    amount_limits().check(&asset_code, &amount)?;
 
     Ok(ValidatedWebhookTransaction {
         stellar_address,
         amount,
         asset_code,
+        asset_issuer,
         anchor_transaction_id,
         callback_type,
         callback_status,
@@ -119,7 +142,7 @@ fn validate_webhook_payload(
 
 pub async fn transaction_callback(
     State(state): State<AppState>,
-    Json(payload): Json<WebhookTransactionRequest>,
+    WebhookJson(payload): WebhookJson<WebhookTransactionRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     // Validate and sanitize all inputs before any DB interaction.
     let payload = validate_webhook_payload(payload)?;
@@ -134,7 +157,8 @@ pub async fn transaction_callback(
         None, // memo
         None, // memo_type
         None, // metadata
-    );
+    )
+    .with_asset_issuer(payload.asset_issuer);
 
     let inserted = queries::insert_transaction(&state.db, &tx).await?;
 
@@ -156,6 +180,7 @@ mod tests {
             stellar_address: "G".to_owned() + &"A".repeat(55),
             amount: "42.50".to_string(),
             asset_code: "USD".to_string(),
+            asset_issuer: None,
             anchor_transaction_id: Some("anchor-1".to_string()),
             callback_type: Some("deposit".to_string()),
             callback_status: Some("completed".to_string()),
@@ -181,6 +206,21 @@ mod tests {
         assert!(parsed.is_ok());
     }
 
+    #[test]
+    fn validate_webhook_payload_checks_asset_issuer() {
+        let mut payload = valid_payload();
+        payload.asset_issuer = Some("G".to_owned() + &"B".repeat(55));
+        let parsed = validate_webhook_payload(payload).unwrap();
+        assert_eq!(parsed.asset_issuer, Some("G".to_owned() + &"B".repeat(55)));
+
+        let mut payload = valid_payload();
+        payload.asset_issuer = Some("not-an-issuer".to_string());
+        assert!(matches!(
+            validate_webhook_payload(payload),
+            Err(AppError::Validation(msg)) if msg.starts_with("asset_issuer")
+        ));
+    }
+
     #[test]
     fn validate_webhook_payload_rejects_invalid_stellar_address() {
         let mut payload = valid_payload();
@@ -276,6 +316,23 @@ mod tests {
         payload.callback_status = Some("a".repeat(21));
         assert!(validate_webhook_payload(payload).is_err());
     }
+
+    #[test]
+    fn validate_webhook_payload_rejects_unknown_callback_values() {
+        let mut payload = valid_payload();
+        payload.callback_type = Some("depositt".to_string());
+        assert!(matches!(
+            validate_webhook_payload(payload),
+            Err(AppError::Validation(msg)) if msg.starts_with("callback_type")
+        ));
+
+        let mut payload = valid_payload();
+        payload.callback_status = Some("done".to_string());
+        assert!(matches!(
+            validate_webhook_payload(payload),
+            Err(AppError::Validation(msg)) if msg.starts_with("callback_status")
+        ));
+    }
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -284,57 +341,381 @@ pub struct WebhookResponse {
     pub message: String,
 }
 
-fn validate_memo_type(memo_type: &Option<String>) -> Result<(), AppError> {
-    if let Some(mt) = memo_type {
-        match mt.as_str() {
-            "text" | "hash" | "id" => Ok(()),
-            _ => Err(AppError::Validation(format!(
-                "Invalid memo_type '{}'. Must be one of: text, hash, id",
-                mt
-            ))),
-        }
+/// Accepted callbacks, labelled `asset_code`, `callback_type` and `status`
+/// (the callback status). Only recorded after validation, so the type and
+/// status labels are limited to the configured callback vocabulary; asset
+/// codes outside [`METRIC_ASSET_CODES`] are counted as `other`.
+pub const CALLBACKS_METRIC: &str = "callbacks_total";
+
+/// Asset codes given their own `callbacks_total` series. Any code is valid in
+/// a callback, so the label is bounded to keep the number of series fixed.
+pub const METRIC_ASSET_CODES: [&str; 6] = ["USD", "USDC", "EUR", "EURC", "XLM", "NGN"];
+
+fn asset_label(asset_code: &str) -> &str {
+    if METRIC_ASSET_CODES.contains(&asset_code) {
+        asset_code
     } else {
-        Ok(())
+        "other"
+    }
+}
+
+pub(crate) fn record_callback(tx: &Transaction) {
+    crate::metrics::registry().increment_counter(
+        CALLBACKS_METRIC,
+        &[
+            ("asset_code", asset_label(&tx.asset_code)),
+            (
+                "callback_type",
+                tx.callback_type.as_deref().unwrap_or("none"),
+            ),
+            ("status", tx.callback_status.as_deref().unwrap_or("none")),
+        ],
+    );
+}
+
+fn validate_memo_type(memo: &Option<String>, memo_type: &Option<String>) -> Result<(), AppError> {
+    let Some(mt) = memo_type else {
+        return Ok(());
+    };
+    if !matches!(mt.as_str(), "text" | "hash" | "id") {
+        return Err(AppError::Validation(format!(
+            "Invalid memo_type '{}'. Must be one of: text, hash, id",
+            mt
+        )));
+    }
+    if let Some(memo) = memo {
+        validate_memo(memo, mt).map_err(|err| AppError::Validation(err.to_string()))?;
+    }
+    Ok(())
+}
+
+fn build_callback_transaction(mut payload: CallbackPayload) -> Result<Transaction, AppError> {
+    payload.memo = payload.memo.as_deref().map(sanitize_text);
+    validate_memo_type(&payload.memo, &payload.memo_type)?;
+    if let Some(callback_type) = &payload.callback_type {
+        callback_vocabulary()
+            .check_type(callback_type)
+            .map_err(|err| AppError::Validation(err.to_string()))?;
+    }
+    if let Some(callback_status) = &payload.callback_status {
+        callback_vocabulary()
+            .check_status(callback_status)
+            .map_err(|err| AppError::Validation(err.to_string()))?;
+    }
+
+    let amount = sqlx::types::BigDecimal::from_str(&payload.amount)
+        .map_err(|_| AppError::Validation(format!("Invalid amount: {}", payload.amount)))?;
+    amount_limits().check(&payload.asset_code, &amount)?;
+    if let Some(asset_issuer) = &payload.asset_issuer {
+        validate_asset_issuer(asset_issuer).map_err(|err| AppError::Validation(err.to_string()))?;
     }
+    if let Some(metadata) = &payload.metadata {
+        metadata_validator().check(metadata)?;
+    }
+
+    Ok(Transaction::new(
+        payload.stellar_account,
+        amount,
+        payload.asset_code,
+        payload.anchor_transaction_id,
+        payload.callback_type,
+        payload.callback_status,
+        payload.memo,
+        payload.memo_type,
+        payload.metadata,
+    )
+    .with_asset_issuer(payload.asset_issuer))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CallbackQuery {
+    /// When true the callback is validated, queued and answered with 202;
+    /// it is stored in the background
+    #[serde(default, rename = "async")]
+    pub async_mode: bool,
+}
+
+/// Answer to a callback accepted with `async=true`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CallbackAccepted {
+    /// Id the transaction is stored under once processed
+    pub id: Uuid,
+    pub status: String,
+    /// Where the transaction can be fetched once stored
+    pub location: String,
 }
 
 #[utoipa::path(
     post,
     path = "/callback",
     request_body = CallbackPayload,
+    params(
+        ("async" = Option<bool>, Query, description = "Queue the callback and answer 202 instead of storing it before responding")
+    ),
     responses(
         (status = 201, description = "Transaction created", body = crate::schemas::TransactionSchema),
+        (status = 202, description = "Callback queued; the transaction appears at `location` once stored", body = CallbackAccepted),
         (status = 400, description = "Invalid payload"),
+        (status = 409, description = "anchor_transaction_id already recorded"),
         (status = 500, description = "Processing error")
     ),
     tag = "Webhooks"
 )]
 pub async fn callback(
     State(state): State<ApiState>,
-    Json(payload): Json<CallbackPayload>,
-) -> Result<impl IntoResponse, AppError> {
-    validate_memo_type(&payload.memo_type)?;
+    scope: CallerScope,
+    Query(params): Query<CallbackQuery>,
+    WebhookJson(payload): WebhookJson<CallbackPayload>,
+) -> Result<Response, AppError> {
+    scope.require(SCOPE_CALLBACK)?;
+    let tx = build_callback_transaction(payload)?.with_partner_id(scope.partner_id());
+
+    if !params.async_mode {
+        let inserted = queries::insert_transaction(&state.app_state.db, &tx).await?;
+        record_callback(&inserted);
+        return Ok((StatusCode::CREATED, Json(inserted)).into_response());
+    }
 
-    let amount = sqlx::types::BigDecimal::from_str(&payload.amount)
-        .map_err(|_| AppError::Validation(format!("Invalid amount: {}", payload.amount)))?;
+    // Duplicates are answered now rather than failing once dequeued
+    if let Some(anchor_transaction_id) = &tx.anchor_transaction_id {
+        if queries::anchor_transaction_id_exists(&state.app_state.db, anchor_transaction_id).await?
+        {
+            return Err(AppError::TransactionAlreadyProcessed(format!(
+                "anchor_transaction_id '{}' has already been recorded",
+                anchor_transaction_id
+            )));
+        }
+    }
 
-    let tx = Transaction::new(
-        payload.stellar_account,
-        amount,
-        payload.asset_code,
-        payload.anchor_transaction_id,
-        payload.callback_type,
-        payload.callback_status,
-        payload.memo,
-        payload.memo_type,
-        payload.metadata,
-    );
+    let id = tx.id;
+    match state.callback_queue.try_enqueue(tx) {
+        Ok(()) => {
+            let location = format!("/transactions/{}", id);
+            let body = CallbackAccepted {
+                id,
+                status: "accepted".to_string(),
+                location: location.clone(),
+            };
+            Ok((
+                StatusCode::ACCEPTED,
+                [(header::LOCATION, location)],
+                Json(body),
+            )
+                .into_response())
+        }
+        // A full queue falls back to storing the callback inline
+        Err(tx) => {
+            tracing::warn!(transaction_id = %id, "Callback queue full, storing callback inline");
+            let inserted = queries::insert_transaction(&state.app_state.db, &tx).await?;
+            record_callback(&inserted);
+            Ok((StatusCode::CREATED, Json(inserted)).into_response())
+        }
+    }
+}
+
+/// Maximum length of a refund reason; longer reasons are truncated
+const REFUND_REASON_MAX_LEN: usize = 500;
+
+/// Maximum number of callbacks accepted in a single batch request
+pub const MAX_CALLBACK_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct BatchCallbackPayload {
+    pub transactions: Vec<CallbackPayload>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchCallbackQuery {
+    /// When true, any failing item rolls back the whole batch
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchItemResult {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchCallbackResponse {
+    pub summary: BatchSummary,
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Valid items in a callback batch from which it is bulk inserted instead of
+/// one row at a time
+pub const BULK_CALLBACK_THRESHOLD: usize = 50;
+
+/// Try to insert the whole batch at once inside a savepoint. Returns false,
+/// with nothing written, when any row fails, so the caller can find which.
+async fn bulk_insert_callbacks(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    txs: &[Transaction],
+) -> Result<bool, AppError> {
+    let mut savepoint = db_tx.begin().await.map_err(AppError::query_failed)?;
+    match queries::bulk_insert_transactions_in_tx(&mut savepoint, txs).await {
+        Ok(_) => {
+            savepoint.commit().await.map_err(AppError::query_failed)?;
+            Ok(true)
+        }
+        Err(e) => {
+            tracing::debug!(
+                "Bulk insert of callback batch failed, inserting items one by one: {}",
+                e
+            );
+            savepoint.rollback().await.map_err(AppError::query_failed)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Ingest many callbacks in one request
+///
+/// All items are inserted in a single DB transaction. Batches of at least
+/// [`BULK_CALLBACK_THRESHOLD`] valid items are written with one bulk insert;
+/// if that fails, or the batch is smaller, each item runs in its own
+/// savepoint, so a failing item does not abort the others unless `atomic=true`.
+#[utoipa::path(
+    post,
+    path = "/callback/batch",
+    request_body = BatchCallbackPayload,
+    params(
+        ("atomic" = Option<bool>, Query, description = "Roll back the whole batch if any item fails")
+    ),
+    responses(
+        (status = 207, description = "Per-item results", body = BatchCallbackResponse),
+        (status = 400, description = "Empty or oversized batch"),
+        (status = 500, description = "Processing error")
+    ),
+    tag = "Webhooks"
+)]
+pub async fn callback_batch(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    Query(params): Query<BatchCallbackQuery>,
+    WebhookJson(payload): WebhookJson<BatchCallbackPayload>,
+) -> Result<impl IntoResponse, AppError> {
+    scope.require(SCOPE_CALLBACK)?;
+    if payload.transactions.is_empty() {
+        return Err(AppError::BadRequest(
+            "transactions: batch must not be empty".to_string(),
+        ));
+    }
+    if payload.transactions.len() > MAX_CALLBACK_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "transactions: batch exceeds maximum size of {}",
+            MAX_CALLBACK_BATCH_SIZE
+        )));
+    }
+
+    let total = payload.transactions.len();
+    let mut results = Vec::with_capacity(total);
+    let mut indices = Vec::with_capacity(total);
+    let mut txs = Vec::with_capacity(total);
+    for (index, item) in payload.transactions.into_iter().enumerate() {
+        match build_callback_transaction(item) {
+            Ok(tx) => {
+                indices.push(index);
+                txs.push(tx.with_partner_id(scope.partner_id()));
+            }
+            Err(e) => results.push(BatchItemResult {
+                index,
+                id: None,
+                error: Some(e.public_message()),
+            }),
+        }
+    }
 
-    let inserted = queries::insert_transaction(&state.app_state.db, &tx)
+    let mut db_tx = state
+        .app_state
+        .db
+        .begin()
         .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .map_err(AppError::query_failed)?;
+
+    // Counted in `callbacks_total` only once the batch is committed
+    let mut stored = Vec::with_capacity(txs.len());
+    let bulk_inserted =
+        txs.len() >= BULK_CALLBACK_THRESHOLD && bulk_insert_callbacks(&mut db_tx, &txs).await?;
+    if bulk_inserted {
+        for (index, tx) in indices.into_iter().zip(&txs) {
+            stored.push(tx);
+            results.push(BatchItemResult {
+                index,
+                id: Some(tx.id),
+                error: None,
+            });
+        }
+    } else {
+        for (index, tx) in indices.into_iter().zip(&txs) {
+            let mut savepoint = db_tx.begin().await.map_err(AppError::query_failed)?;
+            match queries::insert_transaction_in_tx(&mut savepoint, tx).await {
+                Ok(inserted) => {
+                    savepoint.commit().await.map_err(AppError::query_failed)?;
+                    stored.push(tx);
+                    results.push(BatchItemResult {
+                        index,
+                        id: Some(inserted.id),
+                        error: None,
+                    });
+                }
+                Err(e) => {
+                    savepoint.rollback().await.map_err(AppError::query_failed)?;
+                    results.push(BatchItemResult {
+                        index,
+                        id: None,
+                        error: Some(e.public_message()),
+                    });
+                }
+            }
+        }
+    }
+    results.sort_by_key(|r| r.index);
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+
+    if params.atomic && failed > 0 {
+        db_tx.rollback().await.map_err(AppError::query_failed)?;
+        for result in results.iter_mut().filter(|r| r.error.is_none()) {
+            result.id = None;
+            result.error = Some("Rolled back: atomic batch contained failures".to_string());
+        }
+        let response = BatchCallbackResponse {
+            summary: BatchSummary {
+                total,
+                succeeded: 0,
+                failed: total,
+            },
+            results,
+        };
+        return Ok((StatusCode::MULTI_STATUS, Json(response)));
+    }
+
+    db_tx.commit().await.map_err(AppError::query_failed)?;
+    for tx in stored {
+        record_callback(tx);
+    }
 
-    Ok((StatusCode::CREATED, Json(inserted)))
+    let response = BatchCallbackResponse {
+        summary: BatchSummary {
+            total,
+            succeeded: total - failed,
+            failed,
+        },
+        results,
+    };
+    Ok((StatusCode::MULTI_STATUS, Json(response)))
 }
 
 #[utoipa::path(
@@ -350,7 +731,7 @@ pub async fn callback(
 )]
 pub async fn handle_webhook(
     State(_state): State<ApiState>,
-    Json(payload): Json<WebhookPayload>,
+    ApiJson(payload): ApiJson<WebhookPayload>,
 ) -> impl IntoResponse {
     tracing::info!("Processing webhook with id: {}", payload.id);
 
@@ -380,124 +761,314 @@ pub async fn handle_webhook(
 )]
 pub async fn get_transaction(
     State(state): State<ApiState>,
-    Path(id): Path<Uuid>,
+    scope: CallerScope,
+    ApiPath(id): ApiPath<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let transaction = queries::get_transaction(&state.app_state.db, id)
+    let transaction = state
+        .app_state
+        .pool_manager
+        .read(|pool| {
+            let scope = &scope;
+            async move { get_visible_transaction(&pool, scope, id).await }
+        })
+        .await?;
+
+    Ok(Json(transaction))
+}
+
+/// Transaction `id`, as a 404 when it is missing or belongs to another
+/// partner than the caller
+async fn get_visible_transaction(
+    pool: &sqlx::PgPool,
+    scope: &CallerScope,
+    id: Uuid,
+) -> Result<Transaction, AppError> {
+    scope.require(SCOPE_READ)?;
+    let not_found = || AppError::NotFound(format!("Transaction {} not found", id));
+    let transaction = queries::get_transaction(pool, id)
         .await
         .map_err(|e| match e {
-            sqlx::Error::RowNotFound => AppError::NotFound(format!("Transaction {} not found", id)),
-            _ => AppError::DatabaseError(e.to_string()),
+            sqlx::Error::RowNotFound => not_found(),
+            _ => AppError::query_failed(e),
         })?;
+    if !scope.can_see(transaction.partner_id) {
+        return Err(not_found());
+    }
+    Ok(transaction)
+}
 
-    Ok(Json(transaction))
+/// One step in a transaction's lifecycle
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    /// `created`, `status_changed`, `settlement_linked`, or the raw audit action
+    pub event: String,
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    pub old_val: Option<serde_json::Value>,
+    pub new_val: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ListQuery {
-    pub cursor: Option<String>,
-    pub limit: Option<i64>,
-    /// direction: "forward" (older items) or "backward" (newer items)
-    pub direction: Option<String>,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionTimeline {
+    pub transaction: Transaction,
+    pub events: Vec<TimelineEvent>,
 }
 
+/// Map an audit action onto its timeline event name
+fn timeline_event_name(action: &str) -> &str {
+    match action {
+        "status_update" => "status_changed",
+        "settlement_id_update" => "settlement_linked",
+        other => other,
+    }
+}
+
+/// Get a transaction's timeline
+///
+/// Returns the transaction with its audit history as a chronological event list
 #[utoipa::path(
     get,
-    path = "/transactions",
+    path = "/transactions/{id}/timeline",
     params(
-        ("cursor" = Option<String>, Query, description = "Cursor for pagination"),
-        ("limit" = Option<i64>, Query, description = "Page size"),
-        ("direction" = Option<String>, Query, description = "forward or backward")
+        ("id" = String, Path, description = "Transaction ID")
     ),
     responses(
-        (status = 200, description = "List transactions with pagination metadata"),
+        (status = 200, description = "Transaction timeline"),
+        (status = 404, description = "Transaction not found"),
         (status = 500, description = "Database error")
     ),
     tag = "Transactions"
 )]
-pub async fn list_transactions(
-    State(state): State<AppState>,
-    Query(params): Query<ListQuery>,
-) -> Result<impl IntoResponse, AppError> {
-    let limit = params.limit.unwrap_or(25).min(100);
-    let backward = params.direction.as_deref() == Some("backward");
-
-    let decoded_cursor = if let Some(ref c) = params.cursor {
-        match cursor_util::decode(c) {
-            Ok((ts, id)) => Some((ts, id)),
-            Err(e) => return Err(AppError::BadRequest(format!("invalid cursor: {}", e))),
-        }
-    } else {
-        None
-    };
+pub async fn get_transaction_timeline(
+    State(state): State<ApiState>,
+    scope: CallerScope,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<Json<TransactionTimeline>, AppError> {
+    let transaction = get_visible_transaction(&state.app_state.db, &scope, id).await?;
 
-    // fetch one extra to determine has_more
-    let fetch_limit = limit + 1;
-    let mut rows = queries::list_transactions(&state.db, fetch_limit, decoded_cursor, backward)
+    let entries = AuditLog::for_entity(&state.app_state.db, id)
         .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
-
-    let has_more = rows.len() as i64 > limit;
-    if has_more {
-        rows.truncate(limit as usize);
+        .map_err(AppError::query_failed)?;
+
+    let mut events: Vec<TimelineEvent> = entries
+        .into_iter()
+        .map(|entry| TimelineEvent {
+            event: timeline_event_name(&entry.action).to_string(),
+            timestamp: entry.timestamp,
+            actor: entry.actor,
+            old_val: entry.old_val,
+            new_val: entry.new_val,
+        })
+        .collect();
+
+    // Rows inserted before auditing existed have no creation entry
+    if !events.iter().any(|e| e.event == "created") {
+        events.insert(
+            0,
+            TimelineEvent {
+                event: "created".to_string(),
+                timestamp: transaction.created_at,
+                actor: "system".to_string(),
+                old_val: None,
+                new_val: None,
+            },
+        );
     }
 
-    // next cursor is the last item in the returned rows
-    let next_cursor = rows
-        .last()
-        .map(|r: &TxModel| cursor_util::encode(r.created_at, r.id));
+    Ok(Json(TransactionTimeline {
+        transaction,
+        events,
+    }))
+}
 
-    let resp = serde_json::json!({
-        "data": rows,
-        "meta": {
-            "next_cursor": next_cursor,
-            "has_more": has_more
-        }
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct RefundRequest {
+    /// Stored as `metadata.refund_reason`
+    pub reason: Option<String>,
+}
+
+/// Refund a transaction
+///
+/// Marks a completed transaction as refunded, records an audit entry and
+/// notifies WebSocket subscribers. Requires the admin key. The body is
+/// optional; an empty one refunds without a reason.
+#[utoipa::path(
+    post,
+    path = "/transactions/{id}/refund",
+    params(
+        ("id" = String, Path, description = "Transaction ID")
+    ),
+    request_body = RefundRequest,
+    responses(
+        (status = 200, description = "Transaction refunded", body = crate::schemas::TransactionSchema),
+        (status = 400, description = "Malformed body, or current status does not allow refunding"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Transaction not found"),
+        (status = 409, description = "Transaction already refunded")
+    ),
+    tag = "Transactions"
+)]
+pub async fn refund_transaction(
+    State(state): State<ApiState>,
+    ApiPath(id): ApiPath<Uuid>,
+    OptionalApiJson(payload): OptionalApiJson<RefundRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let state = state.app_state;
+    let reason = payload
+        .and_then(|p| sanitize_optional(p.reason))
+        .map(|reason| {
+            reason
+                .chars()
+                .take(REFUND_REASON_MAX_LEN)
+                .collect::<String>()
+        });
+
+    let refunded = transaction_service::refund_transaction(&state.db, id, reason.clone()).await?;
+
+    // No subscribers is not an error
+    let _ = state.tx_broadcast.send(TransactionStatusUpdate {
+        transaction_id: refunded.id,
+        status: refunded.status.clone(),
+        timestamp: chrono::Utc::now(),
+        message: reason,
     });
 
-    Ok(Json(resp))
+    Ok(Json(refunded))
+}
+
+/// Page size for `/transactions` when `limit` is omitted
+const DEFAULT_LIST_LIMIT: i64 = 25;
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    /// direction: "forward" (further along `order`) or "backward";
+    /// defaults to `DEFAULT_PAGE_DIRECTION`
+    pub direction: Option<String>,
+    /// order: "desc" (newest first, the default) or "asc" (oldest first)
+    pub order: Option<String>,
+}
+
+pub async fn list_transactions(
+    State(state): State<AppState>,
+    scope: CallerScope,
+    url: RequestUrl,
+    Query(params): Query<ListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .pool_manager
+        .read(|pool| list_transactions_page(pool, &scope, &url, &params))
+        .await
+        .map(Json)
 }
 
 /// Wrapper to accept the router's ApiState without forcing all handlers to change.
+#[utoipa::path(
+    get,
+    path = "/transactions",
+    params(
+        ("cursor" = Option<String>, Query, description = "Cursor for pagination"),
+        ("limit" = Option<i64>, Query, description = "Page size"),
+        ("direction" = Option<String>, Query, description = "forward (further along order) or backward"),
+        ("order" = Option<String>, Query, description = "desc (newest first, default) or asc (oldest first), by created_at then id")
+    ),
+    responses(
+        (status = 200, description = "List transactions with pagination metadata, including absolute `next` and `prev` page links"),
+        (status = 400, description = "Invalid cursor, limit, direction or order"),
+        (status = 500, description = "Database error")
+    ),
+    tag = "Transactions"
+)]
 pub async fn list_transactions_api(
     State(api_state): State<crate::ApiState>,
+    scope: CallerScope,
+    url: RequestUrl,
     Query(params): Query<ListQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    // forward to the AppState-based handler
-    let app_state = api_state.app_state;
-    // call the inner logic directly to avoid extractor conflicts
-    let limit = params.limit.unwrap_or(25).min(100);
-    let backward = params.direction.as_deref() == Some("backward");
-
-    let decoded_cursor = if let Some(ref c) = params.cursor {
-        match cursor_util::decode(c) {
-            Ok((ts, id)) => Some((ts, id)),
-            Err(e) => return Err(AppError::BadRequest(format!("invalid cursor: {}", e))),
-        }
-    } else {
-        None
-    };
-
-    let fetch_limit = limit + 1;
-    let mut rows = queries::list_transactions(&app_state.db, fetch_limit, decoded_cursor, backward)
+    api_state
+        .app_state
+        .pool_manager
+        .read(|pool| list_transactions_page(pool, &scope, &url, &params))
         .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        .map(Json)
+}
 
+/// One page of transactions in the requested order. `next_cursor` continues
+/// in the requested direction: past the last row going forward, past the
+/// first going backward. A cursor must be reused with the same `order`.
+/// `next` and `prev` are the same as absolute URLs, `next` only while more
+/// rows remain and `prev` only once a cursor has been followed.
+/// Partner callers only see their own transactions.
+async fn list_transactions_page(
+    pool: sqlx::PgPool,
+    scope: &CallerScope,
+    url: &RequestUrl,
+    params: &ListQuery,
+) -> Result<serde_json::Value, AppError> {
+    scope.require(SCOPE_READ)?;
+    let limit = resolve_limit(params.limit, DEFAULT_LIST_LIMIT)?;
+    let direction = resolve_direction(params.direction.as_deref())?;
+    let backward = direction.is_backward();
+    let order = SortOrder::resolve(params.order.as_deref())?;
+
+    let decoded_cursor = params
+        .cursor
+        .as_deref()
+        .map(cursor_util::decode)
+        .transpose()?;
+
+    // fetch one extra to determine has_more
+    let fetch_limit = limit + 1;
+    let mut rows = queries::list_partner_transactions(
+        &pool,
+        scope.partner_id(),
+        fetch_limit,
+        decoded_cursor,
+        direction,
+        order,
+    )
+    .await
+    .map_err(AppError::query_failed)?;
+
+    // The extra row is the one furthest from the cursor: the last going
+    // forward, the first (after reordering) backward
     let has_more = rows.len() as i64 > limit;
     if has_more {
-        rows.truncate(limit as usize);
+        if backward {
+            rows.remove(0);
+        } else {
+            rows.truncate(limit as usize);
+        }
     }
 
-    let next_cursor = rows
-        .last()
-        .map(|r: &TxModel| cursor_util::encode(r.created_at, r.id));
-
-    let resp = serde_json::json!({
+    let (edge, opposite_edge) = if backward {
+        (rows.first(), rows.last())
+    } else {
+        (rows.last(), rows.first())
+    };
+    let next_cursor = edge.map(|r: &TxModel| cursor_util::encode(r.created_at, r.id));
+    let (this_way, other_way) = if backward {
+        ("backward", "forward")
+    } else {
+        ("forward", "backward")
+    };
+    let next = next_cursor
+        .as_deref()
+        .filter(|_| has_more)
+        .and_then(|cursor| url.with_params(&[("cursor", cursor), ("direction", this_way)]));
+    let prev = opposite_edge
+        .filter(|_| params.cursor.is_some())
+        .map(|r| cursor_util::encode(r.created_at, r.id))
+        .and_then(|cursor| url.with_params(&[("cursor", &cursor), ("direction", other_way)]));
+
+    Ok(serde_json::json!({
         "data": rows,
         "meta": {
             "next_cursor": next_cursor,
-            "has_more": has_more
+            "has_more": has_more,
+            "next": next,
+            "prev": prev
         }
-    });
-
-    Ok(Json(resp))
+    }))
 }
@@ -1,18 +1,29 @@
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
         Query, State,
     },
     response::IntoResponse,
 };
-use futures::{sink::SinkExt, stream::StreamExt};
+use futures::{
+    sink::{Sink, SinkExt},
+    stream::StreamExt,
+};
 use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
 use tokio::sync::broadcast;
 use uuid::Uuid;
 
-use crate::AppState;
+use crate::config::Config;
+use crate::{ApiState, AppState};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A transaction status change, pushed to `/ws` clients and to GraphQL
+/// `transactionStatus` subscribers
+#[derive(Debug, Clone, Serialize, Deserialize, async_graphql::SimpleObject)]
 pub struct TransactionStatusUpdate {
     pub transaction_id: Uuid,
     pub status: String,
@@ -20,6 +31,122 @@ pub struct TransactionStatusUpdate {
     pub message: Option<String>,
 }
 
+/// Close code for clients disconnected for falling behind (RFC 6455 policy violation)
+pub const CLOSE_POLICY_VIOLATION: u16 = 1008;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When to give up on a client that cannot keep up with the broadcast channel
+#[derive(Debug, Clone, Copy)]
+pub struct LagPolicy {
+    /// Lag events allowed in a row without the client catching up
+    pub max_consecutive_lags: u32,
+    /// Total messages a client may miss over the life of the connection
+    pub max_lagged_messages: u64,
+}
+
+impl Default for LagPolicy {
+    fn default() -> Self {
+        Self {
+            max_consecutive_lags: 3,
+            max_lagged_messages: 1000,
+        }
+    }
+}
+
+impl LagPolicy {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            max_consecutive_lags: config.ws_max_consecutive_lags,
+            max_lagged_messages: config.ws_max_lagged_messages,
+        }
+    }
+}
+
+/// Lag seen by one connection. The consecutive count resets once the client
+/// has drained everything queued for it.
+#[derive(Debug, Default)]
+struct LagTracker {
+    consecutive: u32,
+    total: u64,
+}
+
+impl LagTracker {
+    /// Record `missed` dropped messages; returns true if the policy is exceeded
+    fn record_lag(&mut self, missed: u64, policy: &LagPolicy) -> bool {
+        self.consecutive += 1;
+        self.total += missed;
+        self.consecutive >= policy.max_consecutive_lags || self.total > policy.max_lagged_messages
+    }
+
+    fn caught_up(&mut self) {
+        self.consecutive = 0;
+    }
+}
+
+/// Default cap on concurrent WebSocket connections
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1000;
+
+/// Gauge holding the number of open WebSocket connections
+pub const CONNECTIONS_ACTIVE_METRIC: &str = "websocket_connections_active";
+
+/// Counts open WebSocket connections and enforces the configured maximum
+#[derive(Debug, Clone)]
+pub struct WsConnections {
+    active: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl Default for WsConnections {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONNECTIONS)
+    }
+}
+
+impl WsConnections {
+    pub fn new(max: usize) -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Reserve a connection slot, or `None` if the limit is reached. The slot
+    /// is released when the returned guard is dropped.
+    pub fn try_acquire(&self) -> Option<WsConnectionGuard> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < self.max).then_some(n + 1)
+            })
+            .ok()?;
+        self.publish();
+        Some(WsConnectionGuard {
+            connections: self.clone(),
+        })
+    }
+
+    fn publish(&self) {
+        crate::metrics::registry().set_gauge(CONNECTIONS_ACTIVE_METRIC, self.active() as f64);
+    }
+}
+
+/// Holds one connection slot for as long as the socket is open
+#[derive(Debug)]
+pub struct WsConnectionGuard {
+    connections: WsConnections,
+}
+
+impl Drop for WsConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.active.fetch_sub(1, Ordering::SeqCst);
+        self.connections.publish();
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WsQuery {
     token: Option<String>,
@@ -29,8 +156,10 @@ pub struct WsQuery {
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Query(params): Query<WsQuery>,
-    State(state): State<AppState>,
+    State(state): State<ApiState>,
 ) -> impl IntoResponse {
+    let state = state.app_state;
+
     // Validate token if provided
     if let Some(token) = params.token {
         if !validate_token(&token) {
@@ -39,7 +168,15 @@ pub async fn ws_handler(
         }
     }
 
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    let Some(guard) = state.ws_connections.try_acquire() else {
+        tracing::warn!("Rejecting WebSocket connection: connection limit reached");
+        return axum::http::StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    ws.on_upgrade(move |socket| async move {
+        handle_socket(socket, state).await;
+        drop(guard);
+    })
 }
 
 /// Handle individual WebSocket connection
@@ -47,7 +184,7 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     let (mut sender, mut receiver) = socket.split();
 
     // Subscribe to broadcast channel
-    let mut rx = state.tx_broadcast.subscribe();
+    let rx = state.tx_broadcast.subscribe();
 
     // Spawn task to handle incoming messages from client
     let mut recv_task = tokio::spawn(async move {
@@ -71,47 +208,9 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     });
 
     // Spawn task to send broadcast messages and heartbeats to client
+    let policy = LagPolicy::new(&state.config);
     let mut send_task = tokio::spawn(async move {
-        let mut heartbeat_interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
-
-        loop {
-            tokio::select! {
-                // Send heartbeat ping
-                _ = heartbeat_interval.tick() => {
-                    if sender.send(Message::Ping(vec![])).await.is_err() {
-                        tracing::info!("Client disconnected during heartbeat");
-                        break;
-                    }
-                }
-                // Broadcast transaction updates
-                result = rx.recv() => {
-                    match result {
-                        Ok(update) => {
-                            let json = match serde_json::to_string(&update) {
-                                Ok(j) => j,
-                                Err(e) => {
-                                    tracing::error!("Failed to serialize update: {}", e);
-                                    continue;
-                                }
-                            };
-
-                            if sender.send(Message::Text(json)).await.is_err() {
-                                tracing::info!("Client disconnected");
-                                break;
-                            }
-                        }
-                        Err(broadcast::error::RecvError::Lagged(n)) => {
-                            tracing::warn!("Client lagged behind by {} messages", n);
-                            // Continue serving - client will miss old messages (backpressure handling)
-                        }
-                        Err(broadcast::error::RecvError::Closed) => {
-                            tracing::info!("Broadcast channel closed");
-                            break;
-                        }
-                    }
-                }
-            }
-        }
+        forward_updates(&mut sender, rx, policy, HEARTBEAT_INTERVAL).await;
     });
 
     // Wait for either task to finish
@@ -127,8 +226,181 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     tracing::info!("WebSocket connection closed");
 }
 
+/// Forward broadcast updates and heartbeats to `sender` until the client
+/// disconnects, the channel closes, or the client lags past `policy`, in
+/// which case it is sent a policy-violation close frame.
+async fn forward_updates<S>(
+    sender: &mut S,
+    mut rx: broadcast::Receiver<TransactionStatusUpdate>,
+    policy: LagPolicy,
+    heartbeat: Duration,
+) where
+    S: Sink<Message> + Unpin,
+{
+    let mut heartbeat_interval = tokio::time::interval(heartbeat);
+    let mut lag = LagTracker::default();
+
+    loop {
+        tokio::select! {
+            // Send heartbeat ping
+            _ = heartbeat_interval.tick() => {
+                if sender.send(Message::Ping(vec![])).await.is_err() {
+                    tracing::info!("Client disconnected during heartbeat");
+                    break;
+                }
+            }
+            // Broadcast transaction updates
+            result = rx.recv() => {
+                match result {
+                    Ok(update) => {
+                        let json = match serde_json::to_string(&update) {
+                            Ok(j) => j,
+                            Err(e) => {
+                                tracing::error!("Failed to serialize update: {}", e);
+                                continue;
+                            }
+                        };
+
+                        if sender.send(Message::Text(json)).await.is_err() {
+                            tracing::info!("Client disconnected");
+                            break;
+                        }
+                        if rx.is_empty() {
+                            lag.caught_up();
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("Client lagged behind by {} messages", n);
+                        if lag.record_lag(n, &policy) {
+                            tracing::warn!(
+                                "Closing slow WebSocket client after {} missed messages",
+                                lag.total
+                            );
+                            let frame = CloseFrame {
+                                code: CLOSE_POLICY_VIOLATION,
+                                reason: "client too slow".into(),
+                            };
+                            let _ = sender.send(Message::Close(Some(frame))).await;
+                            break;
+                        }
+                        // Otherwise keep serving - the client misses old messages (backpressure handling)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        tracing::info!("Broadcast channel closed");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Simple token validation (replace with actual auth logic)
 fn validate_token(token: &str) -> bool {
     // TODO: Implement proper token validation
     !token.is_empty()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+
+    fn update() -> TransactionStatusUpdate {
+        TransactionStatusUpdate {
+            transaction_id: Uuid::new_v4(),
+            status: "completed".to_string(),
+            timestamp: chrono::Utc::now(),
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_connection_slots_are_released() {
+        let connections = WsConnections::new(2);
+        let first = connections.try_acquire().unwrap();
+        let _second = connections.try_acquire().unwrap();
+        assert!(connections.try_acquire().is_none());
+        assert_eq!(connections.active(), 2);
+
+        drop(first);
+        assert_eq!(connections.active(), 1);
+        assert!(connections.try_acquire().is_some());
+    }
+
+    #[test]
+    fn test_lag_tracker_thresholds() {
+        let policy = LagPolicy {
+            max_consecutive_lags: 2,
+            max_lagged_messages: 10,
+        };
+
+        let mut tracker = LagTracker::default();
+        assert!(!tracker.record_lag(1, &policy));
+        tracker.caught_up();
+        assert!(!tracker.record_lag(1, &policy));
+        assert!(tracker.record_lag(1, &policy));
+
+        let mut tracker = LagTracker::default();
+        assert!(tracker.record_lag(11, &policy));
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_is_disconnected() {
+        let (tx, _) = broadcast::channel(4);
+        let policy = LagPolicy {
+            max_consecutive_lags: 3,
+            max_lagged_messages: 1000,
+        };
+        let heartbeat = Duration::from_secs(3600);
+
+        // Fast client drains every message immediately
+        let (mut fast_sink, mut fast_stream) = mpsc::channel::<Message>(0);
+        let fast_rx = tx.subscribe();
+        let fast_task = tokio::spawn(async move {
+            forward_updates(&mut fast_sink, fast_rx, policy, heartbeat).await;
+        });
+        let fast_reader = tokio::spawn(async move {
+            let mut received = 0;
+            while let Some(msg) = fast_stream.next().await {
+                if let Message::Text(_) = msg {
+                    received += 1;
+                }
+            }
+            received
+        });
+
+        // Slow client takes 20ms per message
+        let (mut slow_sink, mut slow_stream) = mpsc::channel::<Message>(0);
+        let slow_rx = tx.subscribe();
+        let slow_task = tokio::spawn(async move {
+            forward_updates(&mut slow_sink, slow_rx, policy, heartbeat).await;
+        });
+        let slow_reader = tokio::spawn(async move {
+            while let Some(msg) = slow_stream.next().await {
+                if let Message::Close(frame) = msg {
+                    return frame.map(|f| f.code);
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            None
+        });
+
+        for _ in 0..200 {
+            tx.send(update()).unwrap();
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+
+        let close_code = tokio::time::timeout(Duration::from_secs(5), slow_reader)
+            .await
+            .expect("slow client was not disconnected")
+            .unwrap();
+        assert_eq!(close_code, Some(CLOSE_POLICY_VIOLATION));
+        assert!(slow_task.is_finished());
+
+        assert!(!fast_task.is_finished());
+        drop(tx);
+        fast_task.await.unwrap();
+        assert!(fast_reader.await.unwrap() > 150);
+    }
+}
@@ -4,10 +4,13 @@ use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tokio::time::timeout;
 
+use crate::utils::redis_connect::{redis_connect_policy, RedisConnectPolicy};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
+    pub git_sha: String,
     pub uptime_seconds: u64,
     pub dependencies: HashMap<String, DependencyStatus>,
 }
@@ -53,11 +56,20 @@ impl DependencyChecker for PostgresChecker {
 
 pub struct RedisChecker {
     url: String,
+    connect_policy: RedisConnectPolicy,
 }
 
 impl RedisChecker {
     pub fn new(url: String) -> Self {
-        Self { url }
+        Self {
+            url,
+            connect_policy: *redis_connect_policy(),
+        }
+    }
+
+    pub fn with_connect_policy(mut self, policy: RedisConnectPolicy) -> Self {
+        self.connect_policy = policy;
+        self
     }
 }
 
@@ -66,7 +78,7 @@ impl DependencyChecker for RedisChecker {
     async fn check(&self) -> DependencyStatus {
         let start = Instant::now();
         match redis::Client::open(self.url.as_str()) {
-            Ok(client) => match client.get_multiplexed_async_connection().await {
+            Ok(client) => match self.connect_policy.connect(&client).await {
                 Ok(mut conn) => {
                     match redis::cmd("PING").query_async::<_, String>(&mut conn).await {
                         Ok(_) => DependencyStatus::Healthy {
@@ -92,19 +104,45 @@ impl DependencyChecker for RedisChecker {
     }
 }
 
+/// Time a Horizon health probe may take, short enough to answer before
+/// [`check_health`] gives up on the dependency
+pub const DEFAULT_HORIZON_HEALTH_TIMEOUT: Duration = Duration::from_secs(3);
+
 pub struct HorizonChecker {
     client: crate::stellar::HorizonClient,
 }
 
 impl HorizonChecker {
+    /// Probe through `client` with [`DEFAULT_HORIZON_HEALTH_TIMEOUT`]; the
+    /// circuit breaker stays shared with `client`
     pub fn new(client: crate::stellar::HorizonClient) -> Self {
-        Self { client }
+        Self {
+            client: client.with_timeout(DEFAULT_HORIZON_HEALTH_TIMEOUT),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    /// Time each probe may take
+    pub fn timeout(&self) -> Duration {
+        self.client.timeout()
     }
 }
 
 #[async_trait]
 impl DependencyChecker for HorizonChecker {
     async fn check(&self) -> DependencyStatus {
+        // A lookup would be rejected anyway; don't spend a health check on it
+        if self.client.circuit_state() == "open" {
+            return DependencyStatus::Unhealthy {
+                status: "unhealthy".to_string(),
+                error: "circuit open".to_string(),
+            };
+        }
+
         let start = Instant::now();
         let test_account = "GAAZI4TCR3TY5OJHCTJC2A4QM7S4WXZ3XQFTKJBBHKS3HZXBCXQXQXQX";
         match self.client.get_account(test_account).await {
@@ -166,7 +204,8 @@ pub async fn check_health(
 
     HealthResponse {
         status: overall_status,
-        version: "0.1.0".to_string(),
+        version: crate::build_info::VERSION.to_string(),
+        git_sha: crate::build_info::GIT_SHA.to_string(),
         uptime_seconds: start_time.elapsed().as_secs(),
         dependencies,
     }
@@ -1,3 +1,4 @@
+pub mod build_info;
 pub mod config;
 pub mod db;
 pub mod error;
@@ -15,16 +16,20 @@ pub mod stellar;
 pub mod utils;
 pub mod validation;
 
+use crate::config::Config;
 use crate::db::pool_manager::PoolManager;
 use crate::graphql::schema::AppSchema;
-use crate::handlers::ws::TransactionStatusUpdate;
+use crate::handlers::ws::{TransactionStatusUpdate, WsConnections};
+use crate::middleware::idempotency::IdempotencyService;
 pub use crate::readiness::ReadinessState;
 use crate::services::feature_flags::FeatureFlagService;
+use crate::services::{CallbackQueue, JobScheduler};
 use crate::stellar::HorizonClient;
 use axum::{
     routing::{get, post},
     Router,
 };
+use std::sync::Arc;
 use tokio::sync::broadcast;
 
 #[derive(Clone)]
@@ -37,34 +42,167 @@ pub struct AppState {
     pub start_time: std::time::Instant,
     pub readiness: ReadinessState,
     pub tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    pub ws_connections: WsConnections,
+    pub idempotency: IdempotencyService,
+    pub config: Arc<Config>,
 }
 
 #[derive(Clone)]
 pub struct ApiState {
     pub app_state: AppState,
     pub graphql_schema: AppSchema,
+    /// Callbacks accepted with `async=true`, stored off the request path
+    pub callback_queue: CallbackQueue,
 }
 
 pub fn create_app(app_state: AppState) -> Router {
+    create_app_with_jobs(app_state, JobScheduler::new())
+}
+
+/// Like [`create_app`], with `jobs` reported by `GET /admin/jobs`
+pub fn create_app_with_jobs(app_state: AppState, jobs: JobScheduler) -> Router {
+    let (callback_queue, _) = CallbackQueue::spawn(
+        app_state.db.clone(),
+        app_state.config.callback_queue_capacity,
+    );
+    create_app_with_queue(app_state, jobs, callback_queue)
+}
+
+/// Like [`create_app_with_jobs`], storing `async=true` callbacks through
+/// `callback_queue`, whose processor finishes once the router is dropped
+pub fn create_app_with_queue(
+    app_state: AppState,
+    jobs: JobScheduler,
+    callback_queue: CallbackQueue,
+) -> Router {
     let graphql_schema = crate::graphql::schema::build_schema(app_state.clone());
+    let admin_routes: Router<ApiState> = Router::new()
+        .merge(handlers::dlq::admin_dlq_routes().with_state(app_state.db.clone()))
+        .merge(handlers::admin::admin_transaction_routes().with_state(app_state.clone()))
+        .merge(handlers::admin::admin_api_key_routes().with_state(app_state.clone()))
+        .merge(handlers::admin::admin_db_routes().with_state(app_state.clone()))
+        .merge(handlers::admin::admin_settlement_routes().with_state(app_state.clone()))
+        .merge(handlers::admin::admin_job_routes().with_state(jobs))
+        .layer(axum::middleware::from_fn(middleware::auth::admin_auth));
     let api_state = ApiState {
         app_state,
         graphql_schema,
+        callback_queue,
     };
 
-    Router::new()
+    let pool_manager = api_state.app_state.pool_manager.clone();
+    let config = api_state.app_state.config.clone();
+    let body_limits = config.body_limits;
+    let timeouts = config.server_timeouts;
+    let rate_limits = Arc::new(middleware::rate_limit::RateLimitConfig::new(&config));
+    let api_keys = services::ApiKeyService::new(api_state.app_state.db.clone());
+    let routes = Router::new()
         .route("/health", get(handlers::health))
+        .route("/version", get(handlers::version))
+        .route("/ratelimit/status", get(handlers::rate_limit_status))
         .route("/ready", get(handlers::ready))
         .route("/errors", get(handlers::error_catalog))
         .route("/settlements", get(handlers::settlements::list_settlements))
+        .route(
+            "/settlements/preview",
+            get(handlers::settlements::preview_settlements),
+        )
+        .route(
+            "/settlements/export",
+            get(handlers::export::export_settlements),
+        )
         .route(
             "/settlements/:id",
             get(handlers::settlements::get_settlement),
         )
-        .route("/callback", post(handlers::webhook::callback))
-        .route("/callback/transaction", post(handlers::webhook::callback)) // Backward compatibility
+        .route(
+            "/settlements/:id/receipt",
+            get(handlers::settlements::get_settlement_receipt),
+        )
+        .route(
+            "/transactions/search",
+            get(handlers::search::search_transactions),
+        )
+        .route(
+            "/transactions/count",
+            get(handlers::search::count_transactions),
+        )
+        .route(
+            "/transactions",
+            get(handlers::webhook::list_transactions_api),
+        )
         .route("/transactions/:id", get(handlers::webhook::get_transaction))
+        .route(
+            "/transactions/:id/timeline",
+            get(handlers::webhook::get_transaction_timeline),
+        )
+        .route(
+            "/transactions/:id/refund",
+            post(handlers::webhook::refund_transaction)
+                .route_layer(axum::middleware::from_fn(middleware::auth::admin_auth)),
+        )
         .route("/graphql", post(handlers::graphql::graphql_handler))
+        .route(
+            "/graphql/ws",
+            get(handlers::graphql::graphql_subscription_handler),
+        )
         .route("/export", get(handlers::export::export_transactions))
-        .with_state(api_state)
+        .route("/export/link", post(handlers::export::create_export_link))
+        .route(
+            "/export/token/:token",
+            get(handlers::export::export_with_token),
+        )
+        .route("/ws", get(handlers::ws::ws_handler));
+    // Redelivered webhooks carrying the same `x-idempotency-key` replay the
+    // first response instead of being stored twice
+    let callback_routes = Router::new()
+        .route("/callback", post(handlers::webhook::callback))
+        .route("/callback/transaction", post(handlers::webhook::callback)); // Backward compatibility
+                                                                            // Batches legitimately carry many transactions, so they get their own cap
+    let batch_routes =
+        Router::new().route("/callback/batch", post(handlers::webhook::callback_batch));
+
+    let idempotency = api_state.app_state.idempotency.clone();
+    let app = Router::new()
+        .merge(middleware::body_limit::limit_body(
+            routes,
+            body_limits.max_body_bytes,
+        ))
+        .merge(
+            middleware::body_limit::limit_body(callback_routes, body_limits.max_body_bytes).layer(
+                axum::middleware::from_fn_with_state(
+                    idempotency,
+                    middleware::idempotency::idempotency_middleware,
+                ),
+            ),
+        )
+        .merge(middleware::body_limit::limit_body(
+            batch_routes,
+            body_limits.max_batch_body_bytes,
+        ))
+        .nest("/admin", admin_routes)
+        .layer(axum::middleware::from_fn_with_state(
+            api_keys,
+            middleware::auth::api_key_auth,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            pool_manager,
+            middleware::read_only::read_only_guard,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            rate_limits,
+            middleware::rate_limit::rate_limit_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            middleware::request_logger::AccessLogConfig {
+                trusted_proxy_depth: config.trusted_proxy_depth,
+            },
+            middleware::request_logger::request_logger_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            middleware::error_response::ErrorResponseConfig::new(&config),
+            middleware::error_response::error_response_middleware,
+        ))
+        .with_state(api_state);
+    middleware::timeout::with_request_timeout(app, timeouts.request)
 }
@@ -1,23 +1,25 @@
-use axum::{
-    middleware as axum_middleware,
-    routing::{get, post},
-    Router,
-};
 use clap::Parser;
 use sqlx::migrate::Migrator;
-use std::{net::SocketAddr, path::Path};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use synapse_core::{
-    config, db,
+    config, create_app_with_queue, db,
     db::pool_manager::PoolManager,
-    graphql::schema::build_schema,
     handlers,
-    handlers::ws::TransactionStatusUpdate,
-    metrics, middleware,
+    handlers::ws::{TransactionStatusUpdate, WsConnections},
+    metrics,
     middleware::idempotency::IdempotencyService,
     schemas,
-    services::{FeatureFlagService, SettlementService},
+    services::{
+        jobs, webhook_dispatcher, BackupScheduler, BackupService, CallbackQueue,
+        FeatureFlagService, Job, JobScheduler, ReconciliationWorker, WebhookDispatcher,
+    },
+    startup,
     stellar::HorizonClient,
-    ApiState, AppState, ReadinessState,
+    AppState, ReadinessState,
 };
 use tokio::sync::broadcast;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -30,21 +32,42 @@ use cli::{BackupCommands, Cli, Commands, DbCommands, TxCommands};
 #[openapi(
     paths(
         handlers::health,
+        handlers::version,
+        handlers::rate_limit_status,
         handlers::settlements::list_settlements,
+        handlers::settlements::preview_settlements,
         handlers::settlements::get_settlement,
+        handlers::settlements::get_settlement_receipt,
         handlers::webhook::handle_webhook,
         handlers::webhook::callback,
+        handlers::webhook::callback_batch,
+        handlers::webhook::list_transactions_api,
         handlers::webhook::get_transaction,
+        handlers::webhook::get_transaction_timeline,
+        handlers::webhook::refund_transaction,
     ),
     components(
         schemas(
             handlers::HealthStatus,
+            synapse_core::build_info::BuildInfo,
+            synapse_core::middleware::rate_limit::RateLimitStatus,
             handlers::DbPoolStats,
             handlers::settlements::Pagination,
             handlers::settlements::SettlementListResponse,
+            synapse_core::services::settlement::SettlementPreview,
+            synapse_core::services::settlement::AssetSettlementPreview,
+            synapse_core::services::settlement::SettlementBatchPreview,
+            synapse_core::services::settlement::SettlementReceipt,
+            synapse_core::services::settlement::ReceiptLine,
             handlers::webhook::WebhookPayload,
             handlers::webhook::WebhookResponse,
             handlers::webhook::CallbackPayload,
+            handlers::webhook::BatchCallbackPayload,
+            handlers::webhook::BatchCallbackResponse,
+            handlers::webhook::CallbackAccepted,
+            handlers::webhook::BatchItemResult,
+            handlers::webhook::BatchSummary,
+            handlers::webhook::RefundRequest,
             schemas::TransactionSchema,
             schemas::SettlementSchema,
         )
@@ -88,12 +111,16 @@ async fn main() -> anyhow::Result<()> {
     }
 
     match cli.command {
-        Some(Commands::Serve) | None => serve(config).await,
+        Some(Commands::Serve) | None => serve(config, cli.skip_validation).await,
         Some(Commands::Tx(tx_cmd)) => match tx_cmd {
             TxCommands::ForceComplete { tx_id } => {
                 let pool = db::create_pool(&config).await?;
                 cli::handle_tx_force_complete(&pool, tx_id).await
             }
+            TxCommands::Reprocess { tx_id } => {
+                let pool = db::create_pool(&config).await?;
+                cli::handle_tx_reprocess(&pool, tx_id).await
+            }
         },
         Some(Commands::Db(db_cmd)) => match db_cmd {
             DbCommands::Migrate => cli::handle_db_migrate(&config).await,
@@ -107,76 +134,119 @@ async fn main() -> anyhow::Result<()> {
                 cli::handle_backup_restore(&config, &filename).await
             }
             BackupCommands::Cleanup => cli::handle_backup_cleanup(&config).await,
+            BackupCommands::Verify => cli::handle_backup_verify(&config).await,
         },
         Some(Commands::Config) => cli::handle_config_validate(&config),
     }
 }
 
-async fn serve(config: config::Config) -> anyhow::Result<()> {
+async fn serve(config: config::Config, skip_validation: bool) -> anyhow::Result<()> {
     let pool = db::create_pool(&config).await?;
 
+    // Run migrations
+    let migrator = Migrator::new(Path::new("./migrations")).await?;
+    migrator.run(&pool).await?;
+    tracing::info!("Database migrations completed");
+
+    let report = startup::validate_environment(&config, &pool).await?;
+    if !report.is_valid() {
+        report.print();
+        if !skip_validation {
+            anyhow::bail!("startup validation failed; pass --skip-validation to start anyway");
+        }
+        tracing::warn!("Startup validation failed, continuing because of --skip-validation");
+    }
+
     // Initialize pool manager for multi-region failover
-    let pool_manager =
-        PoolManager::new(&config.database_url, config.database_replica_url.as_deref()).await?;
+    let pool_manager = PoolManager::with_tls(
+        &config.database_url,
+        config.database_replica_url.as_deref(),
+        &config.db_tls,
+    )
+    .await?
+    .with_replica_query_timeout(std::time::Duration::from_millis(
+        config.replica_query_timeout_ms,
+    ))
+    .with_max_replica_lag(std::time::Duration::from_secs(
+        config.db_replica_max_lag_secs,
+    ));
 
     if pool_manager.replica().is_some() {
         tracing::info!("Database replica configured - read queries will be routed to replica");
     } else {
         tracing::info!("No replica configured - all queries will use primary database");
     }
+    // Writes are refused with 503 while the primary is down
+    pool_manager.spawn_health_monitor(std::time::Duration::from_secs(10));
 
-    // Run migrations
-    let migrator = Migrator::new(Path::new("./migrations")).await?;
-    migrator.run(&pool).await?;
-    tracing::info!("Database migrations completed");
+    // Background jobs, inspected with GET /admin/jobs
+    let job_scheduler = JobScheduler::new();
 
-    // Initialize partition manager (runs every 24 hours)
-    let partition_manager = db::partition::PartitionManager::new(pool.clone(), 24);
-    partition_manager.start();
-    tracing::info!("Partition manager started");
+    // Partition maintenance runs every 24 hours unless configured
+    let partition_manager = db::partition::PartitionManager::new(pool.clone(), 24).with_interval(
+        std::time::Duration::from_secs(config.partition_maintenance_interval_secs),
+    );
+    register_job(
+        &job_scheduler,
+        jobs::PartitionMaintenanceJob::new(partition_manager),
+    )
+    .await?;
 
     // Initialize Stellar Horizon client
-    let horizon_client = HorizonClient::new(config.stellar_horizon_url.clone());
+    let horizon_client = HorizonClient::with_endpoints(config.stellar_horizon_urls.clone())
+        .with_timeout(std::time::Duration::from_secs(config.horizon_timeout_secs));
     tracing::info!(
-        "Stellar Horizon client initialized with URL: {}",
-        config.stellar_horizon_url
+        "Stellar Horizon client initialized with URLs: {} ({}s timeout)",
+        config.stellar_horizon_urls.join(", "),
+        config.horizon_timeout_secs
     );
 
-    // Initialize Settlement Service
-    let _settlement_service = SettlementService::new(pool.clone());
-
-    // Start background settlement worker
-    let settlement_pool = pool.clone();
-    tokio::spawn(async move {
-        let service = SettlementService::new(settlement_pool);
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600)); // Default to hourly
-        loop {
-            interval.tick().await;
-            tracing::info!("Running scheduled settlement job...");
-            match service.run_settlements().await {
-                Ok(results) => {
-                    if !results.is_empty() {
-                        tracing::info!("Successfully generated {} settlements", results.len());
-                    }
-                }
-                Err(e) => tracing::error!("Scheduled settlement job failed: {:?}", e),
-            }
+    // Hourly settlement of every asset
+    register_job(
+        &job_scheduler,
+        jobs::SettlementJob::new(pool.clone(), config.settlement_max_batch_size),
+    )
+    .await?;
+    register_job(&job_scheduler, jobs::DlqRetryJob::new(pool.clone())).await?;
+    match config.transaction_pii_retention_days {
+        Some(days) => {
+            register_job(
+                &job_scheduler,
+                jobs::PiiRetentionJob::new(pool.clone(), days),
+            )
+            .await?
         }
-    });
+        None => tracing::info!("PII retention disabled, TRANSACTION_PII_RETENTION_DAYS unset"),
+    }
+
+    // Start background on-chain reconciliation worker
+    let reconciliation_worker = ReconciliationWorker::new(pool.clone(), horizon_client.clone());
+    tokio::spawn(reconciliation_worker.run());
+
+    // Start scheduled backups
+    if config.backup_schedule_enabled {
+        let backup_service = BackupService::new(
+            config.database_url.clone(),
+            PathBuf::from(&config.backup_dir),
+            config.backup_encryption_key.clone(),
+        )
+        .with_restore_jobs(config.backup_restore_jobs);
+        let backup_scheduler = BackupScheduler::new(backup_service, config.backup_schedule);
+        register_job(&job_scheduler, jobs::BackupJob::new(backup_scheduler)).await?;
+    } else {
+        tracing::info!("Scheduled backups disabled");
+    }
+
+    job_scheduler
+        .start()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to start job scheduler: {}", e))?;
 
     // Initialize metrics
     let _metrics_handle = metrics::init_metrics()
         .map_err(|e| anyhow::anyhow!("Failed to initialize metrics: {}", e))?;
     tracing::info!("Metrics initialized successfully");
 
-    // Initialize rate limiting
-    // let rate_limit_config = Arc::new(RateLimitConfig::new(&config));
-
-    // Load whitelisted IPs from config
-    // if !config.whitelisted_ips.is_empty() {
-    //     rate_limit_config.load_whitelisted_ips(&config.whitelisted_ips).await;
-    // }
-
     tracing::info!(
         "Rate limiting configured: {} req/sec (default), {} req/sec (whitelisted)",
         config.default_rate_limit,
@@ -184,16 +254,43 @@ async fn serve(config: config::Config) -> anyhow::Result<()> {
     );
 
     // Initialize Redis idempotency service
-    let _idempotency_service = IdempotencyService::new(&config.redis_url)?;
+    let idempotency = IdempotencyService::with_lock_ttl(
+        &config.redis_url,
+        std::time::Duration::from_secs(config.idempotency_lock_ttl_secs),
+    )?;
     tracing::info!("Redis idempotency service initialized");
 
+    if config.export_link_secret.is_none() {
+        tracing::warn!("EXPORT_LINK_SECRET not set; shareable export links are disabled");
+    }
+
     // Create broadcast channel for WebSocket notifications
     // Channel capacity of 100 - slow clients will miss old messages (backpressure handling)
     let (tx_broadcast, _) = broadcast::channel::<TransactionStatusUpdate>(100);
     tracing::info!("WebSocket broadcast channel initialized");
 
+    let webhook_config = &config.webhook_dispatch;
+    if let Some(url) = &webhook_config.url {
+        let (queue, dispatcher) = WebhookDispatcher::channel(
+            webhook_config,
+            Arc::new(webhook_dispatcher::HttpWebhookSink::new(url.clone())),
+        );
+        dispatcher.spawn();
+        webhook_dispatcher::forward_status_updates(tx_broadcast.subscribe(), queue);
+        tracing::info!(
+            "Outbound webhooks enabled: queue capacity {}, concurrency {}",
+            webhook_config.queue_capacity,
+            webhook_config.concurrency
+        );
+    }
+
     // Initialize feature flags service
-    let feature_flags = FeatureFlagService::new(pool.clone());
+    let feature_flags = FeatureFlagService::new(pool.clone()).with_ttl(
+        std::time::Duration::from_secs(config.feature_flag_cache_ttl_secs),
+    );
+    if let Err(e) = feature_flags.refresh().await {
+        tracing::warn!("Failed to load feature flags at startup: {}", e);
+    }
     tracing::info!("Feature flags service initialized");
 
     let monitor_pool = pool.clone();
@@ -206,70 +303,80 @@ async fn serve(config: config::Config) -> anyhow::Result<()> {
         start_time: std::time::Instant::now(),
         readiness: ReadinessState::new(),
         tx_broadcast,
-    };
-
-    let graphql_schema = build_schema(app_state.clone());
-    let api_state = ApiState {
-        app_state,
-        graphql_schema,
+        ws_connections: WsConnections::new(config.ws_max_connections),
+        idempotency,
+        config: Arc::new(config.clone()),
     };
 
     tokio::spawn(async move {
         pool_monitor_task(monitor_pool).await;
     });
 
-    let _api_routes: Router = Router::new()
-        .route("/health", get(handlers::health))
-        .route("/settlements", get(handlers::settlements::list_settlements))
-        .route(
-            "/settlements/:id",
-            get(handlers::settlements::get_settlement),
-        )
-        .route("/callback", post(handlers::webhook::callback))
-        .route("/transactions/:id", get(handlers::webhook::get_transaction))
-        .route("/graphql", post(handlers::graphql::graphql_handler))
-        .with_state(api_state.clone());
-
-    let _webhook_routes: Router = Router::new()
-        .route("/webhook", post(handlers::webhook::handle_webhook))
-        .layer(axum_middleware::from_fn_with_state(
-            config.clone(),
-            metrics::metrics_auth_middleware::<axum::body::Body>,
-        ))
-        .with_state(api_state.clone());
-
-    let _dlq_routes: Router =
-        handlers::dlq::dlq_routes().with_state(api_state.app_state.db.clone());
-
-    let _admin_routes: Router = Router::new()
-        .nest("/admin/queue", handlers::admin::admin_routes())
-        .layer(axum_middleware::from_fn(middleware::auth::admin_auth))
-        .with_state(api_state.app_state.db.clone());
-
-    let _search_routes: Router = Router::new()
-        .route(
-            "/transactions/search",
-            get(handlers::search::search_transactions),
-        )
-        .with_state(api_state.app_state.pool_manager.clone());
-
-    let app = Router::new()
-        // Unversioned routes - default to latest (V2) or specific base routes
-        .route("/health", get(handlers::health))
-        .route("/settlements", get(handlers::settlements::list_settlements))
-        .route(
-            "/settlements/:id",
-            get(handlers::settlements::get_settlement),
-        )
-        .with_state(api_state);
+    let (callback_queue, callback_processor) =
+        CallbackQueue::spawn(pool.clone(), config.callback_queue_capacity);
+    let app = create_app_with_queue(app_state, job_scheduler.clone(), callback_queue);
+
+    let timeouts = config.server_timeouts;
 
     let addr = SocketAddr::from(([0, 0, 0, 0], config.server_port));
     tracing::info!("listening on {}", addr);
 
-    axum::Server::bind(&addr)
+    let served = axum::Server::bind(&addr)
+        .http1_header_read_timeout(timeouts.header_read)
+        .tcp_keepalive(Some(timeouts.tcp_keepalive))
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-        .await?;
+        .with_graceful_shutdown(shutdown_signal())
+        .await;
+    // The server has dropped the router, so the queue only has to drain
+    tracing::info!("Storing queued callbacks before exiting");
+    if let Err(e) = callback_processor.await {
+        tracing::error!("Callback queue processor failed: {}", e);
+    }
+    if let Err(e) = job_scheduler.stop().await {
+        tracing::error!("Failed to stop job scheduler: {}", e);
+    }
+    served?;
+
+    Ok(())
+}
+
+/// Resolves on Ctrl+C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received, finishing in-flight requests");
+}
 
+async fn register_job(scheduler: &JobScheduler, job: impl Job + 'static) -> anyhow::Result<()> {
+    let name = job.name().to_string();
+    scheduler
+        .register_job(Box::new(job))
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to register job '{}': {}", name, e))?;
+    tracing::info!("Registered background job '{}'", name);
     Ok(())
 }
 
@@ -1,10 +1,12 @@
+use crate::db::pool_manager::PoolManager;
 use axum::{
     extract::State,
     http::{Request, StatusCode},
     middleware::Next,
     response::Response,
 };
-use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Clone)]
 pub struct MetricsHandle;
@@ -12,18 +14,228 @@ pub struct MetricsHandle;
 #[derive(Clone)]
 pub struct MetricsState {
     pub handle: MetricsHandle,
-    pub pool: PgPool,
+    pub pool_manager: PoolManager,
+}
+
+/// Connections checked out of the primary pool
+pub const ACTIVE_DB_CONNECTIONS_METRIC: &str = "active_db_connections";
+/// Per-pool gauges, labelled `pool="primary"` or `pool="replica_N"`
+pub const POOL_ACTIVE_METRIC: &str = "db_pool_active_connections";
+pub const POOL_IDLE_METRIC: &str = "db_pool_idle_connections";
+pub const POOL_MAX_METRIC: &str = "db_pool_max_connections";
+/// Queries that gave up waiting for a pooled connection
+pub const POOL_ACQUIRE_TIMEOUTS_METRIC: &str = "db_pool_acquire_timeouts_total";
+
+/// Bucket bounds used when a histogram is observed before being registered
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Process-wide gauge, counter and histogram values, rendered in Prometheus
+/// text format
+#[derive(Default)]
+pub struct MetricsRegistry {
+    families: Mutex<BTreeMap<String, Family>>,
+    histograms: Mutex<BTreeMap<String, Histogram>>,
+}
+
+/// One metric name and its series, keyed by rendered label set
+struct Family {
+    kind: &'static str,
+    series: BTreeMap<String, f64>,
+}
+
+/// Cumulative bucket counts plus sum and count of all observations
+struct Histogram {
+    bounds: Vec<f64>,
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            buckets: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter_mut()) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, name: &str) -> String {
+        let mut out = format!("# TYPE {} histogram\n", name);
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            out.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n", name, bound, bucket));
+        }
+        out.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", name, self.count));
+        out.push_str(&format!("{}_sum {}\n", name, self.sum));
+        out.push_str(&format!("{}_count {}\n", name, self.count));
+        out
+    }
+}
+
+impl MetricsRegistry {
+    pub fn set_gauge(&self, name: &str, value: f64) {
+        self.update(name, "gauge", &[], |v| *v = value);
+    }
+
+    pub fn gauge(&self, name: &str) -> Option<f64> {
+        self.value(name, &[])
+    }
+
+    pub fn set_labelled_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, "gauge", labels, |v| *v = value);
+    }
+
+    pub fn labelled_gauge(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        self.value(name, labels)
+    }
+
+    /// Expose a counter at zero before it is first incremented
+    pub fn register_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        self.update(name, "counter", labels, |_| {});
+    }
+
+    pub fn increment_counter(&self, name: &str, labels: &[(&str, &str)]) {
+        self.update(name, "counter", labels, |v| *v += 1.0);
+    }
+
+    pub fn add_counter(&self, name: &str, labels: &[(&str, &str)], amount: u64) {
+        self.update(name, "counter", labels, |v| *v += amount as f64);
+    }
+
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        self.value(name, labels).unwrap_or(0.0) as u64
+    }
+
+    /// Expose an empty histogram with the given upper bucket bounds.
+    /// Registering an existing histogram keeps its samples.
+    pub fn register_histogram(&self, name: &str, bounds: &[f64]) {
+        let mut histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        histograms
+            .entry(name.to_string())
+            .or_insert_with(|| Histogram::new(bounds));
+    }
+
+    pub fn observe_histogram(&self, name: &str, value: f64) {
+        let mut histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        histograms
+            .entry(name.to_string())
+            .or_insert_with(|| Histogram::new(DEFAULT_BUCKETS))
+            .observe(value);
+    }
+
+    /// Number of observations recorded in a histogram
+    pub fn histogram_count(&self, name: &str) -> u64 {
+        let histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        histograms.get(name).map(|h| h.count).unwrap_or(0)
+    }
+
+    pub fn render(&self) -> String {
+        // Families and histograms are interleaved so the output stays sorted by name
+        let mut rendered = BTreeMap::new();
+        {
+            let families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+            for (name, family) in families.iter() {
+                let mut out = format!("# TYPE {} {}\n", name, family.kind);
+                for (labels, value) in &family.series {
+                    out.push_str(&format!("{}{} {}\n", name, labels, value));
+                }
+                rendered.insert(name.clone(), out);
+            }
+        }
+        let histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, histogram) in histograms.iter() {
+            rendered.insert(name.clone(), histogram.render(name));
+        }
+        rendered.into_values().collect()
+    }
+
+    fn update(
+        &self,
+        name: &str,
+        kind: &'static str,
+        labels: &[(&str, &str)],
+        apply: impl FnOnce(&mut f64),
+    ) {
+        let mut families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        let family = families.entry(name.to_string()).or_insert_with(|| Family {
+            kind,
+            series: BTreeMap::new(),
+        });
+        apply(family.series.entry(render_labels(labels)).or_insert(0.0));
+    }
+
+    fn value(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        let families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        families
+            .get(name)
+            .and_then(|family| family.series.get(&render_labels(labels)))
+            .copied()
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, value))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// The global registry shared by all background workers
+pub fn registry() -> &'static MetricsRegistry {
+    static REGISTRY: OnceLock<MetricsRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(MetricsRegistry::default)
 }
 
 pub fn init_metrics() -> Result<MetricsHandle, Box<dyn std::error::Error>> {
+    registry().register_counter(POOL_ACQUIRE_TIMEOUTS_METRIC, &[]);
+    crate::middleware::idempotency::register_metrics(registry());
+    crate::middleware::rate_limit::register_metrics(registry());
+    crate::services::settlement::register_metrics(registry());
+    crate::handlers::export::register_metrics(registry());
     Ok(MetricsHandle)
 }
 
-pub async fn metrics_handler(
-    State(_handle): State<MetricsHandle>,
-    State(_pool): State<PgPool>,
-) -> Result<String, StatusCode> {
-    Ok("# Metrics placeholder\n".to_string())
+/// Publish size, idle and max connections for every pool in `pools`
+pub fn record_pool_stats(registry: &MetricsRegistry, pools: &PoolManager) {
+    for (label, pool) in pools.labelled_pools() {
+        // `size` counts idle connections too
+        let idle = pool.num_idle() as u32;
+        let active = pool.size().saturating_sub(idle);
+        let labels = [("pool", label.as_str())];
+        registry.set_labelled_gauge(POOL_ACTIVE_METRIC, &labels, f64::from(active));
+        registry.set_labelled_gauge(POOL_IDLE_METRIC, &labels, f64::from(idle));
+        registry.set_labelled_gauge(
+            POOL_MAX_METRIC,
+            &labels,
+            f64::from(pool.options().get_max_connections()),
+        );
+        if label == "primary" {
+            registry.set_gauge(ACTIVE_DB_CONNECTIONS_METRIC, f64::from(active));
+        }
+    }
+}
+
+pub async fn metrics_handler(State(state): State<MetricsState>) -> Result<String, StatusCode> {
+    let registry = registry();
+    record_pool_stats(registry, &state.pool_manager);
+    Ok(registry.render())
 }
 
 pub async fn metrics_auth_middleware<B>(
@@ -34,3 +246,64 @@ pub async fn metrics_auth_middleware<B>(
     // Simple auth check - in production, implement proper authentication
     Ok(next.run(request).await)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_gauges() {
+        let registry = MetricsRegistry::default();
+        registry.set_gauge("b_gauge", 2.0);
+        registry.set_gauge("a_gauge", 1.5);
+
+        assert_eq!(registry.gauge("a_gauge"), Some(1.5));
+        assert_eq!(
+            registry.render(),
+            "# TYPE a_gauge gauge\na_gauge 1.5\n# TYPE b_gauge gauge\nb_gauge 2\n"
+        );
+    }
+
+    #[test]
+    fn test_render_labelled_counters() {
+        let registry = MetricsRegistry::default();
+        registry.register_counter("requests_total", &[("scope", "default")]);
+        registry.increment_counter("requests_total", &[("scope", "whitelist")]);
+        registry.increment_counter("requests_total", &[("scope", "whitelist")]);
+
+        assert_eq!(
+            registry.counter("requests_total", &[("scope", "default")]),
+            0
+        );
+        assert_eq!(
+            registry.counter("requests_total", &[("scope", "whitelist")]),
+            2
+        );
+        assert_eq!(
+            registry.render(),
+            "# TYPE requests_total counter\n\
+             requests_total{scope=\"default\"} 0\n\
+             requests_total{scope=\"whitelist\"} 2\n"
+        );
+    }
+
+    #[test]
+    fn test_render_histogram() {
+        let registry = MetricsRegistry::default();
+        registry.register_histogram("batch_size", &[1.0, 10.0]);
+        registry.observe_histogram("batch_size", 1.0);
+        registry.observe_histogram("batch_size", 4.0);
+        registry.observe_histogram("batch_size", 40.0);
+
+        assert_eq!(registry.histogram_count("batch_size"), 3);
+        assert_eq!(
+            registry.render(),
+            "# TYPE batch_size histogram\n\
+             batch_size_bucket{le=\"1\"} 1\n\
+             batch_size_bucket{le=\"10\"} 2\n\
+             batch_size_bucket{le=\"+Inf\"} 3\n\
+             batch_size_sum 45\n\
+             batch_size_count 3\n"
+        );
+    }
+}
@@ -1,23 +1,263 @@
 use axum::{
+    async_trait,
     body::Body,
-    http::{Request, StatusCode},
+    extract::{FromRef, FromRequestParts, State},
+    http::{request::Parts, Request, StatusCode},
     middleware::Next,
     response::Response,
 };
+use uuid::Uuid;
 
-pub async fn admin_auth(req: Request<Body>, next: Next<Body>) -> Result<Response, StatusCode> {
+use crate::error::AppError;
+use crate::services::api_keys::ApiKeyService;
+use crate::{ApiState, AppState};
+
+/// Check an `Authorization` header value against the configured admin API key.
+/// Accepts both `Bearer <key>` and the bare key.
+pub fn is_admin_authorized(auth_header: Option<&str>) -> bool {
+    let admin_api_key =
+        std::env::var("ADMIN_API_KEY").unwrap_or_else(|_| "admin-secret-key".to_string());
+
+    match auth_header {
+        Some(auth) => auth == format!("Bearer {}", admin_api_key) || auth == admin_api_key,
+        None => false,
+    }
+}
+
+/// `Authorization` scheme for partner API keys
+pub const API_KEY_SCHEME: &str = "Api-Key";
+
+/// The key from an `Api-Key <key>` header value; empty if the key is missing,
+/// `None` for other schemes
+pub fn api_key_from_header(auth_header: &str) -> Option<&str> {
+    let (scheme, key) = auth_header.split_once(' ').unwrap_or((auth_header, ""));
+    scheme
+        .eq_ignore_ascii_case(API_KEY_SCHEME)
+        .then(|| key.trim())
+}
+
+pub async fn admin_auth<B>(req: Request<B>, next: Next<B>) -> Result<Response, StatusCode> {
     let auth_header = req
         .headers()
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    let admin_api_key =
-        std::env::var("ADMIN_API_KEY").unwrap_or_else(|_| "admin-secret-key".to_string());
+    if is_admin_authorized(auth_header) {
+        Ok(next.run(req).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
 
-    match auth_header {
-        Some(auth) if auth == format!("Bearer {}", admin_api_key) || auth == admin_api_key => {
-            Ok(next.run(req).await)
+/// Partner a request was authenticated as. [`api_key_auth`] puts this in the
+/// request extensions; [`CallerScope`] then uses it as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartnerContext {
+    pub partner_id: Uuid,
+    /// Key the request was authenticated with
+    pub api_key_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+impl PartnerContext {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// Authenticate `Authorization: Api-Key <key>` against the `api_keys` table
+/// and attach the key's [`PartnerContext`]. Unknown and revoked keys get 401;
+/// requests using another scheme, or none, pass through untouched.
+pub async fn api_key_auth(
+    State(keys): State<ApiKeyService>,
+    mut req: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, AppError> {
+    let key = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(api_key_from_header)
+        .map(str::to_string);
+
+    if let Some(key) = key {
+        let api_key = keys
+            .authenticate(&key)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized("invalid or revoked API key".to_string()))?;
+        req.extensions_mut().insert(PartnerContext {
+            partner_id: api_key.partner_id,
+            api_key_id: api_key.id,
+            scopes: api_key.scopes,
+        });
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Key scope needed to post callbacks
+pub const SCOPE_CALLBACK: &str = "callback";
+/// Key scope needed to list, search, export and look up transactions
+pub const SCOPE_READ: &str = "read";
+
+/// Which transactions a caller may see and which partner it records for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallerScope {
+    /// Authenticated with the admin key: every partner's transactions
+    Admin,
+    /// Only transactions recorded for this partner, within its key's scopes
+    Partner(PartnerContext),
+    /// Keys are not required (`REQUIRE_API_KEY=false`) and none was sent,
+    /// so there is nothing to scope by
+    Unscoped,
+}
+
+impl CallerScope {
+    /// Partner to filter queries by; `None` means no filter
+    pub fn partner_id(&self) -> Option<Uuid> {
+        match self {
+            Self::Partner(context) => Some(context.partner_id),
+            Self::Admin | Self::Unscoped => None,
+        }
+    }
+
+    /// Whether a transaction recorded for `partner_id` is visible to the caller
+    pub fn can_see(&self, partner_id: Option<Uuid>) -> bool {
+        match self.partner_id() {
+            Some(own) => partner_id == Some(own),
+            None => true,
         }
-        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+
+    /// Refuse partner keys without `scope` with 403
+    pub fn require(&self, scope: &str) -> Result<(), AppError> {
+        match self {
+            Self::Partner(context) if !context.has_scope(scope) => Err(
+                AppError::InsufficientPermissions(format!("API key lacks the '{}' scope", scope)),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether callers without a partner key must use the admin key, from
+/// `REQUIRE_API_KEY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequireApiKey(pub bool);
+
+impl FromRef<AppState> for RequireApiKey {
+    fn from_ref(state: &AppState) -> Self {
+        Self(state.config.require_api_key)
+    }
+}
+
+impl FromRef<ApiState> for RequireApiKey {
+    fn from_ref(state: &ApiState) -> Self {
+        Self::from_ref(&state.app_state)
+    }
+}
+
+/// Partner callers come from [`api_key_auth`]. Anyone else needs the admin
+/// key while [`RequireApiKey`] is set, and is rejected with 401.
+#[async_trait]
+impl<S> FromRequestParts<S> for CallerScope
+where
+    S: Send + Sync,
+    RequireApiKey: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(context) = parts.extensions.get::<PartnerContext>() {
+            return Ok(Self::Partner(context.clone()));
+        }
+        let auth_header = parts
+            .headers
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok());
+        if is_admin_authorized(auth_header) {
+            return Ok(Self::Admin);
+        }
+        if RequireApiKey::from_ref(state).0 {
+            return Err(AppError::Unauthorized(
+                "a partner API key is required".to_string(),
+            ));
+        }
+        Ok(Self::Unscoped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTNER_A: &str = "6f1c1a52-5d59-4d0e-9d4a-3b1f9a1d2c01";
+
+    fn partner(scopes: &[&str]) -> CallerScope {
+        CallerScope::Partner(PartnerContext {
+            partner_id: Uuid::parse_str(PARTNER_A).unwrap(),
+            api_key_id: Uuid::new_v4(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_api_key_from_header() {
+        assert_eq!(api_key_from_header("Api-Key sk_abc"), Some("sk_abc"));
+        assert_eq!(api_key_from_header("api-key  sk_abc "), Some("sk_abc"));
+        assert_eq!(api_key_from_header("Api-Key"), Some(""));
+        assert_eq!(api_key_from_header("Bearer sk_abc"), None);
+        assert_eq!(api_key_from_header("sk_abc"), None);
+    }
+
+    #[test]
+    fn test_caller_scope_visibility() {
+        let partner_a = Uuid::parse_str(PARTNER_A).unwrap();
+        let partner_b = Uuid::new_v4();
+
+        let scope = partner(&[SCOPE_READ]);
+        assert_eq!(scope.partner_id(), Some(partner_a));
+        assert!(scope.can_see(Some(partner_a)));
+        assert!(!scope.can_see(Some(partner_b)));
+        assert!(!scope.can_see(None));
+        assert!(CallerScope::Admin.can_see(Some(partner_b)));
+        assert!(CallerScope::Unscoped.can_see(None));
+    }
+
+    #[test]
+    fn test_caller_scope_requires_key_scope() {
+        let scope = partner(&[SCOPE_READ]);
+        assert!(scope.require(SCOPE_READ).is_ok());
+        assert!(matches!(
+            scope.require(SCOPE_CALLBACK),
+            Err(AppError::InsufficientPermissions(_))
+        ));
+        assert!(partner(&[]).require(SCOPE_READ).is_err());
+        assert!(CallerScope::Admin.require(SCOPE_CALLBACK).is_ok());
+        assert!(CallerScope::Unscoped.require(SCOPE_READ).is_ok());
+    }
+
+    async fn scope_for(auth: Option<&str>, required: bool) -> Result<CallerScope, AppError> {
+        let mut request = Request::builder();
+        if let Some(auth) = auth {
+            request = request.header("Authorization", auth);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        CallerScope::from_request_parts(&mut parts, &RequireApiKey(required)).await
+    }
+
+    #[tokio::test]
+    async fn test_caller_scope_follows_require_api_key() {
+        assert!(matches!(
+            scope_for(None, true).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        assert_eq!(scope_for(None, false).await.unwrap(), CallerScope::Unscoped);
+        assert_eq!(
+            scope_for(Some("Bearer admin-secret-key"), true)
+                .await
+                .unwrap(),
+            CallerScope::Admin
+        );
     }
 }
@@ -0,0 +1,72 @@
+use axum::body::Body;
+use axum::extract::DefaultBodyLimit;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use http_body::Limited;
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::config::parse_positive;
+use crate::error::AppError;
+
+/// Largest request body accepted unless `MAX_BODY_BYTES` says otherwise
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Largest `/callback/batch` body unless `MAX_BATCH_BODY_BYTES` says otherwise
+pub const DEFAULT_MAX_BATCH_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Request body caps. Bodies over the cap are rejected with 413 before
+/// they are buffered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub max_body_bytes: usize,
+    pub max_batch_body_bytes: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_batch_body_bytes: DEFAULT_MAX_BATCH_BODY_BYTES,
+        }
+    }
+}
+
+impl BodyLimits {
+    /// Read `MAX_BODY_BYTES` and `MAX_BATCH_BODY_BYTES`; missing values keep
+    /// the defaults and a cap that is not above zero is an error.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            max_body_bytes: parse_positive("MAX_BODY_BYTES", DEFAULT_MAX_BODY_BYTES)?,
+            max_batch_body_bytes: parse_positive(
+                "MAX_BATCH_BODY_BYTES",
+                DEFAULT_MAX_BATCH_BODY_BYTES,
+            )?,
+        })
+    }
+}
+
+/// Cap the bodies of every route in `router` at `max_bytes`, replacing
+/// axum's built-in extractor limit.
+pub fn limit_body<S>(router: Router<S, Limited<Body>>, max_bytes: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_bytes))
+        .layer(axum::middleware::map_response(structured_payload_too_large))
+}
+
+/// The limit layer answers a too-long `Content-Length` with a bare 413;
+/// give it the usual error body
+async fn structured_payload_too_large(res: Response) -> Response {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if res.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        AppError::PayloadTooLarge("request body exceeds the size limit".to_string()).into_response()
+    } else {
+        res
+    }
+}
@@ -0,0 +1,61 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::config::Config;
+use crate::error::{DetailedErrorBody, PoolExhaustedResponse};
+
+/// How error responses are finished before they leave the service
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorResponseConfig {
+    /// Send the underlying cause as `detail`; never on in production
+    pub debug_errors: bool,
+    /// `Retry-After` seconds when no database connection could be acquired
+    pub pool_retry_after_secs: u64,
+}
+
+impl ErrorResponseConfig {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            debug_errors: config.debug_errors,
+            pool_retry_after_secs: config.db_pool_retry_after_secs,
+        }
+    }
+}
+
+/// Finish error responses from the configuration: exhausted-pool responses
+/// get the configured `Retry-After`, and the body carrying `detail` replaces
+/// the public one when `debug_errors` is on. Otherwise the cause stays in
+/// the logs only.
+pub async fn error_response_middleware<B>(
+    State(config): State<ErrorResponseConfig>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    if response
+        .extensions_mut()
+        .remove::<PoolExhaustedResponse>()
+        .is_some()
+    {
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(config.pool_retry_after_secs),
+        );
+    }
+    let Some(DetailedErrorBody(body)) = response.extensions_mut().remove::<DetailedErrorBody>()
+    else {
+        return response;
+    };
+    if !config.debug_errors {
+        return response;
+    }
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    (parts, Json(body)).into_response()
+}
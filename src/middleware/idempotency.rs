@@ -1,19 +1,101 @@
 use axum::{
     body::Body,
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{MatchedPath, State},
+    http::{header, HeaderValue, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use redis::Client;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client, Script};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::metrics::MetricsRegistry;
+use crate::utils::redis_connect::{redis_connect_policy, RedisConnectPolicy};
+
+/// Prefix for all idempotency keys stored in Redis
+const KEY_PREFIX: &str = "idempotency:";
+
+/// Prefix of a lock value; the owning request's token follows the colon
+const PROCESSING_PREFIX: &str = "PROCESSING:";
+
+/// Default lifetime of a processing lock when no renewal happens
+pub const DEFAULT_LOCK_TTL: Duration = Duration::from_secs(30);
+
+/// How long completed responses are cached
+const RESPONSE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Overwrite the lock with the cached response only if we still own it
+const STORE_IF_OWNER: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    redis.call("SET", KEYS[1], ARGV[2], "PX", ARGV[3])
+    return 1
+end
+return 0
+"#;
+
+/// Delete the lock only if we still own it
+const RELEASE_IF_OWNER: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+/// Extend the lock TTL only if we still own it
+const RENEW_IF_OWNER: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+/// Keyed requests by outcome of the idempotency check, labelled by `result`
+pub const IDEMPOTENCY_METRIC: &str = "idempotency_total";
+/// Lock acquired; the request was processed
+pub const RESULT_NEW: &str = "new";
+/// Another request with the key was still in flight
+pub const RESULT_PROCESSING: &str = "processing";
+/// Served from the cached response
+pub const RESULT_COMPLETED: &str = "completed";
+/// Redis failed and the request was processed without a check
+pub const RESULT_ERROR: &str = "error";
+
+/// Expose the idempotency counters at zero so dashboards see them before traffic
+pub fn register_metrics(registry: &MetricsRegistry) {
+    for result in [
+        RESULT_NEW,
+        RESULT_PROCESSING,
+        RESULT_COMPLETED,
+        RESULT_ERROR,
+    ] {
+        registry.register_counter(IDEMPOTENCY_METRIC, &[("result", result)]);
+    }
+}
+
+fn record_result(result: &str) {
+    crate::metrics::registry().increment_counter(IDEMPOTENCY_METRIC, &[("result", result)]);
+}
+
+/// Paths that share the unprefixed legacy namespace, so keys stored before
+/// scoping existed keep matching
+const LEGACY_SCOPE_PATHS: &[&str] = &["/callback", "/callback/transaction"];
 
 #[derive(Clone)]
-#[allow(dead_code)]
 pub struct IdempotencyService {
     client: Client,
+    /// One multiplexed connection shared by every clone, opened on first use
+    /// and re-established automatically when it drops
+    connection: Arc<OnceCell<ConnectionManager>>,
+    connect_policy: RedisConnectPolicy,
+    lock_ttl: Duration,
+    scopes: Arc<HashMap<String, String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,51 +110,276 @@ pub struct IdempotencyKey {
     pub ttl_seconds: u64,
 }
 
+/// Longest `x-idempotency-key` accepted
+pub const MAX_KEY_LEN: usize = 128;
+
+/// A client-supplied `x-idempotency-key`: 1 to [`MAX_KEY_LEN`] characters
+/// of `[A-Za-z0-9_-]`, so it is always safe to use in a Redis key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdempotencyKey(String);
+
+impl ClientIdempotencyKey {
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        if raw.is_empty() || raw.len() > MAX_KEY_LEN {
+            return Err(AppError::BadRequest(format!(
+                "x-idempotency-key must be 1 to {} characters",
+                MAX_KEY_LEN
+            )));
+        }
+        if !raw
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        {
+            return Err(AppError::BadRequest(
+                "x-idempotency-key may only contain letters, digits, '_' and '-'".to_string(),
+            ));
+        }
+        Ok(Self(raw.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Debug)]
 pub enum IdempotencyStatus {
+    /// The lock was acquired by the caller's token
     New,
-    Processing,
+    /// Another request holds the lock; it expires in `retry_after_secs`
+    Processing {
+        retry_after_secs: u64,
+    },
     Completed(CachedResponse),
 }
 
 impl IdempotencyService {
     pub fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
+        Self::with_lock_ttl(redis_url, DEFAULT_LOCK_TTL)
+    }
+
+    /// Creates a new IdempotencyService with a custom processing lock TTL.
+    /// In-flight requests renew the lock every half TTL, so a short TTL only
+    /// matters when the owning process dies mid-request.
+    pub fn with_lock_ttl(redis_url: &str, lock_ttl: Duration) -> Result<Self, redis::RedisError> {
         let client = Client::open(redis_url)?;
-        Ok(Self { client })
+        let scopes = LEGACY_SCOPE_PATHS
+            .iter()
+            .map(|path| (path.to_string(), String::new()))
+            .collect();
+        Ok(Self {
+            client,
+            connection: Arc::new(OnceCell::new()),
+            connect_policy: *redis_connect_policy(),
+            lock_ttl,
+            scopes: Arc::new(scopes),
+        })
+    }
+
+    /// Store keys for `path` under `scope` instead of the path itself. Paths
+    /// given the same scope share one key namespace.
+    pub fn with_scope(mut self, path: &str, scope: &str) -> Self {
+        Arc::make_mut(&mut self.scopes).insert(path.to_string(), scope.to_string());
+        self
+    }
+
+    /// Bound how long connecting to Redis may take before a call fails open
+    pub fn with_connect_policy(mut self, policy: RedisConnectPolicy) -> Self {
+        self.connect_policy = policy;
+        self
+    }
+
+    pub fn lock_ttl(&self) -> Duration {
+        self.lock_ttl
+    }
+
+    /// The shared Redis connection. Connecting is retried on the next call if
+    /// Redis was unreachable, so the service can be built before Redis is up;
+    /// each attempt, and each command sent afterwards, is bounded by the
+    /// connect policy timeout.
+    pub async fn connection(&self) -> Result<ConnectionManager, redis::RedisError> {
+        self.connection
+            .get_or_try_init(|| self.connect_policy.connect_manager(&self.client))
+            .await
+            .cloned()
+    }
+
+    /// Key namespace for requests to the route template `path`; defaults to
+    /// the template itself
+    pub fn scope_for(&self, path: &str) -> String {
+        self.scopes
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| path.to_string())
     }
 
+    /// Try to acquire the processing lock for `key` on behalf of `token`.
     pub async fn check_idempotency(
         &self,
-        _key: &str,
+        key: &str,
+        token: &str,
     ) -> Result<IdempotencyStatus, redis::RedisError> {
-        // Placeholder implementation
-        Ok(IdempotencyStatus::New)
+        let mut conn = self.connection().await?;
+        let redis_key = redis_key(key);
+
+        self.connect_policy
+            .within_timeout(async {
+                let acquired: Option<String> = redis::cmd("SET")
+                    .arg(&redis_key)
+                    .arg(lock_value(token))
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(self.lock_ttl.as_millis() as u64)
+                    .query_async(&mut conn)
+                    .await?;
+
+                if acquired.is_some() {
+                    return Ok(IdempotencyStatus::New);
+                }
+
+                let existing: Option<String> = conn.get(&redis_key).await?;
+                match existing {
+                    Some(value) if value.starts_with(PROCESSING_PREFIX) => {
+                        let remaining_ms: i64 = conn.pttl(&redis_key).await?;
+                        Ok(IdempotencyStatus::Processing {
+                            retry_after_secs: retry_after_secs(remaining_ms),
+                        })
+                    }
+                    Some(value) => match serde_json::from_str::<CachedResponse>(&value) {
+                        Ok(cached) => Ok(IdempotencyStatus::Completed(cached)),
+                        Err(_) => Ok(IdempotencyStatus::Processing {
+                            retry_after_secs: 1,
+                        }),
+                    },
+                    // The lock expired between SET NX and GET; let the client retry immediately
+                    None => Ok(IdempotencyStatus::Processing {
+                        retry_after_secs: 1,
+                    }),
+                }
+            })
+            .await
     }
 
+    /// Replace the lock with the cached response. Returns false if the lock
+    /// is no longer owned by `token` (it expired and was reclaimed).
     pub async fn store_response(
         &self,
-        _key: &str,
-        _status: u16,
-        _body: String,
-    ) -> Result<(), redis::RedisError> {
-        // Placeholder implementation
-        Ok(())
+        key: &str,
+        token: &str,
+        status: u16,
+        body: String,
+    ) -> Result<bool, redis::RedisError> {
+        let mut conn = self.connection().await?;
+        let cached = serde_json::to_string(&CachedResponse { status, body })
+            .unwrap_or_else(|_| "{}".to_string());
+
+        let stored: i32 = self
+            .connect_policy
+            .within_timeout(
+                Script::new(STORE_IF_OWNER)
+                    .key(redis_key(key))
+                    .arg(lock_value(token))
+                    .arg(cached)
+                    .arg(RESPONSE_TTL.as_millis() as u64)
+                    .invoke_async(&mut conn),
+            )
+            .await?;
+
+        Ok(stored == 1)
     }
 
-    pub async fn release_lock(&self, _key: &str) -> Result<(), redis::RedisError> {
-        // Placeholder implementation
-        Ok(())
+    /// Release the processing lock if it is still owned by `token`.
+    pub async fn release_lock(&self, key: &str, token: &str) -> Result<bool, redis::RedisError> {
+        let mut conn = self.connection().await?;
+        let released: i32 = self
+            .connect_policy
+            .within_timeout(
+                Script::new(RELEASE_IF_OWNER)
+                    .key(redis_key(key))
+                    .arg(lock_value(token))
+                    .invoke_async(&mut conn),
+            )
+            .await?;
+
+        Ok(released == 1)
+    }
+
+    /// Extend the processing lock if it is still owned by `token`.
+    pub async fn renew_lock(&self, key: &str, token: &str) -> Result<bool, redis::RedisError> {
+        let mut conn = self.connection().await?;
+        let renewed: i32 = self
+            .connect_policy
+            .within_timeout(
+                Script::new(RENEW_IF_OWNER)
+                    .key(redis_key(key))
+                    .arg(lock_value(token))
+                    .arg(self.lock_ttl.as_millis() as u64)
+                    .invoke_async(&mut conn),
+            )
+            .await?;
+
+        Ok(renewed == 1)
     }
 
     pub async fn check_and_set(
         &self,
-        _key: &str,
-        _value: &str,
-        _ttl: Duration,
+        key: &str,
+        value: &str,
+        ttl: Duration,
     ) -> Result<bool, redis::RedisError> {
-        // Placeholder implementation
-        Ok(true)
+        let mut conn = self.connection().await?;
+        let set: Option<String> = self
+            .connect_policy
+            .within_timeout(
+                redis::cmd("SET")
+                    .arg(redis_key(key))
+                    .arg(value)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(ttl.as_millis() as u64)
+                    .query_async(&mut conn),
+            )
+            .await?;
+
+        Ok(set.is_some())
+    }
+}
+
+/// Prefix `key` with its scope; the empty scope leaves it unchanged
+fn scoped_key(scope: &str, key: &str) -> String {
+    if scope.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}:{}", scope, key)
+    }
+}
+
+fn redis_key(key: &str) -> String {
+    format!("{}{}", KEY_PREFIX, key)
+}
+
+fn lock_value(token: &str) -> String {
+    format!("{}{}", PROCESSING_PREFIX, token)
+}
+
+/// The route template that matched `request`, e.g. `/transactions/:id/refund`,
+/// so ids in the path do not each get their own scope. Falls back to the raw
+/// path outside a router.
+fn route_path(request: &Request<Body>) -> &str {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| request.uri().path())
+}
+
+/// Convert a PTTL reply into whole seconds for `Retry-After`, rounding up.
+/// PTTL returns -1 for keys without expiry and -2 for missing keys.
+fn retry_after_secs(remaining_ms: i64) -> u64 {
+    if remaining_ms <= 0 {
+        return 1;
     }
+    (remaining_ms as u64).div_ceil(1000)
 }
 
 /// Middleware to handle idempotency for webhook requests
@@ -85,29 +392,41 @@ pub async fn idempotency_middleware(
     // This could be from headers, query params, or body
     // For now, we'll extract from a custom header
     let idempotency_key = match request.headers().get("x-idempotency-key") {
-        Some(key) => match key.to_str() {
-            Ok(k) => k.to_string(),
-            Err(_) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({
-                        "error": "Invalid idempotency key format"
-                    })),
-                )
-                    .into_response();
+        Some(key) => {
+            let parsed = key
+                .to_str()
+                .map_err(|_| AppError::BadRequest("x-idempotency-key must be ASCII".to_string()))
+                .and_then(ClientIdempotencyKey::parse);
+            match parsed {
+                Ok(k) => scoped_key(&service.scope_for(route_path(&request)), k.as_str()),
+                Err(e) => return e.into_response(),
             }
-        },
+        }
         None => {
             // If no idempotency key provided, proceed without idempotency check
             return next.run(request).await;
         }
     };
 
+    // The lock is owned by the request id so only this request can release it
+    let token = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
     // Check idempotency status
-    match service.check_idempotency(&idempotency_key).await {
+    match service.check_idempotency(&idempotency_key, &token).await {
         Ok(IdempotencyStatus::New) => {
+            record_result(RESULT_NEW);
+            // Keep the lock alive while the request is in flight
+            let renewal =
+                spawn_lock_renewal(service.clone(), idempotency_key.clone(), token.clone());
+
             // Process the request
             let response: Response = next.run(request).await;
+            renewal.abort();
 
             // If successful (2xx), cache the response
             if response.status().is_success() {
@@ -118,30 +437,44 @@ pub async fn idempotency_middleware(
                 // In production, you might want to capture the actual response body
                 let body = serde_json::json!({"status": "success"}).to_string();
 
-                if let Err(e) = service.store_response(&idempotency_key, status, body).await {
-                    tracing::error!("Failed to store idempotency response: {}", e);
+                match service
+                    .store_response(&idempotency_key, &token, status, body)
+                    .await
+                {
+                    Ok(true) => {}
+                    Ok(false) => tracing::warn!(
+                        "Idempotency lock for '{}' was lost before the response was stored",
+                        idempotency_key
+                    ),
+                    Err(e) => tracing::error!("Failed to store idempotency response: {}", e),
                 }
             } else {
                 // Release lock on failure
-                if let Err(e) = service.release_lock(&idempotency_key).await {
+                if let Err(e) = service.release_lock(&idempotency_key, &token).await {
                     tracing::error!("Failed to release idempotency lock: {}", e);
                 }
             }
 
             response
         }
-        Ok(IdempotencyStatus::Processing) => {
+        Ok(IdempotencyStatus::Processing { retry_after_secs }) => {
+            record_result(RESULT_PROCESSING);
             // Request is currently being processed
-            (
+            let mut response = (
                 StatusCode::TOO_MANY_REQUESTS,
                 Json(serde_json::json!({
                     "error": "Request is currently being processed",
-                    "retry_after": 5
+                    "retry_after": retry_after_secs
                 })),
             )
-                .into_response()
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+            response
         }
         Ok(IdempotencyStatus::Completed(cached)) => {
+            record_result(RESULT_COMPLETED);
             // Return cached response
             let status = StatusCode::from_u16(cached.status).unwrap_or(StatusCode::OK);
             (
@@ -155,8 +488,213 @@ pub async fn idempotency_middleware(
         }
         Err(e) => {
             tracing::error!("Idempotency check failed: {}", e);
+            record_result(RESULT_ERROR);
             // On Redis failure, proceed with request (fail open)
             next.run(request).await
         }
     }
 }
+
+/// Periodically extend the processing lock until the returned task is aborted.
+fn spawn_lock_renewal(
+    service: IdempotencyService,
+    key: String,
+    token: String,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let period = (service.lock_ttl() / 2).max(Duration::from_millis(100));
+        let mut interval = tokio::time::interval(period);
+        interval.tick().await; // Skip first immediate tick
+
+        loop {
+            interval.tick().await;
+            match service.renew_lock(&key, &token).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::warn!("Idempotency lock for '{}' is no longer owned", key);
+                    return;
+                }
+                Err(e) => tracing::error!("Failed to renew idempotency lock: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_value_carries_token() {
+        assert_eq!(lock_value("req-1"), "PROCESSING:req-1");
+        assert_eq!(redis_key("tx-1"), "idempotency:tx-1");
+    }
+
+    #[test]
+    fn test_keys_are_scoped_per_path() {
+        let service = IdempotencyService::new("redis://localhost:6379")
+            .unwrap()
+            .with_scope("/settlements/export", "export");
+
+        assert_eq!(service.scope_for("/callback"), "");
+        assert_eq!(service.scope_for("/callback/transaction"), "");
+        assert_eq!(service.scope_for("/settlements/export"), "export");
+
+        let refund = service.scope_for("/transactions/1/refund");
+        assert_eq!(refund, "/transactions/1/refund");
+        assert_eq!(scoped_key(&service.scope_for("/callback"), "k1"), "k1");
+        assert_eq!(scoped_key(&refund, "k1"), "/transactions/1/refund:k1");
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        assert_eq!(retry_after_secs(1), 1);
+        assert_eq!(retry_after_secs(1000), 1);
+        assert_eq!(retry_after_secs(1001), 2);
+        assert_eq!(retry_after_secs(29_500), 30);
+    }
+
+    #[test]
+    fn test_client_key_validation() {
+        assert!(ClientIdempotencyKey::parse("tx_2024-01-abc").is_ok());
+        assert!(ClientIdempotencyKey::parse(&"a".repeat(MAX_KEY_LEN)).is_ok());
+
+        for bad in [
+            String::new(),
+            "a".repeat(MAX_KEY_LEN + 1),
+            "has space".to_string(),
+            "colon:key".to_string(),
+            "ключ".to_string(),
+        ] {
+            assert!(
+                matches!(
+                    ClientIdempotencyKey::parse(&bad),
+                    Err(AppError::BadRequest(_))
+                ),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    fn app() -> axum::Router {
+        // Nothing listens here: requests that reach Redis fail open
+        let service = IdempotencyService::new("redis://127.0.0.1:1").unwrap();
+        axum::Router::new()
+            .route("/callback", axum::routing::post(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                service,
+                idempotency_middleware,
+            ))
+    }
+
+    fn request_with_key(key: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/callback")
+            .header("x-idempotency-key", key)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_valid_key_passes_through() {
+        use tower::ServiceExt;
+        let registry = crate::metrics::registry();
+        let labels = [("result", RESULT_ERROR)];
+        let errors_before = registry.counter(IDEMPOTENCY_METRIC, &labels);
+
+        let res = app()
+            .oneshot(request_with_key("order-123_A"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        // Redis is unreachable, so the request is counted as failing open
+        assert!(registry.counter(IDEMPOTENCY_METRIC, &labels) > errors_before);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_fails_open_within_connect_timeout() {
+        let policy = RedisConnectPolicy {
+            timeout: Duration::from_millis(300),
+            retries: 5,
+            base_delay: Duration::from_millis(100),
+        };
+        let service = IdempotencyService::new("redis://127.0.0.1:1")
+            .unwrap()
+            .with_connect_policy(policy);
+
+        let start = std::time::Instant::now();
+        assert!(service.check_idempotency("key", "token").await.is_err());
+        assert!(start.elapsed() < Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_redis_fails_open_within_timeout() {
+        // Accepts connections but never answers a command
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let policy = RedisConnectPolicy {
+            timeout: Duration::from_millis(300),
+            retries: 0,
+            base_delay: Duration::from_millis(10),
+        };
+        let service = IdempotencyService::new(&format!("redis://{}", addr))
+            .unwrap()
+            .with_connect_policy(policy);
+
+        let start = std::time::Instant::now();
+        assert!(service.check_idempotency("key", "token").await.is_err());
+        assert!(service.release_lock("key", "token").await.is_err());
+        assert!(start.elapsed() < Duration::from_millis(1500));
+    }
+
+    #[tokio::test]
+    async fn test_scope_uses_the_route_template() {
+        use tower::ServiceExt;
+        let app = axum::Router::new()
+            .route("/items/:id/refund", axum::routing::post(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(
+                |request: Request<Body>, next: Next<Body>| async move {
+                    let path = route_path(&request).to_string();
+                    let mut response = next.run(request).await;
+                    response
+                        .headers_mut()
+                        .insert("x-route", HeaderValue::from_str(&path).unwrap());
+                    response
+                },
+            ));
+
+        let res = app
+            .oneshot(
+                Request::post(format!("/items/{}/refund", Uuid::new_v4()))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["x-route"], "/items/:id/refund");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_keys_are_rejected_before_redis() {
+        use tower::ServiceExt;
+        for key in ["a".repeat(MAX_KEY_LEN + 1), "bad key!".to_string()] {
+            let res = app().oneshot(request_with_key(&key)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_retry_after_handles_missing_ttl() {
+        assert_eq!(retry_after_secs(-1), 1);
+        assert_eq!(retry_after_secs(-2), 1);
+    }
+}
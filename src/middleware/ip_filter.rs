@@ -85,7 +85,7 @@ fn is_allowed(client_ip: Option<IpAddr>, allowed_ips: &AllowedIps) -> bool {
     }
 }
 
-fn extract_client_ip(
+pub(crate) fn extract_client_ip(
     headers: &HeaderMap,
     extensions: &axum::http::Extensions,
     trusted_proxy_depth: usize,
@@ -0,0 +1,303 @@
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{
+        rejection::{BytesRejection, JsonRejection},
+        FromRequest,
+    },
+    http::{Request, StatusCode},
+    Json,
+};
+
+use crate::error::AppError;
+
+/// `Json<T>` whose rejections are [`AppError`]s, so bad bodies get the
+/// stable error format instead of axum's plain-text rejection.
+///
+/// - unparseable or truncated JSON, and JSON that does not fit `T` (missing
+///   or unknown fields, wrong types): `BadRequest`, naming the offending
+///   field or position
+/// - a body over the size limit: `PayloadTooLarge`
+/// - a missing `Content-Type: application/json` and anything else: `BadRequest`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiJson<T>(pub T);
+
+/// [`ApiJson`] for an optional body: an empty body is `None`, anything else
+/// must be JSON of `T` and is rejected like [`ApiJson`] otherwise
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionalApiJson<T>(pub Option<T>);
+
+/// [`ApiJson`] for the `/callback` endpoints, where a body that is not valid
+/// JSON or does not fit `T` is a `MalformedWebhookPayload` instead
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebhookJson<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| ApiJson(value))
+            .map_err(json_rejection_error)
+    }
+}
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for OptionalApiJson<T>
+where
+    Bytes: FromRequest<S, B, Rejection = BytesRejection>,
+    Json<T>: FromRequest<S, Body, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let headers = req.headers().clone();
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| json_rejection_error(e.into()))?;
+        if bytes.is_empty() {
+            return Ok(OptionalApiJson(None));
+        }
+
+        let mut req = Request::new(Body::from(bytes));
+        *req.headers_mut() = headers;
+        ApiJson::<T>::from_request(req, state)
+            .await
+            .map(|ApiJson(value)| OptionalApiJson(Some(value)))
+    }
+}
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for WebhookJson<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| WebhookJson(value))
+            .map_err(webhook_json_rejection_error)
+    }
+}
+
+pub fn json_rejection_error(rejection: JsonRejection) -> AppError {
+    rejection_error(rejection, AppError::BadRequest)
+}
+
+pub fn webhook_json_rejection_error(rejection: JsonRejection) -> AppError {
+    rejection_error(rejection, AppError::MalformedWebhookPayload)
+}
+
+/// Map `rejection`, using `malformed` for bodies that are not JSON of the
+/// expected shape
+fn rejection_error(rejection: JsonRejection, malformed: fn(String) -> AppError) -> AppError {
+    match rejection {
+        JsonRejection::JsonDataError(e) => malformed(detail(&e.body_text())),
+        JsonRejection::JsonSyntaxError(e) => {
+            malformed(format!("invalid JSON: {}", detail(&e.body_text())))
+        }
+        JsonRejection::MissingJsonContentType(_) => AppError::BadRequest(
+            "expected request with `Content-Type: application/json`".to_string(),
+        ),
+        JsonRejection::BytesRejection(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            AppError::PayloadTooLarge("request body exceeds the size limit".to_string())
+        }
+        other => AppError::BadRequest(other.body_text()),
+    }
+}
+
+/// axum prefixes the serde error with a generic sentence; keep the part that
+/// names the field or position
+fn detail(body_text: &str) -> String {
+    body_text
+        .split_once(": ")
+        .map(|(_, detail)| detail)
+        .unwrap_or(body_text)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{Body, HttpBody},
+        extract::DefaultBodyLimit,
+        routing::post,
+        Router,
+    };
+    use serde::Deserialize;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Payload {
+        amount: String,
+        asset_code: String,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", post(|ApiJson(_): ApiJson<Payload>| async { "ok" }))
+            .route(
+                "/callback",
+                post(|WebhookJson(_): WebhookJson<Payload>| async { "ok" }),
+            )
+            .route(
+                "/optional",
+                post(
+                    |OptionalApiJson(payload): OptionalApiJson<Payload>| async move {
+                        match payload {
+                            Some(_) => StatusCode::OK,
+                            None => StatusCode::NO_CONTENT,
+                        }
+                    },
+                ),
+            )
+            .layer(DefaultBodyLimit::max(64))
+    }
+
+    async fn send(path: &str, content_type: &str, body: &str) -> (StatusCode, Value) {
+        let req = Request::post(path)
+            .header("content-type", content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = app().oneshot(req).await.unwrap();
+        let status = res.status();
+        let mut body = res.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_valid_body_is_accepted() {
+        let req = Request::post("/")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"amount":"1","asset_code":"USD"}"#))
+            .unwrap();
+        assert_eq!(app().oneshot(req).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_optional_body() {
+        let req = Request::post("/optional").body(Body::empty()).unwrap();
+        let res = app().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let req = Request::post("/optional")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"amount":"1","asset_code":"USD"}"#))
+            .unwrap();
+        assert_eq!(app().oneshot(req).await.unwrap().status(), StatusCode::OK);
+
+        // A body that is there but not valid is rejected, not ignored
+        let (status, body) = send("/optional", "application/json", r#"{"amount":"1""#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_001");
+        let (status, _) = send("/optional", "text/plain", r#"{"amount":"1"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let padding = "x".repeat(100);
+        let (status, _) = send(
+            "/optional",
+            "application/json",
+            &format!(r#"{{"amount":"{}","asset_code":"USD"}}"#, padding),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_body() {
+        let (status, body) = send(
+            "/callback",
+            "application/json",
+            r#"{"amount":"1","asset_co"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "ERR_WEBHOOK_002");
+        assert!(body["error"].as_str().unwrap().contains("invalid JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_body_outside_callbacks_is_a_bad_request() {
+        let (status, body) = send("/", "application/json", r#"{"amount":"1","asset_co"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_001");
+        assert!(body["error"].as_str().unwrap().contains("invalid JSON"));
+
+        let (_, body) = send(
+            "/",
+            "application/json",
+            r#"{"amount":1,"asset_code":"USD"}"#,
+        )
+        .await;
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_001");
+        assert!(body["error"].as_str().unwrap().contains("amount"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_field_is_named() {
+        let (status, body) = send(
+            "/callback",
+            "application/json",
+            r#"{"amount":"1","asset_code":"USD","bogus":1}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "ERR_WEBHOOK_002");
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("unknown field `bogus`"));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_type_names_the_field() {
+        let (_, body) = send(
+            "/callback",
+            "application/json",
+            r#"{"amount":1,"asset_code":"USD"}"#,
+        )
+        .await;
+        assert_eq!(body["code"], "ERR_WEBHOOK_002");
+        assert!(body["error"].as_str().unwrap().contains("amount"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_content_type_and_oversized_body() {
+        let (status, body) = send("/", "text/plain", "{}").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_001");
+
+        let padding = "x".repeat(100);
+        let (status, body) = send(
+            "/",
+            "application/json",
+            &format!(r#"{{"amount":"{}","asset_code":"USD"}}"#, padding),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_002");
+    }
+}
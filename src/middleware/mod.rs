@@ -1,4 +1,12 @@
 pub mod auth;
+pub mod body_limit;
+pub mod error_response;
 pub mod idempotency;
 pub mod ip_filter;
+pub mod json;
+pub mod path;
+pub mod rate_limit;
+pub mod read_only;
+pub mod request_logger;
+pub mod timeout;
 pub mod versioning;
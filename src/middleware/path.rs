@@ -0,0 +1,98 @@
+use axum::{
+    async_trait,
+    extract::{rejection::PathRejection, FromRequestParts, Path},
+    http::request::Parts,
+};
+
+use crate::error::AppError;
+
+/// `Path<T>` whose rejections are [`AppError`]s, so a malformed segment such
+/// as a non-UUID id gets the stable error format instead of axum's plain-text
+/// rejection.
+///
+/// - a segment that does not parse as `T`: `BadRequest`, naming the parameter
+/// - a route missing the parameters `T` expects: `Internal`, as that is a
+///   routing bug rather than a bad request
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    Path<T>: FromRequestParts<S, Rejection = PathRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Path::<T>::from_request_parts(parts, state)
+            .await
+            .map(|Path(value)| ApiPath(value))
+            .map_err(path_rejection_error)
+    }
+}
+
+pub fn path_rejection_error(rejection: PathRejection) -> AppError {
+    match rejection {
+        PathRejection::FailedToDeserializePathParams(e) => {
+            let body_text = e.body_text();
+            // axum prefixes the parse error with "Invalid URL: "
+            let detail = body_text
+                .strip_prefix("Invalid URL: ")
+                .unwrap_or(&body_text);
+            AppError::BadRequest(format!("invalid path parameter: {}", detail))
+        }
+        other => AppError::Internal(other.body_text()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{Body, HttpBody},
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    async fn send(uri: &str) -> (StatusCode, Value) {
+        let app = Router::new().route(
+            "/items/:id",
+            get(|ApiPath(id): ApiPath<Uuid>| async move { id.to_string() }),
+        );
+        let res = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let mut body = res.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_valid_uuid_is_accepted() {
+        let (status, _) = send(&format!("/items/{}", Uuid::new_v4())).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_uuid_is_a_structured_bad_request() {
+        let (status, body) = send("/items/not-a-uuid").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_001");
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains("invalid path parameter"), "{}", message);
+        assert!(message.contains("UUID parsing failed"), "{}", message);
+    }
+}
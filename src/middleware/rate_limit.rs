@@ -0,0 +1,547 @@
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use governor::clock::{Clock, DefaultClock};
+use governor::middleware::StateInformationMiddleware;
+use governor::state::keyed::DefaultKeyedStateStore;
+use governor::{Quota, RateLimiter};
+use ipnet::IpNet;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::config::Config;
+use crate::error::{codes, AppError};
+use crate::metrics::MetricsRegistry;
+use crate::middleware::ip_filter::extract_client_ip;
+
+/// Requests rejected with 429, labelled by `scope`
+pub const REJECTIONS_METRIC: &str = "rate_limit_rejections_total";
+/// Requests let through the limiter, labelled by `scope`
+pub const ALLOWED_METRIC: &str = "rate_limit_allowed_total";
+
+pub const SCOPE_DEFAULT: &str = "default";
+pub const SCOPE_WHITELIST: &str = "whitelist";
+/// Requests counted against a per-route quota from `RATE_LIMIT_OVERRIDES`
+pub const SCOPE_ROUTE: &str = "route";
+
+/// Per-IP limiter that reports the remaining burst capacity on each check
+type KeyedLimiter =
+    RateLimiter<IpAddr, DefaultKeyedStateStore<IpAddr>, DefaultClock, StateInformationMiddleware>;
+
+/// Per-IP request quotas. Whitelisted IPs get their own, higher quota;
+/// other requests use their route's override if it has one, else the default.
+pub struct RateLimitConfig {
+    default_limit: u32,
+    whitelist_limit: u32,
+    default_limiter: KeyedLimiter,
+    whitelist_limiter: KeyedLimiter,
+    /// Route template, its limit and limiter
+    route_limits: Vec<(String, u32, KeyedLimiter)>,
+    whitelisted: RwLock<Vec<IpNet>>,
+    trusted_proxy_depth: usize,
+}
+
+/// Outcome of checking one request against its quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub scope: &'static str,
+    /// Requests per second allowed in `scope`
+    pub limit: u32,
+    /// Further requests allowed right now, after this one
+    pub remaining: u32,
+    /// How long until the next request would be allowed; `None` if allowed
+    pub retry_after: Option<Duration>,
+}
+
+/// The caller's quota as seen by the request that asked for it, returned by
+/// `GET /ratelimit/status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+pub struct RateLimitStatus {
+    /// Requests per second allowed
+    pub limit: u32,
+    /// Requests still allowed right now
+    pub remaining: u32,
+    /// Seconds until the full quota is available again
+    pub reset_seconds: u64,
+}
+
+impl From<RateLimitDecision> for RateLimitStatus {
+    fn from(decision: RateLimitDecision) -> Self {
+        // Quotas are per second and refill evenly, so a partly used quota is
+        // full again within a second
+        let reset_seconds = match decision.retry_after {
+            Some(wait) => wait.as_secs_f64().ceil().max(1.0) as u64,
+            None if decision.remaining < decision.limit => 1,
+            None => 0,
+        };
+        Self {
+            limit: decision.limit,
+            remaining: decision.remaining,
+            reset_seconds,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn new(config: &Config) -> Self {
+        Self::with_limits(config.default_rate_limit, config.whitelist_rate_limit)
+            .with_trusted_proxy_depth(config.trusted_proxy_depth)
+            .with_route_overrides(&config.rate_limit_overrides)
+            .with_whitelisted_ips(&config.whitelisted_ips)
+    }
+
+    /// Build limiters allowing `default_per_sec` and `whitelist_per_sec`
+    /// requests per second per IP. Zero is treated as one.
+    pub fn with_limits(default_per_sec: u32, whitelist_per_sec: u32) -> Self {
+        Self {
+            default_limit: default_per_sec.max(1),
+            whitelist_limit: whitelist_per_sec.max(1),
+            default_limiter: keyed_limiter(default_per_sec),
+            whitelist_limiter: keyed_limiter(whitelist_per_sec),
+            route_limits: Vec::new(),
+            whitelisted: RwLock::new(Vec::new()),
+            trusted_proxy_depth: 0,
+        }
+    }
+
+    /// Number of proxies in front of the service whose `x-forwarded-for`
+    /// entries are skipped when picking the client IP to key on
+    pub fn with_trusted_proxy_depth(mut self, depth: usize) -> Self {
+        self.trusted_proxy_depth = depth;
+        self
+    }
+
+    /// Give routes their own quotas from a comma-separated list of
+    /// `template:limit/s` entries, such as `/export:2/s,/callback:100/s`.
+    /// Templates are matched exactly, so `/transactions/:id` covers every
+    /// transaction. Invalid entries are logged and skipped.
+    pub fn with_route_overrides(mut self, raw: &str) -> Self {
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match parse_route_override(entry) {
+                Some((route, limit)) => {
+                    self.route_limits
+                        .retain(|(existing, _, _)| *existing != route);
+                    self.route_limits.push((route, limit, keyed_limiter(limit)));
+                }
+                None => tracing::warn!("Ignoring invalid rate limit override '{}'", entry),
+            }
+        }
+        self
+    }
+
+    /// Start with the whitelist in `raw`, as for
+    /// [`load_whitelisted_ips`](Self::load_whitelisted_ips)
+    pub fn with_whitelisted_ips(mut self, raw: &str) -> Self {
+        *self.whitelisted.get_mut() = parse_whitelist(raw);
+        self
+    }
+
+    /// Replace the whitelist with a comma-separated list of IPs or CIDRs.
+    /// Invalid entries are logged and skipped.
+    pub async fn load_whitelisted_ips(&self, raw: &str) {
+        *self.whitelisted.write().await = parse_whitelist(raw);
+    }
+
+    pub async fn is_whitelisted(&self, ip: IpAddr) -> bool {
+        self.whitelisted
+            .read()
+            .await
+            .iter()
+            .any(|net| net.contains(&ip))
+    }
+
+    /// Check one request from `ip`, returning the scope it was counted
+    /// against and whether it is allowed.
+    pub async fn check(&self, ip: IpAddr) -> (&'static str, bool) {
+        let decision = self.decide(ip, None).await;
+        (decision.scope, decision.retry_after.is_none())
+    }
+
+    /// Like [`check`](Self::check), also reporting the quota and, when
+    /// rejected, how long the client should wait. `route` is the matched
+    /// route template, if any.
+    pub async fn decide(&self, ip: IpAddr, route: Option<&str>) -> RateLimitDecision {
+        let route_limit = route.and_then(|route| {
+            self.route_limits
+                .iter()
+                .find(|(template, _, _)| template == route)
+        });
+        let (scope, limit, limiter) = if self.is_whitelisted(ip).await {
+            (
+                SCOPE_WHITELIST,
+                self.whitelist_limit,
+                &self.whitelist_limiter,
+            )
+        } else if let Some((_, limit, limiter)) = route_limit {
+            (SCOPE_ROUTE, *limit, limiter)
+        } else {
+            (SCOPE_DEFAULT, self.default_limit, &self.default_limiter)
+        };
+
+        let (remaining, retry_after) = match limiter.check_key(&ip) {
+            Ok(snapshot) => (snapshot.remaining_burst_capacity(), None),
+            Err(not_until) => (
+                0,
+                Some(not_until.wait_time_from(DefaultClock::default().now())),
+            ),
+        };
+        RateLimitDecision {
+            scope,
+            limit,
+            remaining,
+            retry_after,
+        }
+    }
+}
+
+fn parse_whitelist(raw: &str) -> Vec<IpNet> {
+    let mut entries = Vec::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry
+            .parse::<IpNet>()
+            .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+        {
+            Ok(net) => entries.push(net),
+            Err(_) => tracing::warn!("Ignoring invalid whitelisted IP '{}'", entry),
+        }
+    }
+    entries
+}
+
+/// `template:limit/s`, where the template may itself contain `:` segments.
+/// Zero is treated as one, as for the global limits.
+fn parse_route_override(entry: &str) -> Option<(String, u32)> {
+    let (route, limit) = entry.rsplit_once(':')?;
+    let route = route.trim();
+    let limit: u32 = limit.trim().strip_suffix("/s")?.trim().parse().ok()?;
+    route
+        .starts_with('/')
+        .then(|| (route.to_string(), limit.max(1)))
+}
+
+fn keyed_limiter(limit: u32) -> KeyedLimiter {
+    RateLimiter::keyed(Quota::per_second(
+        NonZeroU32::new(limit).unwrap_or(NonZeroU32::MIN),
+    ))
+    .with_middleware::<StateInformationMiddleware>()
+}
+
+/// Expose the rate-limit counters at zero so dashboards see them before traffic
+pub fn register_metrics(registry: &MetricsRegistry) {
+    for scope in [SCOPE_DEFAULT, SCOPE_WHITELIST, SCOPE_ROUTE] {
+        registry.register_counter(REJECTIONS_METRIC, &[("scope", scope)]);
+        registry.register_counter(ALLOWED_METRIC, &[("scope", scope)]);
+    }
+}
+
+/// Reject requests over the caller's quota with 429. Requests whose client
+/// IP cannot be determined share the unspecified-address bucket. Allowed
+/// requests carry their [`RateLimitStatus`] as an extension.
+pub async fn rate_limit_middleware(
+    State(config): State<Arc<RateLimitConfig>>,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let ip = extract_client_ip(
+        request.headers(),
+        request.extensions(),
+        config.trusted_proxy_depth,
+    )
+    .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let decision = config.decide(ip, route.as_deref()).await;
+    let scope = decision.scope;
+    let registry = crate::metrics::registry();
+
+    if let Some(retry_after) = decision.retry_after {
+        registry.increment_counter(REJECTIONS_METRIC, &[("scope", scope)]);
+        tracing::debug!(client_ip = %ip, scope, "rate limit exceeded");
+        return rejection_response(decision.limit, retry_after);
+    }
+
+    registry.increment_counter(ALLOWED_METRIC, &[("scope", scope)]);
+    request
+        .extensions_mut()
+        .insert(RateLimitStatus::from(decision));
+    next.run(request).await
+}
+
+/// 429 with a `Retry-After` header and a JSON body clients can act on.
+/// Waits are rounded up to whole seconds, never below one.
+fn rejection_response(limit: u32, retry_after: Duration) -> Response {
+    let retry_after_seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let body = Json(json!({
+        "error": AppError::RateLimitExceeded.to_string(),
+        "code": codes::RATE_LIMIT_001.0,
+        "retry_after_seconds": retry_after_seconds,
+        "limit": limit,
+        "remaining": 0,
+    }));
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::HttpBody, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn request_from(ip: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", ip)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_whitelisted_ip_uses_whitelist_quota() {
+        let config = RateLimitConfig::with_limits(1, 5);
+        config
+            .load_whitelisted_ips("203.0.113.0/24, 198.51.100.7, bogus")
+            .await;
+
+        let whitelisted = IpAddr::from([203, 0, 113, 9]);
+        assert!(config.is_whitelisted(whitelisted).await);
+        assert!(config.is_whitelisted(IpAddr::from([198, 51, 100, 7])).await);
+
+        for _ in 0..5 {
+            assert_eq!(config.check(whitelisted).await, (SCOPE_WHITELIST, true));
+        }
+        assert_eq!(config.check(whitelisted).await, (SCOPE_WHITELIST, false));
+    }
+
+    #[tokio::test]
+    async fn test_exceeding_quota_increments_rejection_counter() {
+        let config = Arc::new(RateLimitConfig::with_limits(2, 10));
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    config,
+                    rate_limit_middleware,
+                ));
+
+        let labels = [("scope", SCOPE_DEFAULT)];
+        let registry = crate::metrics::registry();
+        let rejected_before = registry.counter(REJECTIONS_METRIC, &labels);
+        let allowed_before = registry.counter(ALLOWED_METRIC, &labels);
+
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            let res = app
+                .clone()
+                .oneshot(request_from("192.0.2.44"))
+                .await
+                .unwrap();
+            statuses.push(res.status());
+        }
+
+        assert_eq!(
+            statuses,
+            [
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+        assert!(registry.counter(REJECTIONS_METRIC, &labels) > rejected_before);
+        assert!(registry.counter(ALLOWED_METRIC, &labels) >= allowed_before + 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejection_has_retry_after_and_json_body() {
+        let config = Arc::new(RateLimitConfig::with_limits(1, 10));
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    config,
+                    rate_limit_middleware,
+                ));
+
+        let first = app
+            .clone()
+            .oneshot(request_from("192.0.2.45"))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+
+        let rejected = app.oneshot(request_from("192.0.2.45")).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            rejected
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok()),
+            Some("1")
+        );
+
+        let mut body = rejected.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "ERR_RATE_LIMIT_001");
+        assert_eq!(body["retry_after_seconds"], 1);
+        assert_eq!(body["limit"], 1);
+        assert_eq!(body["remaining"], 0);
+    }
+
+    async fn read_status(app: &Router, ip: &str) -> RateLimitStatus {
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/ratelimit/status")
+                    .header("x-forwarded-for", ip)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut body = res.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        RateLimitStatus {
+            limit: body["limit"].as_u64().unwrap() as u32,
+            remaining: body["remaining"].as_u64().unwrap() as u32,
+            reset_seconds: body["reset_seconds"].as_u64().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_remaining_decreases_with_requests() {
+        let config = Arc::new(RateLimitConfig::with_limits(10, 100));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route("/ratelimit/status", get(crate::handlers::rate_limit_status))
+            .layer(middleware::from_fn_with_state(
+                config,
+                rate_limit_middleware,
+            ));
+
+        let first = read_status(&app, "192.0.2.48").await;
+        assert_eq!(first.limit, 10);
+        assert_eq!(first.remaining, 9);
+        assert_eq!(first.reset_seconds, 1);
+
+        for _ in 0..3 {
+            app.clone()
+                .oneshot(request_from("192.0.2.48"))
+                .await
+                .unwrap();
+        }
+
+        let second = read_status(&app, "192.0.2.48").await;
+        assert!(second.remaining < first.remaining);
+    }
+
+    #[tokio::test]
+    async fn test_trusted_proxy_depth_keys_on_real_client() {
+        let config = Arc::new(RateLimitConfig::with_limits(1, 10).with_trusted_proxy_depth(1));
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    config,
+                    rate_limit_middleware,
+                ));
+
+        // The client controls everything left of its own address, so
+        // rotating a spoofed first entry must not earn a fresh quota
+        let first = app
+            .clone()
+            .oneshot(request_from("10.9.9.1, 192.0.2.46, 198.51.100.7"))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+
+        let spoofed = app
+            .clone()
+            .oneshot(request_from("10.9.9.2, 192.0.2.46, 198.51.100.7"))
+            .await
+            .unwrap();
+        assert_eq!(spoofed.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let other_client = app
+            .oneshot(request_from("192.0.2.47, 198.51.100.7"))
+            .await
+            .unwrap();
+        assert_eq!(other_client.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_parse_route_overrides() {
+        let config = RateLimitConfig::with_limits(100, 1000).with_route_overrides(
+            "/export:2/s, /transactions/:id:0/s, /callback:x/s, export:5/s, /ws:3",
+        );
+        let routes: Vec<(&str, u32)> = config
+            .route_limits
+            .iter()
+            .map(|(route, limit, _)| (route.as_str(), *limit))
+            .collect();
+        assert_eq!(routes, [("/export", 2), ("/transactions/:id", 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_route_override_is_stricter_than_other_routes() {
+        let config = Arc::new(
+            RateLimitConfig::with_limits(100, 1000)
+                .with_route_overrides("/export:1/s,/callback:5/s"),
+        );
+        let app = Router::new()
+            .route("/export", get(|| async { "csv" }))
+            .route("/callback", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                config,
+                rate_limit_middleware,
+            ));
+
+        let send = |path: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .uri(path)
+                    .header("x-forwarded-for", "192.0.2.50")
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(send("/export").await, StatusCode::OK);
+        assert_eq!(send("/export").await, StatusCode::TOO_MANY_REQUESTS);
+        // The same client still has its /callback quota
+        for _ in 0..5 {
+            assert_eq!(send("/callback").await, StatusCode::OK);
+        }
+        assert_eq!(send("/callback").await, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,29 @@
+use axum::{
+    extract::State,
+    http::{Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::db::pool_manager::PoolManager;
+use crate::error::AppError;
+
+/// Refuse writes with 503 while the primary database is down. Safe methods
+/// pass through so reads keep being served from replicas.
+pub async fn read_only_guard<B>(
+    State(pool_manager): State<PoolManager>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let is_write = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if is_write && pool_manager.is_read_only().await {
+        return AppError::ReadOnly(
+            "the primary database is unavailable, retry the write later".to_string(),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
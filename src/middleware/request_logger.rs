@@ -1,28 +1,73 @@
 use axum::{
-    body::Body,
-    extract::Request,
-    http::StatusCode,
+    body::{Body, Bytes, HttpBody},
+    extract::{MatchedPath, State},
+    http::{header, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use std::time::Instant;
 use uuid::Uuid;
 
+use crate::middleware::ip_filter::extract_client_ip;
+
 const MAX_BODY_LOG_SIZE: usize = 1024; // 1KB limit for body logging
 
-pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response {
+/// Target of the per-request access log event, for filtering with `RUST_LOG`
+pub const ACCESS_LOG_TARGET: &str = "access_log";
+
+/// Upper bounds of the `latency_bucket` values, in milliseconds
+const LATENCY_BUCKETS_MS: [(u128, &str); 8] = [
+    (10, "le_10ms"),
+    (50, "le_50ms"),
+    (100, "le_100ms"),
+    (250, "le_250ms"),
+    (500, "le_500ms"),
+    (1000, "le_1000ms"),
+    (2500, "le_2500ms"),
+    (5000, "le_5000ms"),
+];
+
+/// Bucket label for a request that took `latency_ms`
+pub fn latency_bucket(latency_ms: u128) -> &'static str {
+    LATENCY_BUCKETS_MS
+        .iter()
+        .find(|(bound, _)| latency_ms <= *bound)
+        .map_or("gt_5000ms", |(_, label)| label)
+}
+
+/// Settings for [`request_logger_middleware`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccessLogConfig {
+    /// Proxies whose `x-forwarded-for` entries are skipped when identifying
+    /// the client
+    pub trusted_proxy_depth: usize,
+}
+
+/// Tag each request with an `x-request-id` and emit one access log event
+/// for it once the response is ready. The event's fields are rendered as
+/// JSON keys when `LOG_FORMAT=json`.
+pub async fn request_logger_middleware(
+    State(config): State<AccessLogConfig>,
+    mut req: Request<Body>,
+    next: Next<Body>,
+) -> Response {
     let request_id = Uuid::new_v4().to_string();
     let method = req.method().clone();
-    let uri = req.uri().clone();
+    // The route template keeps ids out of the path, so logs group by endpoint
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let client_ip = extract_client_ip(req.headers(), req.extensions(), config.trusted_proxy_depth)
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
     let start = Instant::now();
 
     // Insert request ID into headers for downstream handlers
-    req.headers_mut().insert(
-        "x-request-id",
-        request_id.parse().unwrap(),
-    );
+    req.headers_mut()
+        .insert("x-request-id", request_id.parse().unwrap());
 
-    // Log request
     let log_body = std::env::var("LOG_REQUEST_BODY")
         .unwrap_or_else(|_| "false".to_string())
         .parse::<bool>()
@@ -31,87 +76,138 @@ pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response
     if log_body {
         // Extract and log body if enabled (with size limit)
         let (parts, body) = req.into_parts();
-        let bytes = match axum::body::to_bytes(body, MAX_BODY_LOG_SIZE).await {
-            Ok(bytes) => bytes,
-            Err(_) => {
-                tracing::warn!(
-                    request_id = %request_id,
-                    method = %method,
-                    uri = %uri,
-                    "Request body too large or failed to read"
-                );
-                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
-            }
+        let Some(bytes) = read_limited(body, MAX_BODY_LOG_SIZE).await else {
+            tracing::warn!(
+                request_id = %request_id,
+                method = %method,
+                path = %path,
+                "Request body too large or failed to read"
+            );
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
         };
 
         let body_str = String::from_utf8_lossy(&bytes);
-        let sanitized_body = if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_str) {
+        let sanitized_body = if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_str)
+        {
             let sanitized = crate::utils::sanitize::sanitize_json(&json);
             serde_json::to_string(&sanitized).unwrap_or_else(|_| "[invalid json]".to_string())
         } else {
             format!("[non-json, {} bytes]", bytes.len())
         };
 
-        tracing::info!(
+        tracing::debug!(
             request_id = %request_id,
-            method = %method,
-            uri = %uri,
             body_size = bytes.len(),
             body = %sanitized_body,
-            "Incoming request"
+            "Request body"
         );
 
         // Reconstruct request with body
         req = Request::from_parts(parts, Body::from(bytes));
-    } else {
-        tracing::info!(
-            request_id = %request_id,
-            method = %method,
-            uri = %uri,
-            "Incoming request"
-        );
     }
 
-    // Process request
     let response = next.run(req).await;
-    
-    let latency = start.elapsed();
+
+    let latency_ms = start.elapsed().as_millis();
     let status = response.status();
+    // Streamed bodies of unknown length are reported as 0
+    let bytes_out = response.body().size_hint().exact().unwrap_or_else(|| {
+        response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .unwrap_or(0)
+    });
 
-    // Log response
     tracing::info!(
+        target: ACCESS_LOG_TARGET,
         request_id = %request_id,
         method = %method,
-        uri = %uri,
-        status = %status.as_u16(),
-        latency_ms = latency.as_millis(),
-        "Outgoing response"
+        path = %path,
+        status = status.as_u16(),
+        latency_ms = latency_ms as u64,
+        latency_bucket = latency_bucket(latency_ms),
+        client_ip = %client_ip,
+        bytes_out,
+        "Request completed"
     );
 
     // Add request ID to response headers
     let (mut parts, body) = response.into_parts();
-    parts.headers.insert(
-        "x-request-id",
-        request_id.parse().unwrap(),
-    );
+    parts
+        .headers
+        .insert("x-request-id", request_id.parse().unwrap());
 
     Response::from_parts(parts, body)
 }
 
+/// The whole body, or `None` if it is longer than `limit` or fails to read
+async fn read_limited(mut body: Body, limit: usize) -> Option<Bytes> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.ok()?);
+        if bytes.len() > limit {
+            return None;
+        }
+    }
+    Some(Bytes::from(bytes))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::{body::Body, routing::post, Router};
-    use axum::http::Request;
+    use axum::{
+        routing::{get, post},
+        Router,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
     use tower::ServiceExt;
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
 
-    #[tokio::test]
-    async fn test_request_logger_adds_request_id() {
-        let app = Router::new()
+    type Events = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+    /// Records the fields of every access log event
+    struct CaptureAccessLog(Events);
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for CaptureAccessLog {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() == ACCESS_LOG_TARGET {
+                let mut fields = HashMap::new();
+                event.record(&mut FieldVisitor(&mut fields));
+                self.0.lock().unwrap().push(fields);
+            }
+        }
+    }
+
+    fn app() -> Router {
+        Router::new()
             .route("/test", post(|| async { "ok" }))
-            .layer(axum::middleware::from_fn(request_logger_middleware));
+            .route("/items/:id", get(|| async { "item" }))
+            .layer(axum::middleware::from_fn_with_state(
+                AccessLogConfig::default(),
+                request_logger_middleware,
+            ))
+    }
 
-        let response = app
+    #[tokio::test]
+    async fn test_request_logger_adds_request_id() {
+        let response = app()
             .oneshot(
                 Request::builder()
                     .method("POST")
@@ -124,4 +220,44 @@ mod tests {
 
         assert!(response.headers().contains_key("x-request-id"));
     }
+
+    #[tokio::test]
+    async fn test_access_log_event_has_structured_fields() {
+        let events = Events::default();
+        let subscriber = tracing_subscriber::registry().with(CaptureAccessLog(events.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/items/42")
+                    .header("x-forwarded-for", "203.0.113.7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let request_id = response.headers()["x-request-id"].to_str().unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1, "one event per request");
+        let event = &events[0];
+        assert_eq!(event["request_id"], request_id);
+        assert_eq!(event["method"], "GET");
+        assert_eq!(event["path"], "/items/:id");
+        assert_eq!(event["status"], "200");
+        assert_eq!(event["client_ip"], "203.0.113.7");
+        assert_eq!(event["bytes_out"], "4");
+        assert!(event["latency_ms"].parse::<u64>().is_ok());
+        assert!(event.contains_key("latency_bucket"));
+    }
+
+    #[test]
+    fn test_latency_buckets() {
+        assert_eq!(latency_bucket(0), "le_10ms");
+        assert_eq!(latency_bucket(10), "le_10ms");
+        assert_eq!(latency_bucket(11), "le_50ms");
+        assert_eq!(latency_bucket(4999), "le_5000ms");
+        assert_eq!(latency_bucket(60_000), "gt_5000ms");
+    }
 }
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use tower_http::timeout::TimeoutLayer;
+
+use crate::config::parse_positive;
+use crate::error::AppError;
+
+/// Time a handler may take unless `REQUEST_TIMEOUT_SECS` says otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time a client may take to send request headers unless
+/// `HEADER_READ_TIMEOUT_SECS` says otherwise
+pub const DEFAULT_HEADER_READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// TCP keep-alive probe interval unless `TCP_KEEPALIVE_SECS` says otherwise.
+/// This only detects dead peers; an idle HTTP keep-alive connection to a live
+/// client is not closed.
+pub const DEFAULT_TCP_KEEPALIVE: Duration = Duration::from_secs(75);
+
+/// Server-side timeouts, so slow or stalled clients cannot hold connections
+/// open indefinitely
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerTimeouts {
+    pub request: Duration,
+    pub header_read: Duration,
+    pub tcp_keepalive: Duration,
+}
+
+impl Default for ServerTimeouts {
+    fn default() -> Self {
+        Self {
+            request: DEFAULT_REQUEST_TIMEOUT,
+            header_read: DEFAULT_HEADER_READ_TIMEOUT,
+            tcp_keepalive: DEFAULT_TCP_KEEPALIVE,
+        }
+    }
+}
+
+impl ServerTimeouts {
+    /// Read `REQUEST_TIMEOUT_SECS`, `HEADER_READ_TIMEOUT_SECS` and
+    /// `TCP_KEEPALIVE_SECS`; missing values keep the defaults and a timeout
+    /// that is not above zero is an error.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let read = |name: &str, default: Duration| {
+            parse_positive(name, default.as_secs()).map(Duration::from_secs)
+        };
+        Ok(Self {
+            request: read("REQUEST_TIMEOUT_SECS", DEFAULT_REQUEST_TIMEOUT)?,
+            header_read: read("HEADER_READ_TIMEOUT_SECS", DEFAULT_HEADER_READ_TIMEOUT)?,
+            tcp_keepalive: read("TCP_KEEPALIVE_SECS", DEFAULT_TCP_KEEPALIVE)?,
+        })
+    }
+}
+
+/// Answer requests whose handler runs longer than `timeout` with 408
+pub fn with_request_timeout<S>(router: Router<S>, timeout: Duration) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(TimeoutLayer::new(timeout))
+        .layer(axum::middleware::map_response(structured_request_timeout))
+}
+
+/// The timeout layer answers with a bare 408; give it the usual error body
+async fn structured_request_timeout(res: Response) -> Response {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if res.status() == StatusCode::REQUEST_TIMEOUT && !is_json {
+        AppError::RequestTimeout("the request took too long to process".to_string()).into_response()
+    } else {
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, HttpBody};
+    use axum::http::Request;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_slow_handler_times_out() {
+        let router: Router = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            )
+            .route("/fast", get(|| async { "done" }));
+        let app = with_request_timeout(router, Duration::from_millis(50));
+
+        let res = app
+            .clone()
+            .oneshot(Request::get("/slow").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::REQUEST_TIMEOUT);
+        let mut body = res.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "ERR_BAD_REQUEST_003");
+
+        let res = app
+            .oneshot(Request::get("/fast").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
@@ -13,6 +13,9 @@ pub struct ReadinessState {
     drain_timeout_secs: u64,
     /// Flag indicating if drain has started
     is_draining: Arc<AtomicBool>,
+    /// Set once the database schema matched the shipped migrations; until
+    /// then the /ready endpoint returns 503.
+    migrations_current: Arc<AtomicBool>,
 }
 
 impl ReadinessState {
@@ -22,6 +25,7 @@ impl ReadinessState {
             is_ready: Arc::new(AtomicBool::new(true)),
             drain_timeout_secs: 30,
             is_draining: Arc::new(AtomicBool::new(false)),
+            migrations_current: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -31,6 +35,7 @@ impl ReadinessState {
             is_ready: Arc::new(AtomicBool::new(true)),
             drain_timeout_secs,
             is_draining: Arc::new(AtomicBool::new(false)),
+            migrations_current: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -44,6 +49,16 @@ impl ReadinessState {
         self.is_draining.load(Ordering::SeqCst)
     }
 
+    /// Check if the database schema was last seen matching the shipped migrations
+    pub fn migrations_current(&self) -> bool {
+        self.migrations_current.load(Ordering::SeqCst)
+    }
+
+    /// Record whether the database schema matches the shipped migrations
+    pub fn set_migrations_current(&self, current: bool) {
+        self.migrations_current.store(current, Ordering::SeqCst);
+    }
+
     /// Get the drain timeout duration
     pub fn drain_timeout(&self) -> Duration {
         Duration::from_secs(self.drain_timeout_secs)
@@ -128,6 +143,14 @@ mod tests {
         assert!(!state.is_draining());
     }
 
+    #[test]
+    fn test_migrations_not_current_until_checked() {
+        let state = ReadinessState::new();
+        assert!(!state.migrations_current());
+        state.set_migrations_current(true);
+        assert!(state.clone().migrations_current());
+    }
+
     #[test]
     fn test_drain_timeout() {
         let state = ReadinessState::with_drain_timeout(60);
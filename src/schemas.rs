@@ -13,6 +13,8 @@ pub struct TransactionSchema {
     pub amount: String,
     /// Asset code (e.g., USD)
     pub asset_code: String,
+    /// Issuing Stellar account of the asset
+    pub asset_issuer: Option<String>,
     /// Current transaction status
     pub status: String,
     /// Timestamp when transaction was created
@@ -30,6 +32,8 @@ pub struct TransactionSchema {
     pub memo: Option<String>,
     pub memo_type: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    /// Partner that recorded the transaction
+    pub partner_id: Option<String>,
 }
 
 /// Settlement schema for OpenAPI documentation
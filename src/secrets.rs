@@ -1,17 +1,103 @@
 use std::collections::HashMap;
 use std::env;
+use std::str::FromStr;
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use vaultrs::auth::approle;
 use vaultrs::client::{Client, VaultClient, VaultClientSettingsBuilder};
 use vaultrs::kv2;
 
-pub struct SecretsManager {
+/// Source of the credentials the service needs at startup
+#[async_trait]
+pub trait SecretsBackend: Send + Sync {
+    async fn get_db_password(&self) -> Result<String>;
+    async fn get_anchor_secret(&self) -> Result<String>;
+}
+
+/// Which secrets backend to use, chosen with `SECRETS_BACKEND`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretsBackendKind {
+    /// Read `DATABASE_URL` and `ANCHOR_WEBHOOK_SECRET` directly
+    Env,
+    Vault,
+    Aws,
+}
+
+impl FromStr for SecretsBackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "env" | "none" => Ok(Self::Env),
+            "vault" => Ok(Self::Vault),
+            "aws" | "aws-secrets-manager" => Ok(Self::Aws),
+            other => anyhow::bail!(
+                "invalid SECRETS_BACKEND '{}': expected env, vault or aws",
+                other
+            ),
+        }
+    }
+}
+
+impl SecretsBackendKind {
+    /// Pick a backend from `SECRETS_BACKEND`, falling back to Vault when
+    /// AppRole credentials are present and plain environment variables otherwise.
+    pub fn select(configured: Option<&str>, vault_credentials_present: bool) -> Result<Self> {
+        match configured {
+            Some(value) if !value.trim().is_empty() => value.parse(),
+            _ if vault_credentials_present => Ok(Self::Vault),
+            _ => Ok(Self::Env),
+        }
+    }
+
+    /// Read the selection from the process environment
+    pub fn from_env() -> Result<Self> {
+        let vault_credentials_present =
+            env::var("VAULT_ROLE_ID").is_ok() && env::var("VAULT_SECRET_ID").is_ok();
+        Self::select(
+            env::var("SECRETS_BACKEND").ok().as_deref(),
+            vault_credentials_present,
+        )
+    }
+
+    /// Connect to the selected backend; `None` for [`SecretsBackendKind::Env`]
+    pub async fn connect(self) -> Result<Option<Box<dyn SecretsBackend>>> {
+        match self {
+            Self::Env => Ok(None),
+            Self::Vault => Ok(Some(Box::new(VaultBackend::new().await?))),
+            Self::Aws => connect_aws().await.map(Some),
+        }
+    }
+}
+
+/// Fetch the database URL and anchor secret from `backend`. The password is
+/// substituted for `{password}` in `db_url_template`; without a template
+/// `fallback_db_url` is used as is.
+pub async fn resolve_credentials(
+    backend: &dyn SecretsBackend,
+    db_url_template: Option<&str>,
+    fallback_db_url: Option<&str>,
+) -> Result<(String, String)> {
+    let db_password = backend.get_db_password().await?;
+    let anchor_secret = backend.get_anchor_secret().await?;
+
+    let db_url = db_url_template
+        .map(|template| template.replace("{password}", &db_password))
+        .unwrap_or_else(|| fallback_db_url.unwrap_or_default().to_string());
+
+    Ok((db_url, anchor_secret))
+}
+
+pub struct VaultBackend {
     client: VaultClient,
     kv_mount: String,
 }
 
-impl SecretsManager {
+/// The Vault backend, under its original name
+pub type SecretsManager = VaultBackend;
+
+impl VaultBackend {
     pub async fn new() -> Result<Self> {
         let vault_addr =
             env::var("VAULT_ADDR").unwrap_or_else(|_| "http://127.0.0.1:8200".to_string());
@@ -36,8 +122,11 @@ impl SecretsManager {
 
         Ok(Self { client, kv_mount })
     }
+}
 
-    pub async fn get_db_password(&self) -> Result<String> {
+#[async_trait]
+impl SecretsBackend for VaultBackend {
+    async fn get_db_password(&self) -> Result<String> {
         let secret: HashMap<String, String> = kv2::read(&self.client, &self.kv_mount, "database")
             .await
             .context("failed to read secret/database from Vault")?;
@@ -48,7 +137,7 @@ impl SecretsManager {
             .context("password key not found in Vault secret/database")
     }
 
-    pub async fn get_anchor_secret(&self) -> Result<String> {
+    async fn get_anchor_secret(&self) -> Result<String> {
         let secret: HashMap<String, String> = kv2::read(&self.client, &self.kv_mount, "anchor")
             .await
             .context("failed to read secret/anchor from Vault")?;
@@ -59,3 +148,144 @@ impl SecretsManager {
             .context("secret key not found in Vault secret/anchor")
     }
 }
+
+#[cfg(feature = "aws-secrets")]
+async fn connect_aws() -> Result<Box<dyn SecretsBackend>> {
+    Ok(Box::new(aws::AwsSecretsBackend::from_env().await))
+}
+
+#[cfg(not(feature = "aws-secrets"))]
+async fn connect_aws() -> Result<Box<dyn SecretsBackend>> {
+    anyhow::bail!("SECRETS_BACKEND=aws requires building with the `aws-secrets` feature")
+}
+
+/// Pull `key` out of a secret stored as a JSON object, or use the whole
+/// string when the secret is not JSON
+#[cfg_attr(not(feature = "aws-secrets"), allow(dead_code))]
+fn secret_field(raw: &str, key: &str) -> Result<String> {
+    match serde_json::from_str::<HashMap<String, serde_json::Value>>(raw) {
+        Ok(fields) => fields
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .with_context(|| format!("{} key not found in secret", key)),
+        Err(_) => Ok(raw.to_string()),
+    }
+}
+
+#[cfg(feature = "aws-secrets")]
+pub mod aws {
+    use super::*;
+    use aws_sdk_secretsmanager::Client;
+
+    /// AWS Secrets Manager backend. Credentials and region come from the
+    /// default AWS provider chain; secret ids from `AWS_DB_SECRET_ID` and
+    /// `AWS_ANCHOR_SECRET_ID`.
+    pub struct AwsSecretsBackend {
+        client: Client,
+        db_secret_id: String,
+        anchor_secret_id: String,
+    }
+
+    impl AwsSecretsBackend {
+        pub async fn from_env() -> Self {
+            let config = aws_config::load_from_env().await;
+            Self {
+                client: Client::new(&config),
+                db_secret_id: env::var("AWS_DB_SECRET_ID")
+                    .unwrap_or_else(|_| "synapse/database".to_string()),
+                anchor_secret_id: env::var("AWS_ANCHOR_SECRET_ID")
+                    .unwrap_or_else(|_| "synapse/anchor".to_string()),
+            }
+        }
+
+        async fn read(&self, secret_id: &str) -> Result<String> {
+            let output = self
+                .client
+                .get_secret_value()
+                .secret_id(secret_id)
+                .send()
+                .await
+                .with_context(|| format!("failed to read {} from Secrets Manager", secret_id))?;
+            output
+                .secret_string()
+                .map(str::to_string)
+                .with_context(|| format!("{} has no string value", secret_id))
+        }
+    }
+
+    #[async_trait]
+    impl SecretsBackend for AwsSecretsBackend {
+        async fn get_db_password(&self) -> Result<String> {
+            secret_field(&self.read(&self.db_secret_id).await?, "password")
+        }
+
+        async fn get_anchor_secret(&self) -> Result<String> {
+            secret_field(&self.read(&self.anchor_secret_id).await?, "secret")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockBackend;
+
+    #[async_trait]
+    impl SecretsBackend for MockBackend {
+        async fn get_db_password(&self) -> Result<String> {
+            Ok("s3cret".to_string())
+        }
+
+        async fn get_anchor_secret(&self) -> Result<String> {
+            Ok("anchor-key".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credentials_from_mock_backend() {
+        let (db_url, anchor) = resolve_credentials(
+            &MockBackend,
+            Some("postgres://synapse:{password}@db/synapse"),
+            Some("postgres://ignored"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(db_url, "postgres://synapse:s3cret@db/synapse");
+        assert_eq!(anchor, "anchor-key");
+
+        let (db_url, _) = resolve_credentials(&MockBackend, None, Some("postgres://plain"))
+            .await
+            .unwrap();
+        assert_eq!(db_url, "postgres://plain");
+    }
+
+    #[test]
+    fn test_backend_selection() {
+        use SecretsBackendKind::*;
+        assert_eq!(SecretsBackendKind::select(None, false).unwrap(), Env);
+        assert_eq!(SecretsBackendKind::select(None, true).unwrap(), Vault);
+        assert_eq!(SecretsBackendKind::select(Some("aws"), true).unwrap(), Aws);
+        assert_eq!(
+            SecretsBackendKind::select(Some("Vault"), false).unwrap(),
+            Vault
+        );
+        assert_eq!(SecretsBackendKind::select(Some("env"), true).unwrap(), Env);
+        assert_eq!(SecretsBackendKind::select(Some(""), true).unwrap(), Vault);
+        assert!(SecretsBackendKind::select(Some("gcp"), false).is_err());
+    }
+
+    #[test]
+    fn test_secret_field_reads_json_or_raw() {
+        assert_eq!(
+            secret_field(r#"{"password":"pw","user":"u"}"#, "password").unwrap(),
+            "pw"
+        );
+        assert_eq!(
+            secret_field("plain-value", "password").unwrap(),
+            "plain-value"
+        );
+        assert!(secret_field(r#"{"user":"u"}"#, "password").is_err());
+    }
+}
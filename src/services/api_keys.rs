@@ -0,0 +1,201 @@
+use axum::extract::FromRef;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{ApiState, AppState};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Every generated key starts with this, so leaked keys are easy to spot
+pub const KEY_PREFIX: &str = "sk_";
+/// Characters of a key stored in the clear to find its row
+const LOOKUP_PREFIX_LEN: usize = KEY_PREFIX.len() + 8;
+
+/// A stored API key. The key itself is only returned once, by
+/// [`ApiKeyService::create`].
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub partner_id: Uuid,
+    pub key_prefix: String,
+    #[serde(skip)]
+    pub key_salt: String,
+    #[serde(skip)]
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Check `key` against the stored salted hash in constant time
+    pub fn matches(&self, key: &str) -> bool {
+        let Ok(expected) = hex::decode(&self.key_hash) else {
+            return false;
+        };
+        keyed_mac(&self.key_salt, key)
+            .verify_slice(&expected)
+            .is_ok()
+    }
+}
+
+fn keyed_mac(salt: &str, key: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(salt.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(key.as_bytes());
+    mac
+}
+
+/// Hex HMAC-SHA256 of `key`, keyed with `salt`
+pub fn hash_key(salt: &str, key: &str) -> String {
+    hex::encode(keyed_mac(salt, key).finalize().into_bytes())
+}
+
+fn lookup_prefix(key: &str) -> Option<&str> {
+    key.get(..LOOKUP_PREFIX_LEN)
+}
+
+#[derive(Clone)]
+pub struct ApiKeyService {
+    pool: PgPool,
+}
+
+impl ApiKeyService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Issue a new key for `partner_id`. Returns the stored record and the
+    /// plaintext key, which cannot be recovered later.
+    pub async fn create(
+        &self,
+        partner_id: Uuid,
+        scopes: &[String],
+    ) -> Result<(ApiKey, String), sqlx::Error> {
+        let key = format!(
+            "{}{}{}",
+            KEY_PREFIX,
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+        let salt = Uuid::new_v4().simple().to_string();
+
+        let record = sqlx::query_as::<_, ApiKey>(
+            r#"
+            INSERT INTO api_keys (partner_id, key_prefix, key_salt, key_hash, scopes)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(partner_id)
+        .bind(lookup_prefix(&key))
+        .bind(&salt)
+        .bind(hash_key(&salt, &key))
+        .bind(scopes)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((record, key))
+    }
+
+    /// The active key matching `key`, or `None` if it is unknown or revoked
+    pub async fn authenticate(&self, key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        let Some(prefix) = lookup_prefix(key) else {
+            return Ok(None);
+        };
+        let candidates = sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys WHERE key_prefix = $1 AND revoked_at IS NULL",
+        )
+        .bind(prefix)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(candidates
+            .into_iter()
+            .find(|candidate| candidate.matches(key)))
+    }
+
+    /// Revoke a key; later requests with it are rejected. Returns false if
+    /// no active key has this id.
+    pub async fn revoke(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_for_partner(&self, partner_id: Uuid) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys WHERE partner_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(partner_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+impl FromRef<AppState> for ApiKeyService {
+    fn from_ref(state: &AppState) -> Self {
+        Self::new(state.db.clone())
+    }
+}
+
+impl FromRef<ApiState> for ApiKeyService {
+    fn from_ref(state: &ApiState) -> Self {
+        Self::from_ref(&state.app_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(key: &str, salt: &str) -> ApiKey {
+        ApiKey {
+            id: Uuid::new_v4(),
+            partner_id: Uuid::new_v4(),
+            key_prefix: lookup_prefix(key).unwrap().to_string(),
+            key_salt: salt.to_string(),
+            key_hash: hash_key(salt, key),
+            scopes: vec!["callback".to_string()],
+            created_at: Utc::now(),
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_hash_is_salted() {
+        let key = "sk_0123456789abcdef";
+        assert_ne!(hash_key("salt-a", key), hash_key("salt-b", key));
+        assert_eq!(hash_key("salt-a", key), hash_key("salt-a", key));
+    }
+
+    #[test]
+    fn test_matches_only_the_issued_key() {
+        let record = stored("sk_0123456789abcdef", "salt");
+        assert!(record.matches("sk_0123456789abcdef"));
+        assert!(!record.matches("sk_0123456789abcdeg"));
+        assert!(record.has_scope("callback"));
+        assert!(!record.has_scope("admin"));
+    }
+
+    #[test]
+    fn test_short_keys_have_no_lookup_prefix() {
+        assert_eq!(lookup_prefix("sk_123"), None);
+        assert_eq!(lookup_prefix("sk_12345678rest"), Some("sk_12345678"));
+    }
+}
@@ -1,8 +1,12 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
@@ -13,6 +17,36 @@ pub enum BackupType {
     Monthly,
 }
 
+impl BackupType {
+    /// How often a scheduled backup of this type runs
+    pub fn interval(&self) -> Duration {
+        match self {
+            BackupType::Hourly => Duration::from_secs(60 * 60),
+            BackupType::Daily => Duration::from_secs(24 * 60 * 60),
+            BackupType::Monthly => Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl FromStr for BackupType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "hourly" => Ok(BackupType::Hourly),
+            "daily" => Ok(BackupType::Daily),
+            "monthly" => Ok(BackupType::Monthly),
+            other => anyhow::bail!(
+                "Invalid backup type '{}': expected hourly, daily or monthly",
+                other
+            ),
+        }
+    }
+}
+
+/// Gauge holding the Unix time of the last successful scheduled backup
+pub const BACKUP_LAST_SUCCESS_METRIC: &str = "backup_last_success_timestamp";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupMetadata {
     pub filename: String,
@@ -22,12 +56,49 @@ pub struct BackupMetadata {
     pub compressed: bool,
     pub encrypted: bool,
     pub checksum: String,
+    /// Metadata written before custom-format dumps existed has no format
+    #[serde(default)]
+    pub format: BackupFormat,
+}
+
+/// pg_dump output format; decides which tool restores the backup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupFormat {
+    /// SQL script replayed with psql
+    #[default]
+    Plain,
+    /// pg_dump archive restored with pg_restore
+    Custom,
+}
+
+/// Default number of parallel pg_restore jobs
+pub const DEFAULT_RESTORE_JOBS: usize = 4;
+
+/// Tables that must exist in a restored backup
+const REQUIRED_TABLES: &[&str] = &["_sqlx_migrations", "transactions", "settlements"];
+
+/// Row count of one table in a verified restore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableCheck {
+    pub table: String,
+    pub row_count: i64,
+}
+
+/// Result of restoring a backup into a scratch database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreVerificationReport {
+    pub filename: String,
+    pub scratch_database: String,
+    pub tables: Vec<TableCheck>,
+    pub duration_ms: u64,
 }
 
 pub struct BackupService {
     database_url: String,
     backup_dir: PathBuf,
     encryption_key: Option<String>,
+    restore_jobs: usize,
 }
 
 impl BackupService {
@@ -36,9 +107,16 @@ impl BackupService {
             database_url,
             backup_dir,
             encryption_key,
+            restore_jobs: DEFAULT_RESTORE_JOBS,
         }
     }
 
+    /// Set the number of parallel jobs used when restoring custom-format backups
+    pub fn with_restore_jobs(mut self, jobs: usize) -> Self {
+        self.restore_jobs = jobs.max(1);
+        self
+    }
+
     pub async fn create_backup(&self, backup_type: BackupType) -> Result<BackupMetadata> {
         // Ensure backup directory exists
         fs::create_dir_all(&self.backup_dir)
@@ -50,9 +128,17 @@ impl BackupService {
         let backup_path = self.backup_dir.join(&filename);
         let temp_path = self.backup_dir.join(format!("{}.tmp", filename));
 
+        // Custom archives need pg_restore; without it, fall back to plain SQL
+        let format = if pg_restore_available() {
+            BackupFormat::Custom
+        } else {
+            tracing::warn!("pg_restore not found, falling back to plain-format backup");
+            BackupFormat::Plain
+        };
+
         // Run pg_dump
         tracing::info!("Running pg_dump for {:?} backup", backup_type);
-        self.run_pg_dump(&temp_path).await?;
+        self.run_pg_dump(&temp_path, format).await?;
 
         // Compress the backup
         tracing::info!("Compressing backup");
@@ -87,6 +173,7 @@ impl BackupService {
             compressed: true,
             encrypted: self.encryption_key.is_some(),
             checksum,
+            format,
         };
 
         // Save metadata
@@ -118,7 +205,7 @@ impl BackupService {
         }
 
         // Sort by timestamp descending
-        backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        backups.sort_by_key(|b| std::cmp::Reverse(b.timestamp));
 
         Ok(backups)
     }
@@ -138,25 +225,14 @@ impl BackupService {
         self.verify_backup(&backup_path, &metadata).await?;
 
         let temp_dir = self.backup_dir.join("restore_temp");
-        fs::create_dir_all(&temp_dir)
-            .await
-            .context("Failed to create temp directory")?;
-
-        let mut current_path = backup_path.clone();
-
-        // Decrypt if encrypted
-        if metadata.encrypted {
-            tracing::info!("Decrypting backup");
-            current_path = self.decrypt_backup(&current_path, &temp_dir).await?;
-        }
-
-        // Decompress
-        tracing::info!("Decompressing backup");
-        let sql_path = self.decompress_backup(&current_path, &temp_dir).await?;
+        let dump_path = self
+            .prepare_restore_file(&backup_path, &metadata, &temp_dir)
+            .await?;
 
         // Restore to database
         tracing::info!("Restoring to database");
-        self.run_pg_restore(&sql_path).await?;
+        self.run_restore(&self.database_url, &dump_path, metadata.format, false)
+            .await?;
 
         // Cleanup temp directory
         fs::remove_dir_all(&temp_dir)
@@ -168,6 +244,100 @@ impl BackupService {
         Ok(())
     }
 
+    /// Restore a backup into a temporary, randomly-named database and run
+    /// sanity checks against it. The live database is never touched and the
+    /// scratch database is dropped whether or not verification succeeds.
+    pub async fn verify_restore(&self, filename: &str) -> Result<RestoreVerificationReport> {
+        let started = std::time::Instant::now();
+        let backup_path = self.backup_dir.join(filename);
+
+        if !backup_path.exists() {
+            anyhow::bail!("Backup file not found: {}", filename);
+        }
+
+        let meta_path = backup_path.with_extension("meta");
+        let metadata = self.load_metadata(&meta_path).await?;
+
+        tracing::info!("Verifying backup integrity");
+        self.verify_backup(&backup_path, &metadata).await?;
+
+        let scratch_database = format!("synapse_verify_{}", uuid::Uuid::new_v4().simple());
+        let temp_dir = self.backup_dir.join(format!("{}_temp", scratch_database));
+        let scratch_url = scratch_database_url(&self.database_url, &scratch_database)?;
+
+        let admin = PgPool::connect(&self.database_url)
+            .await
+            .context("Failed to connect to database server")?;
+        sqlx::query(&format!("CREATE DATABASE \"{}\"", scratch_database))
+            .execute(&admin)
+            .await
+            .context("Failed to create scratch database")?;
+
+        let result = async {
+            let dump_path = self
+                .prepare_restore_file(&backup_path, &metadata, &temp_dir)
+                .await?;
+            tracing::info!(
+                "Restoring backup into scratch database {}",
+                scratch_database
+            );
+            self.run_restore(&scratch_url, &dump_path, metadata.format, true)
+                .await?;
+            check_restored_tables(&scratch_url).await
+        }
+        .await;
+
+        if let Err(e) = sqlx::query(&format!(
+            "DROP DATABASE IF EXISTS \"{}\" WITH (FORCE)",
+            scratch_database
+        ))
+        .execute(&admin)
+        .await
+        {
+            tracing::error!(
+                "Failed to drop scratch database {}: {}",
+                scratch_database,
+                e
+            );
+        }
+        admin.close().await;
+        if temp_dir.exists() {
+            let _ = fs::remove_dir_all(&temp_dir).await;
+        }
+
+        let tables = result?;
+        tracing::info!("Backup {} verified successfully", filename);
+
+        Ok(RestoreVerificationReport {
+            filename: filename.to_string(),
+            scratch_database,
+            tables,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Recompute the checksum of every listed backup and compare it with its
+    /// `.meta`. Returns the filenames that no longer match, including those
+    /// whose backup file is gone.
+    pub async fn verify_all(&self) -> Result<Vec<String>> {
+        let mut corrupt = Vec::new();
+
+        for backup in self.list_backups().await? {
+            let backup_path = self.backup_dir.join(&backup.filename);
+            if !backup_path.exists() {
+                tracing::warn!("Backup file missing: {}", backup.filename);
+                corrupt.push(backup.filename);
+                continue;
+            }
+            if let Err(e) = self.verify_backup(&backup_path, &backup).await {
+                tracing::warn!("Backup {} failed verification: {}", backup.filename, e);
+                corrupt.push(backup.filename);
+            }
+        }
+
+        Ok(corrupt)
+    }
+
     pub async fn apply_retention_policy(&self) -> Result<()> {
         let backups = self.list_backups().await?;
 
@@ -222,10 +392,14 @@ impl BackupService {
         Ok(())
     }
 
-    async fn run_pg_dump(&self, output_path: &Path) -> Result<()> {
+    async fn run_pg_dump(&self, output_path: &Path, format: BackupFormat) -> Result<()> {
+        let format_arg = match format {
+            BackupFormat::Plain => "--format=plain",
+            BackupFormat::Custom => "--format=custom",
+        };
         let output = Command::new("pg_dump")
             .arg(&self.database_url)
-            .arg("--format=plain")
+            .arg(format_arg)
             .arg("--no-owner")
             .arg("--no-acl")
             .arg(format!("--file={}", output_path.display()))
@@ -240,9 +414,106 @@ impl BackupService {
         Ok(())
     }
 
-    async fn run_pg_restore(&self, sql_path: &Path) -> Result<()> {
-        let output = Command::new("psql")
-            .arg(&self.database_url)
+    /// Decrypt (if needed) and decompress a backup into `temp_dir`
+    async fn prepare_restore_file(
+        &self,
+        backup_path: &Path,
+        metadata: &BackupMetadata,
+        temp_dir: &Path,
+    ) -> Result<PathBuf> {
+        fs::create_dir_all(temp_dir)
+            .await
+            .context("Failed to create temp directory")?;
+
+        let mut current_path = backup_path.to_path_buf();
+
+        // Decrypt if encrypted
+        if metadata.encrypted {
+            tracing::info!("Decrypting backup");
+            current_path = self.decrypt_backup(&current_path, temp_dir).await?;
+        }
+
+        // Decompress
+        tracing::info!("Decompressing backup");
+        let output_name = match metadata.format {
+            BackupFormat::Plain => "restore.sql",
+            BackupFormat::Custom => "restore.dump",
+        };
+        self.decompress_backup(&current_path, &temp_dir.join(output_name))
+            .await
+    }
+
+    /// Restore `dump_path` into `database_url` with the tool matching `format`
+    async fn run_restore(
+        &self,
+        database_url: &str,
+        dump_path: &Path,
+        format: BackupFormat,
+        stop_on_error: bool,
+    ) -> Result<()> {
+        match format {
+            BackupFormat::Plain => self.run_psql(database_url, dump_path, stop_on_error).await,
+            BackupFormat::Custom => {
+                if !pg_restore_available() {
+                    anyhow::bail!("Backup is in custom format but pg_restore is not installed");
+                }
+                self.run_pg_restore(database_url, dump_path, stop_on_error)
+                    .await
+            }
+        }
+    }
+
+    /// Restore a custom-format archive with `pg_restore --clean`, using
+    /// `restore_jobs` parallel jobs. Without `stop_on_error`, failing objects
+    /// are reported and skipped like psql does for plain dumps.
+    async fn run_pg_restore(
+        &self,
+        database_url: &str,
+        dump_path: &Path,
+        stop_on_error: bool,
+    ) -> Result<()> {
+        let mut command = Command::new("pg_restore");
+        command
+            .arg(format!("--dbname={}", database_url))
+            .arg(format!("--jobs={}", self.restore_jobs))
+            .arg("--clean")
+            .arg("--if-exists")
+            .arg("--no-owner")
+            .arg("--no-acl");
+        if stop_on_error {
+            command.arg("--exit-on-error");
+        }
+        let output = command
+            .arg(dump_path)
+            .output()
+            .context("Failed to execute pg_restore")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !stop_on_error && stderr.contains("errors ignored on restore") {
+                tracing::warn!("pg_restore completed with errors: {}", stderr);
+                return Ok(());
+            }
+            anyhow::bail!("pg_restore failed: {}", stderr);
+        }
+
+        Ok(())
+    }
+
+    /// Replay a plain SQL dump with psql. With `stop_on_error`, the first failing
+    /// statement aborts the restore instead of being skipped.
+    async fn run_psql(
+        &self,
+        database_url: &str,
+        sql_path: &Path,
+        stop_on_error: bool,
+    ) -> Result<()> {
+        let mut command = Command::new("psql");
+        command.arg(database_url);
+        if stop_on_error {
+            command.arg("--set=ON_ERROR_STOP=1");
+        }
+        let output = command
             .arg("--file")
             .arg(sql_path)
             .output()
@@ -286,9 +557,7 @@ impl BackupService {
         Ok(output_path)
     }
 
-    async fn decompress_backup(&self, input_path: &Path, temp_dir: &Path) -> Result<PathBuf> {
-        let output_path = temp_dir.join("restore.sql");
-
+    async fn decompress_backup(&self, input_path: &Path, output_path: &Path) -> Result<PathBuf> {
         let output = Command::new("gunzip")
             .arg("-c")
             .arg(input_path)
@@ -300,7 +569,7 @@ impl BackupService {
             anyhow::bail!("gunzip failed: {}", stderr);
         }
 
-        let mut file = fs::File::create(&output_path)
+        let mut file = fs::File::create(output_path)
             .await
             .context("Failed to create decompressed file")?;
 
@@ -308,7 +577,7 @@ impl BackupService {
             .await
             .context("Failed to write decompressed data")?;
 
-        Ok(output_path)
+        Ok(output_path.to_path_buf())
     }
 
     async fn encrypt_backup(&self, input_path: &Path) -> Result<PathBuf> {
@@ -455,3 +724,121 @@ impl BackupService {
         Ok(metadata)
     }
 }
+
+/// Creates a backup on a fixed interval and then applies the retention policy
+pub struct BackupScheduler {
+    service: Arc<BackupService>,
+    backup_type: BackupType,
+    interval: Duration,
+}
+
+impl BackupScheduler {
+    pub fn new(service: BackupService, backup_type: BackupType) -> Self {
+        Self {
+            service: Arc::new(service),
+            backup_type,
+            interval: backup_type.interval(),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Run forever; the first backup is taken one interval after start
+    pub async fn run(self) {
+        tracing::info!(
+            "Backup scheduler started ({:?}, interval: {}s)",
+            self.backup_type,
+            self.interval.as_secs()
+        );
+        let start = tokio::time::Instant::now() + self.interval;
+        let mut interval = tokio::time::interval_at(start, self.interval);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.run_once().await {
+                tracing::error!("Scheduled {:?} backup failed: {:#}", self.backup_type, e);
+            }
+        }
+    }
+
+    /// Create one backup and prune old ones
+    pub async fn run_once(&self) -> Result<BackupMetadata> {
+        let metadata = self.service.create_backup(self.backup_type).await?;
+        crate::metrics::registry().set_gauge(
+            BACKUP_LAST_SUCCESS_METRIC,
+            metadata.timestamp.timestamp() as f64,
+        );
+        tracing::info!(
+            "Scheduled backup {} created ({} bytes)",
+            metadata.filename,
+            metadata.size_bytes
+        );
+
+        if let Err(e) = self.service.apply_retention_policy().await {
+            tracing::error!("Failed to apply backup retention policy: {:#}", e);
+        }
+
+        Ok(metadata)
+    }
+}
+
+/// Whether a usable `pg_restore` binary is on the PATH
+fn pg_restore_available() -> bool {
+    Command::new("pg_restore")
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Point `database_url` at `database` on the same server
+fn scratch_database_url(database_url: &str, database: &str) -> Result<String> {
+    let mut url = url::Url::parse(database_url).context("Invalid database URL")?;
+    url.set_path(&format!("/{}", database));
+    Ok(url.to_string())
+}
+
+/// Check that the required tables exist and that the migration history is not empty
+async fn check_restored_tables(database_url: &str) -> Result<Vec<TableCheck>> {
+    let pool = PgPool::connect(database_url)
+        .await
+        .context("Failed to connect to scratch database")?;
+
+    let mut tables = Vec::new();
+    let result = async {
+        for table in REQUIRED_TABLES {
+            let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+                .bind(format!("public.{}", table))
+                .fetch_one(&pool)
+                .await?;
+            if !exists {
+                anyhow::bail!("Restored database is missing table '{}'", table);
+            }
+
+            let row_count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM \"{}\"", table))
+                .fetch_one(&pool)
+                .await?;
+            tables.push(TableCheck {
+                table: table.to_string(),
+                row_count,
+            });
+        }
+
+        let migrations = tables.iter().find(|t| t.table == "_sqlx_migrations");
+        if migrations.is_none_or(|t| t.row_count == 0) {
+            anyhow::bail!("Restored database has no migration history");
+        }
+        Ok(())
+    }
+    .await;
+
+    pool.close().await;
+    result.map(|_| tables)
+}
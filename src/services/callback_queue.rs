@@ -0,0 +1,114 @@
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::db::models::Transaction;
+use crate::db::queries;
+use crate::services::transaction_processor::ProcessingError;
+
+/// Callbacks accepted with `async=true` and waiting to be stored, unless
+/// `CALLBACK_QUEUE_CAPACITY` says otherwise
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1000;
+
+/// Queued callbacks stored as transactions
+pub const PROCESSED_METRIC: &str = "callback_queue_processed_total";
+/// Queued callbacks that could not be stored
+pub const FAILED_METRIC: &str = "callback_queue_failed_total";
+
+/// Producer side of the async callback queue
+#[derive(Clone)]
+pub struct CallbackQueue {
+    tx: mpsc::Sender<Transaction>,
+}
+
+impl CallbackQueue {
+    /// Create a queue holding up to `capacity` callbacks and spawn the
+    /// processor that stores them. The processor stops once every clone of
+    /// the queue is dropped and the queued callbacks are stored; await the
+    /// returned handle to wait for that.
+    pub fn spawn(pool: PgPool, capacity: usize) -> (Self, JoinHandle<()>) {
+        let registry = crate::metrics::registry();
+        for metric in [PROCESSED_METRIC, FAILED_METRIC] {
+            registry.register_counter(metric, &[]);
+        }
+
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let handle = tokio::spawn(CallbackProcessor { rx, pool }.run());
+        (Self { tx }, handle)
+    }
+
+    /// Queue a validated transaction without waiting. When the queue is full
+    /// or the processor has stopped the transaction is handed back.
+    pub fn try_enqueue(&self, tx: Transaction) -> Result<(), Box<Transaction>> {
+        self.tx.try_send(tx).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(tx) | mpsc::error::TrySendError::Closed(tx) => {
+                Box::new(tx)
+            }
+        })
+    }
+}
+
+/// Consumer side: stores queued transactions one at a time. Once every
+/// producer is dropped it drains what is still queued, then returns.
+struct CallbackProcessor {
+    rx: mpsc::Receiver<Transaction>,
+    pool: PgPool,
+}
+
+impl CallbackProcessor {
+    async fn run(mut self) {
+        while let Some(tx) = self.rx.recv().await {
+            let registry = crate::metrics::registry();
+            match queries::insert_transaction(&self.pool, &tx).await {
+                Ok(inserted) => {
+                    registry.increment_counter(PROCESSED_METRIC, &[]);
+                    crate::handlers::webhook::record_callback(&inserted);
+                }
+                Err(e) => {
+                    registry.increment_counter(FAILED_METRIC, &[]);
+                    tracing::error!(
+                        transaction_id = %tx.id,
+                        "Failed to store queued callback: {}",
+                        e
+                    );
+                    let err = ProcessingError::from(e);
+                    if let Err(dlq_err) = record_failure(&self.pool, &tx, &err).await {
+                        tracing::error!(
+                            transaction_id = %tx.id,
+                            "Failed to record queued callback in DLQ: {}",
+                            dlq_err
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Record a queued callback that could not be stored in `transaction_dlq`
+/// under the id it was accepted with, so the failure can be looked up
+async fn record_failure(
+    pool: &PgPool,
+    tx: &Transaction,
+    err: &ProcessingError,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO transaction_dlq (
+            transaction_id, stellar_account, amount, asset_code,
+            anchor_transaction_id, error_reason, error_code, original_created_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(tx.id)
+    .bind(&tx.stellar_account)
+    .bind(&tx.amount)
+    .bind(&tx.asset_code)
+    .bind(&tx.anchor_transaction_id)
+    .bind(err.to_string())
+    .bind(err.dlq_code().as_str())
+    .bind(tx.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,10 +1,29 @@
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// How long cached flags are served before the next check reloads them
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct FeatureFlagService {
     pool: PgPool,
+    ttl: Duration,
+    cache: Arc<RwLock<FlagCache>>,
+}
+
+#[derive(Default)]
+struct FlagCache {
+    flags: HashMap<String, bool>,
+    loaded_at: Option<Instant>,
+}
+
+impl FlagCache {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        self.loaded_at.is_some_and(|at| at.elapsed() < ttl)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -16,27 +35,45 @@ pub struct FeatureFlag {
 
 impl FeatureFlagService {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            ttl: DEFAULT_CACHE_TTL,
+            cache: Arc::new(RwLock::new(FlagCache::default())),
+        }
     }
 
-    pub async fn is_enabled(&self, flag_name: &str) -> Result<bool, sqlx::Error> {
-        let result =
-            sqlx::query_scalar::<_, bool>("SELECT enabled FROM feature_flags WHERE name = $1")
-                .bind(flag_name)
-                .fetch_optional(&self.pool)
-                .await?;
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
 
-        Ok(result.unwrap_or(false))
+    /// Serve from the cache, reloading every flag once it is older than the TTL.
+    /// Unknown flags are disabled.
+    pub async fn is_enabled(&self, flag_name: &str) -> Result<bool, sqlx::Error> {
+        self.ensure_fresh().await?;
+        let cache = self.cache.read().unwrap_or_else(|e| e.into_inner());
+        Ok(cache.flags.get(flag_name).copied().unwrap_or(false))
     }
 
-    pub async fn get_all_flags(&self) -> Result<HashMap<String, bool>, sqlx::Error> {
+    /// Reload all flags from the database into the cache
+    pub async fn refresh(&self) -> Result<HashMap<String, bool>, sqlx::Error> {
         let flags = sqlx::query_as::<_, FeatureFlag>(
             "SELECT name, enabled, description FROM feature_flags",
         )
         .fetch_all(&self.pool)
         .await?;
+        let flags: HashMap<String, bool> = flags.into_iter().map(|f| (f.name, f.enabled)).collect();
 
-        Ok(flags.into_iter().map(|f| (f.name, f.enabled)).collect())
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        cache.flags = flags.clone();
+        cache.loaded_at = Some(Instant::now());
+        Ok(flags)
+    }
+
+    pub async fn get_all_flags(&self) -> Result<HashMap<String, bool>, sqlx::Error> {
+        self.ensure_fresh().await?;
+        let cache = self.cache.read().unwrap_or_else(|e| e.into_inner());
+        Ok(cache.flags.clone())
     }
 
     pub async fn get_all(&self) -> Result<HashMap<String, bool>, sqlx::Error> {
@@ -44,12 +81,45 @@ impl FeatureFlagService {
     }
 
     pub async fn update(&self, name: &str, enabled: bool) -> Result<FeatureFlag, sqlx::Error> {
-        sqlx::query_as::<_, FeatureFlag>(
+        let flag = sqlx::query_as::<_, FeatureFlag>(
             "UPDATE feature_flags SET enabled = $2 WHERE name = $1 RETURNING name, enabled, description",
         )
         .bind(name)
         .bind(enabled)
         .fetch_one(&self.pool)
-        .await
+        .await?;
+
+        self.cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .flags
+            .insert(flag.name.clone(), flag.enabled);
+        Ok(flag)
+    }
+
+    /// Refresh the cache if it has expired. If the database is unavailable
+    /// the last loaded values keep being served until the next TTL elapses.
+    async fn ensure_fresh(&self) -> Result<(), sqlx::Error> {
+        if self
+            .cache
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_fresh(self.ttl)
+        {
+            return Ok(());
+        }
+
+        match self.refresh().await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+                if cache.loaded_at.is_none() {
+                    return Err(e);
+                }
+                tracing::warn!("Feature flag refresh failed, serving cached values: {}", e);
+                cache.loaded_at = Some(Instant::now());
+                Ok(())
+            }
+        }
     }
 }
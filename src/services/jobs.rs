@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::db::partition::PartitionManager;
+use crate::db::queries;
+use crate::services::backup::BackupScheduler;
+use crate::services::scheduler::{Job, JobSchedule};
+use crate::services::settlement::SettlementService;
+use crate::services::transaction_processor::{DlqErrorCode, DlqFilter, TransactionProcessor};
+
+/// Settles every asset once an hour
+pub struct SettlementJob {
+    service: SettlementService,
+}
+
+impl SettlementJob {
+    /// Settle with at most `max_batch_size` transactions per settlement
+    pub fn new(pool: PgPool, max_batch_size: usize) -> Self {
+        Self {
+            service: SettlementService::new(pool).with_max_batch_size(max_batch_size),
+        }
+    }
+}
+
+#[async_trait]
+impl Job for SettlementJob {
+    fn name(&self) -> &str {
+        "settlement"
+    }
+
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Cron("0 0 * * * *".to_string()) // Top of every hour
+    }
+
+    async fn execute(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let results = self.service.run_settlements().await?;
+        if !results.is_empty() {
+            tracing::info!("Successfully generated {} settlements", results.len());
+        }
+        Ok(())
+    }
+}
+
+/// Creates upcoming partitions and detaches expired ones
+pub struct PartitionMaintenanceJob {
+    manager: PartitionManager,
+}
+
+impl PartitionMaintenanceJob {
+    /// Run `manager`'s maintenance on its configured interval
+    pub fn new(manager: PartitionManager) -> Self {
+        Self { manager }
+    }
+}
+
+#[async_trait]
+impl Job for PartitionMaintenanceJob {
+    fn name(&self) -> &str {
+        "partition_maintenance"
+    }
+
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Every(self.manager.interval())
+    }
+
+    async fn execute(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.manager.maintain_partitions().await?;
+        Ok(())
+    }
+}
+
+/// Takes a scheduled backup and prunes old ones
+pub struct BackupJob {
+    scheduler: BackupScheduler,
+}
+
+impl BackupJob {
+    pub fn new(scheduler: BackupScheduler) -> Self {
+        Self { scheduler }
+    }
+}
+
+#[async_trait]
+impl Job for BackupJob {
+    fn name(&self) -> &str {
+        "backup"
+    }
+
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Every(self.scheduler.interval())
+    }
+
+    async fn execute(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.scheduler.run_once().await?;
+        Ok(())
+    }
+}
+
+/// How often DLQ entries whose retries ran out are given another chance
+pub const DLQ_RETRY_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Requeues DLQ entries that failed transiently and have sat in the DLQ for
+/// at least one retry interval. Permanent failures are left for an operator.
+pub struct DlqRetryJob {
+    processor: TransactionProcessor,
+}
+
+impl DlqRetryJob {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            processor: TransactionProcessor::new(pool),
+        }
+    }
+}
+
+#[async_trait]
+impl Job for DlqRetryJob {
+    fn name(&self) -> &str {
+        "dlq_retry"
+    }
+
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Every(DLQ_RETRY_INTERVAL)
+    }
+
+    async fn execute(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let filter = DlqFilter {
+            error_code: Some(DlqErrorCode::RetriesExhausted),
+            before: Some(Utc::now() - chrono::Duration::from_std(DLQ_RETRY_INTERVAL)?),
+            ..DlqFilter::default()
+        };
+        let summary = self.processor.requeue_dlq_matching(&filter).await?;
+        if summary.succeeded > 0 || summary.failed > 0 {
+            tracing::info!(
+                requeued = summary.succeeded,
+                failed = summary.failed,
+                "Requeued DLQ entries with exhausted retries"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Transactions anonymized per database transaction by [`PiiRetentionJob`]
+pub const PII_RETENTION_BATCH_SIZE: i64 = 500;
+
+/// Clears the account, memo and metadata of transactions older than the
+/// retention window, once a day. Rows in partitions that have already been
+/// detached are not reached.
+pub struct PiiRetentionJob {
+    pool: PgPool,
+    retention_days: u32,
+}
+
+impl PiiRetentionJob {
+    pub fn new(pool: PgPool, retention_days: u32) -> Self {
+        Self {
+            pool,
+            retention_days,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for PiiRetentionJob {
+    fn name(&self) -> &str {
+        "pii_retention"
+    }
+
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Cron("0 30 3 * * *".to_string()) // 03:30 every day
+    }
+
+    async fn execute(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let cutoff = Utc::now() - chrono::Duration::days(self.retention_days.into());
+        let mut total = 0;
+        loop {
+            let ids = queries::anonymize_transactions_before(
+                &self.pool,
+                cutoff,
+                PII_RETENTION_BATCH_SIZE,
+            )
+            .await?;
+            total += ids.len();
+            if (ids.len() as i64) < PII_RETENTION_BATCH_SIZE {
+                break;
+            }
+        }
+        if total > 0 {
+            tracing::info!(
+                anonymized = total,
+                retention_days = self.retention_days,
+                "Anonymized transactions past the PII retention window"
+            );
+        }
+        Ok(())
+    }
+}
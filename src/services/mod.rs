@@ -1,14 +1,27 @@
+pub mod api_keys;
 pub mod backup;
+pub mod callback_queue;
 pub mod feature_flags;
+pub mod jobs;
 pub mod processor;
+pub mod reconciliation;
 pub mod scheduler;
 pub mod settlement;
+pub mod transaction;
 pub mod transaction_processor;
 pub mod transaction_processor_job;
+pub mod webhook_dispatcher;
 
-pub use backup::BackupService;
+pub use api_keys::ApiKeyService;
+pub use backup::{BackupScheduler, BackupService};
+pub use callback_queue::CallbackQueue;
 pub use feature_flags::FeatureFlagService;
-pub use scheduler::{Job, JobScheduler, JobStatus};
+pub use reconciliation::ReconciliationWorker;
+pub use scheduler::{Job, JobRunResult, JobSchedule, JobScheduler, JobStatus, RunJobError};
 pub use settlement::SettlementService;
-pub use transaction_processor::TransactionProcessor;
+pub use transaction_processor::{
+    DlqErrorCode, DlqErrorGroup, DlqFilter, ProcessingError, ReprocessOutcome, RequeueSummary,
+    TransactionProcessor,
+};
 pub use transaction_processor_job::TransactionProcessorJob;
+pub use webhook_dispatcher::{WebhookDispatcher, WebhookQueue};
@@ -31,7 +31,7 @@ pub async fn process_batch(pool: &PgPool, _horizon_client: &HorizonClient) -> an
         r#"
         SELECT id, stellar_account, amount, asset_code, status, created_at, updated_at,
                anchor_transaction_id, callback_type, callback_status, settlement_id,
-               memo, memo_type, metadata
+               memo, memo_type, metadata, asset_issuer, partner_id
         FROM transactions
         WHERE status = 'pending'
         ORDER BY created_at ASC
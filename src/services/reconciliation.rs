@@ -0,0 +1,175 @@
+use crate::db::models::Transaction;
+use crate::services::transaction::{
+    transition_status_with_metadata, STATUS_COMPLETED, STATUS_RECONCILIATION_FAILED,
+};
+use crate::stellar::{HorizonClient, HorizonError};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// How often the worker runs by default
+pub const DEFAULT_RECONCILIATION_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Maximum number of transactions verified per run
+const BATCH_SIZE: i64 = 100;
+
+/// Only transactions completed within this window are verified
+const LOOKBACK_HOURS: i64 = 24;
+
+/// Outcome of a single reconciliation run
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconciliationReport {
+    pub checked: usize,
+    pub verified: usize,
+    pub flagged: usize,
+    pub skipped: usize,
+}
+
+/// Verifies completed transactions against Horizon and flags those whose
+/// `anchor_transaction_id` is missing or unsuccessful on-chain.
+///
+/// Verified rows get `metadata.reconciled_at`; mismatches move to
+/// `reconciliation_failed` with the details under `metadata.reconciliation`.
+/// Lookups that fail for transient reasons are retried on the next run.
+#[derive(Clone)]
+pub struct ReconciliationWorker {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    interval: Duration,
+}
+
+impl ReconciliationWorker {
+    pub fn new(pool: PgPool, horizon_client: HorizonClient) -> Self {
+        Self {
+            pool,
+            horizon_client,
+            interval: DEFAULT_RECONCILIATION_INTERVAL,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Run forever, reconciling once per interval
+    pub async fn run(self) {
+        info!(
+            "Reconciliation worker started (interval: {}s)",
+            self.interval.as_secs()
+        );
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+            match self.run_once().await {
+                Ok(report) if report.flagged > 0 => warn!(
+                    "Reconciliation flagged {} of {} transaction(s)",
+                    report.flagged, report.checked
+                ),
+                Ok(report) => info!(
+                    "Reconciliation verified {} transaction(s), skipped {}",
+                    report.verified, report.skipped
+                ),
+                Err(e) => error!("Reconciliation run failed: {}", e),
+            }
+        }
+    }
+
+    /// Verify one batch of recently completed transactions
+    pub async fn run_once(&self) -> anyhow::Result<ReconciliationReport> {
+        let candidates = sqlx::query_as::<_, Transaction>(
+            r#"
+            SELECT * FROM transactions
+            WHERE status = $1
+              AND anchor_transaction_id IS NOT NULL
+              AND updated_at >= NOW() - make_interval(hours => $2::int)
+              AND (metadata IS NULL OR NOT metadata ? 'reconciled_at')
+            ORDER BY updated_at ASC
+            LIMIT $3
+            "#,
+        )
+        .bind(STATUS_COMPLETED)
+        .bind(LOOKBACK_HOURS as i32)
+        .bind(BATCH_SIZE)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut report = ReconciliationReport::default();
+
+        for tx in candidates {
+            report.checked += 1;
+            let Some(hash) = tx.anchor_transaction_id.as_deref() else {
+                continue;
+            };
+
+            let mismatch = match self.horizon_client.get_transaction(hash).await {
+                Ok(onchain) if onchain.successful => None,
+                Ok(onchain) => Some(json!({
+                    "reason": "unsuccessful_on_chain",
+                    "hash": onchain.hash,
+                    "ledger": onchain.ledger,
+                })),
+                Err(HorizonError::TransactionNotFound(_)) => Some(json!({
+                    "reason": "not_found_on_chain",
+                    "hash": hash,
+                })),
+                Err(e) => {
+                    warn!("Skipping reconciliation of {}: {}", tx.id, e);
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+
+            match mismatch {
+                None => {
+                    self.mark_verified(&tx).await?;
+                    report.verified += 1;
+                }
+                Some(mut details) => {
+                    details["checked_at"] = json!(Utc::now().to_rfc3339());
+                    let flagged = transition_status_with_metadata(
+                        &self.pool,
+                        tx.id,
+                        STATUS_COMPLETED,
+                        STATUS_RECONCILIATION_FAILED,
+                        Some(json!({ "reconciliation": details })),
+                    )
+                    .await;
+
+                    match flagged {
+                        Ok(_) => {
+                            warn!("Transaction {} failed on-chain reconciliation", tx.id);
+                            report.flagged += 1;
+                        }
+                        // Status changed since we read it (e.g. refunded); leave it alone
+                        Err(e) => {
+                            warn!("Could not flag transaction {}: {}", tx.id, e);
+                            report.skipped += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn mark_verified(&self, tx: &Transaction) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE transactions
+            SET metadata = COALESCE(metadata, '{}'::jsonb) || jsonb_build_object('reconciled_at', $2::text)
+            WHERE id = $1 AND status = $3
+            "#,
+        )
+        .bind(tx.id)
+        .bind(Utc::now().to_rfc3339())
+        .bind(STATUS_COMPLETED)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -1,28 +1,116 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use cron::Schedule;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tracing::{error, info};
 
+use crate::error::AppError;
+
+/// When a job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobSchedule {
+    /// A cron expression with a seconds field, e.g. `0 0 * * * *` for hourly
+    Cron(String),
+    /// A fixed interval; the first run is one interval after the scheduler starts
+    Every(Duration),
+}
+
+impl JobSchedule {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            JobSchedule::Cron(expr) => Schedule::from_str(expr)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid cron expression '{}': {}", expr, e)),
+            JobSchedule::Every(interval) if interval.is_zero() => {
+                Err("Job interval must be greater than zero".to_string())
+            }
+            JobSchedule::Every(_) => Ok(()),
+        }
+    }
+
+    /// First run time strictly after `after`
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            JobSchedule::Cron(expr) => Schedule::from_str(expr).ok()?.after(&after).next(),
+            JobSchedule::Every(interval) => {
+                Some(after + chrono::Duration::from_std(*interval).ok()?)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for JobSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobSchedule::Cron(expr) => f.write_str(expr),
+            JobSchedule::Every(interval) => write!(f, "every {}s", interval.as_secs_f64()),
+        }
+    }
+}
+
 /// Represents a scheduled job that can be executed at specific intervals
 #[async_trait]
 pub trait Job: Send + Sync {
     /// Unique name of the job
     fn name(&self) -> &str;
 
-    /// Cron expression defining when the job should run
-    fn schedule(&self) -> &str;
+    /// When the job should run
+    fn schedule(&self) -> JobSchedule;
 
     /// Execute the job's business logic
     async fn execute(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
 }
 
-/// A job scheduler that manages cron-based recurring tasks
+/// Run history of one job, kept for [`JobScheduler::get_job_status`]
+#[derive(Debug, Clone, Default)]
+struct JobRuns {
+    running: bool,
+    last_run: Option<DateTime<Utc>>,
+    next_run: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    run_count: u64,
+}
+
+/// Outcome of one run of a job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobRunResult {
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Why [`JobScheduler::run_now`] did not run a job
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RunJobError {
+    #[error("no job named '{0}'")]
+    NotFound(String),
+    #[error("job '{0}' is already running")]
+    AlreadyRunning(String),
+}
+
+impl From<RunJobError> for AppError {
+    fn from(err: RunJobError) -> Self {
+        match err {
+            RunJobError::NotFound(_) => AppError::NotFound(err.to_string()),
+            RunJobError::AlreadyRunning(_) => AppError::JobAlreadyRunning(err.to_string()),
+        }
+    }
+}
+
+type JobRunsMap = Arc<Mutex<HashMap<String, JobRuns>>>;
+
+/// A job scheduler that manages recurring tasks. Clones share the same jobs.
+#[derive(Clone)]
 pub struct JobScheduler {
     jobs: Arc<Mutex<HashMap<String, Arc<dyn Job>>>>,
+    runs: JobRunsMap,
     active_handles: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
     shutdown_tx: tokio::sync::broadcast::Sender<()>,
 }
@@ -39,6 +127,7 @@ impl JobScheduler {
         let (shutdown_tx, _) = tokio::sync::broadcast::channel(1);
         Self {
             jobs: Arc::new(Mutex::new(HashMap::new())),
+            runs: Arc::new(Mutex::new(HashMap::new())),
             active_handles: Arc::new(Mutex::new(HashMap::new())),
             shutdown_tx,
         }
@@ -50,10 +139,7 @@ impl JobScheduler {
         job: Box<dyn Job>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let name = job.name().to_string();
-
-        // Validate the cron expression
-        Schedule::from_str(job.schedule())
-            .map_err(|e| format!("Invalid cron expression '{}': {}", job.schedule(), e))?;
+        job.schedule().validate()?;
 
         let mut jobs = self.jobs.lock().await;
         jobs.insert(name, Arc::from(job));
@@ -74,9 +160,9 @@ impl JobScheduler {
             let handle = tokio::spawn(Self::run_job_loop(
                 name_clone,
                 job_clone,
-                self.shutdown_tx.clone(),
                 shutdown_rx,
                 active_handles_clone,
+                Arc::clone(&self.runs),
             ));
 
             active_handles.lock().await.insert(name.clone(), handle);
@@ -113,20 +199,29 @@ impl JobScheduler {
     /// Get status information about all registered jobs
     pub async fn get_job_status(&self) -> HashMap<String, JobStatus> {
         let jobs = self.jobs.lock().await;
+        let runs = self.runs.lock().await;
         let active_handles = self.active_handles.lock().await;
         let mut status = HashMap::new();
 
         for (name, job) in jobs.iter() {
-            // Parse the schedule to get the next run time
-            let next_run = Self::get_next_run_time(job.schedule());
+            let schedule = job.schedule();
+            let run = runs.get(name).cloned().unwrap_or_default();
+            // Until the job loop has planned its next run, report the next
+            // time the schedule would fire
+            let next_run = run.next_run.or_else(|| schedule.next_after(Utc::now()));
 
             status.insert(
                 name.clone(),
                 JobStatus {
                     name: name.clone(),
-                    schedule: job.schedule().to_string(),
+                    schedule: schedule.to_string(),
                     next_run,
                     is_active: active_handles.contains_key(name),
+                    is_running: run.running,
+                    last_run: run.last_run,
+                    last_success: run.last_run.map(|_| run.last_error.is_none()),
+                    last_error: run.last_error,
+                    run_count: run.run_count,
                 },
             );
         }
@@ -134,31 +229,71 @@ impl JobScheduler {
         status
     }
 
+    /// Run the job called `name` now and wait for it to finish. Fails if no
+    /// such job is registered or a run of it is already in progress.
+    pub async fn run_now(&self, name: &str) -> Result<JobRunResult, RunJobError> {
+        let job = self
+            .jobs
+            .lock()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| RunJobError::NotFound(name.to_string()))?;
+        info!("Running job '{}' on demand", name);
+        Self::run_tracked(name, job.as_ref(), &self.runs)
+            .await
+            .ok_or_else(|| RunJobError::AlreadyRunning(name.to_string()))
+    }
+
+    /// Execute `job` and record the run, or return `None` without running it
+    /// if a run is already in progress
+    async fn run_tracked(name: &str, job: &dyn Job, runs: &JobRunsMap) -> Option<JobRunResult> {
+        {
+            let mut runs = runs.lock().await;
+            let run = runs.entry(name.to_string()).or_default();
+            if run.running {
+                return None;
+            }
+            run.running = true;
+        }
+
+        let started_at = Utc::now();
+        let error = job.execute().await.err().map(|e| e.to_string());
+        let finished_at = Utc::now();
+
+        let mut runs = runs.lock().await;
+        let run = runs.entry(name.to_string()).or_default();
+        run.running = false;
+        run.last_run = Some(finished_at);
+        run.last_error = error.clone();
+        run.run_count += 1;
+
+        Some(JobRunResult {
+            name: name.to_string(),
+            started_at,
+            finished_at,
+            success: error.is_none(),
+            error,
+        })
+    }
+
     /// Internal function that runs the job execution loop
     async fn run_job_loop(
         name: String,
         job: Arc<dyn Job>,
-        _shutdown_tx: tokio::sync::broadcast::Sender<()>,
         mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
         active_handles: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+        runs: JobRunsMap,
     ) {
-        info!("Starting job '{}' with schedule: {}", name, job.schedule());
-
-        let schedule = match Schedule::from_str(job.schedule()) {
-            Ok(schedule) => schedule,
-            Err(e) => {
-                error!("Failed to parse cron schedule for job '{}': {}", name, e);
-                return;
-            }
-        };
+        let schedule = job.schedule();
+        info!("Starting job '{}' with schedule: {}", name, schedule);
 
         loop {
             // Calculate next run time
             let now = Utc::now();
-            let next_run = schedule.after(&now).next();
-
-            let next_run_time = match next_run {
+            let next_run_time = match schedule.next_after(now) {
                 Some(next_time) => {
+                    runs.lock().await.entry(name.clone()).or_default().next_run = Some(next_time);
                     let duration = (next_time - now)
                         .to_std()
                         .unwrap_or_else(|_| std::time::Duration::from_secs(1));
@@ -183,45 +318,46 @@ impl JobScheduler {
             };
 
             // Execute the job
-            match job.execute().await {
-                Ok(()) => {
+            runs.lock().await.entry(name.clone()).or_default().next_run = None;
+            match Self::run_tracked(&name, job.as_ref(), &runs).await {
+                Some(result) if result.success => {
                     info!(
                         "Job '{}' executed successfully at {}",
                         name,
                         next_run_time.format("%Y-%m-%d %H:%M:%S")
                     );
                 }
-                Err(e) => {
+                Some(result) => {
                     error!(
                         "Job '{}' failed at {}: {}",
                         name,
                         next_run_time.format("%Y-%m-%d %H:%M:%S"),
-                        e
+                        result.error.unwrap_or_default()
                     );
                 }
+                None => {
+                    info!("Job '{}' skipped: a run is already in progress", name);
+                }
             }
         }
     }
-
-    /// Helper function to get the next run time for a schedule
-    fn get_next_run_time(schedule_expr: &str) -> Option<DateTime<Utc>> {
-        match Schedule::from_str(schedule_expr) {
-            Ok(schedule) => {
-                let now = Utc::now();
-                schedule.after(&now).next()
-            }
-            Err(_) => None,
-        }
-    }
 }
 
 /// Status information for a scheduled job
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct JobStatus {
     pub name: String,
     pub schedule: String,
     pub next_run: Option<DateTime<Utc>>,
     pub is_active: bool,
+    /// A run, scheduled or manual, is in progress
+    pub is_running: bool,
+    /// When the last run finished
+    pub last_run: Option<DateTime<Utc>>,
+    /// Whether the last run succeeded; absent until the job has run
+    pub last_success: Option<bool>,
+    pub last_error: Option<String>,
+    pub run_count: u64,
 }
 
 #[cfg(test)]
@@ -231,14 +367,14 @@ mod tests {
     #[derive(Clone)]
     struct TestJob {
         name: String,
-        schedule: String,
+        schedule: JobSchedule,
     }
 
     impl TestJob {
-        fn new(name: &str, schedule: &str) -> Self {
+        fn new(name: &str, schedule: JobSchedule) -> Self {
             Self {
                 name: name.to_string(),
-                schedule: schedule.to_string(),
+                schedule,
             }
         }
     }
@@ -249,8 +385,8 @@ mod tests {
             &self.name
         }
 
-        fn schedule(&self) -> &str {
-            &self.schedule
+        fn schedule(&self) -> JobSchedule {
+            self.schedule.clone()
         }
 
         async fn execute(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -263,9 +399,88 @@ mod tests {
     async fn test_scheduler_basic() {
         let scheduler = JobScheduler::new();
 
-        let test_job = TestJob::new("test_job", "*/1 * * * * *"); // Every second
+        let test_job = TestJob::new("test_job", JobSchedule::Cron("*/1 * * * * *".into())); // Every second
         scheduler.register_job(Box::new(test_job)).await.unwrap();
 
         assert_eq!(scheduler.jobs.lock().await.len(), 1);
+
+        let invalid = TestJob::new("invalid", JobSchedule::Cron("not cron".into()));
+        assert!(scheduler.register_job(Box::new(invalid)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registered_job_runs_and_records_last_run() {
+        let scheduler = JobScheduler::new();
+        let job = TestJob::new("fast", JobSchedule::Every(Duration::from_millis(50)));
+        scheduler.register_job(Box::new(job)).await.unwrap();
+
+        let before = scheduler.get_job_status().await["fast"].clone();
+        assert_eq!(before.run_count, 0);
+        assert!(before.last_run.is_none());
+        assert!(before.last_success.is_none());
+        assert!(before.next_run.is_some());
+
+        let started = Utc::now();
+        scheduler.start().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let status = scheduler.get_job_status().await["fast"].clone();
+        assert!(status.is_active);
+        assert!(status.run_count >= 1);
+        assert!(status.last_run.unwrap() > started);
+        assert_eq!(status.last_success, Some(true));
+        assert_eq!(status.schedule, "every 0.05s");
+
+        scheduler.stop().await.unwrap();
+        assert!(!scheduler.get_job_status().await["fast"].is_active);
+    }
+
+    /// Takes a while, so a second run can be attempted while it is in progress
+    struct SlowJob;
+
+    #[async_trait::async_trait]
+    impl Job for SlowJob {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        fn schedule(&self) -> JobSchedule {
+            JobSchedule::Every(Duration::from_secs(3600))
+        }
+
+        async fn execute(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Err("slow job failed".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_now_rejects_unknown_and_concurrent_runs() {
+        let scheduler = JobScheduler::new();
+        scheduler.register_job(Box::new(SlowJob)).await.unwrap();
+
+        assert_eq!(
+            scheduler.run_now("missing").await.unwrap_err(),
+            RunJobError::NotFound("missing".to_string())
+        );
+
+        let first = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.run_now("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(scheduler.get_job_status().await["slow"].is_running);
+        assert_eq!(
+            scheduler.run_now("slow").await.unwrap_err(),
+            RunJobError::AlreadyRunning("slow".to_string())
+        );
+
+        let result = first.await.unwrap().unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("slow job failed"));
+        let status = scheduler.get_job_status().await["slow"].clone();
+        assert_eq!(status.run_count, 1);
+        assert_eq!(status.last_success, Some(false));
+        assert!(!status.is_running);
     }
 }
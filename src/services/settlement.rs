@@ -1,32 +1,201 @@
-use crate::db::models::Settlement;
+use crate::db::models::{Settlement, Transaction};
 use crate::db::queries;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 use crate::error::AppError;
+use crate::metrics::MetricsRegistry;
 use bigdecimal::BigDecimal;
+use std::time::Instant;
+
+/// Histogram of `tx_count` per settlement created
+pub const BATCH_TRANSACTIONS_METRIC: &str = "settlement_batch_transactions";
+/// Histogram of how long a full `run_settlements` pass takes
+pub const RUN_DURATION_METRIC: &str = "settlement_run_duration_seconds";
+/// Settlements created, labelled by `asset_code`
+pub const CREATED_METRIC: &str = "settlements_created_total";
+
+/// Transactions swept into one settlement unless `SETTLEMENT_MAX_BATCH_SIZE`
+/// says otherwise; a larger backlog is split across several settlements
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 10_000;
+
+const BATCH_TRANSACTIONS_BUCKETS: &[f64] = &[
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0,
+];
+const RUN_DURATION_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Expose the settlement histograms before the first run. The created
+/// counter appears per asset once that asset is settled.
+pub fn register_metrics(registry: &MetricsRegistry) {
+    registry.register_histogram(BATCH_TRANSACTIONS_METRIC, BATCH_TRANSACTIONS_BUCKETS);
+    registry.register_histogram(RUN_DURATION_METRIC, RUN_DURATION_BUCKETS);
+}
+
+/// Where a settlement is in its lifecycle. The `settlements_status_check`
+/// constraint enforces the same values in the database.
+///
+/// ```text
+/// pending   -> completed | voided
+/// completed -> paid | voided
+/// paid      -> (terminal)
+/// voided    -> (terminal)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementStatus {
+    Pending,
+    Completed,
+    Paid,
+    Voided,
+}
+
+impl SettlementStatus {
+    pub const ALL: [SettlementStatus; 4] = [
+        SettlementStatus::Pending,
+        SettlementStatus::Completed,
+        SettlementStatus::Paid,
+        SettlementStatus::Voided,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SettlementStatus::Pending => "pending",
+            SettlementStatus::Completed => "completed",
+            SettlementStatus::Paid => "paid",
+            SettlementStatus::Voided => "voided",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == status)
+    }
+
+    pub fn can_transition_to(self, to: SettlementStatus) -> bool {
+        use SettlementStatus::*;
+        matches!(
+            (self, to),
+            (Pending, Completed) | (Pending, Voided) | (Completed, Paid) | (Completed, Voided)
+        )
+    }
+
+    /// `to` if the move is allowed, otherwise
+    /// [`AppError::InvalidSettlementTransition`]
+    pub fn transition(self, to: SettlementStatus) -> Result<SettlementStatus, AppError> {
+        if self.can_transition_to(to) {
+            Ok(to)
+        } else {
+            Err(AppError::InvalidSettlementTransition(format!(
+                "cannot move settlement from '{}' to '{}'",
+                self, to
+            )))
+        }
+    }
+}
+
+impl std::fmt::Display for SettlementStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a settlement run would create, computed without writing anything
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct SettlementPreview {
+    /// Transactions last updated at or before this instant are included
+    pub period_end: DateTime<Utc>,
+    pub tx_count: usize,
+    pub assets: Vec<AssetSettlementPreview>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct AssetSettlementPreview {
+    pub asset_code: String,
+    pub tx_count: usize,
+    #[schema(value_type = String)]
+    pub total_amount: BigDecimal,
+    pub transaction_ids: Vec<Uuid>,
+    /// One entry per settlement the run would create, split by the
+    /// configured maximum batch size
+    pub batches: Vec<SettlementBatchPreview>,
+}
+
+/// A settlement a run would create
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct SettlementBatchPreview {
+    pub tx_count: usize,
+    #[schema(value_type = String)]
+    pub total_amount: BigDecimal,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+}
+
+/// A settlement together with the transactions it paid out, as handed to partners
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct SettlementReceipt {
+    pub settlement_id: Uuid,
+    pub asset_code: String,
+    pub status: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub tx_count: i32,
+    #[schema(value_type = String)]
+    pub total_amount: BigDecimal,
+    pub transactions: Vec<ReceiptLine>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ReceiptLine {
+    pub transaction_id: Uuid,
+    pub stellar_account: String,
+    #[schema(value_type = String)]
+    pub amount: BigDecimal,
+    pub anchor_transaction_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
 
 pub struct SettlementService {
     pool: PgPool,
+    max_batch_size: usize,
 }
 
 impl SettlementService {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        }
+    }
+
+    /// Put at most `max` transactions in one settlement
+    pub fn with_max_batch_size(mut self, max: usize) -> Self {
+        self.max_batch_size = max.max(1);
+        self
     }
 
     /// Run settlement for all assets with completed, unsettled transactions.
     pub async fn run_settlements(&self) -> Result<Vec<Settlement>, AppError> {
+        let started = Instant::now();
+        let result = self.settle_all_assets().await;
+        crate::metrics::registry()
+            .observe_histogram(RUN_DURATION_METRIC, started.elapsed().as_secs_f64());
+        result
+    }
+
+    async fn settle_all_assets(&self) -> Result<Vec<Settlement>, AppError> {
         let assets = queries::get_unique_assets_to_settle(&self.pool)
             .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            .map_err(AppError::query_failed)?;
 
         let mut results = Vec::new();
         for asset in assets {
-            match self.settle_asset(&asset).await {
-                Ok(Some(settlement)) => results.push(settlement),
-                Ok(None) => tracing::info!("No transactions to settle for asset {}", asset),
+            match self.settle_asset_batches(&asset).await {
+                Ok(settlements) if settlements.is_empty() => {
+                    tracing::info!("No transactions to settle for asset {}", asset)
+                }
+                Ok(settlements) => results.extend(settlements),
                 Err(e) => tracing::error!("Failed to settle asset {}: {:?}", asset, e),
             }
         }
@@ -34,29 +203,214 @@ impl SettlementService {
         Ok(results)
     }
 
-    /// Settle transactions for a specific asset.
+    /// Settle every pending transaction for `asset_code`, one settlement per
+    /// batch of at most the configured size. Batches already committed are
+    /// kept if a later one fails.
+    pub async fn settle_asset_batches(
+        &self,
+        asset_code: &str,
+    ) -> Result<Vec<Settlement>, AppError> {
+        let mut settlements = Vec::new();
+        while let Some(settlement) = self.settle_asset(asset_code).await? {
+            let full = settlement.tx_count as usize >= self.max_batch_size;
+            settlements.push(settlement);
+            if !full {
+                break;
+            }
+        }
+        Ok(settlements)
+    }
+
+    /// Select the transactions a settlement would pick up, per asset, and
+    /// split them into batches the way [`Self::settle_asset_batches`] would,
+    /// without locking or modifying them. With a `partner_id` only that
+    /// partner's transactions are listed and totalled, like a receipt;
+    /// batches holding none of them are left out.
+    pub async fn preview(
+        &self,
+        asset_code: Option<&str>,
+        period_end: DateTime<Utc>,
+        partner_id: Option<Uuid>,
+    ) -> Result<SettlementPreview, AppError> {
+        let unsettled =
+            queries::preview_unsettled_transactions(&self.pool, asset_code, period_end).await?;
+
+        let mut by_asset: BTreeMap<&str, Vec<&Transaction>> = BTreeMap::new();
+        for tx in &unsettled {
+            by_asset.entry(&tx.asset_code).or_default().push(tx);
+        }
+
+        let visible = |tx: &&Transaction| partner_id.is_none() || tx.partner_id == partner_id;
+        let mut tx_count = 0;
+        let mut assets = Vec::new();
+        for (asset_code, txs) in by_asset {
+            let mut preview = AssetSettlementPreview {
+                asset_code: asset_code.to_string(),
+                tx_count: 0,
+                total_amount: BigDecimal::from(0),
+                transaction_ids: Vec::new(),
+                batches: Vec::new(),
+            };
+            for batch in split_batches(&txs, self.max_batch_size) {
+                let mine: Vec<&Transaction> = batch.iter().copied().filter(visible).collect();
+                if mine.is_empty() {
+                    continue;
+                }
+                let total = mine
+                    .iter()
+                    .fold(BigDecimal::from(0), |total, tx| total + &tx.amount);
+                preview.tx_count += mine.len();
+                preview.total_amount += &total;
+                preview.transaction_ids.extend(mine.iter().map(|tx| tx.id));
+                preview.batches.push(SettlementBatchPreview {
+                    tx_count: mine.len(),
+                    total_amount: total,
+                    period_start: batch
+                        .iter()
+                        .map(|tx| tx.created_at)
+                        .min()
+                        .unwrap_or(period_end),
+                    period_end: batch
+                        .iter()
+                        .map(|tx| tx.updated_at)
+                        .max()
+                        .unwrap_or(period_end),
+                });
+            }
+            if !preview.batches.is_empty() {
+                tx_count += preview.tx_count;
+                assets.push(preview);
+            }
+        }
+
+        Ok(SettlementPreview {
+            period_end,
+            tx_count,
+            assets,
+        })
+    }
+
+    /// Assemble the receipt for settlement `id`. With a `partner_id` only
+    /// that partner's transactions are listed and totalled, and a settlement
+    /// holding none of them is not found.
+    pub async fn receipt(
+        &self,
+        id: Uuid,
+        partner_id: Option<Uuid>,
+    ) -> Result<SettlementReceipt, AppError> {
+        let not_found = || AppError::NotFound(format!("Settlement {} not found", id));
+        let settlement = queries::get_settlement_optional(&self.pool, id)
+            .await?
+            .ok_or_else(not_found)?;
+        let mut transactions = queries::list_settlement_transactions(&self.pool, id).await?;
+
+        let (tx_count, total_amount) = match partner_id {
+            None => (settlement.tx_count, settlement.total_amount),
+            Some(partner_id) => {
+                transactions.retain(|tx| tx.partner_id == Some(partner_id));
+                if transactions.is_empty() {
+                    return Err(not_found());
+                }
+                let total = transactions
+                    .iter()
+                    .fold(BigDecimal::from(0), |total, tx| total + &tx.amount);
+                (transactions.len() as i32, total)
+            }
+        };
+
+        Ok(SettlementReceipt {
+            settlement_id: settlement.id,
+            asset_code: settlement.asset_code,
+            status: settlement.status,
+            period_start: settlement.period_start,
+            period_end: settlement.period_end,
+            tx_count,
+            total_amount,
+            transactions: transactions
+                .into_iter()
+                .map(|tx| ReceiptLine {
+                    transaction_id: tx.id,
+                    stellar_account: tx.stellar_account,
+                    amount: tx.amount,
+                    anchor_transaction_id: tx.anchor_transaction_id,
+                    created_at: tx.created_at,
+                })
+                .collect(),
+        })
+    }
+
+    /// Move settlement `id` to `to`, rejecting moves the lifecycle does not
+    /// allow. A concurrent change to the same settlement makes this call fail
+    /// rather than overwrite it.
+    pub async fn update_status(
+        &self,
+        id: Uuid,
+        to: SettlementStatus,
+    ) -> Result<Settlement, AppError> {
+        let settlement = queries::get_settlement_optional(&self.pool, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Settlement {} not found", id)))?;
+        let from = SettlementStatus::parse(&settlement.status).ok_or_else(|| {
+            AppError::Internal(format!(
+                "settlement {} has unknown status '{}'",
+                id, settlement.status
+            ))
+        })?;
+        from.transition(to)?;
+
+        let updated = queries::update_settlement_status(&self.pool, id, from.as_str(), to.as_str())
+            .await?
+            .ok_or_else(|| {
+                AppError::InvalidSettlementTransition(format!(
+                    "settlement {} is no longer '{}'",
+                    id, from
+                ))
+            })?;
+        tracing::info!(settlement_id = %id, from = %from, to = %to, "Settlement status updated");
+        Ok(updated)
+    }
+
+    /// Settle the oldest batch of up to the configured size of transactions
+    /// for a specific asset. A batch cut part way through transactions created
+    /// at the same instant takes the rest of them too, so consecutive batches
+    /// never share a period.
     pub async fn settle_asset(&self, asset_code: &str) -> Result<Option<Settlement>, AppError> {
-        let mut tx = self
-            .pool
-            .begin()
-            .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        let mut tx = self.pool.begin().await.map_err(AppError::query_failed)?;
 
         // We settle everything up to "now"
         let end_time = Utc::now();
 
-        // Fetch candidate transactions with FOR UPDATE lock
-        let unsettled = queries::get_unsettled_transactions(&mut tx, asset_code, end_time)
-            .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        // Fetch candidate transactions with FOR UPDATE lock. Transactions
+        // already in a settlement are never candidates, so a period that is
+        // fully settled yields nothing.
+        let mut unsettled = queries::get_unsettled_transactions(
+            &mut tx,
+            asset_code,
+            end_time,
+            self.max_batch_size as i64,
+        )
+        .await
+        .map_err(AppError::query_failed)?;
 
         if unsettled.is_empty() {
-            tx.rollback()
-                .await
-                .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            tx.rollback().await.map_err(AppError::query_failed)?;
             return Ok(None);
         }
 
+        if unsettled.len() >= self.max_batch_size {
+            let last = &unsettled[unsettled.len() - 1];
+            let ties = queries::get_unsettled_transactions_created_at(
+                &mut tx,
+                asset_code,
+                end_time,
+                last.created_at,
+                last.id,
+            )
+            .await
+            .map_err(AppError::query_failed)?;
+            unsettled.extend(ties);
+        }
+
         let tx_count = unsettled.len() as i32;
         let total_amount: BigDecimal = unsettled
             .iter()
@@ -82,25 +436,27 @@ impl SettlementService {
             tx_count,
             period_start,
             period_end,
-            status: "completed".to_string(),
+            status: SettlementStatus::Completed.as_str().to_string(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
 
-        // Save settlement record
-        let saved_settlement = queries::insert_settlement(&mut tx, &settlement)
-            .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        // Save settlement record. The candidates are locked and unsettled, so
+        // a live settlement for the same period means the data is
+        // inconsistent; fail rather than skip them silently.
+        let saved_settlement = queries::insert_settlement(&mut tx, &settlement).await?;
 
         // Link transactions to settlement
         let tx_ids: Vec<Uuid> = unsettled.iter().map(|t| t.id).collect();
         queries::update_transactions_settlement(&mut tx, &tx_ids, saved_settlement.id)
             .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+            .map_err(AppError::query_failed)?;
 
-        tx.commit()
-            .await
-            .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+        tx.commit().await.map_err(AppError::query_failed)?;
+
+        let registry = crate::metrics::registry();
+        registry.observe_histogram(BATCH_TRANSACTIONS_METRIC, f64::from(tx_count));
+        registry.increment_counter(CREATED_METRIC, &[("asset_code", asset_code)]);
 
         tracing::info!(
             "Settled {} transactions for asset {} (ID: {})",
@@ -112,3 +468,26 @@ impl SettlementService {
         Ok(Some(saved_settlement))
     }
 }
+
+/// Split `txs`, ordered by `created_at` then id, into the batches
+/// [`SettlementService::settle_asset`] would take one run at a time: at most
+/// `max_batch_size` each, except that a full batch also takes the rest of the
+/// transactions created at the same instant as its last one.
+fn split_batches<'a>(
+    txs: &'a [&'a Transaction],
+    max_batch_size: usize,
+) -> Vec<&'a [&'a Transaction]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    while start < txs.len() {
+        let mut end = (start + max_batch_size).min(txs.len());
+        if end - start >= max_batch_size {
+            while end < txs.len() && txs[end].created_at == txs[end - 1].created_at {
+                end += 1;
+            }
+        }
+        batches.push(&txs[start..end]);
+        start = end;
+    }
+    batches
+}
@@ -0,0 +1,410 @@
+use crate::db::audit::{AuditLog, ENTITY_TRANSACTION};
+use crate::db::models::Transaction;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_PROCESSING: &str = "processing";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_REFUNDED: &str = "refunded";
+pub const STATUS_RECONCILIATION_FAILED: &str = "reconciliation_failed";
+/// Moved to the dead letter queue after exhausting retries
+pub const STATUS_DLQ: &str = "dlq";
+
+/// Every status a transaction can be in. The `transactions_status_check`
+/// constraint enforces the same list in the database, so adding a status
+/// takes a migration as well.
+pub const ALL_STATUSES: &[&str] = &[
+    STATUS_PENDING,
+    STATUS_PROCESSING,
+    STATUS_COMPLETED,
+    STATUS_FAILED,
+    STATUS_REFUNDED,
+    STATUS_RECONCILIATION_FAILED,
+    STATUS_DLQ,
+];
+
+/// Name of the CHECK constraint restricting `transactions.status`
+pub const STATUS_CHECK_CONSTRAINT: &str = "transactions_status_check";
+
+/// Reject statuses outside [`ALL_STATUSES`] before they reach the database
+pub fn validate_status(status: &str) -> Result<(), AppError> {
+    if ALL_STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidStatusTransition(format!(
+            "unknown status '{}': expected one of {}",
+            status,
+            ALL_STATUSES.join(", ")
+        )))
+    }
+}
+
+/// Map a violation of [`STATUS_CHECK_CONSTRAINT`] to
+/// [`AppError::InvalidStatusTransition`]; other errors stay database errors.
+pub fn map_status_violation(err: sqlx::Error) -> AppError {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.constraint() == Some(STATUS_CHECK_CONSTRAINT) => {
+            AppError::InvalidStatusTransition(format!(
+                "status rejected by the database: {}",
+                db_err.message()
+            ))
+        }
+        _ => AppError::Database(err),
+    }
+}
+
+/// Most transactions accepted by one [`bulk_transition_status`] call
+pub const MAX_BULK_STATUS_IDS: usize = 500;
+
+/// Returns true if a transaction may move from `from` to `to`.
+///
+/// ```text
+/// pending    -> processing | completed | failed | dlq
+/// processing -> completed | failed | dlq
+/// failed     -> pending (retry)
+/// dlq        -> pending (requeue)
+/// completed  -> refunded | reconciliation_failed
+/// refunded   -> (terminal)
+/// reconciliation_failed -> completed (manual resolution)
+/// ```
+pub fn is_allowed_transition(from: &str, to: &str) -> bool {
+    matches!(
+        (from, to),
+        (STATUS_PENDING, STATUS_PROCESSING)
+            | (STATUS_PENDING, STATUS_COMPLETED)
+            | (STATUS_PENDING, STATUS_FAILED)
+            | (STATUS_PROCESSING, STATUS_COMPLETED)
+            | (STATUS_PROCESSING, STATUS_FAILED)
+            | (STATUS_PENDING, STATUS_DLQ)
+            | (STATUS_PROCESSING, STATUS_DLQ)
+            | (STATUS_FAILED, STATUS_PENDING)
+            | (STATUS_DLQ, STATUS_PENDING)
+            | (STATUS_COMPLETED, STATUS_REFUNDED)
+            | (STATUS_COMPLETED, STATUS_RECONCILIATION_FAILED)
+            | (STATUS_RECONCILIATION_FAILED, STATUS_COMPLETED)
+    )
+}
+
+/// Move a transaction from `from_expected` to `to`.
+///
+/// The update only applies while the row is still in `from_expected`, so two
+/// concurrent callers cannot both win; the loser gets `InvalidStatusTransition`.
+pub async fn transition_status(
+    pool: &PgPool,
+    id: Uuid,
+    from_expected: &str,
+    to: &str,
+) -> Result<Transaction, AppError> {
+    transition_status_with_metadata(pool, id, from_expected, to, None).await
+}
+
+/// Refund a transaction, optionally recording `reason` under
+/// `metadata.refund_reason`. Refunding twice is a conflict.
+pub async fn refund_transaction(
+    pool: &PgPool,
+    id: Uuid,
+    reason: Option<String>,
+) -> Result<Transaction, AppError> {
+    let current: Option<String> =
+        sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(AppError::query_failed)?;
+
+    let current =
+        current.ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?;
+    if current == STATUS_REFUNDED {
+        return Err(AppError::TransactionAlreadyProcessed(format!(
+            "transaction {} is already refunded",
+            id
+        )));
+    }
+
+    let patch = reason.map(|reason| json!({ "refund_reason": reason }));
+    transition_status_with_metadata(pool, id, &current, STATUS_REFUNDED, patch).await
+}
+
+/// Same as [`transition_status`], additionally merging `metadata_patch`
+/// into the transaction's existing metadata.
+pub async fn transition_status_with_metadata(
+    pool: &PgPool,
+    id: Uuid,
+    from_expected: &str,
+    to: &str,
+    metadata_patch: Option<Value>,
+) -> Result<Transaction, AppError> {
+    if !is_allowed_transition(from_expected, to) {
+        return Err(AppError::InvalidStatusTransition(format!(
+            "cannot move transaction {} from '{}' to '{}'",
+            id, from_expected, to
+        )));
+    }
+
+    let mut db_tx = pool.begin().await.map_err(AppError::query_failed)?;
+
+    let updated = sqlx::query_as::<_, Transaction>(
+        r#"
+        UPDATE transactions
+        SET status = $3,
+            metadata = CASE WHEN $4::jsonb IS NULL THEN metadata
+                            ELSE COALESCE(metadata, '{}'::jsonb) || $4::jsonb END,
+            updated_at = NOW()
+        WHERE id = $1 AND status = $2
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(from_expected)
+    .bind(to)
+    .bind(metadata_patch)
+    .fetch_optional(&mut *db_tx)
+    .await
+    .map_err(AppError::query_failed)?;
+
+    let updated = match updated {
+        Some(tx) => tx,
+        None => {
+            db_tx.rollback().await.map_err(AppError::query_failed)?;
+            return Err(stale_transition_error(pool, id, from_expected).await);
+        }
+    };
+
+    AuditLog::log_status_change(
+        &mut db_tx,
+        id,
+        ENTITY_TRANSACTION,
+        from_expected,
+        to,
+        "system",
+    )
+    .await
+    .map_err(AppError::query_failed)?;
+
+    db_tx.commit().await.map_err(AppError::query_failed)?;
+
+    Ok(updated)
+}
+
+/// Audit action recorded by [`force_complete`]
+pub const ACTION_FORCE_COMPLETE: &str = "force_complete";
+
+/// Mark a `pending` transaction completed by hand, recording a
+/// [`ACTION_FORCE_COMPLETE`] audit entry for `actor`. Transactions in any
+/// other status, including completed and settled ones, are refused with
+/// `InvalidStatusTransition`.
+pub async fn force_complete(pool: &PgPool, id: Uuid, actor: &str) -> Result<Transaction, AppError> {
+    let mut db_tx = pool.begin().await.map_err(AppError::query_failed)?;
+
+    // Lock the row so its status cannot change between the check and the update
+    let current: Option<(String, Option<Uuid>)> =
+        sqlx::query_as("SELECT status, settlement_id FROM transactions WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *db_tx)
+            .await
+            .map_err(AppError::query_failed)?;
+
+    let (status, settlement_id) =
+        current.ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?;
+    if let Some(settlement_id) = settlement_id {
+        return Err(AppError::InvalidStatusTransition(format!(
+            "transaction {} is already settled in {}",
+            id, settlement_id
+        )));
+    }
+    if status != STATUS_PENDING {
+        return Err(AppError::InvalidStatusTransition(format!(
+            "transaction {} is '{}'; only pending transactions can be force-completed",
+            id, status
+        )));
+    }
+
+    let updated = sqlx::query_as::<_, Transaction>(
+        "UPDATE transactions SET status = $2, updated_at = NOW() WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(STATUS_COMPLETED)
+    .fetch_one(&mut *db_tx)
+    .await
+    .map_err(AppError::query_failed)?;
+
+    AuditLog::log(
+        &mut db_tx,
+        id,
+        ENTITY_TRANSACTION,
+        ACTION_FORCE_COMPLETE,
+        Some(json!({ "status": STATUS_PENDING })),
+        Some(json!({ "status": STATUS_COMPLETED })),
+        actor,
+    )
+    .await
+    .map_err(AppError::query_failed)?;
+
+    db_tx.commit().await.map_err(AppError::query_failed)?;
+
+    Ok(updated)
+}
+
+/// Outcome of one id in a [`bulk_transition_status`] call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusUpdateResult {
+    pub id: Uuid,
+    pub updated: bool,
+    /// Status before the update; `None` when the transaction does not exist
+    pub previous_status: Option<String>,
+    /// Status after the call, unchanged when the update was refused
+    pub status: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Move every transaction in `ids` to `to` inside one database transaction.
+///
+/// Each id is checked against [`is_allowed_transition`] on its own; ids that
+/// are missing or cannot make the move are reported and left untouched while
+/// the rest are updated and audited. Duplicate ids are processed once.
+/// Moves into or out of `dlq` are refused, since they must go together with
+/// the `transaction_dlq` entry.
+pub async fn bulk_transition_status(
+    pool: &PgPool,
+    ids: &[Uuid],
+    to: &str,
+    actor: &str,
+) -> Result<Vec<StatusUpdateResult>, AppError> {
+    if !ALL_STATUSES.contains(&to) {
+        return Err(AppError::BadRequest(format!(
+            "unknown status '{}': expected one of {}",
+            to,
+            ALL_STATUSES.join(", ")
+        )));
+    }
+    if ids.is_empty() || ids.len() > MAX_BULK_STATUS_IDS {
+        return Err(AppError::BadRequest(format!(
+            "ids must contain between 1 and {} transaction ids",
+            MAX_BULK_STATUS_IDS
+        )));
+    }
+
+    let mut db_tx = pool.begin().await.map_err(AppError::query_failed)?;
+
+    // Lock the rows so their status cannot change between the check and the update
+    let current: HashMap<Uuid, String> = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT id, status FROM transactions WHERE id = ANY($1) FOR UPDATE",
+    )
+    .bind(ids)
+    .fetch_all(&mut *db_tx)
+    .await
+    .map_err(AppError::query_failed)?
+    .into_iter()
+    .collect();
+
+    let mut results = Vec::with_capacity(ids.len());
+    let mut seen = std::collections::HashSet::new();
+    for &id in ids {
+        if !seen.insert(id) {
+            continue;
+        }
+        let Some(from) = current.get(&id) else {
+            results.push(StatusUpdateResult {
+                id,
+                updated: false,
+                previous_status: None,
+                status: None,
+                error: Some(format!("Transaction {} not found", id)),
+            });
+            continue;
+        };
+        if from == STATUS_DLQ || to == STATUS_DLQ || !is_allowed_transition(from, to) {
+            results.push(StatusUpdateResult {
+                id,
+                updated: false,
+                previous_status: Some(from.clone()),
+                status: Some(from.clone()),
+                error: Some(format!("cannot move from '{}' to '{}'", from, to)),
+            });
+            continue;
+        }
+
+        sqlx::query("UPDATE transactions SET status = $2, updated_at = NOW() WHERE id = $1")
+            .bind(id)
+            .bind(to)
+            .execute(&mut *db_tx)
+            .await
+            .map_err(AppError::query_failed)?;
+        AuditLog::log_status_change(&mut db_tx, id, ENTITY_TRANSACTION, from, to, actor)
+            .await
+            .map_err(AppError::query_failed)?;
+
+        results.push(StatusUpdateResult {
+            id,
+            updated: true,
+            previous_status: Some(from.clone()),
+            status: Some(to.to_string()),
+            error: None,
+        });
+    }
+
+    db_tx.commit().await.map_err(AppError::query_failed)?;
+
+    Ok(results)
+}
+
+/// Explain why a guarded update matched no row: either the transaction does not
+/// exist or its status changed since the caller read it.
+async fn stale_transition_error(pool: &PgPool, id: Uuid, from_expected: &str) -> AppError {
+    let current: Result<Option<String>, sqlx::Error> =
+        sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await;
+
+    match current {
+        Ok(Some(status)) => AppError::InvalidStatusTransition(format!(
+            "transaction {} is '{}', expected '{}'",
+            id, status, from_expected
+        )),
+        Ok(None) => AppError::NotFound(format!("Transaction {} not found", id)),
+        Err(e) => AppError::query_failed(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_transitions() {
+        assert!(is_allowed_transition(STATUS_PENDING, STATUS_COMPLETED));
+        assert!(is_allowed_transition(STATUS_PROCESSING, STATUS_FAILED));
+        assert!(is_allowed_transition(STATUS_FAILED, STATUS_PENDING));
+        assert!(is_allowed_transition(STATUS_COMPLETED, STATUS_REFUNDED));
+        assert!(is_allowed_transition(STATUS_PROCESSING, STATUS_DLQ));
+        assert!(is_allowed_transition(STATUS_DLQ, STATUS_PENDING));
+    }
+
+    #[test]
+    fn test_rejected_transitions() {
+        assert!(!is_allowed_transition(STATUS_REFUNDED, STATUS_COMPLETED));
+        assert!(!is_allowed_transition(STATUS_COMPLETED, STATUS_COMPLETED));
+        assert!(!is_allowed_transition(STATUS_COMPLETED, STATUS_PENDING));
+        assert!(!is_allowed_transition(STATUS_PENDING, "bogus"));
+        assert!(!is_allowed_transition(STATUS_COMPLETED, STATUS_DLQ));
+        assert!(!is_allowed_transition(STATUS_REFUNDED, STATUS_DLQ));
+    }
+
+    #[test]
+    fn test_validate_status() {
+        for status in ALL_STATUSES {
+            assert!(validate_status(status).is_ok());
+        }
+        assert!(matches!(
+            validate_status("compleeted"),
+            Err(AppError::InvalidStatusTransition(_))
+        ));
+    }
+}
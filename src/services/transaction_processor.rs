@@ -1,42 +1,617 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::audit::{AuditLog, ENTITY_TRANSACTION};
+use crate::error::AppError;
+use crate::services::transaction::{
+    self as transaction_service, STATUS_COMPLETED, STATUS_DLQ, STATUS_PENDING,
+};
+
+/// Attempts made for a transient failure before it is sent to the DLQ
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled on each further attempt
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// DLQ entries requeued per database transaction by [`TransactionProcessor::requeue_dlq_matching`]
+pub const REQUEUE_BATCH_SIZE: usize = 100;
+
+/// Selects DLQ entries for a bulk requeue. Criteria are combined with AND.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct DlqFilter {
+    pub ids: Option<Vec<Uuid>>,
+    pub asset_code: Option<String>,
+    /// Case-insensitive substring of `error_reason`; `%` and `_` match
+    /// themselves rather than acting as wildcards
+    pub error_reason: Option<String>,
+    pub error_code: Option<DlqErrorCode>,
+    /// Only entries moved to the DLQ before this instant
+    pub before: Option<DateTime<Utc>>,
+}
+
+impl DlqFilter {
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_none()
+            && self.asset_code.is_none()
+            && self.error_reason.is_none()
+            && self.error_code.is_none()
+            && self.before.is_none()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RequeueSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Requested ids that were missing or could not be requeued
+    pub failed_ids: Vec<Uuid>,
+}
+
+/// Why processing a transaction failed, and whether trying again can help
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessingError {
+    /// Timeouts, dropped connections, serialization conflicts: worth retrying
+    #[error("transient processing error: {0}")]
+    Transient(String),
+    /// Bad data or a missing transaction: retrying gives the same result
+    #[error("permanent processing error: {0}")]
+    Permanent(String),
+    /// The transaction to process does not exist
+    #[error("transaction not found: {0}")]
+    NotFound(String),
+    /// The database rejected the data, e.g. a constraint or type violation
+    #[error("invalid transaction data: {0}")]
+    InvalidData(String),
+    /// The transaction's status does not allow the change, e.g. it was
+    /// refunded meanwhile. Nothing was changed and it is not sent to the DLQ.
+    #[error("refused: {0}")]
+    Refused(String),
+}
+
+impl ProcessingError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ProcessingError::Transient(_))
+    }
+
+    pub fn reason(&self) -> &str {
+        match self {
+            ProcessingError::Transient(reason)
+            | ProcessingError::Permanent(reason)
+            | ProcessingError::NotFound(reason)
+            | ProcessingError::InvalidData(reason)
+            | ProcessingError::Refused(reason) => reason,
+        }
+    }
+
+    /// Code recorded in `transaction_dlq.error_code` when this error sends a
+    /// transaction to the DLQ
+    pub fn dlq_code(&self) -> DlqErrorCode {
+        match self {
+            ProcessingError::Transient(_) => DlqErrorCode::RetriesExhausted,
+            ProcessingError::Permanent(_) | ProcessingError::Refused(_) => {
+                DlqErrorCode::PermanentFailure
+            }
+            ProcessingError::NotFound(_) => DlqErrorCode::TransactionNotFound,
+            ProcessingError::InvalidData(_) => DlqErrorCode::InvalidData,
+        }
+    }
+}
+
+impl From<AppError> for ProcessingError {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::Database(e) => e.into(),
+            AppError::PoolExhausted(_) | AppError::DatabaseError(_) => {
+                ProcessingError::Transient(err.to_string())
+            }
+            AppError::NotFound(_) => ProcessingError::NotFound(err.to_string()),
+            AppError::InvalidStatusTransition(_) => ProcessingError::Refused(err.to_string()),
+            _ => ProcessingError::Permanent(err.to_string()),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ProcessingError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::Io(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => ProcessingError::Transient(err.to_string()),
+            // serialization_failure, deadlock_detected, too_many_connections,
+            // admin_shutdown / cannot_connect_now
+            sqlx::Error::Database(db)
+                if matches!(
+                    db.code().as_deref(),
+                    Some("40001" | "40P01" | "53300" | "57P01" | "57P03")
+                ) =>
+            {
+                ProcessingError::Transient(err.to_string())
+            }
+            // data_exception and integrity_constraint_violation classes
+            sqlx::Error::Database(db)
+                if db
+                    .code()
+                    .is_some_and(|code| code.starts_with("22") || code.starts_with("23")) =>
+            {
+                ProcessingError::InvalidData(err.to_string())
+            }
+            sqlx::Error::RowNotFound => ProcessingError::NotFound(err.to_string()),
+            _ => ProcessingError::Permanent(err.to_string()),
+        }
+    }
+}
+
+/// Failure causes recorded in `transaction_dlq.error_code`, so DLQ entries
+/// can be grouped without parsing `error_reason`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DlqErrorCode {
+    /// A transient failure that kept happening until the attempts ran out
+    RetriesExhausted,
+    TransactionNotFound,
+    InvalidData,
+    /// Any other failure that retrying cannot fix
+    PermanentFailure,
+    /// Moved to the DLQ before codes were recorded
+    Unknown,
+}
+
+impl DlqErrorCode {
+    pub const ALL: [DlqErrorCode; 5] = [
+        DlqErrorCode::RetriesExhausted,
+        DlqErrorCode::TransactionNotFound,
+        DlqErrorCode::InvalidData,
+        DlqErrorCode::PermanentFailure,
+        DlqErrorCode::Unknown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DlqErrorCode::RetriesExhausted => "retries_exhausted",
+            DlqErrorCode::TransactionNotFound => "transaction_not_found",
+            DlqErrorCode::InvalidData => "invalid_data",
+            DlqErrorCode::PermanentFailure => "permanent_failure",
+            DlqErrorCode::Unknown => "unknown",
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == code)
+    }
+}
+
+impl std::fmt::Display for DlqErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// DLQ entries sharing one `error_code`
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DlqErrorGroup {
+    pub error_code: String,
+    pub count: i64,
+    pub oldest_moved_at: DateTime<Utc>,
+    pub latest_moved_at: DateTime<Utc>,
+}
+
+/// Result of re-running a single transaction through the processor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReprocessOutcome {
+    Completed,
+    /// Processing failed and the transaction could not be moved to the DLQ
+    Failed(String),
+    SentToDlq(String),
+    /// The transaction's status cannot move to completed; nothing was changed
+    Refused(String),
+}
+
+impl std::fmt::Display for ReprocessOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReprocessOutcome::Completed => write!(f, "completed"),
+            ReprocessOutcome::Failed(reason) => write!(f, "failed: {}", reason),
+            ReprocessOutcome::SentToDlq(reason) => write!(f, "sent to DLQ: {}", reason),
+            ReprocessOutcome::Refused(reason) => write!(f, "refused: {}", reason),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct TransactionProcessor {
     pool: PgPool,
+    max_attempts: u32,
+    retry_backoff: Duration,
 }
 
 impl TransactionProcessor {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+
+    /// Allow `max_attempts` tries (at least one) for transient failures,
+    /// waiting `backoff`, then twice as long, and so on between them.
+    pub fn with_retry_policy(mut self, max_attempts: u32, backoff: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Complete a transaction, retrying transient failures. Permanent failures
+    /// and transient ones that exhaust their retries are moved to the DLQ.
+    pub async fn process_transaction(&self, tx_id: Uuid) -> Result<(), ProcessingError> {
+        self.process_with(tx_id, || self.complete(tx_id)).await
+    }
+
+    /// Process an existing transaction again and report where it ended up.
+    /// Fails only if the transaction does not exist. A status that may not
+    /// move to completed, such as `refunded`, is refused without touching the
+    /// transaction or the DLQ.
+    pub async fn reprocess(&self, tx_id: Uuid) -> anyhow::Result<ReprocessOutcome> {
+        let current: Option<String> =
+            sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+                .bind(tx_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        let Some(current) = current else {
+            anyhow::bail!("Transaction {} not found", tx_id);
+        };
+        if current != STATUS_COMPLETED
+            && !transaction_service::is_allowed_transition(&current, STATUS_COMPLETED)
+        {
+            return Ok(ReprocessOutcome::Refused(format!(
+                "cannot move transaction {} from '{}' to '{}'",
+                tx_id, current, STATUS_COMPLETED
+            )));
+        }
+
+        let err = match self.process_transaction(tx_id).await {
+            Ok(()) => return Ok(ReprocessOutcome::Completed),
+            // The status changed between the check above and processing
+            Err(ProcessingError::Refused(reason)) => return Ok(ReprocessOutcome::Refused(reason)),
+            Err(err) => err,
+        };
+
+        let status: Option<String> =
+            sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+                .bind(tx_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        let reason = err.to_string();
+        Ok(match status.as_deref() {
+            Some("dlq") => ReprocessOutcome::SentToDlq(reason),
+            _ => ReprocessOutcome::Failed(reason),
+        })
+    }
+
+    /// Run `step` under the retry policy, sending the transaction to the DLQ
+    /// if it fails permanently or runs out of attempts. A
+    /// [`ProcessingError::Refused`] step is returned as is.
+    pub async fn process_with<F, Fut>(
+        &self,
+        tx_id: Uuid,
+        mut step: F,
+    ) -> Result<(), ProcessingError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), ProcessingError>>,
+    {
+        let mut attempt = 1;
+        let mut backoff = self.retry_backoff;
+
+        loop {
+            let err = match step().await {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            if let ProcessingError::Refused(reason) = &err {
+                tracing::warn!(%tx_id, "Not processing transaction: {}", reason);
+                return Err(err);
+            }
+
+            if err.is_transient() && attempt < self.max_attempts {
+                tracing::warn!(
+                    %tx_id,
+                    attempt,
+                    "Transient failure processing transaction, retrying in {:?}: {}",
+                    backoff,
+                    err.reason()
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2);
+                attempt += 1;
+                continue;
+            }
+
+            tracing::error!(%tx_id, attempt, "Moving transaction to DLQ: {}", err);
+            if let Err(dlq_err) = self.move_to_dlq(tx_id, &err, attempt - 1).await {
+                tracing::error!(%tx_id, "Failed to move transaction to DLQ: {}", dlq_err);
+            }
+            return Err(err);
+        }
     }
 
-    pub async fn process_transaction(&self, tx_id: uuid::Uuid) -> anyhow::Result<()> {
+    /// Move the transaction from its current status to completed through
+    /// the guarded, audited transition. Already completed is a no-op.
+    async fn complete(&self, tx_id: Uuid) -> Result<(), ProcessingError> {
+        let current: Option<String> =
+            sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+                .bind(tx_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        let current = current
+            .ok_or_else(|| ProcessingError::NotFound(format!("transaction {} not found", tx_id)))?;
+        if current == STATUS_COMPLETED {
+            return Ok(());
+        }
+
+        transaction_service::transition_status(&self.pool, tx_id, &current, STATUS_COMPLETED)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark the transaction `dlq`, copy it into `transaction_dlq` and audit
+    /// the change. Only statuses allowed to move to `dlq` are touched, so a
+    /// transaction completed or refunded meanwhile stays as it is.
+    async fn move_to_dlq(
+        &self,
+        tx_id: Uuid,
+        err: &ProcessingError,
+        retry_count: u32,
+    ) -> anyhow::Result<()> {
+        let mut db_tx = self.pool.begin().await?;
+
+        let current: Option<String> =
+            sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1 FOR UPDATE")
+                .bind(tx_id)
+                .fetch_optional(&mut *db_tx)
+                .await?;
+        let Some(current) = current else {
+            anyhow::bail!("transaction {} not found", tx_id);
+        };
+        if !transaction_service::is_allowed_transition(&current, STATUS_DLQ) {
+            anyhow::bail!(
+                "cannot move transaction {} from '{}' to '{}'",
+                tx_id,
+                current,
+                STATUS_DLQ
+            );
+        }
+
+        let updated = sqlx::query(
+            "UPDATE transactions SET status = $2, updated_at = NOW() WHERE id = $1 AND status = $3",
+        )
+        .bind(tx_id)
+        .bind(STATUS_DLQ)
+        .bind(&current)
+        .execute(&mut *db_tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            anyhow::bail!("transaction {} is no longer '{}'", tx_id, current);
+        }
+
         sqlx::query(
-            "UPDATE transactions SET status = 'completed', updated_at = NOW() WHERE id = $1",
+            r#"
+            INSERT INTO transaction_dlq (
+                transaction_id, stellar_account, amount, asset_code,
+                anchor_transaction_id, error_reason, error_code, retry_count,
+                original_created_at, last_retry_at
+            )
+            SELECT id, stellar_account, amount, asset_code,
+                   anchor_transaction_id, $2, $4, $3, created_at,
+                   CASE WHEN $3 > 0 THEN NOW() END
+            FROM transactions WHERE id = $1
+            "#,
         )
         .bind(tx_id)
-        .execute(&self.pool)
+        .bind(err.to_string())
+        .bind(retry_count as i32)
+        .bind(err.dlq_code().as_str())
+        .execute(&mut *db_tx)
         .await?;
+
+        AuditLog::log_status_change(
+            &mut db_tx,
+            tx_id,
+            ENTITY_TRANSACTION,
+            &current,
+            STATUS_DLQ,
+            "system",
+        )
+        .await?;
+
+        db_tx.commit().await?;
         Ok(())
     }
 
-    pub async fn requeue_dlq(&self, dlq_id: uuid::Uuid) -> anyhow::Result<()> {
-        let tx_id: uuid::Uuid =
+    /// DLQ entries counted per `error_code`, largest group first
+    pub async fn dlq_error_summary(&self) -> anyhow::Result<Vec<DlqErrorGroup>> {
+        let groups = sqlx::query_as::<_, DlqErrorGroup>(
+            r#"
+            SELECT error_code, COUNT(*) AS count,
+                   MIN(moved_to_dlq_at) AS oldest_moved_at,
+                   MAX(moved_to_dlq_at) AS latest_moved_at
+            FROM transaction_dlq
+            GROUP BY error_code
+            ORDER BY count DESC, error_code
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(groups)
+    }
+
+    /// Move the entry's transaction from `dlq` back to `pending` and drop the
+    /// entry. Fails, changing nothing, if the transaction is no longer `dlq`.
+    pub async fn requeue_dlq(&self, dlq_id: Uuid) -> anyhow::Result<()> {
+        let tx_id: Uuid =
             sqlx::query_scalar("SELECT transaction_id FROM transaction_dlq WHERE id = $1")
                 .bind(dlq_id)
                 .fetch_one(&self.pool)
                 .await?;
 
-        sqlx::query("UPDATE transactions SET status = 'pending', updated_at = NOW() WHERE id = $1")
-            .bind(tx_id)
-            .execute(&self.pool)
+        let skipped = self.requeue_batch(&[(dlq_id, tx_id)]).await?;
+        if !skipped.is_empty() {
+            anyhow::bail!("transaction {} is not in '{}'", tx_id, STATUS_DLQ);
+        }
+        Ok(())
+    }
+
+    /// Requeue every DLQ entry matching `filter`, [`REQUEUE_BATCH_SIZE`] at a
+    /// time. Each batch commits or rolls back as a whole.
+    pub async fn requeue_dlq_matching(&self, filter: &DlqFilter) -> anyhow::Result<RequeueSummary> {
+        let matched: Vec<(Uuid, Uuid)> = sqlx::query_as(
+            r#"
+            SELECT id, transaction_id FROM transaction_dlq
+            WHERE ($1::uuid[] IS NULL OR id = ANY($1))
+              AND ($2::text IS NULL OR asset_code = $2)
+              AND ($3::text IS NULL OR error_reason ILIKE '%' || $3 || '%' ESCAPE '\')
+              AND ($4::timestamptz IS NULL OR moved_to_dlq_at < $4)
+              AND ($5::text IS NULL OR error_code = $5)
+            ORDER BY moved_to_dlq_at ASC
+            "#,
+        )
+        .bind(filter.ids.as_deref())
+        .bind(filter.asset_code.as_deref())
+        .bind(filter.error_reason.as_deref().map(escape_like))
+        .bind(filter.before)
+        .bind(filter.error_code.map(|c| c.as_str()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut summary = RequeueSummary::default();
+        if let Some(ids) = &filter.ids {
+            for id in ids {
+                if !matched.iter().any(|(dlq_id, _)| dlq_id == id) {
+                    summary.failed_ids.push(*id);
+                }
+            }
+        }
+
+        for batch in matched.chunks(REQUEUE_BATCH_SIZE) {
+            match self.requeue_batch(batch).await {
+                Ok(skipped) => {
+                    if !skipped.is_empty() {
+                        tracing::warn!(
+                            "Left {} DLQ entries whose transaction is no longer '{}'",
+                            skipped.len(),
+                            STATUS_DLQ
+                        );
+                    }
+                    summary.succeeded += batch.len() - skipped.len();
+                    summary.failed_ids.extend(skipped);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to requeue {} DLQ entries: {}", batch.len(), e);
+                    summary
+                        .failed_ids
+                        .extend(batch.iter().map(|(dlq_id, _)| *dlq_id));
+                }
+            }
+        }
+
+        summary.failed = summary.failed_ids.len();
+        Ok(summary)
+    }
+
+    /// Requeue `(dlq_id, transaction_id)` pairs in one database transaction,
+    /// auditing each status change. Entries whose transaction is no longer
+    /// `dlq` are left in place and their ids returned.
+    async fn requeue_batch(&self, batch: &[(Uuid, Uuid)]) -> anyhow::Result<Vec<Uuid>> {
+        let tx_ids: Vec<Uuid> = batch.iter().map(|(_, tx_id)| *tx_id).collect();
+        let mut db_tx = self.pool.begin().await?;
+
+        let requeued: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE transactions SET status = $2, updated_at = NOW()
+            WHERE id = ANY($1) AND status = $3
+            RETURNING id
+            "#,
+        )
+        .bind(&tx_ids)
+        .bind(STATUS_PENDING)
+        .bind(STATUS_DLQ)
+        .fetch_all(&mut *db_tx)
+        .await?;
+
+        for tx_id in &requeued {
+            AuditLog::log_status_change(
+                &mut db_tx,
+                *tx_id,
+                ENTITY_TRANSACTION,
+                STATUS_DLQ,
+                STATUS_PENDING,
+                "system",
+            )
             .await?;
+        }
 
-        sqlx::query("DELETE FROM transaction_dlq WHERE id = $1")
-            .bind(dlq_id)
-            .execute(&self.pool)
+        let (done, skipped): (Vec<_>, Vec<_>) = batch
+            .iter()
+            .partition(|(_, tx_id)| requeued.contains(tx_id));
+        let done: Vec<Uuid> = done.into_iter().map(|(dlq_id, _)| dlq_id).collect();
+        sqlx::query("DELETE FROM transaction_dlq WHERE id = ANY($1)")
+            .bind(&done)
+            .execute(&mut *db_tx)
             .await?;
 
-        Ok(())
+        db_tx.commit().await?;
+        Ok(skipped.into_iter().map(|(dlq_id, _)| dlq_id).collect())
+    }
+}
+
+/// Escape the `LIKE` wildcards in `input`, for use with `ESCAPE '\'`
+fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_like_escapes_wildcards() {
+        assert_eq!(escape_like("timeout"), "timeout");
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+
+    #[test]
+    fn test_sqlx_errors_are_classified() {
+        assert!(ProcessingError::from(sqlx::Error::PoolTimedOut).is_transient());
+        assert!(!ProcessingError::from(sqlx::Error::RowNotFound).is_transient());
+    }
+
+    #[test]
+    fn test_dlq_codes_round_trip() {
+        assert_eq!(
+            ProcessingError::from(sqlx::Error::RowNotFound).dlq_code(),
+            DlqErrorCode::TransactionNotFound
+        );
+        assert_eq!(
+            ProcessingError::Transient("timeout".to_string()).dlq_code(),
+            DlqErrorCode::RetriesExhausted
+        );
+        for code in DlqErrorCode::ALL {
+            assert_eq!(DlqErrorCode::parse(code.as_str()), Some(code));
+        }
+        assert_eq!(DlqErrorCode::parse("timeout"), None);
     }
 }
@@ -1,4 +1,4 @@
-use crate::services::scheduler::Job;
+use crate::services::scheduler::{Job, JobSchedule};
 use crate::stellar::HorizonClient;
 use async_trait::async_trait;
 use sqlx::PgPool;
@@ -27,8 +27,8 @@ impl Job for TransactionProcessorJob {
         "transaction_processor"
     }
 
-    fn schedule(&self) -> &str {
-        "*/5 * * * * *" // Every 5 seconds
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Cron("*/5 * * * * *".to_string()) // Every 5 seconds
     }
 
     async fn execute(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -0,0 +1,275 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::task::JoinHandle;
+
+use crate::config::parse_positive;
+use crate::handlers::ws::TransactionStatusUpdate;
+
+/// Status changes queued for delivery before new ones are dropped, unless
+/// `WEBHOOK_QUEUE_CAPACITY` says otherwise
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1000;
+/// Deliveries in flight at once unless `WEBHOOK_DISPATCH_CONCURRENCY` says otherwise
+pub const DEFAULT_CONCURRENCY: usize = 8;
+/// Time allowed for one delivery attempt
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Status changes dropped because the delivery queue was full
+pub const DROPPED_METRIC: &str = "webhook_dispatch_dropped_total";
+/// Deliveries that reached the endpoint
+pub const DELIVERED_METRIC: &str = "webhook_dispatch_delivered_total";
+/// Deliveries that failed or timed out
+pub const FAILED_METRIC: &str = "webhook_dispatch_failed_total";
+
+/// Settings for outbound status webhooks. Delivery is off unless
+/// `OUTBOUND_WEBHOOK_URL` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookDispatchConfig {
+    pub url: Option<String>,
+    pub queue_capacity: usize,
+    pub concurrency: usize,
+}
+
+impl Default for WebhookDispatchConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl WebhookDispatchConfig {
+    /// Read `OUTBOUND_WEBHOOK_URL`, `WEBHOOK_QUEUE_CAPACITY` and
+    /// `WEBHOOK_DISPATCH_CONCURRENCY`; missing values keep the defaults and a
+    /// capacity or concurrency that is not above zero is an error.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            url: std::env::var("OUTBOUND_WEBHOOK_URL")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+            queue_capacity: parse_positive("WEBHOOK_QUEUE_CAPACITY", DEFAULT_QUEUE_CAPACITY)?,
+            concurrency: parse_positive("WEBHOOK_DISPATCH_CONCURRENCY", DEFAULT_CONCURRENCY)?,
+        })
+    }
+}
+
+/// Where queued status changes are delivered
+#[async_trait]
+pub trait WebhookSink: Send + Sync + 'static {
+    async fn deliver(&self, update: &TransactionStatusUpdate) -> anyhow::Result<()>;
+}
+
+/// POSTs each status change as JSON to a fixed URL
+pub struct HttpWebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpWebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl WebhookSink for HttpWebhookSink {
+    async fn deliver(&self, update: &TransactionStatusUpdate) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .timeout(DELIVERY_TIMEOUT)
+            .json(update)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Producer side of the delivery queue
+#[derive(Clone)]
+pub struct WebhookQueue {
+    tx: mpsc::Sender<TransactionStatusUpdate>,
+}
+
+impl WebhookQueue {
+    /// Queue `update`, waiting for room while the queue is full. Returns
+    /// false once the dispatcher has stopped.
+    pub async fn enqueue(&self, update: TransactionStatusUpdate) -> bool {
+        self.tx.send(update).await.is_ok()
+    }
+
+    /// Queue `update` without waiting. When the queue is full the update is
+    /// dropped and counted in [`DROPPED_METRIC`].
+    pub fn try_enqueue(&self, update: TransactionStatusUpdate) -> bool {
+        match self.tx.try_send(update) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(update)) => {
+                crate::metrics::registry().increment_counter(DROPPED_METRIC, &[]);
+                tracing::warn!(
+                    transaction_id = %update.transaction_id,
+                    "Webhook queue full, dropping status update"
+                );
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Consumer side: drains the queue into a [`WebhookSink`], at most
+/// `concurrency` deliveries at a time
+pub struct WebhookDispatcher {
+    rx: mpsc::Receiver<TransactionStatusUpdate>,
+    sink: Arc<dyn WebhookSink>,
+    concurrency: usize,
+}
+
+impl WebhookDispatcher {
+    /// Create a bounded queue and the dispatcher that drains it
+    pub fn channel(
+        config: &WebhookDispatchConfig,
+        sink: Arc<dyn WebhookSink>,
+    ) -> (WebhookQueue, Self) {
+        let registry = crate::metrics::registry();
+        for metric in [DROPPED_METRIC, DELIVERED_METRIC, FAILED_METRIC] {
+            registry.register_counter(metric, &[]);
+        }
+
+        let (tx, rx) = mpsc::channel(config.queue_capacity.max(1));
+        let dispatcher = Self {
+            rx,
+            sink,
+            concurrency: config.concurrency.max(1),
+        };
+        (WebhookQueue { tx }, dispatcher)
+    }
+
+    /// Deliver queued updates until every [`WebhookQueue`] is dropped, then
+    /// wait for deliveries still in flight
+    pub async fn run(mut self) {
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        while let Some(update) = self.rx.recv().await {
+            let permit = permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("dispatcher semaphore is never closed");
+            let sink = self.sink.clone();
+            tokio::spawn(async move {
+                let registry = crate::metrics::registry();
+                match sink.deliver(&update).await {
+                    Ok(()) => registry.increment_counter(DELIVERED_METRIC, &[]),
+                    Err(e) => {
+                        registry.increment_counter(FAILED_METRIC, &[]);
+                        tracing::warn!(
+                            transaction_id = %update.transaction_id,
+                            "Webhook delivery failed: {}",
+                            e
+                        );
+                    }
+                }
+                drop(permit);
+            });
+        }
+        let _ = permits.acquire_many(self.concurrency as u32).await;
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+/// Feed status changes from the broadcast channel into `queue`, dropping
+/// them when the queue is full so publishers are never slowed down
+pub fn forward_status_updates(
+    mut updates: broadcast::Receiver<TransactionStatusUpdate>,
+    queue: WebhookQueue,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match updates.recv().await {
+                Ok(update) => {
+                    queue.try_enqueue(update);
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Webhook forwarder lagged, {} status updates missed", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    /// Blocks every delivery until the test adds permits to `gate`
+    struct GatedSink {
+        gate: Semaphore,
+        delivered: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl WebhookSink for GatedSink {
+        async fn deliver(&self, _update: &TransactionStatusUpdate) -> anyhow::Result<()> {
+            self.gate.acquire().await?.forget();
+            self.delivered.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn update() -> TransactionStatusUpdate {
+        TransactionStatusUpdate {
+            transaction_id: Uuid::new_v4(),
+            status: "completed".to_string(),
+            timestamp: chrono::Utc::now(),
+            message: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_and_keeps_draining() {
+        let sink = Arc::new(GatedSink {
+            gate: Semaphore::new(0),
+            delivered: AtomicUsize::new(0),
+        });
+        let config = WebhookDispatchConfig {
+            url: None,
+            queue_capacity: 4,
+            concurrency: 2,
+        };
+        let (queue, dispatcher) = WebhookDispatcher::channel(&config, sink.clone());
+        let handle = dispatcher.spawn();
+
+        let registry = crate::metrics::registry();
+        let dropped_before = registry.counter(DROPPED_METRIC, &[]);
+        let accepted = (0..100).filter(|_| queue.try_enqueue(update())).count();
+        let dropped = 100 - accepted;
+
+        assert!(dropped > 0);
+        assert!(accepted >= config.queue_capacity);
+        assert_eq!(
+            registry.counter(DROPPED_METRIC, &[]) - dropped_before,
+            dropped as u64
+        );
+
+        // Once deliveries are unblocked everything accepted is delivered
+        sink.gate.add_permits(accepted);
+        drop(queue);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sink.delivered.load(Ordering::SeqCst), accepted);
+    }
+}
@@ -1,8 +1,58 @@
 use crate::config::Config;
+use crate::utils::redis_connect::redis_connect_policy;
 use anyhow::{Context, Result};
 use sqlx::PgPool;
 use std::time::Duration;
 
+/// Time each startup Horizon probe may take unless
+/// `HORIZON_STARTUP_TIMEOUT_SECS` says otherwise
+pub const DEFAULT_HORIZON_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Probes made after the first failed one unless `HORIZON_STARTUP_RETRIES`
+/// says otherwise
+pub const DEFAULT_HORIZON_CHECK_RETRIES: usize = 2;
+/// Wait before the first retry; doubled after every further attempt
+pub const DEFAULT_HORIZON_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// How hard startup validation tries to reach Horizon before reporting it
+/// unavailable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HorizonCheckPolicy {
+    pub timeout: Duration,
+    pub retries: usize,
+    pub base_delay: Duration,
+}
+
+impl Default for HorizonCheckPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_HORIZON_CHECK_TIMEOUT,
+            retries: DEFAULT_HORIZON_CHECK_RETRIES,
+            base_delay: DEFAULT_HORIZON_RETRY_BASE_DELAY,
+        }
+    }
+}
+
+impl HorizonCheckPolicy {
+    /// Read `HORIZON_STARTUP_TIMEOUT_SECS` and `HORIZON_STARTUP_RETRIES`;
+    /// missing or invalid values keep the defaults.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            timeout: std::env::var("HORIZON_STARTUP_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            retries: std::env::var("HORIZON_STARTUP_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.retries),
+            ..defaults
+        }
+    }
+}
+
 pub struct ValidationReport {
     pub environment: bool,
     pub database: bool,
@@ -78,7 +128,9 @@ pub async fn validate_environment(config: &Config, pool: &PgPool) -> Result<Vali
     }
 
     // Validate Horizon
-    if let Err(e) = validate_horizon(&config.stellar_horizon_url).await {
+    if let Err(e) =
+        validate_horizon(&config.stellar_horizon_url, &HorizonCheckPolicy::from_env()).await
+    {
         report.horizon = false;
         report.errors.push(format!("Horizon: {}", e));
     }
@@ -103,6 +155,14 @@ fn validate_env_vars(config: &Config) -> Result<()> {
     // Validate URL formats
     url::Url::parse(&config.stellar_horizon_url)
         .context("STELLAR_HORIZON_URL is not a valid URL")?;
+    for horizon_url in &config.stellar_horizon_urls {
+        url::Url::parse(horizon_url).with_context(|| {
+            format!(
+                "STELLAR_HORIZON_URLS entry '{}' is not a valid URL",
+                horizon_url
+            )
+        })?;
+    }
 
     Ok(())
 }
@@ -113,14 +173,14 @@ async fn validate_database(pool: &PgPool) -> Result<()> {
         .await
         .context("Failed to connect to database")?;
 
-    // Check if migrations are up to date
-    let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations")
-        .fetch_one(pool)
+    // Check the database matches the migrations this binary was built with
+    let status = crate::db::migrations::migration_status(pool, &crate::db::migrations::MIGRATOR)
         .await
         .context("Failed to check migrations table")?;
 
-    if applied == 0 {
-        anyhow::bail!("No migrations applied");
+    if !status.up_to_date {
+        let drift: Vec<String> = status.drift.iter().map(ToString::to_string).collect();
+        anyhow::bail!("Migration drift: {}", drift.join(", "));
     }
 
     Ok(())
@@ -129,8 +189,8 @@ async fn validate_database(pool: &PgPool) -> Result<()> {
 async fn validate_redis(redis_url: &str) -> Result<()> {
     let client = redis::Client::open(redis_url).context("Invalid Redis URL")?;
 
-    let mut conn = client
-        .get_multiplexed_tokio_connection()
+    let mut conn = redis_connect_policy()
+        .connect(&client)
         .await
         .context("Failed to connect to Redis")?;
 
@@ -142,44 +202,61 @@ async fn validate_redis(redis_url: &str) -> Result<()> {
     Ok(())
 }
 
-async fn validate_horizon(horizon_url: &str) -> Result<()> {
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()?;
-
-    let response = client
-        .get(horizon_url)
-        .send()
-        .await
-        .context("Failed to connect to Horizon")?;
-
-    if !response.status().is_success() {
-        anyhow::bail!("Horizon returned status: {}", response.status());
+/// Probe Horizon, retrying with backoff. A 429 means Horizon is up and only
+/// throttling us, so it counts as reachable.
+async fn validate_horizon(horizon_url: &str, policy: &HorizonCheckPolicy) -> Result<()> {
+    let client = reqwest::Client::builder().timeout(policy.timeout).build()?;
+
+    let mut delay = policy.base_delay;
+    let mut attempt = 0;
+    loop {
+        let result = match client.get(horizon_url).send().await {
+            Ok(response)
+                if response.status().is_success()
+                    || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                return Ok(());
+            }
+            Ok(response) => Err(anyhow::anyhow!(
+                "Horizon returned status: {}",
+                response.status()
+            )),
+            Err(e) => Err(anyhow::Error::new(e).context("Failed to connect to Horizon")),
+        };
+        if attempt >= policy.retries {
+            return result;
+        }
+        attempt += 1;
+        tracing::warn!(
+            "Horizon check attempt {} failed, retrying in {:?}",
+            attempt,
+            delay
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
     }
-
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn test_config() -> Config {
+        Config {
+            database_url: "postgres://localhost:5432/test".to_string(),
+            stellar_horizon_url: "https://horizon-testnet.stellar.org".to_string(),
+            stellar_horizon_urls: vec!["https://horizon-testnet.stellar.org".to_string()],
+            anchor_webhook_secret: "test".to_string(),
+            backup_dir: "/tmp".to_string(),
+            ..Config::default()
+        }
+    }
+
     #[test]
     fn test_validate_env_vars_empty_database_url() {
         let config = Config {
-            server_port: 3000,
             database_url: String::new(),
-            database_replica_url: None,
-            stellar_horizon_url: "https://horizon-testnet.stellar.org".to_string(),
-            anchor_webhook_secret: "test".to_string(),
-            redis_url: "redis://localhost:6379".to_string(),
-            default_rate_limit: 100,
-            whitelist_rate_limit: 1000,
-            whitelisted_ips: String::new(),
-            log_format: crate::config::LogFormat::Text,
-            allowed_ips: crate::config::AllowedIps::Any,
-            backup_dir: "/tmp".to_string(),
-            backup_encryption_key: None,
+            ..test_config()
         };
 
         assert!(validate_env_vars(&config).is_err());
@@ -188,21 +265,95 @@ mod tests {
     #[test]
     fn test_validate_env_vars_invalid_url() {
         let config = Config {
-            server_port: 3000,
-            database_url: "postgres://localhost:5432/test".to_string(),
-            database_replica_url: None,
             stellar_horizon_url: "not-a-url".to_string(),
-            anchor_webhook_secret: "test".to_string(),
-            redis_url: "redis://localhost:6379".to_string(),
-            default_rate_limit: 100,
-            whitelist_rate_limit: 1000,
-            whitelisted_ips: String::new(),
-            log_format: crate::config::LogFormat::Text,
-            allowed_ips: crate::config::AllowedIps::Any,
-            backup_dir: "/tmp".to_string(),
-            backup_encryption_key: None,
+            stellar_horizon_urls: vec!["not-a-url".to_string()],
+            ..test_config()
         };
 
         assert!(validate_env_vars(&config).is_err());
     }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_fails_validation() {
+        let config = Config {
+            redis_url: "redis://127.0.0.1:1".to_string(),
+            stellar_horizon_url: "http://127.0.0.1:1".to_string(),
+            stellar_horizon_urls: vec!["http://127.0.0.1:1".to_string()],
+            ..test_config()
+        };
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("postgres://127.0.0.1:1/unused")
+            .unwrap();
+
+        let report = validate_environment(&config, &pool).await.unwrap();
+        assert!(report.environment);
+        assert!(!report.redis);
+        assert!(!report.is_valid());
+        assert!(report.errors.iter().any(|e| e.starts_with("Redis:")));
+    }
+
+    fn fast_retries() -> HorizonCheckPolicy {
+        HorizonCheckPolicy {
+            timeout: Duration::from_secs(2),
+            retries: 2,
+            base_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_horizon_retry_after_503_succeeds() {
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server
+            .mock("GET", "/")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let healthy = server
+            .mock("GET", "/")
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+
+        assert!(validate_horizon(&server.url(), &fast_retries())
+            .await
+            .is_ok());
+        unavailable.assert_async().await;
+        healthy.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_horizon_is_reachable() {
+        let mut server = mockito::Server::new_async().await;
+        let throttled = server
+            .mock("GET", "/")
+            .with_status(429)
+            .expect(1)
+            .create_async()
+            .await;
+
+        assert!(validate_horizon(&server.url(), &fast_retries())
+            .await
+            .is_ok());
+        throttled.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_horizon_gives_up_after_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let unavailable = server
+            .mock("GET", "/")
+            .with_status(503)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let err = validate_horizon(&server.url(), &fast_retries())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("503"));
+        unavailable.assert_async().await;
+    }
 }
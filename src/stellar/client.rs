@@ -1,16 +1,34 @@
 use failsafe::futures::CircuitBreaker as FuturesCircuitBreaker;
 use failsafe::{backoff, failure_policy, Config, Error as FailsafeError, StateMachine};
+use futures::Stream;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 
+use super::sse::SseParser;
+
+/// Time a Horizon request may take unless [`HorizonClient::with_timeout`]
+/// says otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait before reconnecting a dropped payments stream, unless Horizon sends
+/// its own `retry`
+pub const DEFAULT_STREAM_RETRY: Duration = Duration::from_secs(1);
+/// Longest wait between reconnect attempts while Horizon stays unreachable
+pub const MAX_STREAM_RETRY: Duration = Duration::from_secs(30);
+
 #[derive(Error, Debug)]
 pub enum HorizonError {
     #[error("HTTP request failed: {0}")]
     RequestError(#[from] reqwest::Error),
     #[error("Account not found: {0}")]
     AccountNotFound(String),
+    #[error("Transaction not found: {0}")]
+    TransactionNotFound(String),
     #[error("Invalid response from Horizon: {0}")]
     InvalidResponse(String),
     #[error("Circuit breaker open: {0}")]
@@ -39,31 +57,61 @@ pub struct Balance {
     pub asset_issuer: Option<String>,
 }
 
+/// Response from Horizon /transactions endpoint (fields used for reconciliation)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionResponse {
+    pub id: String,
+    pub hash: String,
+    pub successful: bool,
+    pub ledger: i64,
+    pub created_at: String,
+    pub memo: Option<String>,
+}
+
+/// A payment operation as delivered by Horizon's payments stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentEvent {
+    pub id: String,
+    pub paging_token: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub amount: Option<String>,
+    pub asset_type: Option<String>,
+    pub asset_code: Option<String>,
+    pub asset_issuer: Option<String>,
+    pub transaction_hash: String,
+    pub created_at: String,
+}
+
 /// HTTP client for interacting with the Stellar Horizon API
+///
+/// Holds one or more Horizon base URLs. Requests go to the current primary
+/// endpoint and fail over to the next one on connection errors or 5xx
+/// responses; the circuit breaker only records a failure once every
+/// endpoint has been tried. A 404 is Horizon answering normally, so it does
+/// not count against the breaker.
 #[derive(Clone)]
 pub struct HorizonClient {
     client: Client,
-    base_url: String,
+    timeout: Duration,
+    /// No overall timeout, so long-lived event streams stay open
+    stream_client: Client,
+    base_urls: Arc<Vec<String>>,
+    primary: Arc<AtomicUsize>,
     circuit_breaker: StateMachine<failure_policy::ConsecutiveFailures<backoff::EqualJittered>, ()>,
 }
 
 impl HorizonClient {
     /// Creates a new HorizonClient with the specified base URL
     pub fn new(base_url: String) -> Self {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_default();
-
-        let backoff = backoff::equal_jittered(Duration::from_secs(60), Duration::from_secs(120));
-        let policy = failure_policy::consecutive_failures(3, backoff);
-        let circuit_breaker = Config::new().failure_policy(policy).build();
+        Self::with_endpoints(vec![base_url])
+    }
 
-        HorizonClient {
-            client,
-            base_url,
-            circuit_breaker,
-        }
+    /// Creates a new HorizonClient that fails over between `base_urls` in order
+    pub fn with_endpoints(base_urls: Vec<String>) -> Self {
+        Self::with_endpoints_and_circuit_breaker(base_urls, 3, 60)
     }
 
     /// Creates a new HorizonClient with custom circuit breaker configuration
@@ -72,8 +120,29 @@ impl HorizonClient {
         failure_threshold: u32,
         reset_timeout_secs: u64,
     ) -> Self {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+        Self::with_endpoints_and_circuit_breaker(
+            vec![base_url],
+            failure_threshold,
+            reset_timeout_secs,
+        )
+    }
+
+    /// Creates a new multi-endpoint HorizonClient with custom circuit breaker configuration
+    pub fn with_endpoints_and_circuit_breaker(
+        base_urls: Vec<String>,
+        failure_threshold: u32,
+        reset_timeout_secs: u64,
+    ) -> Self {
+        assert!(
+            !base_urls.is_empty(),
+            "HorizonClient requires at least one base URL"
+        );
+
+        let mut stream_headers = HeaderMap::new();
+        stream_headers.insert(ACCEPT, HeaderValue::from_static("text/event-stream"));
+        let stream_client = Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .default_headers(stream_headers)
             .build()
             .unwrap_or_default();
 
@@ -85,12 +154,42 @@ impl HorizonClient {
         let circuit_breaker = Config::new().failure_policy(policy).build();
 
         HorizonClient {
-            client,
-            base_url,
+            client: Client::builder()
+                .timeout(DEFAULT_REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            stream_client,
+            base_urls: Arc::new(base_urls),
+            primary: Arc::new(AtomicUsize::new(0)),
             circuit_breaker,
         }
     }
 
+    /// Bound each request, including reading the response, by `timeout`.
+    /// Clones made before this keep their timeout but share the connection
+    /// pool, circuit breaker and primary endpoint, so a health check can use
+    /// a tighter bound than background work on the same Horizon.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the time each request may take
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns the base URL requests are currently sent to first
+    pub fn base_url(&self) -> &str {
+        &self.base_urls[self.primary.load(Ordering::Relaxed)]
+    }
+
+    /// Returns all configured base URLs in failover order
+    pub fn endpoints(&self) -> &[String] {
+        &self.base_urls
+    }
+
     /// Returns the current state of the circuit breaker
     pub fn circuit_state(&self) -> String {
         if self.circuit_breaker.is_call_permitted() {
@@ -102,18 +201,18 @@ impl HorizonClient {
 
     /// Fetches account details from the Horizon API
     pub async fn get_account(&self, address: &str) -> Result<AccountResponse, HorizonError> {
-        let url = format!(
-            "{}/accounts/{}",
-            self.base_url.trim_end_matches('/'),
-            address
-        );
+        let path = format!("accounts/{}", address);
         let client = self.client.clone();
+        let timeout = self.timeout;
+        let base_urls = Arc::clone(&self.base_urls);
+        let primary = Arc::clone(&self.primary);
         let addr = address.to_string();
 
         let result = self
             .circuit_breaker
-            .call(async move {
-                let response = client.get(&url).send().await?;
+            .call_with(counts_as_failure, async move {
+                let response =
+                    send_with_failover(&client, &base_urls, &primary, &path, Some(timeout)).await?;
 
                 if response.status() == 404 {
                     return Err(HorizonError::AccountNotFound(addr));
@@ -132,6 +231,237 @@ impl HorizonClient {
             Err(FailsafeError::Inner(e)) => Err(e),
         }
     }
+
+    /// Fetches an on-chain transaction by hash from the Horizon API
+    pub async fn get_transaction(&self, hash: &str) -> Result<TransactionResponse, HorizonError> {
+        let path = format!("transactions/{}", hash);
+        let client = self.client.clone();
+        let timeout = self.timeout;
+        let base_urls = Arc::clone(&self.base_urls);
+        let primary = Arc::clone(&self.primary);
+        let tx_hash = hash.to_string();
+
+        let result = self
+            .circuit_breaker
+            .call_with(counts_as_failure, async move {
+                let response =
+                    send_with_failover(&client, &base_urls, &primary, &path, Some(timeout)).await?;
+
+                if response.status() == 404 {
+                    return Err(HorizonError::TransactionNotFound(tx_hash));
+                }
+
+                let transaction = response.json::<TransactionResponse>().await?;
+                Ok(transaction)
+            })
+            .await;
+
+        match result {
+            Ok(transaction) => Ok(transaction),
+            Err(FailsafeError::Rejected) => Err(HorizonError::CircuitBreakerOpen(
+                "Horizon API circuit breaker is open".to_string(),
+            )),
+            Err(FailsafeError::Inner(e)) => Err(e),
+        }
+    }
+
+    /// Streams payments to and from `account`, starting after `cursor` or
+    /// from now when `None`. A dropped connection is reopened from the last
+    /// event received, waiting Horizon's `retry` interval and backing off
+    /// while it stays unreachable. Every connect goes through the circuit
+    /// breaker; only a failed first connect ends the stream, with that error.
+    pub fn stream_payments(
+        &self,
+        account: &str,
+        cursor: Option<String>,
+    ) -> impl Stream<Item = Result<PaymentEvent, HorizonError>> + Send + 'static {
+        let client = self.clone();
+        let account = account.to_string();
+        let mut cursor = cursor.unwrap_or_else(|| "now".to_string());
+
+        async_stream::stream! {
+            let mut retry = DEFAULT_STREAM_RETRY;
+            let mut connected = false;
+            let mut failures: u32 = 0;
+
+            loop {
+                let path = format!("accounts/{}/payments?cursor={}", account, cursor);
+                match client.connect_stream(&path, &account).await {
+                    Ok(mut response) => {
+                        connected = true;
+                        failures = 0;
+                        let mut parser = SseParser::default();
+                        loop {
+                            let chunk = match response.chunk().await {
+                                Ok(Some(chunk)) => chunk,
+                                Ok(None) => break,
+                                Err(e) => {
+                                    tracing::warn!(
+                                        "Horizon payments stream for {} failed: {}",
+                                        account,
+                                        e
+                                    );
+                                    break;
+                                }
+                            };
+                            for event in parser.feed(&chunk) {
+                                if let Some(delay) = event.retry {
+                                    retry = delay;
+                                }
+                                // Horizon announces the stream with an `open` event
+                                let kind = event.event.as_deref().unwrap_or("message");
+                                if kind != "message" || event.data.is_empty()
+                                {
+                                    continue;
+                                }
+                                match serde_json::from_str::<PaymentEvent>(&event.data) {
+                                    Ok(payment) => {
+                                        cursor = event
+                                            .id
+                                            .unwrap_or_else(|| payment.paging_token.clone());
+                                        yield Ok(payment);
+                                    }
+                                    Err(e) => tracing::warn!(
+                                        "Skipping unreadable Horizon payment event: {}",
+                                        e
+                                    ),
+                                }
+                            }
+                        }
+                        tracing::info!(
+                            "Horizon payments stream for {} closed, resuming from cursor {}",
+                            account,
+                            cursor
+                        );
+                    }
+                    Err(e) if !connected => {
+                        yield Err(e);
+                        return;
+                    }
+                    Err(e) => {
+                        failures = failures.saturating_add(1);
+                        tracing::warn!(
+                            "Reconnecting Horizon payments stream for {} failed: {}",
+                            account,
+                            e
+                        );
+                    }
+                }
+
+                let delay = retry
+                    .saturating_mul(2u32.saturating_pow(failures))
+                    .min(MAX_STREAM_RETRY);
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    /// Open an event stream on `path`, failing over between endpoints
+    async fn connect_stream(
+        &self,
+        path: &str,
+        account: &str,
+    ) -> Result<reqwest::Response, HorizonError> {
+        let path = path.to_string();
+        let client = self.stream_client.clone();
+        let base_urls = Arc::clone(&self.base_urls);
+        let primary = Arc::clone(&self.primary);
+        let addr = account.to_string();
+
+        let result = self
+            .circuit_breaker
+            .call_with(counts_as_failure, async move {
+                let response =
+                    send_with_failover(&client, &base_urls, &primary, &path, None).await?;
+
+                if response.status() == 404 {
+                    return Err(HorizonError::AccountNotFound(addr));
+                }
+                if !response.status().is_success() {
+                    return Err(HorizonError::InvalidResponse(format!(
+                        "payments stream returned {}",
+                        response.status()
+                    )));
+                }
+                Ok(response)
+            })
+            .await;
+
+        match result {
+            Ok(response) => Ok(response),
+            Err(FailsafeError::Rejected) => Err(HorizonError::CircuitBreakerOpen(
+                "Horizon API circuit breaker is open".to_string(),
+            )),
+            Err(FailsafeError::Inner(e)) => Err(e),
+        }
+    }
+}
+
+/// Whether an error should count against the circuit breaker. A missing
+/// account or transaction means Horizon is up and answering.
+fn counts_as_failure(err: &HorizonError) -> bool {
+    !matches!(
+        err,
+        HorizonError::AccountNotFound(_) | HorizonError::TransactionNotFound(_)
+    )
+}
+
+/// GET `path` from each endpoint in turn, starting at the current primary.
+/// Connection errors and 5xx responses move on to the next endpoint; the
+/// endpoint that answers becomes the new primary. `timeout`, when given,
+/// bounds each attempt.
+async fn send_with_failover(
+    client: &Client,
+    base_urls: &[String],
+    primary: &AtomicUsize,
+    path: &str,
+    timeout: Option<Duration>,
+) -> Result<reqwest::Response, HorizonError> {
+    let start = primary.load(Ordering::Relaxed);
+    let mut last_error = None;
+
+    for offset in 0..base_urls.len() {
+        let index = (start + offset) % base_urls.len();
+        let url = format!("{}/{}", base_urls[index].trim_end_matches('/'), path);
+
+        let mut request = client.get(&url);
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_server_error() => {
+                tracing::warn!(
+                    "Horizon endpoint {} returned {}, trying next endpoint",
+                    base_urls[index],
+                    response.status()
+                );
+                last_error = Some(HorizonError::InvalidResponse(format!(
+                    "{} returned {}",
+                    base_urls[index],
+                    response.status()
+                )));
+            }
+            Ok(response) => {
+                if index != start {
+                    tracing::info!("Horizon primary endpoint is now {}", base_urls[index]);
+                    primary.store(index, Ordering::Relaxed);
+                }
+                return Ok(response);
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Horizon endpoint {} unreachable: {}, trying next endpoint",
+                    base_urls[index],
+                    e
+                );
+                last_error = Some(HorizonError::RequestError(e));
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        HorizonError::InvalidResponse("no Horizon endpoints configured".to_string())
+    }))
 }
 
 #[cfg(test)]
@@ -141,7 +471,7 @@ mod tests {
     #[test]
     fn test_horizon_client_creation() {
         let client = HorizonClient::new("https://horizon-testnet.stellar.org".to_string());
-        assert_eq!(client.base_url, "https://horizon-testnet.stellar.org");
+        assert_eq!(client.base_url(), "https://horizon-testnet.stellar.org");
     }
 
     #[tokio::test]
@@ -247,4 +577,191 @@ mod tests {
         let result = client.get_account("TEST_ACCOUNT").await;
         assert!(matches!(result, Err(HorizonError::CircuitBreakerOpen(_))));
     }
+
+    #[tokio::test]
+    async fn test_failover_to_second_endpoint() {
+        let mut failing = mockito::Server::new_async().await;
+        let mut healthy = mockito::Server::new_async().await;
+
+        let failing_mock = failing
+            .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+            .with_status(500)
+            .expect(1)
+            .create_async()
+            .await;
+        let healthy_mock = healthy
+            .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{
+                    "id": "GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ",
+                    "account_id": "GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ",
+                    "balances": [],
+                    "sequence": "1",
+                    "subentry_count": 0,
+                    "home_domain": null,
+                    "last_modified_ledger": 1,
+                    "last_modified_time": "2021-01-01T00:00:00Z"
+                }"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        // With a threshold of 1, any counted failure would open the circuit
+        let client = HorizonClient::with_endpoints_and_circuit_breaker(
+            vec![failing.url(), healthy.url()],
+            1,
+            60,
+        );
+        assert_eq!(client.base_url(), failing.url());
+
+        let account = client
+            .get_account("GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ")
+            .await;
+        assert!(account.is_ok());
+        assert_eq!(client.circuit_state(), "closed");
+        assert_eq!(client.base_url(), healthy.url());
+
+        // The next call goes straight to the new primary
+        assert!(client
+            .get_account("GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ")
+            .await
+            .is_ok());
+
+        failing_mock.assert_async().await;
+        healthy_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_all_endpoints_failing_counts_one_failure() {
+        let mut first = mockito::Server::new_async().await;
+        let mut second = mockito::Server::new_async().await;
+
+        let _first = first
+            .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+            .with_status(503)
+            .create_async()
+            .await;
+        let _second = second
+            .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let client = HorizonClient::with_endpoints_and_circuit_breaker(
+            vec![first.url(), second.url()],
+            2,
+            60,
+        );
+
+        let result = client.get_account("TEST_ACCOUNT").await;
+        assert!(matches!(result, Err(HorizonError::InvalidResponse(_))));
+        assert_eq!(client.circuit_state(), "closed");
+
+        let _ = client.get_account("TEST_ACCOUNT").await;
+        assert_eq!(client.circuit_state(), "open");
+    }
+
+    #[tokio::test]
+    async fn test_not_found_does_not_trip_the_circuit_breaker() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _accounts = server
+            .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+        let _transactions = server
+            .mock("GET", mockito::Matcher::Regex(r".*/transactions/.*".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let client = HorizonClient::with_circuit_breaker(server.url(), 1, 60);
+
+        for _ in 0..3 {
+            let result = client.get_account("TEST_ACCOUNT").await;
+            assert!(matches!(result, Err(HorizonError::AccountNotFound(_))));
+            let result = client.get_transaction("abc123").await;
+            assert!(matches!(result, Err(HorizonError::TransactionNotFound(_))));
+        }
+        assert_eq!(client.circuit_state(), "closed");
+    }
+
+    #[tokio::test]
+    async fn test_payments_stream_resumes_from_last_cursor() {
+        use futures::StreamExt;
+
+        let mut server = mockito::Server::new_async().await;
+        let event = |token: &str| {
+            let payment = serde_json::json!({
+                "id": token,
+                "paging_token": token,
+                "type": "payment",
+                "from": "GA",
+                "to": "GB",
+                "amount": "10.0000000",
+                "asset_type": "native",
+                "transaction_hash": format!("hash{}", token),
+                "created_at": "2024-01-01T00:00:00Z",
+            });
+            format!("id: {}\ndata: {}\n\n", token, payment)
+        };
+
+        // Two events, then the connection closes
+        let first = server
+            .mock("GET", "/accounts/GACCOUNT/payments")
+            .match_query(mockito::Matcher::UrlEncoded("cursor".into(), "now".into()))
+            .match_header("accept", "text/event-stream")
+            .with_header("content-type", "text/event-stream")
+            .with_body(format!(
+                "retry: 10\nevent: open\ndata: \"hello\"\n\n{}{}",
+                event("101"),
+                event("102")
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        let resumed = server
+            .mock("GET", "/accounts/GACCOUNT/payments")
+            .match_query(mockito::Matcher::UrlEncoded("cursor".into(), "102".into()))
+            .with_header("content-type", "text/event-stream")
+            .with_body(event("103"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = HorizonClient::new(server.url());
+        let payments: Vec<PaymentEvent> = client
+            .stream_payments("GACCOUNT", None)
+            .take(3)
+            .map(|payment| payment.unwrap())
+            .collect()
+            .await;
+
+        let tokens: Vec<&str> = payments.iter().map(|p| p.paging_token.as_str()).collect();
+        assert_eq!(tokens, ["101", "102", "103"]);
+        assert_eq!(payments[2].transaction_hash, "hash103");
+        first.assert_async().await;
+        resumed.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_payments_stream_ends_when_first_connect_fails() {
+        use futures::StreamExt;
+
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", mockito::Matcher::Regex(r".*/payments.*".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let client = HorizonClient::new(server.url());
+        let results: Vec<_> = client.stream_payments("GMISSING", None).collect().await;
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(HorizonError::AccountNotFound(_))));
+    }
 }
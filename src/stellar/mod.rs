@@ -1,4 +1,5 @@
 pub mod client;
+mod sse;
 
 pub use client::HorizonClient;
-pub use client::{AccountResponse, Balance, HorizonError};
+pub use client::{AccountResponse, Balance, HorizonError, PaymentEvent, TransactionResponse};
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+/// One event of a `text/event-stream` body
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+    /// Reconnection delay requested by the server
+    pub retry: Option<Duration>,
+}
+
+/// Incremental `text/event-stream` parser. Chunks may split lines and
+/// events anywhere.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    current: SseEvent,
+    has_data: bool,
+}
+
+impl SseParser {
+    /// Consume `chunk` and return the events it completed
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let raw: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if let Some(event) = self.dispatch() {
+                    events.push(event);
+                }
+                continue;
+            }
+            if line.starts_with(':') {
+                // Comment, used by servers as a keepalive
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "id" => self.current.id = Some(value.to_string()),
+                "event" => self.current.event = Some(value.to_string()),
+                "data" => {
+                    if self.has_data {
+                        self.current.data.push('\n');
+                    }
+                    self.current.data.push_str(value);
+                    self.has_data = true;
+                }
+                "retry" => {
+                    if let Ok(millis) = value.parse() {
+                        self.current.retry = Some(Duration::from_millis(millis));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.current);
+        self.has_data = false;
+        (event != SseEvent::default()).then_some(event)
+    }
+}
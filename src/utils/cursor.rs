@@ -2,6 +2,30 @@ use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+use crate::error::AppError;
+
+/// Why a cursor could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CursorError {
+    #[error("cursor is not valid base64")]
+    MalformedBase64,
+    #[error("cursor is not in the expected timestamp|id format")]
+    BadFormat,
+    #[error("cursor timestamp is not a valid RFC 3339 date")]
+    InvalidTimestamp,
+    #[error("cursor id is not a valid UUID")]
+    InvalidUuid,
+    /// Reserved for signed cursors, whose signature does not match
+    #[error("cursor signature does not match")]
+    InvalidSignature,
+}
+
+impl From<CursorError> for AppError {
+    fn from(err: CursorError) -> Self {
+        AppError::BadRequest(format!("invalid cursor: {}", err))
+    }
+}
+
 /// Cursor helpers: encode/decode a (created_at, id) tuple into a base64 string.
 /// Format used internally: "{created_at_rfc3339}|{uuid}" then base64 encoded.
 pub fn encode(created_at: DateTime<Utc>, id: Uuid) -> String {
@@ -9,21 +33,61 @@ pub fn encode(created_at: DateTime<Utc>, id: Uuid) -> String {
     general_purpose::STANDARD.encode(s)
 }
 
-pub fn decode(cursor: &str) -> Result<(DateTime<Utc>, Uuid), String> {
+pub fn decode(cursor: &str) -> Result<(DateTime<Utc>, Uuid), CursorError> {
     let decoded = general_purpose::STANDARD
         .decode(cursor)
-        .map_err(|e| format!("base64 decode error: {}", e))?;
-    let s = String::from_utf8(decoded).map_err(|e| format!("utf8 error: {}", e))?;
-    let mut parts = s.splitn(2, '|');
-    let ts_str = parts
-        .next()
-        .ok_or_else(|| "missing timestamp in cursor".to_string())?;
-    let id_str = parts
-        .next()
-        .ok_or_else(|| "missing id in cursor".to_string())?;
+        .map_err(|_| CursorError::MalformedBase64)?;
+    let s = String::from_utf8(decoded).map_err(|_| CursorError::BadFormat)?;
+    let (ts_str, id_str) = s.split_once('|').ok_or(CursorError::BadFormat)?;
     let ts = DateTime::parse_from_rfc3339(ts_str)
-        .map_err(|e| format!("timestamp parse error: {}", e))?
+        .map_err(|_| CursorError::InvalidTimestamp)?
         .with_timezone(&Utc);
-    let id = Uuid::parse_str(id_str).map_err(|e| format!("uuid parse error: {}", e))?;
+    let id = Uuid::parse_str(id_str).map_err(|_| CursorError::InvalidUuid)?;
     Ok((ts, id))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn b64(raw: &str) -> String {
+        general_purpose::STANDARD.encode(raw)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let created_at = Utc::now();
+        let id = Uuid::new_v4();
+        let (ts, decoded_id) = decode(&encode(created_at, id)).unwrap();
+        assert_eq!(ts.timestamp_micros(), created_at.timestamp_micros());
+        assert_eq!(decoded_id, id);
+    }
+
+    #[test]
+    fn test_decode_failures() {
+        let id = Uuid::new_v4();
+        assert_eq!(decode("not base64!"), Err(CursorError::MalformedBase64));
+        assert_eq!(decode(&b64("no-separator")), Err(CursorError::BadFormat));
+        assert_eq!(
+            decode(&general_purpose::STANDARD.encode([0xff, 0xfe, b'|'])),
+            Err(CursorError::BadFormat)
+        );
+        assert_eq!(
+            decode(&b64(&format!("yesterday|{}", id))),
+            Err(CursorError::InvalidTimestamp)
+        );
+        assert_eq!(
+            decode(&b64("2026-01-01T00:00:00+00:00|not-a-uuid")),
+            Err(CursorError::InvalidUuid)
+        );
+    }
+
+    #[test]
+    fn test_maps_to_bad_request() {
+        let err: AppError = CursorError::InvalidSignature.into();
+        assert!(matches!(
+            err,
+            AppError::BadRequest(msg) if msg == "invalid cursor: cursor signature does not match"
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::config::Config;
+use crate::error::AppError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Lifetime of a link unless the request or `EXPORT_LINK_TTL_SECS` says otherwise
+pub const DEFAULT_LINK_TTL_SECS: u64 = 3600;
+/// Longest lifetime a caller may ask for
+pub const MAX_LINK_TTL_SECS: u64 = 7 * 24 * 3600;
+
+/// Why an export link token was rejected
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExportLinkError {
+    #[error("export link is malformed")]
+    Malformed,
+    #[error("export link signature does not match")]
+    InvalidSignature,
+    #[error("export link has expired")]
+    Expired,
+}
+
+impl From<ExportLinkError> for AppError {
+    fn from(err: ExportLinkError) -> Self {
+        AppError::InsufficientPermissions(err.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignedPayload<T> {
+    exp: i64,
+    claims: T,
+}
+
+/// Signs and verifies export link tokens: `base64url(payload).hex(hmac)`,
+/// where the payload holds the claims and the expiry as unix seconds
+#[derive(Clone)]
+pub struct ExportLinkSigner {
+    secret: Vec<u8>,
+    default_ttl_secs: u64,
+}
+
+impl ExportLinkSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            default_ttl_secs: DEFAULT_LINK_TTL_SECS,
+        }
+    }
+
+    pub fn with_default_ttl_secs(mut self, secs: u64) -> Self {
+        self.default_ttl_secs = secs.clamp(1, MAX_LINK_TTL_SECS);
+        self
+    }
+
+    /// Signer for the configured `EXPORT_LINK_SECRET` and
+    /// `EXPORT_LINK_TTL_SECS`, or `None` when no secret is set and export
+    /// links are disabled
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let secret = config.export_link_secret.as_deref()?;
+        Some(Self::new(secret).with_default_ttl_secs(config.export_link_ttl_secs))
+    }
+
+    /// Expiry for a link requested with `ttl_secs`, capped at [`MAX_LINK_TTL_SECS`]
+    pub fn expiry(&self, now: DateTime<Utc>, ttl_secs: Option<u64>) -> DateTime<Utc> {
+        let ttl = ttl_secs
+            .unwrap_or(self.default_ttl_secs)
+            .clamp(1, MAX_LINK_TTL_SECS);
+        now + chrono::Duration::seconds(ttl as i64)
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T, expires_at: DateTime<Utc>) -> String {
+        let payload = SignedPayload {
+            exp: expires_at.timestamp(),
+            claims,
+        };
+        let encoded = general_purpose::URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&payload).expect("claims serialize to JSON"));
+        let signature = hex::encode(self.mac(&encoded).finalize().into_bytes());
+        format!("{}.{}", encoded, signature)
+    }
+
+    /// Claims of `token` if its signature matches and it has not expired at `now`
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<T, ExportLinkError> {
+        let (encoded, signature) = token.split_once('.').ok_or(ExportLinkError::Malformed)?;
+        let signature = hex::decode(signature).map_err(|_| ExportLinkError::Malformed)?;
+        self.mac(encoded)
+            .verify_slice(&signature)
+            .map_err(|_| ExportLinkError::InvalidSignature)?;
+
+        let bytes = general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| ExportLinkError::Malformed)?;
+        let payload: SignedPayload<T> =
+            serde_json::from_slice(&bytes).map_err(|_| ExportLinkError::Malformed)?;
+        let expires_at = Utc
+            .timestamp_opt(payload.exp, 0)
+            .single()
+            .ok_or(ExportLinkError::Malformed)?;
+        if now >= expires_at {
+            return Err(ExportLinkError::Expired);
+        }
+        Ok(payload.claims)
+    }
+
+    fn mac(&self, encoded: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(encoded.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tampered_and_expired_tokens_are_rejected() {
+        let signer = ExportLinkSigner::new("secret");
+        let now = Utc::now();
+        let token = signer.sign(&"asset_code=USD", signer.expiry(now, Some(60)));
+        assert_eq!(
+            signer.verify::<String>(&token, now).unwrap(),
+            "asset_code=USD"
+        );
+
+        let forged = signer.sign(&"asset_code=EUR", signer.expiry(now, Some(60)));
+        let (forged_payload, _) = forged.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        let tampered = format!("{}.{}", forged_payload, signature);
+        assert_eq!(
+            signer.verify::<String>(&tampered, now),
+            Err(ExportLinkError::InvalidSignature)
+        );
+
+        let later = now + chrono::Duration::seconds(61);
+        assert_eq!(
+            signer.verify::<String>(&token, later),
+            Err(ExportLinkError::Expired)
+        );
+        assert!(ExportLinkSigner::new("other")
+            .verify::<String>(&token, now)
+            .is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::convert::Infallible;
+
+use axum::async_trait;
+use axum::extract::{FromRef, FromRequestParts, OriginalUri};
+use axum::http::{header, request::Parts, HeaderMap};
+use url::Url;
+
+use crate::config::Config;
+use crate::{ApiState, AppState};
+
+/// How absolute links in responses find the address clients use
+#[derive(Debug, Clone, Default)]
+pub struct LinkConfig {
+    /// Canonical scheme, host and optional path prefix, overriding the
+    /// request's own address
+    pub public_base_url: Option<Url>,
+    /// Proxies whose `x-forwarded-proto` and `x-forwarded-host` are trusted;
+    /// zero ignores both headers
+    pub trusted_proxy_depth: usize,
+}
+
+impl LinkConfig {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            public_base_url: config.public_base_url.clone(),
+            trusted_proxy_depth: config.trusted_proxy_depth,
+        }
+    }
+
+    /// Scheme and host the client addressed, or `None` when the request
+    /// names no host
+    pub fn base_url(&self, headers: &HeaderMap) -> Option<Url> {
+        if let Some(base) = &self.public_base_url {
+            return Some(base.clone());
+        }
+        let scheme = self
+            .forwarded(headers, "x-forwarded-proto")
+            .unwrap_or_else(|| "http".to_string());
+        let host = self.forwarded(headers, "x-forwarded-host").or_else(|| {
+            headers
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        })?;
+        Url::parse(&format!("{}://{}", scheme, host)).ok()
+    }
+
+    /// `path` under the address the client used, or `None` when the request
+    /// names no host
+    pub fn url_for(&self, headers: &HeaderMap, path: &str) -> Option<Url> {
+        self.base_url(headers).map(|mut url| {
+            let joined = format!("{}{}", url.path().trim_end_matches('/'), path);
+            url.set_path(&joined);
+            url
+        })
+    }
+
+    /// The value of a forwarding header as set by the outermost trusted
+    /// proxy. Each proxy appends its own entry, so with `N` trusted proxies
+    /// that is the `N`th entry from the end.
+    fn forwarded(&self, headers: &HeaderMap, name: &str) -> Option<String> {
+        if self.trusted_proxy_depth == 0 {
+            return None;
+        }
+        let chain: Vec<&str> = headers
+            .get(name)?
+            .to_str()
+            .ok()?
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect();
+        chain
+            .get(chain.len().saturating_sub(self.trusted_proxy_depth))
+            .map(|v| v.to_string())
+    }
+}
+
+impl FromRef<AppState> for LinkConfig {
+    fn from_ref(state: &AppState) -> Self {
+        Self::new(&state.config)
+    }
+}
+
+impl FromRef<ApiState> for LinkConfig {
+    fn from_ref(state: &ApiState) -> Self {
+        Self::from_ref(&state.app_state)
+    }
+}
+
+/// The absolute URL of the current request, for building links to other
+/// pages of the same listing
+#[derive(Debug, Clone)]
+pub struct RequestUrl(Option<Url>);
+
+impl RequestUrl {
+    pub fn from_parts(config: &LinkConfig, headers: &HeaderMap, uri: &axum::http::Uri) -> Self {
+        Self(config.url_for(headers, uri.path()).map(|mut url| {
+            url.set_query(uri.query());
+            url
+        }))
+    }
+
+    /// This URL with each of `params` replacing any existing value of the
+    /// same name. `None` when the request's address is unknown.
+    pub fn with_params(&self, params: &[(&str, &str)]) -> Option<String> {
+        let mut url = self.0.clone()?;
+        let kept: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| params.iter().all(|(name, _)| key != name))
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(kept)
+            .extend_pairs(params);
+        Some(url.to_string())
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestUrl
+where
+    S: Send + Sync,
+    LinkConfig: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Nested routers see a stripped URI; links need the one the client sent
+        let uri = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map(|original| original.0.clone())
+            .unwrap_or_else(|| parts.uri.clone());
+        Ok(Self::from_parts(
+            &LinkConfig::from_ref(state),
+            &parts.headers,
+            &uri,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderValue, Uri};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn config(depth: usize) -> LinkConfig {
+        LinkConfig {
+            public_base_url: None,
+            trusted_proxy_depth: depth,
+        }
+    }
+
+    #[test]
+    fn test_base_url_from_host_and_forwarded_headers() {
+        let forwarded = headers(&[
+            ("host", "10.0.0.5:3000"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "api.example.com"),
+        ]);
+        // Without trusted proxies the forwarding headers are ignored
+        assert_eq!(
+            config(0).base_url(&forwarded).unwrap().as_str(),
+            "http://10.0.0.5:3000/"
+        );
+        assert_eq!(
+            config(1).base_url(&forwarded).unwrap().as_str(),
+            "https://api.example.com/"
+        );
+
+        // The outermost trusted proxy's entry wins over client-supplied ones
+        let chained = headers(&[
+            ("x-forwarded-proto", "ftp, https, http"),
+            (
+                "x-forwarded-host",
+                "evil.test, api.example.com, lb.internal",
+            ),
+        ]);
+        assert_eq!(
+            config(2).base_url(&chained).unwrap().as_str(),
+            "https://api.example.com/"
+        );
+
+        assert!(config(0).base_url(&HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_public_base_url_overrides_request() {
+        let config = LinkConfig {
+            public_base_url: Some(Url::parse("https://payments.example.com/api/").unwrap()),
+            trusted_proxy_depth: 1,
+        };
+        let headers = headers(&[("host", "internal:3000"), ("x-forwarded-host", "other")]);
+        let uri: Uri = "/transactions?limit=5&cursor=abc".parse().unwrap();
+        let url = RequestUrl::from_parts(&config, &headers, &uri);
+        assert_eq!(
+            url.with_params(&[("cursor", "x+y/z=")]).unwrap(),
+            "https://payments.example.com/api/transactions?limit=5&cursor=x%2By%2Fz%3D"
+        );
+    }
+}
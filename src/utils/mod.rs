@@ -1,2 +1,7 @@
 pub mod cursor;
+pub mod export_link;
+pub mod links;
+pub mod pagination;
+pub mod redis_connect;
 pub mod sanitize;
+pub mod time;
@@ -0,0 +1,214 @@
+use std::sync::OnceLock;
+
+use crate::error::AppError;
+
+/// Largest page any list endpoint returns unless `MAX_PAGE_SIZE` says otherwise
+pub const DEFAULT_MAX_PAGE_SIZE: i64 = 100;
+
+/// Upper bound shared by every paginated endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimits {
+    pub max_page_size: i64,
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self {
+            max_page_size: DEFAULT_MAX_PAGE_SIZE,
+        }
+    }
+}
+
+impl PageLimits {
+    /// Read `MAX_PAGE_SIZE`. Missing or non-positive values fall back to
+    /// [`DEFAULT_MAX_PAGE_SIZE`].
+    pub fn from_env() -> Self {
+        let max_page_size = std::env::var("MAX_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_MAX_PAGE_SIZE);
+        Self { max_page_size }
+    }
+
+    /// Resolve a requested `limit`: absent uses `default` (itself capped),
+    /// zero or negative is a 400, and anything over the maximum is clamped.
+    pub fn resolve(&self, requested: Option<i64>, default: i64) -> Result<i64, AppError> {
+        match requested {
+            None => Ok(default.min(self.max_page_size)),
+            Some(limit) if limit <= 0 => Err(AppError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                self.max_page_size
+            ))),
+            Some(limit) => Ok(limit.min(self.max_page_size)),
+        }
+    }
+}
+
+/// Process-wide limits, read from the environment on first use
+pub fn page_limits() -> &'static PageLimits {
+    static LIMITS: OnceLock<PageLimits> = OnceLock::new();
+    LIMITS.get_or_init(PageLimits::from_env)
+}
+
+/// Shorthand for `page_limits().resolve(requested, default)`
+pub fn resolve_limit(requested: Option<i64>, default: i64) -> Result<i64, AppError> {
+    page_limits().resolve(requested, default)
+}
+
+/// Which way a cursor page moves through a listing. Rows are ordered by
+/// `(created_at, id)`, so rows sharing a `created_at` still have a fixed
+/// order and a cursor never skips or repeats them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageDirection {
+    /// Further along the [`SortOrder`]: older rows when newest first
+    #[default]
+    Forward,
+    /// Back towards the start of the [`SortOrder`]: newer rows when newest first
+    Backward,
+}
+
+impl PageDirection {
+    /// Parse `forward` or `backward`, ignoring case
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        if raw.eq_ignore_ascii_case("forward") {
+            Ok(Self::Forward)
+        } else if raw.eq_ignore_ascii_case("backward") {
+            Ok(Self::Backward)
+        } else {
+            Err(AppError::BadRequest(format!(
+                "direction must be forward or backward, got {:?}",
+                raw
+            )))
+        }
+    }
+
+    /// Read `DEFAULT_PAGE_DIRECTION`. Missing or invalid values mean
+    /// [`PageDirection::Forward`].
+    pub fn from_env() -> Self {
+        std::env::var("DEFAULT_PAGE_DIRECTION")
+            .ok()
+            .and_then(|v| Self::parse(&v).ok())
+            .unwrap_or_default()
+    }
+
+    /// The requested direction, or `default` when none was given
+    pub fn resolve(requested: Option<&str>, default: Self) -> Result<Self, AppError> {
+        requested.map_or(Ok(default), Self::parse)
+    }
+
+    pub fn is_backward(&self) -> bool {
+        *self == Self::Backward
+    }
+}
+
+/// Process-wide default direction, read from the environment on first use
+pub fn default_page_direction() -> PageDirection {
+    static DIRECTION: OnceLock<PageDirection> = OnceLock::new();
+    *DIRECTION.get_or_init(PageDirection::from_env)
+}
+
+/// Shorthand for `PageDirection::resolve(requested, default_page_direction())`
+pub fn resolve_direction(requested: Option<&str>) -> Result<PageDirection, AppError> {
+    PageDirection::resolve(requested, default_page_direction())
+}
+
+/// Order a listing is presented in, by `(created_at, id)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Oldest first
+    Asc,
+    /// Newest first
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    /// Parse `asc` or `desc`, ignoring case
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        if raw.eq_ignore_ascii_case("asc") {
+            Ok(Self::Asc)
+        } else if raw.eq_ignore_ascii_case("desc") {
+            Ok(Self::Desc)
+        } else {
+            Err(AppError::BadRequest(format!(
+                "order must be asc or desc, got {:?}",
+                raw
+            )))
+        }
+    }
+
+    /// The requested order, newest first when none was given
+    pub fn resolve(requested: Option<&str>) -> Result<Self, AppError> {
+        requested.map_or(Ok(Self::default()), Self::parse)
+    }
+
+    /// Whether the rows of a page moving `direction` are scanned in
+    /// ascending `(created_at, id)` order
+    pub fn scans_ascending(self, direction: PageDirection) -> bool {
+        (self == Self::Asc) != direction.is_backward()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_over_max_limit_is_clamped() {
+        let limits = PageLimits { max_page_size: 50 };
+        assert_eq!(limits.resolve(Some(10), 20).unwrap(), 10);
+        assert_eq!(limits.resolve(Some(1_000_000), 20).unwrap(), 50);
+        assert_eq!(limits.resolve(None, 20).unwrap(), 20);
+        assert_eq!(limits.resolve(None, 80).unwrap(), 50);
+    }
+
+    #[test]
+    fn test_zero_and_negative_limits_are_rejected() {
+        let limits = PageLimits::default();
+        assert!(matches!(
+            limits.resolve(Some(0), 20),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            limits.resolve(Some(-5), 20),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_direction_resolution() {
+        let forward = PageDirection::Forward;
+        assert_eq!(
+            PageDirection::resolve(None, PageDirection::Backward).unwrap(),
+            PageDirection::Backward
+        );
+        assert_eq!(
+            PageDirection::resolve(Some("BACKWARD"), forward).unwrap(),
+            PageDirection::Backward
+        );
+        assert_eq!(
+            PageDirection::resolve(Some("forward"), PageDirection::Backward).unwrap(),
+            forward
+        );
+        assert!(matches!(
+            PageDirection::resolve(Some("sideways"), forward),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_sort_order_resolution() {
+        assert_eq!(SortOrder::resolve(None).unwrap(), SortOrder::Desc);
+        assert_eq!(SortOrder::resolve(Some("ASC")).unwrap(), SortOrder::Asc);
+        assert!(matches!(
+            SortOrder::resolve(Some("oldest")),
+            Err(AppError::BadRequest(_))
+        ));
+
+        assert!(!SortOrder::Desc.scans_ascending(PageDirection::Forward));
+        assert!(SortOrder::Desc.scans_ascending(PageDirection::Backward));
+        assert!(SortOrder::Asc.scans_ascending(PageDirection::Forward));
+        assert!(!SortOrder::Asc.scans_ascending(PageDirection::Backward));
+    }
+}
@@ -0,0 +1,160 @@
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use redis::aio::{ConnectionManager, MultiplexedConnection};
+use redis::{Client, ErrorKind, RedisError, RedisResult};
+
+/// Total time allowed to connect unless `REDIS_CONNECT_TIMEOUT_MS` says otherwise
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Attempts made after the first failed one unless `REDIS_CONNECT_RETRIES` says otherwise
+pub const DEFAULT_CONNECT_RETRIES: usize = 2;
+/// Wait before the first retry; doubled after every further attempt
+pub const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// How hard to try reaching Redis before giving up. `timeout` bounds the
+/// whole attempt, retries and backoff included, so a flapping Redis costs
+/// callers at most that long. [`RedisConnectPolicy::within_timeout`] applies
+/// the same bound to commands sent once connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedisConnectPolicy {
+    pub timeout: Duration,
+    pub retries: usize,
+    pub base_delay: Duration,
+}
+
+impl Default for RedisConnectPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_CONNECT_TIMEOUT,
+            retries: DEFAULT_CONNECT_RETRIES,
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+        }
+    }
+}
+
+impl RedisConnectPolicy {
+    /// Read `REDIS_CONNECT_TIMEOUT_MS` and `REDIS_CONNECT_RETRIES`; missing or
+    /// invalid values keep the defaults.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            timeout: std::env::var("REDIS_CONNECT_TIMEOUT_MS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.timeout),
+            retries: std::env::var("REDIS_CONNECT_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.retries),
+            ..defaults
+        }
+    }
+
+    /// Open a multiplexed connection, retrying with backoff
+    pub async fn connect(&self, client: &Client) -> RedisResult<MultiplexedConnection> {
+        self.run(|| client.get_multiplexed_async_connection()).await
+    }
+
+    /// Open a [`ConnectionManager`]. Later reconnects made by the manager use
+    /// the same retry count and base delay.
+    pub async fn connect_manager(&self, client: &Client) -> RedisResult<ConnectionManager> {
+        let factor_ms = self.base_delay.as_millis() as u64 / 2;
+        self.within_timeout(ConnectionManager::new_with_backoff(
+            client.clone(),
+            2,
+            factor_ms.max(1),
+            self.retries,
+        ))
+        .await
+    }
+
+    /// Run `attempt` until it succeeds, fails `retries + 1` times, or the
+    /// timeout elapses
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> RedisResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        self.within_timeout(async {
+            let mut delay = self.base_delay;
+            let mut retries_left = self.retries;
+            loop {
+                match attempt().await {
+                    Ok(value) => return Ok(value),
+                    Err(e) if retries_left > 0 => {
+                        tracing::debug!("Redis connection attempt failed, retrying: {}", e);
+                        tokio::time::sleep(delay).await;
+                        delay *= 2;
+                        retries_left -= 1;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        })
+        .await
+    }
+
+    /// Fail `fut` with an I/O error if it takes longer than the timeout
+    pub async fn within_timeout<T>(
+        &self,
+        fut: impl Future<Output = RedisResult<T>>,
+    ) -> RedisResult<T> {
+        tokio::time::timeout(self.timeout, fut)
+            .await
+            .unwrap_or_else(|_| {
+                Err(RedisError::from((
+                    ErrorKind::IoError,
+                    "timed out waiting for Redis",
+                )))
+            })
+    }
+}
+
+/// Process-wide policy, read from the environment on first use
+pub fn redis_connect_policy() -> &'static RedisConnectPolicy {
+    static POLICY: OnceLock<RedisConnectPolicy> = OnceLock::new();
+    POLICY.get_or_init(RedisConnectPolicy::from_env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_retries_are_bounded() {
+        let policy = RedisConnectPolicy {
+            timeout: Duration::from_secs(5),
+            retries: 2,
+            base_delay: Duration::from_millis(1),
+        };
+        let attempts = AtomicUsize::new(0);
+        let result: RedisResult<()> = policy
+            .run(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(RedisError::from((ErrorKind::IoError, "refused")))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_closed_port_fails_within_timeout() {
+        let policy = RedisConnectPolicy {
+            timeout: Duration::from_millis(300),
+            retries: 10,
+            base_delay: Duration::from_millis(100),
+        };
+        let client = Client::open("redis://127.0.0.1:1").unwrap();
+
+        let start = Instant::now();
+        assert!(policy.connect(&client).await.is_err());
+        assert!(policy.connect_manager(&client).await.is_err());
+        assert!(start.elapsed() < Duration::from_millis(900));
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+
+use crate::error::AppError;
+
+/// The span of time a date filter refers to, as `[start, end)`.
+///
+/// Calendar forms cover their whole day or month; instants (`now`, `now-7d`,
+/// RFC 3339) cover one microsecond, the resolution Postgres stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlexibleDate {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl FlexibleDate {
+    fn instant(at: DateTime<Utc>) -> Self {
+        Self {
+            start: at,
+            end: at + Duration::microseconds(1),
+        }
+    }
+
+    /// Last representable instant inside the span, for inclusive `<=` bounds
+    pub fn last_instant(&self) -> DateTime<Utc> {
+        self.end - Duration::microseconds(1)
+    }
+}
+
+/// Parse a date filter relative to the current time. See [`parse_flexible_date_at`].
+pub fn parse_flexible_date(input: &str) -> Result<FlexibleDate, AppError> {
+    parse_flexible_date_at(input, Utc::now())
+}
+
+/// Parse a date filter in any of these forms:
+///
+/// - `now`, `now-7d`, `now-12h`, `now-30m`
+/// - `2025-01` (the whole month)
+/// - `2025-01-31` (the whole day)
+/// - `2025-01-31T12:00:00Z` (RFC 3339)
+pub fn parse_flexible_date_at(input: &str, now: DateTime<Utc>) -> Result<FlexibleDate, AppError> {
+    let input = input.trim();
+    parse(input, now).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Invalid date '{}': expected YYYY-MM-DD, YYYY-MM, RFC 3339 or now-N(d|h|m)",
+            input
+        ))
+    })
+}
+
+fn parse(input: &str, now: DateTime<Utc>) -> Option<FlexibleDate> {
+    if input == "now" {
+        return Some(FlexibleDate::instant(now));
+    }
+    if let Some(offset) = input.strip_prefix("now-") {
+        return now
+            .checked_sub_signed(parse_offset(offset)?)
+            .map(FlexibleDate::instant);
+    }
+
+    if input.len() == 7 {
+        let first = NaiveDate::parse_from_str(&format!("{}-01", input), "%Y-%m-%d").ok()?;
+        let next = if first.month() == 12 {
+            NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)?
+        };
+        return Some(FlexibleDate {
+            start: start_of_day(first),
+            end: start_of_day(next),
+        });
+    }
+
+    if input.len() == 10 {
+        let day = NaiveDate::parse_from_str(input, "%Y-%m-%d").ok()?;
+        return Some(FlexibleDate {
+            start: start_of_day(day),
+            end: start_of_day(day.succ_opt()?),
+        });
+    }
+
+    DateTime::parse_from_rfc3339(input)
+        .ok()
+        .map(|dt| FlexibleDate::instant(dt.with_timezone(&Utc)))
+}
+
+/// `7d`, `12h` or `30m`
+fn parse_offset(offset: &str) -> Option<Duration> {
+    let unit = offset.chars().last()?;
+    let amount: i64 = offset[..offset.len() - unit.len_utf8()].parse().ok()?;
+    if amount < 0 {
+        return None;
+    }
+    match unit {
+        'd' => Duration::try_days(amount),
+        'h' => Duration::try_hours(amount),
+        'm' => Duration::try_minutes(amount),
+        _ => None,
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("midnight is always valid")
+        .and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-03-15T10:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_relative_dates() {
+        assert_eq!(parse_flexible_date_at("now", now()).unwrap().start, now());
+        assert_eq!(
+            parse_flexible_date_at("now-7d", now()).unwrap().start,
+            utc("2025-03-08T10:30:00Z")
+        );
+        assert_eq!(
+            parse_flexible_date_at("now-2h", now()).unwrap().start,
+            utc("2025-03-15T08:30:00Z")
+        );
+        assert_eq!(
+            parse_flexible_date_at("now-45m", now()).unwrap().start,
+            utc("2025-03-15T09:45:00Z")
+        );
+    }
+
+    #[test]
+    fn test_month_covers_whole_month() {
+        let parsed = parse_flexible_date_at("2025-01", now()).unwrap();
+        assert_eq!(parsed.start, utc("2025-01-01T00:00:00Z"));
+        assert_eq!(parsed.end, utc("2025-02-01T00:00:00Z"));
+
+        let december = parse_flexible_date_at("2024-12", now()).unwrap();
+        assert_eq!(december.end, utc("2025-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_day_and_full_timestamp() {
+        let day = parse_flexible_date_at("2025-01-31", now()).unwrap();
+        assert_eq!(day.start, utc("2025-01-31T00:00:00Z"));
+        assert_eq!(day.end, utc("2025-02-01T00:00:00Z"));
+
+        let instant = parse_flexible_date_at("2025-01-31T12:00:00+02:00", now()).unwrap();
+        assert_eq!(instant.start, utc("2025-01-31T10:00:00Z"));
+        assert_eq!(instant.last_instant(), instant.start);
+    }
+
+    #[test]
+    fn test_unparseable_dates_are_bad_requests() {
+        for input in [
+            "",
+            "yesterday",
+            "now-7x",
+            "now--1d",
+            "now-d",
+            "2025-13",
+            "2025-02-30",
+        ] {
+            assert!(
+                matches!(
+                    parse_flexible_date_at(input, now()),
+                    Err(AppError::BadRequest(_))
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+}
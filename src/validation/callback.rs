@@ -0,0 +1,107 @@
+use std::sync::OnceLock;
+
+use super::{validate_enum, ValidationResult};
+
+/// `callback_type` values accepted unless `CALLBACK_TYPES` says otherwise
+pub const DEFAULT_CALLBACK_TYPES: &[&str] = &["deposit", "withdraw", "withdrawal", "refund"];
+
+/// `callback_status` values accepted unless `CALLBACK_STATUSES` says otherwise
+pub const DEFAULT_CALLBACK_STATUSES: &[&str] = &[
+    "pending",
+    "processing",
+    "completed",
+    "failed",
+    "refunded",
+    "expired",
+    "error",
+];
+
+/// Allowed values for the anchor's `callback_type` and `callback_status`,
+/// so typos such as `depositt` are rejected instead of stored.
+#[derive(Debug, Clone)]
+pub struct CallbackVocabulary {
+    types: Vec<String>,
+    statuses: Vec<String>,
+}
+
+impl Default for CallbackVocabulary {
+    fn default() -> Self {
+        Self::new(DEFAULT_CALLBACK_TYPES, DEFAULT_CALLBACK_STATUSES)
+    }
+}
+
+impl CallbackVocabulary {
+    pub fn new(types: &[&str], statuses: &[&str]) -> Self {
+        Self {
+            types: types.iter().map(|s| s.to_string()).collect(),
+            statuses: statuses.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Read comma-separated `CALLBACK_TYPES` and `CALLBACK_STATUSES`; an unset
+    /// or empty variable keeps the defaults for that field.
+    pub fn from_env() -> Self {
+        let mut vocabulary = Self::default();
+        if let Some(types) = read_list("CALLBACK_TYPES") {
+            vocabulary.types = types;
+        }
+        if let Some(statuses) = read_list("CALLBACK_STATUSES") {
+            vocabulary.statuses = statuses;
+        }
+        vocabulary
+    }
+
+    pub fn check_type(&self, callback_type: &str) -> ValidationResult {
+        validate_enum("callback_type", callback_type, &as_strs(&self.types))
+    }
+
+    pub fn check_status(&self, callback_status: &str) -> ValidationResult {
+        validate_enum("callback_status", callback_status, &as_strs(&self.statuses))
+    }
+}
+
+fn read_list(var: &str) -> Option<Vec<String>> {
+    let values: Vec<String> = std::env::var(var)
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect();
+    (!values.is_empty()).then_some(values)
+}
+
+fn as_strs(values: &[String]) -> Vec<&str> {
+    values.iter().map(String::as_str).collect()
+}
+
+/// Process-wide vocabulary, read from the environment on first use
+pub fn callback_vocabulary() -> &'static CallbackVocabulary {
+    static VOCABULARY: OnceLock<CallbackVocabulary> = OnceLock::new();
+    VOCABULARY.get_or_init(CallbackVocabulary::from_env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_vocabulary() {
+        let vocabulary = CallbackVocabulary::default();
+        assert!(vocabulary.check_type("deposit").is_ok());
+        assert!(vocabulary.check_status("completed").is_ok());
+
+        let err = vocabulary.check_type("depositt").unwrap_err();
+        assert_eq!(err.field, "callback_type");
+        assert!(vocabulary.check_status("done").is_err());
+    }
+
+    #[test]
+    fn test_configured_vocabulary_replaces_defaults() {
+        let vocabulary = CallbackVocabulary::new(&["payout"], &["settled"]);
+        assert!(vocabulary.check_type("payout").is_ok());
+        assert!(vocabulary.check_status("settled").is_ok());
+        assert!(vocabulary.check_type("deposit").is_err());
+        assert!(vocabulary.check_status("completed").is_err());
+    }
+}
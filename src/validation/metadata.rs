@@ -0,0 +1,158 @@
+use std::sync::OnceLock;
+
+use jsonschema::JSONSchema;
+use serde_json::Value;
+
+use crate::error::AppError;
+
+/// Largest serialized `metadata` accepted unless `METADATA_MAX_BYTES` says otherwise
+pub const DEFAULT_METADATA_MAX_BYTES: usize = 16 * 1024;
+
+/// Size cap and optional JSON schema applied to callback `metadata`.
+///
+/// The schema is read from the file named by `METADATA_SCHEMA_PATH`; without
+/// one only the size cap applies.
+pub struct MetadataValidator {
+    max_bytes: usize,
+    schema: Option<JSONSchema>,
+}
+
+impl Default for MetadataValidator {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_METADATA_MAX_BYTES,
+            schema: None,
+        }
+    }
+}
+
+impl MetadataValidator {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            schema: None,
+        }
+    }
+
+    /// Validate against `schema`, failing if it is not a valid JSON schema
+    pub fn with_schema(mut self, schema: &Value) -> Result<Self, String> {
+        let compiled =
+            JSONSchema::compile(schema).map_err(|e| format!("invalid metadata schema: {}", e))?;
+        self.schema = Some(compiled);
+        Ok(self)
+    }
+
+    /// Read `METADATA_MAX_BYTES` and `METADATA_SCHEMA_PATH`. An unreadable or
+    /// invalid schema is logged and skipped so startup is not blocked.
+    pub fn from_env() -> Self {
+        let max_bytes = std::env::var("METADATA_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_METADATA_MAX_BYTES);
+
+        let Ok(path) = std::env::var("METADATA_SCHEMA_PATH") else {
+            return Self::new(max_bytes);
+        };
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| serde_json::from_str::<Value>(&raw).map_err(|e| e.to_string()))
+            .and_then(|schema| Self::new(max_bytes).with_schema(&schema))
+            .unwrap_or_else(|e| {
+                tracing::warn!("Ignoring METADATA_SCHEMA_PATH '{}': {}", path, e);
+                Self::new(max_bytes)
+            })
+    }
+
+    pub fn check(&self, metadata: &Value) -> Result<(), AppError> {
+        let size = serde_json::to_vec(metadata)
+            .map_err(|e| AppError::Validation(format!("metadata: {}", e)))?
+            .len();
+        if size > self.max_bytes {
+            return Err(AppError::Validation(format!(
+                "metadata: {} bytes exceeds the limit of {} bytes",
+                size, self.max_bytes
+            )));
+        }
+
+        if let Some(schema) = &self.schema {
+            if let Err(errors) = schema.validate(metadata) {
+                let details: Vec<String> = errors
+                    .map(|e| {
+                        let path = e.instance_path.to_string();
+                        if path.is_empty() {
+                            e.to_string()
+                        } else {
+                            format!("{}: {}", path, e)
+                        }
+                    })
+                    .collect();
+                return Err(AppError::Validation(format!(
+                    "metadata does not match schema: {}",
+                    details.join("; ")
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Process-wide validator, read from the environment on first use
+pub fn metadata_validator() -> &'static MetadataValidator {
+    static VALIDATOR: OnceLock<MetadataValidator> = OnceLock::new();
+    VALIDATOR.get_or_init(MetadataValidator::from_env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validator() -> MetadataValidator {
+        MetadataValidator::default()
+            .with_schema(&json!({
+                "type": "object",
+                "properties": {
+                    "order_id": { "type": "string" },
+                    "priority": { "type": "integer", "minimum": 0 }
+                },
+                "required": ["order_id"]
+            }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_conforming_metadata_passes() {
+        assert!(validator()
+            .check(&json!({ "order_id": "A-1", "priority": 2 }))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_non_conforming_metadata_fails() {
+        let err = validator().check(&json!({ "priority": -1 })).unwrap_err();
+        match err {
+            AppError::Validation(message) => {
+                assert!(message.contains("does not match schema"), "{}", message);
+                assert!(message.contains("/priority"), "{}", message);
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_oversized_metadata_is_rejected() {
+        let big = "x".repeat(DEFAULT_METADATA_MAX_BYTES);
+        let err = MetadataValidator::default()
+            .check(&json!({ "blob": big }))
+            .unwrap_err();
+        assert!(matches!(err, AppError::Validation(ref m) if m.contains("exceeds the limit")));
+    }
+
+    #[test]
+    fn test_invalid_schema_is_reported() {
+        assert!(MetadataValidator::default()
+            .with_schema(&json!({ "type": 12 }))
+            .is_err());
+    }
+}
@@ -1,6 +1,16 @@
+use crate::error::AppError;
 use bigdecimal::BigDecimal;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+pub mod callback;
+pub mod metadata;
+
+pub use callback::{callback_vocabulary, CallbackVocabulary};
+pub use metadata::{metadata_validator, MetadataValidator};
 
 pub const STELLAR_ACCOUNT_LEN: usize = 56;
 pub const ASSET_CODE_MAX_LEN: usize = 12;
@@ -9,6 +19,10 @@ pub const CALLBACK_TYPE_MAX_LEN: usize = 20;
 pub const CALLBACK_STATUS_MAX_LEN: usize = 20;
 pub const AMOUNT_INPUT_MAX_LEN: usize = 64;
 pub const ALLOWED_ASSET_CODES: &[&str] = &["USD"];
+/// Stellar limits a text memo to 28 bytes of UTF-8
+pub const MEMO_TEXT_MAX_BYTES: usize = 28;
+/// A hash memo is 32 bytes, written as hex
+pub const MEMO_HASH_HEX_LEN: usize = 64;
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -42,24 +56,53 @@ impl std::error::Error for ValidationError {}
 
 pub type ValidationResult = Result<(), ValidationError>;
 
+/// How much of a string's whitespace survives sanitizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizePolicy {
+    /// Trim and collapse every whitespace run, including newlines, to one
+    /// space. For identifiers and other single-token fields.
+    Collapse,
+    /// Keep newlines, tabs and spacing as sent; only other control
+    /// characters are dropped. For memos and other free text.
+    PreserveWhitespace,
+}
+
+impl SanitizePolicy {
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            SanitizePolicy::Collapse => value
+                .chars()
+                .filter_map(|ch| {
+                    if ch.is_control() {
+                        if ch.is_whitespace() {
+                            Some(' ')
+                        } else {
+                            None
+                        }
+                    } else {
+                        Some(ch)
+                    }
+                })
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            // `\r` goes too, so CRLF line endings become `\n`
+            SanitizePolicy::PreserveWhitespace => value
+                .chars()
+                .filter(|ch| !ch.is_control() || matches!(ch, '\n' | '\t'))
+                .collect(),
+        }
+    }
+}
+
 pub fn sanitize_string(value: &str) -> String {
-    value
-        .chars()
-        .filter_map(|ch| {
-            if ch.is_control() {
-                if ch.is_whitespace() {
-                    Some(' ')
-                } else {
-                    None
-                }
-            } else {
-                Some(ch)
-            }
-        })
-        .collect::<String>()
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
+    SanitizePolicy::Collapse.apply(value)
+}
+
+/// Sanitize free text such as a memo without flattening its lines
+pub fn sanitize_text(value: &str) -> String {
+    SanitizePolicy::PreserveWhitespace.apply(value)
 }
 
 pub fn validate_required(field: &'static str, value: &str) -> ValidationResult {
@@ -123,6 +166,43 @@ pub fn validate_stellar_address(stellar_address: &str) -> ValidationResult {
     Ok(())
 }
 
+/// Check that `memo` fits its declared `memo_type`: text memos are at most
+/// 28 bytes, hash memos are 32 bytes of hex, and id memos are a u64
+pub fn validate_memo(memo: &str, memo_type: &str) -> ValidationResult {
+    match memo_type {
+        "text" if memo.len() > MEMO_TEXT_MAX_BYTES => Err(ValidationError::new(
+            "memo",
+            format!(
+                "text memo must be at most {} bytes, got {}",
+                MEMO_TEXT_MAX_BYTES,
+                memo.len()
+            ),
+        )),
+        "hash"
+            if memo.len() != MEMO_HASH_HEX_LEN || !memo.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Err(ValidationError::new(
+                "memo",
+                format!(
+                    "hash memo must be {} hex characters (32 bytes)",
+                    MEMO_HASH_HEX_LEN
+                ),
+            ))
+        }
+        "id" if memo.parse::<u64>().is_err() => Err(ValidationError::new(
+            "memo",
+            "id memo must be an unsigned 64-bit integer",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// An asset issuer is a Stellar account, reported under its own field name
+pub fn validate_asset_issuer(issuer: &str) -> ValidationResult {
+    validate_stellar_address(issuer)
+        .map_err(|err| ValidationError::new("asset_issuer", err.message))
+}
+
 pub fn validate_stellar_account(account: &str) -> ValidationResult {
     validate_stellar_address(account)
 }
@@ -155,11 +235,90 @@ pub fn validate_positive_amount(amount: &BigDecimal) -> ValidationResult {
     Ok(())
 }
 
+/// Per-asset inclusive amount bounds, configured via `AMOUNT_LIMITS`
+/// as `ASSET:MIN:MAX` entries separated by commas, e.g. `USD:1:10000,EUR:1:9000`.
+#[derive(Debug, Clone, Default)]
+pub struct AmountLimits {
+    limits: HashMap<String, (BigDecimal, BigDecimal)>,
+}
+
+impl AmountLimits {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut limits = HashMap::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+            let [asset, min, max] = parts.as_slice() else {
+                return Err(format!(
+                    "invalid amount limit '{}': expected ASSET:MIN:MAX",
+                    entry
+                ));
+            };
+            let min = BigDecimal::from_str(min)
+                .map_err(|_| format!("invalid minimum in amount limit '{}'", entry))?;
+            let max = BigDecimal::from_str(max)
+                .map_err(|_| format!("invalid maximum in amount limit '{}'", entry))?;
+            if min > max {
+                return Err(format!(
+                    "invalid amount limit '{}': minimum exceeds maximum",
+                    entry
+                ));
+            }
+            limits.insert(asset.to_string(), (min, max));
+        }
+
+        Ok(Self { limits })
+    }
+
+    /// Read `AMOUNT_LIMITS`; an unset or invalid value means no per-asset limits.
+    pub fn from_env() -> Self {
+        match std::env::var("AMOUNT_LIMITS") {
+            Ok(spec) => Self::parse(&spec).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring AMOUNT_LIMITS: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn get(&self, asset_code: &str) -> Option<&(BigDecimal, BigDecimal)> {
+        self.limits.get(asset_code)
+    }
+
+    /// Check `amount` against the bounds for `asset_code`. Assets without
+    /// configured limits are accepted.
+    pub fn check(&self, asset_code: &str, amount: &BigDecimal) -> Result<(), AppError> {
+        let Some((min, max)) = self.get(asset_code) else {
+            return Ok(());
+        };
+
+        if amount < min {
+            return Err(AppError::AmountBelowMinimum(format!(
+                "{} {} is below the minimum of {}",
+                amount, asset_code, min
+            )));
+        }
+        if amount > max {
+            return Err(AppError::AmountAboveMaximum(format!(
+                "{} {} is above the maximum of {}",
+                amount, asset_code, max
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Process-wide limits, read from the environment on first use
+pub fn amount_limits() -> &'static AmountLimits {
+    static LIMITS: OnceLock<AmountLimits> = OnceLock::new();
+    LIMITS.get_or_init(AmountLimits::from_env)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde::Deserialize;
-    use std::str::FromStr;
 
     fn valid_stellar_address() -> String {
         "G".to_owned() + &"A".repeat(55)
@@ -191,6 +350,18 @@ mod tests {
         assert_eq!(sanitize_string("ab\u{0000}cd\u{0007}"), "abcd");
     }
 
+    #[test]
+    fn sanitize_text_keeps_line_structure() {
+        let memo = "Invoice 1042\r\n\tline one\u{0000}\n\n  line two\u{001b}[31m";
+        assert_eq!(
+            sanitize_text(memo),
+            "Invoice 1042\n\tline one\n\n  line two[31m"
+        );
+        assert_eq!(sanitize_text("single"), "single");
+        // Identifiers still collapse
+        assert_eq!(sanitize_string("Invoice\n\tline"), "Invoice line");
+    }
+
     #[test]
     fn validates_stellar_address() {
         assert!(validate_stellar_address(&valid_stellar_address()).is_ok());
@@ -222,6 +393,25 @@ mod tests {
         assert!(validate_positive_amount(&negative).is_err());
     }
 
+    #[test]
+    fn validates_memo_against_memo_type() {
+        assert!(validate_memo("payment for invoice #1042", "text").is_ok());
+        assert!(validate_memo(&"a".repeat(28), "text").is_ok());
+        // Length is counted in bytes, so 15 two-byte characters are too long
+        assert!(validate_memo(&"é".repeat(15), "text").is_err());
+        assert!(validate_memo(&"a".repeat(29), "text").is_err());
+
+        assert!(validate_memo(&"ab".repeat(32), "hash").is_ok());
+        assert!(validate_memo(&"AB".repeat(32), "hash").is_ok());
+        assert!(validate_memo("abc123def456", "hash").is_err());
+        assert!(validate_memo(&"zz".repeat(32), "hash").is_err());
+
+        assert!(validate_memo("18446744073709551615", "id").is_ok());
+        assert!(validate_memo("18446744073709551616", "id").is_err());
+        assert!(validate_memo("-1", "id").is_err());
+        assert!(validate_memo("invoice-7", "id").is_err());
+    }
+
     #[test]
     fn strict_payload_accepts_known_fields() {
         #[derive(Debug, Deserialize, PartialEq, Eq)]
@@ -253,4 +443,55 @@ mod tests {
         let parsed = serde_json::from_str::<StrictPayload<Payload>>(r#"{"id":"tx-1","extra":"x"}"#);
         assert!(parsed.is_err());
     }
+
+    fn sample_limits() -> AmountLimits {
+        AmountLimits::parse("USD:1:10000, EUR:5:500").expect("valid limits")
+    }
+
+    #[test]
+    fn parses_amount_limits() {
+        let limits = sample_limits();
+        let (min, max) = limits.get("USD").expect("USD limits");
+        assert_eq!(min, &BigDecimal::from(1));
+        assert_eq!(max, &BigDecimal::from(10000));
+        assert!(limits.get("GBP").is_none());
+
+        assert!(AmountLimits::parse("USD:1").is_err());
+        assert!(AmountLimits::parse("USD:abc:10").is_err());
+        assert!(AmountLimits::parse("USD:10:1").is_err());
+        assert!(AmountLimits::parse("").is_ok());
+    }
+
+    #[test]
+    fn rejects_amount_below_minimum() {
+        let limits = sample_limits();
+        let amount = BigDecimal::from_str("0.50").unwrap();
+        let err = limits.check("USD", &amount).unwrap_err();
+        assert!(matches!(err, AppError::AmountBelowMinimum(_)));
+        assert!(err.to_string().contains("minimum of 1"));
+
+        let err = limits.check("EUR", &BigDecimal::from(4)).unwrap_err();
+        assert!(matches!(err, AppError::AmountBelowMinimum(_)));
+    }
+
+    #[test]
+    fn rejects_amount_above_maximum() {
+        let limits = sample_limits();
+        let err = limits.check("USD", &BigDecimal::from(10001)).unwrap_err();
+        assert!(matches!(err, AppError::AmountAboveMaximum(_)));
+        assert!(err.to_string().contains("maximum of 10000"));
+
+        let err = limits.check("EUR", &BigDecimal::from(501)).unwrap_err();
+        assert!(matches!(err, AppError::AmountAboveMaximum(_)));
+    }
+
+    #[test]
+    fn accepts_amount_within_range() {
+        let limits = sample_limits();
+        assert!(limits.check("USD", &BigDecimal::from(1)).is_ok());
+        assert!(limits.check("USD", &BigDecimal::from(10000)).is_ok());
+        assert!(limits.check("EUR", &BigDecimal::from(250)).is_ok());
+        // Assets without configured limits are not restricted
+        assert!(limits.check("GBP", &BigDecimal::from(1_000_000)).is_ok());
+    }
 }
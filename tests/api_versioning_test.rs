@@ -1,7 +1,6 @@
+mod common;
+
 use reqwest::StatusCode;
-use sqlx::{migrate::Migrator, PgPool};
-use std::path::Path;
-use synapse_core::{create_app, AppState};
 use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::postgres::Postgres;
 
@@ -15,45 +14,9 @@ async fn test_api_versioning_headers() {
         host_port
     );
 
-    // Run migrations
-    let pool = PgPool::connect(&database_url).await.unwrap();
-    let migrator = Migrator::new(Path::join(
-        Path::new(env!("CARGO_MANIFEST_DIR")),
-        "migrations",
-    ))
-    .await
-    .unwrap();
-    migrator.run(&pool).await.unwrap();
-
-    let (tx, _rx) = tokio::sync::broadcast::channel(100);
-
-    // Start App
-    let app_state = AppState {
-        db: pool.clone(),
-        pool_manager: synapse_core::db::pool_manager::PoolManager::new(&database_url, None)
-            .await
-            .unwrap(),
-        horizon_client: synapse_core::stellar::HorizonClient::new(
-            "https://horizon-testnet.stellar.org".to_string(),
-        ),
-        feature_flags: synapse_core::services::feature_flags::FeatureFlagService::new(pool.clone()),
-        redis_url: "redis://localhost:6379".to_string(),
-        start_time: std::time::Instant::now(),
-        readiness: synapse_core::ReadinessState::new(),
-        tx_broadcast: tx,
-    };
-    let app = create_app(app_state);
-
-    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
-    let actual_addr = server.local_addr();
-
-    tokio::spawn(async move {
-        server.await.unwrap();
-    });
-
-    let client = reqwest::Client::new();
-    let base_url = format!("http://{}", actual_addr);
+    let pool = common::setup_db(&database_url).await;
+    let base_url = common::spawn_app(&database_url, pool).await;
+    let client = common::client();
 
     // 1. Test V1 health (expect deprecation headers)
     let res = client
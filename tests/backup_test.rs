@@ -1,3 +1,5 @@
+mod common;
+
 use anyhow::Result;
 use tempfile::TempDir;
 
@@ -182,3 +184,185 @@ async fn test_backup_checksum_verification() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_verify_restore_valid_backup() -> Result<()> {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return Ok(());
+    };
+    let temp_dir = TempDir::new()?;
+
+    let service = synapse_core::services::backup::BackupService::new(
+        database_url,
+        temp_dir.path().to_path_buf(),
+        Some("test-encryption-key-32-chars!!".to_string()),
+    );
+
+    let metadata = service
+        .create_backup(synapse_core::services::backup::BackupType::Hourly)
+        .await?;
+
+    let report = service.verify_restore(&metadata.filename).await?;
+    assert_eq!(report.filename, metadata.filename);
+    assert!(report.scratch_database.starts_with("synapse_verify_"));
+    let migrations = report
+        .tables
+        .iter()
+        .find(|t| t.table == "_sqlx_migrations")
+        .unwrap();
+    assert!(migrations.row_count > 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_restore_corrupted_backup_fails() -> Result<()> {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return Ok(());
+    };
+    let temp_dir = TempDir::new()?;
+
+    let service = synapse_core::services::backup::BackupService::new(
+        database_url,
+        temp_dir.path().to_path_buf(),
+        None,
+    );
+
+    let metadata = service
+        .create_backup(synapse_core::services::backup::BackupType::Hourly)
+        .await?;
+
+    // Truncate the backup so neither the checksum nor gunzip can succeed
+    let backup_path = temp_dir.path().join(&metadata.filename);
+    let bytes = std::fs::read(&backup_path)?;
+    std::fs::write(&backup_path, &bytes[..bytes.len() / 2])?;
+
+    assert!(service.verify_restore(&metadata.filename).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_all_detects_tampered_backup() -> Result<()> {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return Ok(());
+    };
+    let temp_dir = TempDir::new()?;
+
+    let service = synapse_core::services::backup::BackupService::new(
+        database_url,
+        temp_dir.path().to_path_buf(),
+        None,
+    );
+
+    let intact = service
+        .create_backup(synapse_core::services::backup::BackupType::Hourly)
+        .await?;
+    let tampered = service
+        .create_backup(synapse_core::services::backup::BackupType::Daily)
+        .await?;
+    assert!(service.verify_all().await?.is_empty());
+
+    // Flip one byte in place, keeping the size the same
+    let backup_path = temp_dir.path().join(&tampered.filename);
+    let mut bytes = std::fs::read(&backup_path)?;
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    std::fs::write(&backup_path, &bytes)?;
+
+    let corrupt = service.verify_all().await?;
+    assert_eq!(corrupt, vec![tampered.filename.clone()]);
+    assert!(!corrupt.contains(&intact.filename));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_custom_format_backup_restores() -> Result<()> {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return Ok(());
+    };
+    let temp_dir = TempDir::new()?;
+
+    let service = synapse_core::services::backup::BackupService::new(
+        database_url,
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .with_restore_jobs(2);
+
+    let metadata = service
+        .create_backup(synapse_core::services::backup::BackupType::Daily)
+        .await?;
+    assert_eq!(
+        metadata.format,
+        synapse_core::services::backup::BackupFormat::Custom
+    );
+
+    let report = service.verify_restore(&metadata.filename).await?;
+    assert!(report.tables.iter().any(|t| t.table == "transactions"));
+
+    Ok(())
+}
+
+#[test]
+fn test_legacy_metadata_defaults_to_plain_format() {
+    let json = r#"{
+        "filename": "backup_hourly_20240101_000000.sql.gz",
+        "backup_type": "Hourly",
+        "timestamp": "2024-01-01T00:00:00Z",
+        "size_bytes": 1024,
+        "compressed": true,
+        "encrypted": false,
+        "checksum": "abc"
+    }"#;
+
+    let metadata: synapse_core::services::backup::BackupMetadata =
+        serde_json::from_str(json).unwrap();
+    assert_eq!(
+        metadata.format,
+        synapse_core::services::backup::BackupFormat::Plain
+    );
+}
+
+#[tokio::test]
+async fn test_backup_scheduler_creates_backup_on_tick() -> Result<()> {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return Ok(());
+    };
+    let temp_dir = TempDir::new()?;
+
+    let service = synapse_core::services::backup::BackupService::new(
+        database_url.clone(),
+        temp_dir.path().to_path_buf(),
+        None,
+    );
+    let scheduler = synapse_core::services::backup::BackupScheduler::new(
+        service,
+        synapse_core::services::backup::BackupType::Hourly,
+    )
+    .with_interval(std::time::Duration::from_millis(200));
+    let handle = tokio::spawn(scheduler.run());
+
+    let reader = synapse_core::services::backup::BackupService::new(
+        database_url,
+        temp_dir.path().to_path_buf(),
+        None,
+    );
+    let last_success = || {
+        synapse_core::metrics::registry()
+            .gauge(synapse_core::services::backup::BACKUP_LAST_SUCCESS_METRIC)
+    };
+    for _ in 0..50 {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        if last_success().is_some() {
+            break;
+        }
+    }
+    handle.abort();
+
+    assert!(last_success().unwrap() > 0.0);
+    assert!(!reader.list_backups().await?.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::{setup_db, spawn_app};
+use reqwest::StatusCode;
+use std::sync::Arc;
+use synapse_core::config::Config;
+use synapse_core::create_app;
+use synapse_core::middleware::body_limit::BodyLimits;
+
+#[tokio::test]
+async fn test_oversized_bodies_are_rejected_with_413() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let mut app_state = common::test_state(&database_url, pool).await;
+    app_state.config = Arc::new(Config {
+        body_limits: BodyLimits {
+            max_body_bytes: 4096,
+            max_batch_body_bytes: 65536,
+        },
+        ..Config::default()
+    });
+    let base_url = common::serve(create_app(app_state)).await;
+    let client = common::client();
+    let oversized = serde_json::json!({ "padding": "x".repeat(8192) }).to_string();
+
+    let res = client
+        .post(format!("{}/callback", base_url))
+        .header("content-type", "application/json")
+        .body(oversized.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_BAD_REQUEST_002");
+
+    // The same body fits under the batch endpoint's higher cap, so it gets
+    // as far as payload validation
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .header("content-type", "application/json")
+        .body(oversized)
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let huge_batch = serde_json::json!({ "padding": "x".repeat(70_000) }).to_string();
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .header("content-type", "application/json")
+        .body(huge_batch)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_malformed_json_gets_structured_error() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    for (path, body, code) in [
+        (
+            "/callback",
+            r#"{"stellar_account":"GAAA","amount":"10"#,
+            "ERR_WEBHOOK_002",
+        ),
+        (
+            "/graphql",
+            r#"{"query":"{ transactions"#,
+            "ERR_BAD_REQUEST_001",
+        ),
+    ] {
+        let res = client
+            .post(format!("{}{}", base_url, path))
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", path);
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(body["code"], code, "{}", path);
+        assert!(body["error"].as_str().unwrap().contains("invalid JSON"));
+    }
+
+    // A body of the wrong shape names the field
+    let res = client
+        .post(format!("{}/callback", base_url))
+        .json(&serde_json::json!({
+            "stellar_account": "GAAA",
+            "amount": 10,
+            "asset_code": "USD"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_WEBHOOK_002");
+    assert!(body["error"].as_str().unwrap().contains("amount"));
+}
@@ -0,0 +1,201 @@
+//! Fixtures shared by the integration tests. Each test binary uses only some
+//! of them.
+#![allow(dead_code)]
+
+use axum::Router;
+use sqlx::types::BigDecimal;
+use sqlx::{migrate::Migrator, PgPool};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use synapse_core::config::Config;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::pool_manager::PoolManager;
+use synapse_core::db::queries;
+use synapse_core::middleware::idempotency::IdempotencyService;
+use synapse_core::services::feature_flags::FeatureFlagService;
+use synapse_core::services::transaction::{transition_status, STATUS_COMPLETED, STATUS_PENDING};
+use synapse_core::{create_app, AppState};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+/// `DATABASE_URL`, or `None` after noting that the calling test is skipped
+pub fn database_url_or_skip() -> Option<String> {
+    match std::env::var("DATABASE_URL") {
+        Ok(url) => Some(url),
+        Err(_) => {
+            println!(
+                "Skipping {}: DATABASE_URL not set",
+                std::thread::current().name().unwrap_or("test")
+            );
+            None
+        }
+    }
+}
+
+/// Connect to `database_url`, run the migrations and make sure the current
+/// month's transactions partition exists
+pub async fn setup_db(database_url: &str) -> PgPool {
+    let pool = PgPool::connect(database_url).await.unwrap();
+    migrate(&pool).await;
+    create_partition(&pool, "NOW()").await;
+    pool
+}
+
+pub async fn migrate(pool: &PgPool) {
+    let migrator = Migrator::new(Path::join(
+        Path::new(env!("CARGO_MANIFEST_DIR")),
+        "migrations",
+    ))
+    .await
+    .unwrap();
+    migrator.run(pool).await.unwrap();
+}
+
+/// Create the monthly transactions partition holding the instant `at`, an SQL
+/// timestamp expression such as `NOW() - INTERVAL '60 days'`
+pub async fn create_partition(pool: &PgPool, at: &str) {
+    let _ = sqlx::query(&format!(
+        r#"
+        DO $$
+        DECLARE
+            partition_date DATE;
+            partition_name TEXT;
+            start_date TEXT;
+            end_date TEXT;
+        BEGIN
+            partition_date := DATE_TRUNC('month', {});
+            partition_name := 'transactions_y' || TO_CHAR(partition_date, 'YYYY') || 'm' || TO_CHAR(partition_date, 'MM');
+            start_date := TO_CHAR(partition_date, 'YYYY-MM-DD');
+            end_date := TO_CHAR(partition_date + INTERVAL '1 month', 'YYYY-MM-DD');
+
+            IF NOT EXISTS (SELECT 1 FROM pg_class WHERE relname = partition_name) THEN
+                EXECUTE format(
+                    'CREATE TABLE %I PARTITION OF transactions FOR VALUES FROM (%L) TO (%L)',
+                    partition_name, start_date, end_date
+                );
+            END IF;
+        END $$;
+        "#,
+        at
+    ))
+    .execute(pool)
+    .await;
+}
+
+/// Stellar account the fixture transactions are sent from
+pub const TEST_ACCOUNT: &str = "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+/// Asset code no other test uses, so settlement runs and exports filtered on
+/// it only see the calling test's rows
+pub fn unique_asset(prefix: &str) -> String {
+    format!("{}{}", prefix, &Uuid::new_v4().simple().to_string()[..8]).to_uppercase()
+}
+
+/// Unsaved pending deposit of `amount` `asset_code` from [`TEST_ACCOUNT`].
+/// Adjust its fields before inserting it.
+pub fn deposit(asset_code: &str, amount: &str) -> Transaction {
+    Transaction::new(
+        TEST_ACCOUNT.to_string(),
+        BigDecimal::from_str(amount).unwrap(),
+        asset_code.to_string(),
+        None,
+        Some("deposit".to_string()),
+        None,
+        None,
+        None,
+        None,
+    )
+}
+
+/// Insert a pending deposit of `amount` `asset_code`
+pub async fn pending_transaction(pool: &PgPool, asset_code: &str, amount: &str) -> Transaction {
+    queries::insert_transaction(pool, &deposit(asset_code, amount))
+        .await
+        .unwrap()
+}
+
+/// Insert `tx` and complete it, making it eligible for settlement
+pub async fn insert_completed(pool: &PgPool, tx: &Transaction) -> Transaction {
+    let tx = queries::insert_transaction(pool, tx).await.unwrap();
+    transition_status(pool, tx.id, STATUS_PENDING, STATUS_COMPLETED)
+        .await
+        .unwrap()
+}
+
+/// Insert a completed deposit of `amount` `asset_code`
+pub async fn completed_transaction(pool: &PgPool, asset_code: &str, amount: &str) -> Transaction {
+    insert_completed(pool, &deposit(asset_code, amount)).await
+}
+
+/// Application state over `pool`, with a primary-only pool manager and
+/// default settings
+pub async fn test_state(database_url: &str, pool: PgPool) -> AppState {
+    let pool_manager = PoolManager::new(database_url, None).await.unwrap();
+    test_state_with_pool_manager(pool, pool_manager)
+}
+
+pub fn test_state_with_pool_manager(pool: PgPool, pool_manager: PoolManager) -> AppState {
+    let (tx_broadcast, _) = tokio::sync::broadcast::channel(100);
+    AppState {
+        db: pool.clone(),
+        pool_manager,
+        horizon_client: synapse_core::stellar::HorizonClient::new(
+            "https://horizon-testnet.stellar.org".to_string(),
+        ),
+        feature_flags: FeatureFlagService::new(pool),
+        redis_url: "redis://localhost:6379".to_string(),
+        start_time: std::time::Instant::now(),
+        tx_broadcast,
+        ws_connections: synapse_core::handlers::ws::WsConnections::default(),
+        readiness: synapse_core::ReadinessState::new(),
+        idempotency: IdempotencyService::new("redis://localhost:6379").unwrap(),
+        config: Arc::new(Config::default()),
+    }
+}
+
+/// `Authorization` value for the default admin key, which sees every
+/// partner's transactions
+pub const ADMIN_AUTH: &str = "Bearer admin-secret-key";
+
+/// HTTP client sending [`ADMIN_AUTH`] unless a request sets its own
+/// `Authorization`. Callers without credentials are refused by the callback
+/// and transaction endpoints, as `REQUIRE_API_KEY` defaults to on.
+pub fn client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        reqwest::header::HeaderValue::from_static(ADMIN_AUTH),
+    );
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
+/// Serve `app` on a free local port and return its base URL
+pub async fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        axum::Server::from_tcp(listener.into_std().unwrap())
+            .unwrap()
+            .serve(app.into_make_service())
+            .await
+            .unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+/// Serve the full application over `pool` and return its base URL
+pub async fn spawn_app(database_url: &str, pool: PgPool) -> String {
+    serve(create_app(test_state(database_url, pool).await)).await
+}
+
+/// Serve the full application over `pool`, routing reads through
+/// `pool_manager`, and return its base URL
+pub async fn spawn_app_with_pool_manager(pool: PgPool, pool_manager: PoolManager) -> String {
+    serve(create_app(test_state_with_pool_manager(pool, pool_manager))).await
+}
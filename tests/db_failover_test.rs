@@ -1,13 +1,21 @@
-use synapse_core::db::pool_manager::PoolManager;
+mod common;
+
+use common::{pending_transaction, setup_db, spawn_app, spawn_app_with_pool_manager};
+use reqwest::{header, StatusCode};
+use sqlx::postgres::PgPoolOptions;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use synapse_core::config::Config;
+use synapse_core::create_app;
+use synapse_core::db::pool_manager::{PoolManager, REPLICA_LAG_METRIC, REPLICA_TIMEOUT_METRIC};
+use synapse_core::metrics::{self, POOL_ACQUIRE_TIMEOUTS_METRIC};
+use uuid::Uuid;
 
 #[tokio::test]
 async fn test_pool_manager_primary_only() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping DB failover test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
     let pool_manager = PoolManager::new(&database_url, None)
@@ -27,12 +35,8 @@ async fn test_pool_manager_primary_only() {
 
 #[tokio::test]
 async fn test_pool_manager_with_replica() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping DB failover test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
     let replica_url = std::env::var("DATABASE_REPLICA_URL").ok();
@@ -58,12 +62,8 @@ async fn test_pool_manager_with_replica() {
 
 #[tokio::test]
 async fn test_query_routing() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping DB failover test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
     let pool_manager = PoolManager::new(&database_url, None)
@@ -85,12 +85,8 @@ async fn test_query_routing() {
 
 #[tokio::test]
 async fn test_health_check_with_invalid_replica() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping DB failover test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
     // Try to create with invalid replica URL
@@ -103,3 +99,180 @@ async fn test_health_check_with_invalid_replica() {
     // Should fail to connect to invalid replica
     assert!(result.is_err());
 }
+
+#[tokio::test]
+async fn test_health_check_publishes_replica_lag() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    // A primary standing in as its own replica reports zero lag
+    let pool_manager = PoolManager::new(&database_url, Some(&database_url))
+        .await
+        .expect("Failed to create pool manager");
+    pool_manager.check_health().await;
+
+    let lag = synapse_core::metrics::registry()
+        .labelled_gauge(REPLICA_LAG_METRIC, &[("pool", "replica_1")])
+        .expect("replica lag gauge recorded");
+    assert_eq!(lag, 0.0);
+    assert!(synapse_core::metrics::registry()
+        .render()
+        .contains("db_replica_lag_seconds{pool=\"replica_1\"} 0"));
+
+    // Within the threshold the replica keeps serving reads
+    let read_pool = pool_manager.get_read_pool().await;
+    assert!(!std::ptr::eq(read_pool, pool_manager.primary()));
+}
+
+#[tokio::test]
+async fn test_slow_replica_read_falls_back_to_primary_within_timeout() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool_manager = PoolManager::new(&database_url, Some(&database_url))
+        .await
+        .expect("Failed to create pool manager")
+        .with_replica_query_timeout(Duration::from_millis(200));
+    let timeouts_before =
+        synapse_core::metrics::registry().counter(REPLICA_TIMEOUT_METRIC, &[("pool", "replica_1")]);
+
+    // The first attempt goes to the replica and hangs; the retry on the
+    // primary answers at once
+    let attempts = AtomicUsize::new(0);
+    let started = Instant::now();
+    let value: i32 = pool_manager
+        .read(|pool| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                let sleep = if attempt == 0 { 5.0 } else { 0.0 };
+                sqlx::query_scalar("SELECT 1 FROM pg_sleep($1)")
+                    .bind(sleep)
+                    .fetch_one(&pool)
+                    .await
+            }
+        })
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    assert_eq!(value, 1);
+    assert_eq!(
+        synapse_core::metrics::registry().counter(REPLICA_TIMEOUT_METRIC, &[("pool", "replica_1")]),
+        timeouts_before + 1
+    );
+}
+
+#[tokio::test]
+async fn test_primary_down_serves_reads_and_rejects_writes() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "42").await;
+
+    // The replica points at the same database, so only the primary pool goes down
+    let pool_manager = PoolManager::new(&database_url, Some(&database_url))
+        .await
+        .unwrap();
+    pool_manager.check_health().await;
+    assert!(!pool_manager.is_read_only().await);
+
+    let base_url = spawn_app_with_pool_manager(pool, pool_manager.clone()).await;
+    let client = common::client();
+
+    pool_manager.primary().close().await;
+    pool_manager.check_health().await;
+    assert!(pool_manager.is_read_only().await);
+
+    let res = client
+        .get(format!("{}/transactions/{}", base_url, tx.id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["id"], tx.id.to_string());
+
+    let res = client
+        .get(format!("{}/settlements", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .post(format!("{}/callback", base_url))
+        .json(&serde_json::json!({
+            "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            "amount": "10.00",
+            "asset_code": "USD"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_DATABASE_003");
+    assert_eq!(body["status"], 503);
+}
+
+/// Serve the app on a pool of one connection that gives up waiting quickly
+#[tokio::test]
+async fn test_exhausted_pool_returns_503_with_retry_after() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    setup_db(&database_url).await;
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_millis(200))
+        .connect(&database_url)
+        .await
+        .unwrap();
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let url = format!("{}/transactions/{}/timeline", base_url, Uuid::new_v4());
+
+    // With the pool free the lookup runs and finds nothing
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let held = pool.acquire().await.unwrap();
+    let timeouts = metrics::registry().counter(POOL_ACQUIRE_TIMEOUTS_METRIC, &[]);
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.headers()[header::RETRY_AFTER], "1");
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_DATABASE_004");
+    assert!(body.get("detail").is_none());
+    assert!(metrics::registry().counter(POOL_ACQUIRE_TIMEOUTS_METRIC, &[]) > timeouts);
+
+    // With DEBUG_ERRORS the cause is sent along, and Retry-After follows
+    // DB_POOL_RETRY_AFTER_SECS
+    let mut app_state = common::test_state(&database_url, pool.clone()).await;
+    app_state.config = Arc::new(Config {
+        debug_errors: true,
+        db_pool_retry_after_secs: 5,
+        ..Config::default()
+    });
+    let debug_url = format!(
+        "{}/transactions/{}/timeline",
+        common::serve(create_app(app_state)).await,
+        Uuid::new_v4()
+    );
+    let res = client.get(&debug_url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.headers()[header::RETRY_AFTER], "5");
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_DATABASE_004");
+    assert!(body["detail"].as_str().unwrap().contains("timed out"));
+
+    drop(held);
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
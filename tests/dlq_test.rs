@@ -1,36 +1,26 @@
+mod common;
+
 use bigdecimal::BigDecimal;
-use sqlx::migrate::Migrator;
 use sqlx::PgPool;
-use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 use synapse_core::db::models::Transaction;
-use synapse_core::services::TransactionProcessor;
-
-async fn setup_db(pool: &PgPool) {
-    let migrator = Migrator::new(Path::join(
-        Path::new(env!("CARGO_MANIFEST_DIR")),
-        "migrations",
-    ))
-    .await;
-    if let Ok(m) = migrator {
-        let _ = m.run(pool).await;
-    }
-}
+use synapse_core::error::AppError;
+use synapse_core::services::{
+    DlqErrorCode, DlqFilter, ProcessingError, ReprocessOutcome, TransactionProcessor,
+};
 
 #[tokio::test]
 async fn test_dlq_workflow() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping DLQ test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
     let pool = PgPool::connect(&database_url)
         .await
         .expect("Failed to connect to test DB");
-    setup_db(&pool).await;
+    common::migrate(&pool).await;
 
     // Create a test transaction
     let tx_id = uuid::Uuid::new_v4();
@@ -72,18 +62,14 @@ async fn test_dlq_workflow() {
 
 #[tokio::test]
 async fn test_requeue_dlq() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping DLQ test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
     let pool = PgPool::connect(&database_url)
         .await
         .expect("Failed to connect to test DB");
-    setup_db(&pool).await;
+    common::migrate(&pool).await;
 
     // Create a test transaction
     let tx_id = uuid::Uuid::new_v4();
@@ -152,3 +138,570 @@ async fn test_requeue_dlq() {
 
     println!("✓ Requeue DLQ test passed");
 }
+
+async fn insert_pending(pool: &PgPool) -> uuid::Uuid {
+    let tx_id = uuid::Uuid::new_v4();
+    sqlx::query(
+        r#"
+        INSERT INTO transactions (
+            id, stellar_account, amount, asset_code, status
+        ) VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(tx_id)
+    .bind("GABCD1234TEST")
+    .bind(BigDecimal::from_str("42.00").unwrap())
+    .bind("USD")
+    .bind("pending")
+    .execute(pool)
+    .await
+    .expect("Failed to insert test transaction");
+    tx_id
+}
+
+async fn dlq_entries(pool: &PgPool, tx_id: uuid::Uuid) -> Vec<(String, i32)> {
+    sqlx::query_as(
+        "SELECT error_reason, retry_count FROM transaction_dlq WHERE transaction_id = $1",
+    )
+    .bind(tx_id)
+    .fetch_all(pool)
+    .await
+    .expect("Failed to fetch DLQ entries")
+}
+
+#[tokio::test]
+async fn test_transient_failure_is_retried() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let tx_id = insert_pending(&pool).await;
+
+    let processor =
+        TransactionProcessor::new(pool.clone()).with_retry_policy(3, Duration::from_millis(1));
+    let attempts = AtomicU32::new(0);
+    let result = processor
+        .process_with(tx_id, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(ProcessingError::Transient("connection reset".to_string()))
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+
+    assert!(result.is_ok(), "Third attempt should succeed");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert!(dlq_entries(&pool, tx_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_exhausted_transient_failure_goes_to_dlq() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let tx_id = insert_pending(&pool).await;
+
+    let processor =
+        TransactionProcessor::new(pool.clone()).with_retry_policy(2, Duration::from_millis(1));
+    let result = processor
+        .process_with(tx_id, || async {
+            Err(ProcessingError::Transient("horizon timeout".to_string()))
+        })
+        .await;
+
+    assert!(matches!(result, Err(ProcessingError::Transient(_))));
+    let entries = dlq_entries(&pool, tx_id).await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].1, 1, "One retry was made before giving up");
+}
+
+#[tokio::test]
+async fn test_permanent_failure_goes_straight_to_dlq() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let tx_id = insert_pending(&pool).await;
+
+    let processor =
+        TransactionProcessor::new(pool.clone()).with_retry_policy(5, Duration::from_millis(1));
+    let attempts = AtomicU32::new(0);
+    let result = processor
+        .process_with(tx_id, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(ProcessingError::Permanent("invalid asset code".to_string()))
+        })
+        .await;
+
+    assert!(matches!(result, Err(ProcessingError::Permanent(_))));
+    assert_eq!(
+        attempts.load(Ordering::SeqCst),
+        1,
+        "Permanent errors are not retried"
+    );
+
+    let entries = dlq_entries(&pool, tx_id).await;
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].0.contains("invalid asset code"));
+    assert_eq!(entries[0].1, 0);
+
+    let tx = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = $1")
+        .bind(tx_id)
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to fetch transaction");
+    assert_eq!(tx.status, "dlq");
+
+    let audited: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1 AND action = 'status_update' AND old_val->>'status' = 'pending' AND new_val->>'status' = 'dlq'",
+    )
+    .bind(tx_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audited, 1);
+}
+
+#[tokio::test]
+async fn test_failure_does_not_move_finished_transaction_to_dlq() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let processor =
+        TransactionProcessor::new(pool.clone()).with_retry_policy(1, Duration::from_millis(1));
+
+    // Completed or refunded while the step was failing
+    for status in ["completed", "refunded"] {
+        let tx_id = insert_pending(&pool).await;
+        sqlx::query("UPDATE transactions SET status = $2 WHERE id = $1")
+            .bind(tx_id)
+            .bind(status)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let result = processor
+            .process_with(tx_id, || async {
+                Err(ProcessingError::Permanent("invalid asset code".to_string()))
+            })
+            .await;
+        assert!(matches!(result, Err(ProcessingError::Permanent(_))));
+
+        let current: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+            .bind(tx_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(current, status);
+        assert!(dlq_entries(&pool, tx_id).await.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_refused_transition_is_not_sent_to_dlq() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let tx_id = insert_pending(&pool).await;
+
+    let processor =
+        TransactionProcessor::new(pool.clone()).with_retry_policy(3, Duration::from_millis(1));
+    let attempts = AtomicU32::new(0);
+    let result = processor
+        .process_with(tx_id, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(AppError::InvalidStatusTransition("transaction is 'refunded'".to_string()).into())
+        })
+        .await;
+
+    assert!(matches!(result, Err(ProcessingError::Refused(_))));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    assert!(dlq_entries(&pool, tx_id).await.is_empty());
+    let current: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+        .bind(tx_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(current, "pending");
+}
+
+/// A transaction in `status` with a DLQ entry pointing at it, as
+/// `(dlq_id, transaction_id)`
+async fn insert_dlq_entry(pool: &PgPool, status: &str) -> (uuid::Uuid, uuid::Uuid) {
+    let tx_id = insert_pending(pool).await;
+    sqlx::query("UPDATE transactions SET status = $2 WHERE id = $1")
+        .bind(tx_id)
+        .bind(status)
+        .execute(pool)
+        .await
+        .unwrap();
+    let dlq_id: uuid::Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO transaction_dlq (
+            transaction_id, stellar_account, amount, asset_code,
+            error_reason, retry_count, original_created_at
+        ) VALUES ($1, 'GABCD1234TEST', 42, 'USD', 'Horizon outage', 3, NOW())
+        RETURNING id
+        "#,
+    )
+    .bind(tx_id)
+    .fetch_one(pool)
+    .await
+    .expect("Failed to insert DLQ entry");
+    (dlq_id, tx_id)
+}
+
+#[tokio::test]
+async fn test_requeue_only_moves_transactions_still_in_dlq() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let processor = TransactionProcessor::new(pool.clone());
+
+    let (in_dlq, requeued_tx) = insert_dlq_entry(&pool, "dlq").await;
+    let (stale, completed_tx) = insert_dlq_entry(&pool, "completed").await;
+
+    // A transaction completed since it reached the DLQ is not reset
+    assert!(processor.requeue_dlq(stale).await.is_err());
+
+    let summary = processor
+        .requeue_dlq_matching(&DlqFilter {
+            ids: Some(vec![in_dlq, stale]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(summary.succeeded, 1);
+    assert_eq!(summary.failed_ids, vec![stale]);
+
+    let statuses: Vec<(uuid::Uuid, String)> =
+        sqlx::query_as("SELECT id, status FROM transactions WHERE id = ANY($1)")
+            .bind(vec![requeued_tx, completed_tx])
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    for (id, status) in statuses {
+        let expected = if id == requeued_tx {
+            "pending"
+        } else {
+            "completed"
+        };
+        assert_eq!(status, expected);
+    }
+    let remaining: Vec<uuid::Uuid> =
+        sqlx::query_scalar("SELECT id FROM transaction_dlq WHERE id = ANY($1)")
+            .bind(vec![in_dlq, stale])
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(remaining, vec![stale]);
+
+    let audited: Vec<uuid::Uuid> = sqlx::query_scalar(
+        "SELECT entity_id FROM audit_logs WHERE entity_id = ANY($1) AND action = 'status_update' AND old_val->>'status' = 'dlq' AND new_val->>'status' = 'pending'",
+    )
+    .bind(vec![requeued_tx, completed_tx])
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audited, vec![requeued_tx]);
+}
+
+#[tokio::test]
+async fn test_bulk_requeue_by_asset_code() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+
+    // Unique asset codes keep this test independent of other DLQ rows
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let target_asset = format!("A{}", suffix);
+    let other_asset = format!("B{}", suffix);
+
+    let mut target_tx_ids = Vec::new();
+    let mut other_dlq_id = None;
+    for asset in [&target_asset, &target_asset, &target_asset, &other_asset] {
+        let tx_id = uuid::Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO transactions (id, stellar_account, amount, asset_code, status) \
+             VALUES ($1, $2, $3, $4, 'dlq')",
+        )
+        .bind(tx_id)
+        .bind("GABCD1234TEST")
+        .bind(BigDecimal::from_str("10").unwrap())
+        .bind(asset)
+        .execute(&pool)
+        .await
+        .expect("Failed to insert test transaction");
+
+        let dlq_id: uuid::Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO transaction_dlq (
+                transaction_id, stellar_account, amount, asset_code,
+                error_reason, retry_count, original_created_at
+            ) VALUES ($1, $2, $3, $4, 'Horizon outage', 3, NOW())
+            RETURNING id
+            "#,
+        )
+        .bind(tx_id)
+        .bind("GABCD1234TEST")
+        .bind(BigDecimal::from_str("10").unwrap())
+        .bind(asset)
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to insert DLQ entry");
+
+        if asset == &target_asset {
+            target_tx_ids.push(tx_id);
+        } else {
+            other_dlq_id = Some(dlq_id);
+        }
+    }
+
+    let processor = TransactionProcessor::new(pool.clone());
+    let summary = processor
+        .requeue_dlq_matching(&DlqFilter {
+            asset_code: Some(target_asset.clone()),
+            ..Default::default()
+        })
+        .await
+        .expect("Bulk requeue should succeed");
+
+    assert_eq!(summary.succeeded, 3);
+    assert_eq!(summary.failed, 0);
+
+    let pending: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM transactions WHERE id = ANY($1) AND status = 'pending'",
+    )
+    .bind(&target_tx_ids)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(pending, 3);
+
+    let remaining: Vec<uuid::Uuid> =
+        sqlx::query_scalar("SELECT id FROM transaction_dlq WHERE asset_code = ANY($1)")
+            .bind(vec![target_asset, other_asset.clone()])
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(remaining, vec![other_dlq_id.unwrap()]);
+
+    // LIKE wildcards in the reason filter match literally
+    let summary = processor
+        .requeue_dlq_matching(&DlqFilter {
+            asset_code: Some(other_asset.clone()),
+            error_reason: Some("horizon_outage".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(summary.succeeded, 0);
+    let summary = processor
+        .requeue_dlq_matching(&DlqFilter {
+            asset_code: Some(other_asset),
+            error_reason: Some("HORIZON OUT".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(summary.succeeded, 1);
+
+    // Unknown ids are reported rather than silently ignored
+    let missing = uuid::Uuid::new_v4();
+    let summary = processor
+        .requeue_dlq_matching(&DlqFilter {
+            ids: Some(vec![missing]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(summary.succeeded, 0);
+    assert_eq!(summary.failed_ids, vec![missing]);
+}
+
+#[tokio::test]
+async fn test_reprocess_completes_pending_transaction() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let tx_id = insert_pending(&pool).await;
+
+    let processor = TransactionProcessor::new(pool.clone());
+    let outcome = processor.reprocess(tx_id).await.unwrap();
+    assert_eq!(outcome, ReprocessOutcome::Completed);
+
+    let tx = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = $1")
+        .bind(tx_id)
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to fetch transaction");
+    assert_eq!(tx.status, "completed");
+
+    assert!(processor.reprocess(uuid::Uuid::new_v4()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_reprocess_refuses_statuses_that_cannot_complete() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+    let processor = TransactionProcessor::new(pool.clone());
+
+    for status in ["refunded", "failed"] {
+        let tx_id = insert_pending(&pool).await;
+        sqlx::query("UPDATE transactions SET status = $2 WHERE id = $1")
+            .bind(tx_id)
+            .bind(status)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let outcome = processor.reprocess(tx_id).await.unwrap();
+        assert!(
+            matches!(outcome, ReprocessOutcome::Refused(_)),
+            "{}: {:?}",
+            status,
+            outcome
+        );
+
+        let current: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+            .bind(tx_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(current, status);
+        let in_dlq: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM transaction_dlq WHERE transaction_id = $1")
+                .bind(tx_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(in_dlq, 0);
+    }
+
+    // Completing a pending transaction goes through the audited transition
+    let tx_id = insert_pending(&pool).await;
+    assert_eq!(
+        processor.reprocess(tx_id).await.unwrap(),
+        ReprocessOutcome::Completed
+    );
+    let audited: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1 AND action = 'status_update' AND new_val->>'status' = 'completed'",
+    )
+    .bind(tx_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audited, 1);
+}
+
+type MakeError = fn(String) -> ProcessingError;
+
+#[tokio::test]
+async fn test_dlq_entries_carry_error_code_and_group_by_it() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to test DB");
+    common::migrate(&pool).await;
+
+    let processor =
+        TransactionProcessor::new(pool.clone()).with_retry_policy(2, Duration::from_millis(1));
+    let failures: [(MakeError, &str, DlqErrorCode); 3] = [
+        (
+            ProcessingError::Transient,
+            "horizon timeout",
+            DlqErrorCode::RetriesExhausted,
+        ),
+        (
+            ProcessingError::InvalidData,
+            "amount overflow",
+            DlqErrorCode::InvalidData,
+        ),
+        (
+            ProcessingError::Permanent,
+            "invalid asset code",
+            DlqErrorCode::PermanentFailure,
+        ),
+    ];
+    for (make_error, reason, expected) in failures {
+        let tx_id = insert_pending(&pool).await;
+        let result = processor
+            .process_with(tx_id, || async move { Err(make_error(reason.to_string())) })
+            .await;
+        assert!(result.is_err());
+
+        let (code, error_reason): (String, String) = sqlx::query_as(
+            "SELECT error_code, error_reason FROM transaction_dlq WHERE transaction_id = $1",
+        )
+        .bind(tx_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(code, expected.as_str());
+        assert!(error_reason.contains(reason), "the detail is kept");
+    }
+
+    // Other tests share the table, so only check our codes are grouped
+    let groups = processor.dlq_error_summary().await.unwrap();
+    for (_, _, expected) in failures {
+        let group = groups
+            .iter()
+            .find(|g| g.error_code == expected.as_str())
+            .unwrap_or_else(|| panic!("no group for {}", expected));
+        assert!(group.count >= 1);
+        assert!(group.oldest_moved_at <= group.latest_moved_at);
+    }
+    let mut codes: Vec<&str> = groups.iter().map(|g| g.error_code.as_str()).collect();
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes.len(), groups.len(), "one group per code");
+    assert!(groups.windows(2).all(|w| w[0].count >= w[1].count));
+}
@@ -1,8 +1,16 @@
+mod common;
+
 use bigdecimal::BigDecimal;
+use common::{deposit, pending_transaction, setup_db, spawn_app, unique_asset};
 use reqwest::StatusCode;
-use sqlx::{migrate::Migrator, PgPool};
-use std::path::Path;
-use synapse_core::{create_app, AppState};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use synapse_core::config::Config;
+use synapse_core::create_app;
+use synapse_core::db::queries;
 use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::postgres::Postgres;
 
@@ -14,69 +22,8 @@ async fn setup_test_app() -> (String, PgPool, impl std::any::Any) {
         host_port
     );
 
-    let pool = PgPool::connect(&database_url).await.unwrap();
-    let migrator = Migrator::new(Path::join(
-        Path::new(env!("CARGO_MANIFEST_DIR")),
-        "migrations",
-    ))
-    .await
-    .unwrap();
-    migrator.run(&pool).await.unwrap();
-
-    // Create partition for current month
-    let _ = sqlx::query(
-        r#"
-        DO $$
-        DECLARE
-            partition_date DATE;
-            partition_name TEXT;
-            start_date TEXT;
-            end_date TEXT;
-        BEGIN
-            partition_date := DATE_TRUNC('month', NOW());
-            partition_name := 'transactions_y' || TO_CHAR(partition_date, 'YYYY') || 'm' || TO_CHAR(partition_date, 'MM');
-            start_date := TO_CHAR(partition_date, 'YYYY-MM-DD');
-            end_date := TO_CHAR(partition_date + INTERVAL '1 month', 'YYYY-MM-DD');
-            
-            IF NOT EXISTS (SELECT 1 FROM pg_class WHERE relname = partition_name) THEN
-                EXECUTE format(
-                    'CREATE TABLE %I PARTITION OF transactions FOR VALUES FROM (%L) TO (%L)',
-                    partition_name, start_date, end_date
-                );
-            END IF;
-        END $$;
-        "#
-    )
-    .execute(&pool)
-    .await;
-
-    let (tx, _rx) = tokio::sync::broadcast::channel(100);
-
-    let app_state = AppState {
-        db: pool.clone(),
-        pool_manager: synapse_core::db::pool_manager::PoolManager::new(&database_url, None)
-            .await
-            .unwrap(),
-        horizon_client: synapse_core::stellar::HorizonClient::new(
-            "https://horizon-testnet.stellar.org".to_string(),
-        ),
-        feature_flags: synapse_core::services::feature_flags::FeatureFlagService::new(pool.clone()),
-        redis_url: "redis://localhost:6379".to_string(),
-        start_time: std::time::Instant::now(),
-        readiness: synapse_core::ReadinessState::new(),
-        tx_broadcast: tx,
-    };
-    let app = create_app(app_state);
-
-    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
-    let actual_addr = server.local_addr();
-
-    tokio::spawn(async move {
-        server.await.unwrap();
-    });
-
-    let base_url = format!("http://{}", actual_addr);
+    let pool = common::setup_db(&database_url).await;
+    let base_url = common::spawn_app(&database_url, pool.clone()).await;
     (base_url, pool, container)
 }
 
@@ -111,7 +58,7 @@ async fn insert_test_transaction(
 #[tokio::test]
 async fn test_export_csv_with_filters() {
     let (base_url, pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     insert_test_transaction(&pool, "GABC123", "100.50", "USD", "pending").await;
     insert_test_transaction(&pool, "GDEF456", "200.00", "USD", "completed").await;
@@ -143,7 +90,7 @@ async fn test_export_csv_with_filters() {
 #[tokio::test]
 async fn test_export_json_with_filters() {
     let (base_url, pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     insert_test_transaction(&pool, "GABC123", "100.50", "USD", "pending").await;
     insert_test_transaction(&pool, "GDEF456", "200.00", "USDC", "completed").await;
@@ -157,7 +104,7 @@ async fn test_export_json_with_filters() {
     assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(
         res.headers().get("content-type").unwrap(),
-        "application/json"
+        "application/x-ndjson"
     );
 
     let body = res.text().await.unwrap();
@@ -169,7 +116,7 @@ async fn test_export_json_with_filters() {
 #[tokio::test]
 async fn test_export_date_range() {
     let (base_url, pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let id1 = uuid::Uuid::new_v4();
     let id2 = uuid::Uuid::new_v4();
@@ -227,7 +174,7 @@ async fn test_export_date_range() {
 #[tokio::test]
 async fn test_export_large_dataset_streaming() {
     let (base_url, pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     for i in 0..2500 {
         insert_test_transaction(
@@ -255,7 +202,7 @@ async fn test_export_large_dataset_streaming() {
 #[tokio::test]
 async fn test_export_empty_results() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let res = client
         .get(format!("{}/export?format=csv&status=nonexistent", base_url))
@@ -272,7 +219,7 @@ async fn test_export_empty_results() {
 #[tokio::test]
 async fn test_export_headers_and_filename() {
     let (base_url, pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     insert_test_transaction(&pool, "GABC123", "100.50", "USD", "pending").await;
 
@@ -303,7 +250,7 @@ async fn test_export_headers_and_filename() {
     assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(
         res.headers().get("content-type").unwrap(),
-        "application/json"
+        "application/x-ndjson"
     );
 
     let content_disposition = res
@@ -315,3 +262,314 @@ async fn test_export_headers_and_filename() {
     assert!(content_disposition.starts_with("attachment; filename=\"transactions_"));
     assert!(content_disposition.ends_with(".json\""));
 }
+
+#[tokio::test]
+async fn test_export_settlements_csv() {
+    let (base_url, pool, _container) = setup_test_app().await;
+    let client = common::client();
+
+    let id = uuid::Uuid::new_v4();
+    let period_end = chrono::Utc::now();
+    let period_start = period_end - chrono::Duration::days(1);
+    sqlx::query(
+        r#"
+        INSERT INTO settlements (id, asset_code, total_amount, tx_count, period_start, period_end, status)
+        VALUES ($1, 'USD', $2, 3, $3, $4, 'completed')
+        "#,
+    )
+    .bind(id)
+    .bind(BigDecimal::from(450))
+    .bind(period_start)
+    .bind(period_end)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let res = client
+        .get(format!("{}/settlements/export?format=csv", base_url))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("content-type").unwrap(), "text/csv");
+    let content_disposition = res
+        .headers()
+        .get("content-disposition")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(content_disposition.starts_with("attachment; filename=\"settlements_"));
+
+    let body = res.text().await.unwrap();
+    let mut lines = body.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "id,asset_code,total_amount,tx_count,period_start,period_end,status"
+    );
+    let row = lines
+        .find(|line| line.starts_with(&id.to_string()))
+        .expect("settlement row missing");
+    assert!(row.contains(",USD,450,3,"));
+    assert!(row.ends_with(",completed"));
+}
+
+#[tokio::test]
+async fn test_csv_export_neutralizes_formula_cells() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // A dedicated asset keeps the export limited to this test's row
+    let asset_code = unique_asset("F");
+    let mut tx = deposit(&asset_code, "1.00");
+    tx.memo = Some("=cmd()".to_string());
+    tx.memo_type = Some("text".to_string());
+    queries::insert_transaction(&pool, &tx).await.unwrap();
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .get(format!("{}/export?asset_code={}", base_url, asset_code))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.text().await.unwrap();
+    let row = body.lines().nth(1).expect("one data row");
+    assert!(row.ends_with(",'=cmd()"), "row: {}", row);
+    assert!(!row.contains(",=cmd()"));
+
+    // Opting out exports the memo verbatim
+    let res = client
+        .get(format!(
+            "{}/export?asset_code={}&safe_csv=false",
+            base_url, asset_code
+        ))
+        .send()
+        .await
+        .unwrap();
+    let body = res.text().await.unwrap();
+    assert!(body.lines().nth(1).unwrap().ends_with(",=cmd()"));
+}
+
+async fn create_link(client: &reqwest::Client, base_url: &str, body: Value) -> String {
+    let res = client
+        .post(format!("{}/export/link", base_url))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let link: Value = res.json().await.unwrap();
+    assert!(link["expires_at"].is_string());
+    let url = link["url"].as_str().unwrap();
+    // Links are absolute so they can be handed on as they are
+    assert!(
+        url.starts_with(&format!("{}/export/token/", base_url)),
+        "{}",
+        url
+    );
+    url.to_string()
+}
+
+/// The full application with export links signed by a test secret
+async fn spawn_app_with_export_links(database_url: &str, pool: PgPool) -> String {
+    let mut app_state = common::test_state(database_url, pool).await;
+    app_state.config = Arc::new(Config {
+        export_link_secret: Some("export-link-test-secret".to_string()),
+        ..Config::default()
+    });
+    common::serve(create_app(app_state)).await
+}
+
+#[tokio::test]
+async fn test_export_link_token_exports_and_expires() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let asset_code = unique_asset("L");
+    for _ in 0..3 {
+        pending_transaction(&pool, &asset_code, "1.00").await;
+    }
+
+    let base_url = spawn_app_with_export_links(&database_url, pool).await;
+    let client = common::client();
+
+    // A valid token streams the export with the filters it was created with
+    let url = create_link(
+        &client,
+        &base_url,
+        json!({ "format": "json", "asset_code": asset_code }),
+    )
+    .await;
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.text().await.unwrap();
+    let rows: Vec<Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows
+        .iter()
+        .all(|row| row["asset_code"] == asset_code.as_str()));
+
+    // Any change to the token breaks the signature
+    let flipped = if url.ends_with('0') { '1' } else { '0' };
+    let tampered = format!("{}{}", &url[..url.len() - 1], flipped);
+    let res = client.get(&tampered).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let url = create_link(
+        &client,
+        &base_url,
+        json!({ "asset_code": asset_code, "expires_in_secs": 1 }),
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_export_links_are_disabled_without_secret() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .post(format!("{}/export/link", base_url))
+        .json(&json!({ "asset_code": "USD" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // Malformed bodies get the usual structured 400
+    let res = client
+        .post(format!("{}/export/link", base_url))
+        .header("content-type", "application/json")
+        .body("{")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = res.json().await.unwrap();
+    assert!(body["code"].is_string());
+}
+
+#[tokio::test]
+async fn test_manifest_row_count_matches_data_rows() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // A dedicated asset keeps the export limited to this test's rows
+    let asset_code = unique_asset("M");
+    for _ in 0..4 {
+        pending_transaction(&pool, &asset_code, "1.00").await;
+    }
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    for format in ["csv", "json"] {
+        let res = client
+            .get(format!(
+                "{}/export?format={}&asset_code={}&manifest=true",
+                base_url, format, asset_code
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let row_count: usize = res.headers()["x-export-row-count"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let checksum = res.headers()["x-export-checksum"]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let body = res.text().await.unwrap();
+        assert_eq!(checksum, hex::encode(Sha256::digest(body.as_bytes())));
+
+        let data_rows = if format == "csv" {
+            let lines: Vec<&str> = body.lines().collect();
+            assert!(lines[0].starts_with("id,stellar_account,amount"));
+            assert_eq!(lines.last().unwrap(), &format!("# row_count={}", row_count));
+            lines.len() - 2
+        } else {
+            body.lines().count()
+        };
+        assert_eq!(data_rows, 4, "{} export", format);
+        assert_eq!(row_count, data_rows, "{} export", format);
+    }
+
+    // Without the flag the export is unchanged
+    let res = client
+        .get(format!("{}/export?asset_code={}", base_url, asset_code))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.headers().get("x-export-row-count").is_none());
+    assert!(!res.text().await.unwrap().contains("# row_count"));
+}
+
+#[tokio::test]
+async fn test_json_export_is_newline_delimited() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // A dedicated asset keeps the export limited to this test's rows
+    let asset_code = unique_asset("N");
+    for memo in ["first", "line\nbreak", "third"] {
+        let mut tx = deposit(&asset_code, "1.00");
+        tx.memo = Some(memo.to_string());
+        tx.memo_type = Some("text".to_string());
+        queries::insert_transaction(&pool, &tx).await.unwrap();
+    }
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let res = common::client()
+        .get(format!(
+            "{}/export?format=json&asset_code={}",
+            base_url, asset_code
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "application/x-ndjson"
+    );
+
+    let body = res.text().await.unwrap();
+    assert!(body.ends_with('\n'));
+    let records: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line is a JSON object"))
+        .collect();
+    assert_eq!(records.len(), 3);
+    assert!(records.iter().all(|r| r["asset_code"] == asset_code));
+}
@@ -1,9 +1,56 @@
-#[cfg(test)]
-mod feature_flag_tests {
-    #[tokio::test]
-    async fn test_feature_flag_service_initialization() {
-        // This test verifies the service can be created
-        // Full integration tests require a running database
-        // Placeholder test
-    }
+mod common;
+
+use common::setup_db;
+use sqlx::PgPool;
+use std::time::Duration;
+use synapse_core::services::feature_flags::FeatureFlagService;
+use uuid::Uuid;
+
+async fn set_flag_in_db(pool: &PgPool, name: &str, enabled: bool) {
+    sqlx::query(
+        "INSERT INTO feature_flags (name, enabled) VALUES ($1, $2)
+         ON CONFLICT (name) DO UPDATE SET enabled = EXCLUDED.enabled",
+    )
+    .bind(name)
+    .bind(enabled)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_flag_changes_are_cached_until_ttl_expires() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let flag = format!("test_flag_{}", Uuid::new_v4().simple());
+    set_flag_in_db(&pool, &flag, false).await;
+
+    let ttl = Duration::from_millis(500);
+    let flags = FeatureFlagService::new(pool.clone()).with_ttl(ttl);
+    assert!(!flags.is_enabled(&flag).await.unwrap());
+
+    // Changed behind the service's back: still served from cache
+    set_flag_in_db(&pool, &flag, true).await;
+    assert!(!flags.is_enabled(&flag).await.unwrap());
+    assert_eq!(flags.get_all().await.unwrap().get(&flag), Some(&false));
+
+    tokio::time::sleep(ttl + Duration::from_millis(100)).await;
+    assert!(flags.is_enabled(&flag).await.unwrap());
+
+    // An explicit refresh picks up changes immediately
+    set_flag_in_db(&pool, &flag, false).await;
+    flags.refresh().await.unwrap();
+    assert!(!flags.is_enabled(&flag).await.unwrap());
+
+    // Updates through the service are visible without waiting for the TTL
+    flags.update(&flag, true).await.unwrap();
+    assert!(flags.is_enabled(&flag).await.unwrap());
+
+    sqlx::query("DELETE FROM feature_flags WHERE name = $1")
+        .bind(&flag)
+        .execute(&pool)
+        .await
+        .unwrap();
 }
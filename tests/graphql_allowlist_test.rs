@@ -0,0 +1,60 @@
+mod common;
+
+use common::{serve, setup_db, test_state};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::io::Write;
+use synapse_core::create_app;
+use synapse_core::graphql::allowlist::{query_hash, NOT_ALLOWED_MESSAGE};
+use synapse_core::graphql::schema::build_schema;
+use synapse_core::middleware::auth::CallerScope;
+
+// The allowlist is read once per process, so both cases share one test
+#[tokio::test]
+async fn test_allowlist_runs_registered_queries_only() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let allowed = "{ transactions { id status } }";
+    let unregistered = "{ transactions { id status amount } }";
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "# dashboard queries\n{}", query_hash(allowed)).unwrap();
+    // Only test in this binary, so the allowlist cannot leak into other GraphQL tests
+    std::env::set_var("GRAPHQL_ALLOWLIST_FILE", file.path());
+
+    let pool = setup_db(&database_url).await;
+    let app_state = test_state(&database_url, pool).await;
+    let base_url = serve(create_app(app_state.clone())).await;
+    let client = common::client();
+
+    let res = client
+        .post(format!("{}/graphql", base_url))
+        .json(&json!({ "query": allowed }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body["data"]["transactions"].is_array(), "{}", body);
+
+    let res = client
+        .post(format!("{}/graphql", base_url))
+        .json(&json!({ "query": unregistered }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["errors"][0]["message"], NOT_ALLOWED_MESSAGE);
+
+    // The schema, which also serves subscriptions, applies the same list
+    let schema = build_schema(app_state);
+    let res = schema.execute(unregistered).await;
+    assert_eq!(res.errors.len(), 1);
+    assert_eq!(res.errors[0].message, NOT_ALLOWED_MESSAGE);
+    let res = schema
+        .execute(async_graphql::Request::new(allowed).data(CallerScope::Admin))
+        .await;
+    assert!(res.errors.is_empty(), "errors: {:?}", res.errors);
+}
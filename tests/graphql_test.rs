@@ -1,90 +1,39 @@
+mod common;
+
+use common::{
+    deposit, pending_transaction, serve, setup_db, spawn_app, spawn_app_with_pool_manager,
+    test_state, unique_asset, ADMIN_AUTH,
+};
+use futures::{SinkExt, StreamExt};
 use reqwest::StatusCode;
-use serde_json::json;
-use sqlx::{migrate::Migrator, PgPool};
-use std::path::Path;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::time::Duration;
 use synapse_core::db::pool_manager::PoolManager;
-use synapse_core::services::feature_flags::FeatureFlagService;
+use synapse_core::db::queries;
+use synapse_core::graphql::auth::AuthContext;
+use synapse_core::graphql::schema::{build_schema_with_limits, SchemaLimits};
+use synapse_core::handlers::graphql::SUBSCRIPTIONS_ONLY_MESSAGE;
+use synapse_core::handlers::ws::WsConnections;
+use synapse_core::middleware::auth::{SCOPE_CALLBACK, SCOPE_READ};
+use synapse_core::services::ApiKeyService;
 use synapse_core::{create_app, AppState};
-use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{error::Error as WsError, Message};
+use uuid::Uuid;
 
 #[tokio::test]
 async fn test_graphql_queries() {
-    let database_url = match std::env::var("DATABASE_URL") {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Skipping GraphQL test: DATABASE_URL not set");
-            return;
-        }
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
     };
 
-    let pool = PgPool::connect(&database_url).await.unwrap();
-    let migrator = Migrator::new(Path::join(
-        Path::new(env!("CARGO_MANIFEST_DIR")),
-        "migrations",
-    ))
-    .await
-    .unwrap();
-    migrator.run(&pool).await.unwrap();
-
-    // Create partition for current month
-    let _ = sqlx::query(
-        r#"
-        DO $$
-        DECLARE
-            partition_date DATE;
-            partition_name TEXT;
-            start_date TEXT;
-            end_date TEXT;
-        BEGIN
-            partition_date := DATE_TRUNC('month', NOW());
-            partition_name := 'transactions_y' || TO_CHAR(partition_date, 'YYYY') || 'm' || TO_CHAR(partition_date, 'MM');
-            start_date := TO_CHAR(partition_date, 'YYYY-MM-DD');
-            end_date := TO_CHAR(partition_date + INTERVAL '1 month', 'YYYY-MM-DD');
-            
-            IF NOT EXISTS (SELECT 1 FROM pg_class WHERE relname = partition_name) THEN
-                EXECUTE format(
-                    'CREATE TABLE %I PARTITION OF transactions FOR VALUES FROM (%L) TO (%L)',
-                    partition_name, start_date, end_date
-                );
-            END IF;
-        END $$;
-        "#
-    )
-    .execute(&pool)
-    .await;
-
-    let pool_manager = PoolManager::new(&database_url, None).await.unwrap();
-    let feature_flags = FeatureFlagService::new(pool.clone());
-    let (tx_broadcast, _) = tokio::sync::broadcast::channel(100);
-    let readiness = synapse_core::ReadinessState::new();
+    let pool = setup_db(&database_url).await;
 
-    let app_state = AppState {
-        db: pool.clone(),
-        pool_manager,
-        horizon_client: synapse_core::stellar::HorizonClient::new(
-            "https://horizon-testnet.stellar.org".to_string(),
-        ),
-        feature_flags,
-        redis_url: "redis://localhost:6379".to_string(),
-        start_time: std::time::Instant::now(),
-        tx_broadcast,
-        readiness,
-    };
-    let app = create_app(app_state);
+    let base_url = spawn_app(&database_url, pool.clone()).await;
 
-    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-    let addr = listener.local_addr().unwrap();
-
-    tokio::spawn(async move {
-        axum::Server::from_tcp(listener.into_std().unwrap())
-            .unwrap()
-            .serve(app.into_make_service())
-            .await
-            .unwrap();
-    });
-
-    let client = reqwest::Client::new();
-    let graphql_url = format!("http://{}/graphql", addr);
+    let client = common::client();
+    let graphql_url = format!("{}/graphql", base_url);
 
     let query = json!({
         "query": "{ transactions { id status } }"
@@ -92,7 +41,7 @@ async fn test_graphql_queries() {
     let res = client.post(&graphql_url).json(&query).send().await.unwrap();
     assert_eq!(res.status(), StatusCode::OK);
 
-    let callback_url = format!("http://{}/callback", addr);
+    let callback_url = format!("{}/callback", base_url);
     let payload = json!({
         "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
         "amount": "100.50",
@@ -125,3 +74,554 @@ async fn test_graphql_queries() {
 
     assert_eq!(body["data"]["transaction"]["assetCode"], "USD");
 }
+
+#[tokio::test]
+async fn test_run_settlements_mutation() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // Use a unique asset code so settlements from other tests don't interfere
+    let asset_code = unique_asset("T");
+    sqlx::query(
+        "INSERT INTO transactions (id, stellar_account, amount, asset_code, status) VALUES ($1, $2, $3, $4, 'completed')",
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind("GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA")
+    .bind(sqlx::types::BigDecimal::from(250))
+    .bind(&asset_code)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let graphql_url = format!("{}/graphql", base_url);
+
+    let mutation = format!(
+        "mutation {{ runSettlements(assetCode: \"{}\") {{ id assetCode totalAmount txCount }} }}",
+        asset_code
+    );
+
+    // Without admin credentials the guard rejects the mutation
+    let res = reqwest::Client::new()
+        .post(&graphql_url)
+        .json(&json!({ "query": mutation }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("admin credentials required"));
+
+    let res = client
+        .post(&graphql_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({ "query": mutation }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body.get("errors").is_none(), "{}", body);
+    let settlements = body["data"]["runSettlements"].as_array().unwrap();
+    assert_eq!(settlements.len(), 1);
+    assert_eq!(settlements[0]["assetCode"], asset_code.as_str());
+    assert_eq!(settlements[0]["txCount"], 1);
+    let settlement_id = settlements[0]["id"].as_str().unwrap().to_string();
+
+    let query = format!(
+        "{{ settlement(id: \"{}\") {{ id assetCode totalAmount status }} }}",
+        settlement_id
+    );
+    let res = client
+        .post(&graphql_url)
+        .json(&json!({ "query": query }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["data"]["settlement"]["id"], settlement_id.as_str());
+    assert_eq!(body["data"]["settlement"]["status"], "completed");
+    let total: f64 = body["data"]["settlement"]["totalAmount"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(total, 250.0);
+}
+
+#[tokio::test]
+async fn test_force_complete_requires_pending() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let insert = |status: &'static str| {
+        let pool = pool.clone();
+        async move {
+            let id = uuid::Uuid::new_v4();
+            sqlx::query(
+                "INSERT INTO transactions (id, stellar_account, amount, asset_code, status) VALUES ($1, $2, $3, 'USD', $4)",
+            )
+            .bind(id)
+            .bind("GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA")
+            .bind(sqlx::types::BigDecimal::from(10))
+            .bind(status)
+            .execute(&pool)
+            .await
+            .unwrap();
+            id
+        }
+    };
+    let pending_id = insert("pending").await;
+    let settled_id = insert("completed").await;
+    let settlement_id = uuid::Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO settlements (id, asset_code, total_amount, tx_count, period_start, period_end, status) VALUES ($1, 'USD', 10, 1, NOW(), NOW(), 'completed')",
+    )
+    .bind(settlement_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("UPDATE transactions SET settlement_id = $1 WHERE id = $2")
+        .bind(settlement_id)
+        .bind(settled_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app_state = test_state(&database_url, pool.clone()).await;
+    let schema = synapse_core::graphql::schema::build_schema(app_state.clone());
+    let mutation = |id: uuid::Uuid| {
+        format!(
+            "mutation {{ forceCompleteTransaction(id: \"{}\") {{ id status }} }}",
+            id
+        )
+    };
+
+    let as_admin = |query: String| async_graphql::Request::new(query).data(AuthContext::admin());
+
+    // Without admin credentials the guard rejects the mutation
+    let res = schema.execute(mutation(pending_id).as_str()).await;
+    assert!(!res.errors.is_empty());
+
+    let res = schema.execute(as_admin(mutation(pending_id))).await;
+    assert!(res.errors.is_empty(), "errors: {:?}", res.errors);
+    let body = res.data.into_json().unwrap();
+    assert_eq!(body["forceCompleteTransaction"]["status"], "completed");
+
+    let audit = synapse_core::db::audit::AuditLog::for_entity(&pool, pending_id)
+        .await
+        .unwrap();
+    let entry = audit
+        .iter()
+        .find(|e| e.action == "force_complete")
+        .expect("force-complete audit entry");
+    assert_eq!(entry.actor, "graphql");
+    assert_eq!(entry.old_val, Some(json!({ "status": "pending" })));
+    assert_eq!(entry.new_val, Some(json!({ "status": "completed" })));
+
+    // Settled and already-completed transactions are refused
+    let res = schema.execute(as_admin(mutation(settled_id))).await;
+    assert!(!res.errors.is_empty());
+    assert!(res.errors[0].message.contains("already settled"));
+    let res = schema.execute(as_admin(mutation(pending_id))).await;
+    assert!(res.errors[0].message.contains("only pending"));
+
+    // The HTTP handler answers the same refusal with 400
+    let base_url = serve(create_app(app_state)).await;
+    let res = common::client()
+        .post(format!("{}/graphql", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({ "query": mutation(settled_id) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let status: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+        .bind(settled_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "completed");
+    let settled_audit = synapse_core::db::audit::AuditLog::for_entity(&pool, settled_id)
+        .await
+        .unwrap();
+    assert!(settled_audit.iter().all(|e| e.action != "force_complete"));
+}
+
+#[tokio::test]
+async fn test_schema_limits() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = PgPool::connect(&database_url).await.unwrap();
+    let app_state = test_state(&database_url, pool).await;
+    let schema = build_schema_with_limits(
+        app_state.clone(),
+        SchemaLimits {
+            max_depth: 4,
+            max_complexity: 50,
+            introspection_enabled: true,
+        },
+    );
+
+    // A normal query stays within the limits
+    let res = schema
+        .execute("{ settlements(limit: 1) { id status } }")
+        .await;
+    assert!(res.errors.is_empty(), "errors: {:?}", res.errors);
+
+    // Deeply nested queries are rejected before execution
+    let res = schema
+        .execute("{ __schema { types { fields { type { ofType { name } } } } } }")
+        .await;
+    assert!(!res.errors.is_empty());
+    assert!(res.errors[0].message.contains("nested too deep"));
+
+    // Wide queries exceed the complexity limit
+    let wide = format!(
+        "{{ {} }}",
+        (0..60)
+            .map(|i| format!("s{}: settlements {{ id }}", i))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let res = schema.execute(wide.as_str()).await;
+    assert!(!res.errors.is_empty());
+    assert!(res.errors[0].message.contains("too complex"));
+
+    // Introspection can be switched off
+    let schema = build_schema_with_limits(
+        app_state,
+        SchemaLimits {
+            introspection_enabled: false,
+            ..SchemaLimits::default()
+        },
+    );
+    let res = schema.execute("{ __schema { queryType { name } } }").await;
+    let body = res.data.into_json().unwrap();
+    assert!(body["__schema"].is_null());
+}
+
+/// A second database standing in for a read replica: migrated like the
+/// primary but holding none of its rows
+async fn setup_replica(database_url: &str, primary: &PgPool) -> String {
+    let (base, name) = database_url.rsplit_once('/').unwrap();
+    let replica_name = format!("{}_replica", name.split('?').next().unwrap());
+    // Fails harmlessly if an earlier run already created it
+    let _ = sqlx::query(&format!("CREATE DATABASE \"{}\"", replica_name))
+        .execute(primary)
+        .await;
+    let replica_url = format!("{}/{}", base, replica_name);
+    setup_db(&replica_url).await;
+    replica_url
+}
+
+async fn spawn_app_with_replica(database_url: &str, replica_url: &str, pool: PgPool) -> String {
+    let pool_manager = PoolManager::new(database_url, Some(replica_url))
+        .await
+        .unwrap();
+    spawn_app_with_pool_manager(pool, pool_manager).await
+}
+
+#[tokio::test]
+async fn test_queries_read_replica_and_mutations_write_primary() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let primary = setup_db(&database_url).await;
+    let replica_url = setup_replica(&database_url, &primary).await;
+    let replica = PgPool::connect(&replica_url).await.unwrap();
+    let base = spawn_app_with_replica(&database_url, &replica_url, primary.clone()).await;
+    let client = common::client();
+    let graphql_url = format!("{}/graphql", base);
+
+    // Only the replica holds this row, so finding it proves the read went there
+    let marker = pending_transaction(&replica, "USD", "10.00").await;
+    let res = client
+        .post(&graphql_url)
+        .json(&json!({
+            "query": format!("{{ transaction(id: \"{}\") {{ id status }} }}", marker.id)
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["data"]["transaction"]["id"], marker.id.to_string());
+
+    // Only the primary holds this one; the write must land there
+    let pending = pending_transaction(&primary, "USD", "10.00").await;
+    let res = client
+        .post(&graphql_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({
+            "query": format!(
+                "mutation {{ forceCompleteTransaction(id: \"{}\") {{ id status }} }}",
+                pending.id
+            )
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let status: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+        .bind(pending.id)
+        .fetch_one(&primary)
+        .await
+        .unwrap();
+    assert_eq!(status, "completed");
+    let on_replica: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE id = $1")
+        .bind(pending.id)
+        .fetch_one(&replica)
+        .await
+        .unwrap();
+    assert_eq!(on_replica, 0);
+}
+
+/// Next text frame from the server, parsed as JSON
+async fn next_json<S>(ws: &mut S) -> Value
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("message within 5s")
+            .expect("connection open")
+            .unwrap();
+        if let Message::Text(text) = msg {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+type WsStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Upgrade request for `/graphql/ws` speaking `graphql-transport-ws`,
+/// authenticated with `authorization`
+fn ws_request(
+    base_url: &str,
+    authorization: &str,
+) -> tokio_tungstenite::tungstenite::handshake::client::Request {
+    let mut request = format!("{}/graphql/ws", base_url.replace("http://", "ws://"))
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        "graphql-transport-ws".parse().unwrap(),
+    );
+    request
+        .headers_mut()
+        .insert("authorization", authorization.parse().unwrap());
+    request
+}
+
+/// Open `/graphql/ws` as the admin and complete the handshake
+async fn connect(base_url: &str) -> WsStream {
+    connect_as(base_url, ADMIN_AUTH).await
+}
+
+/// Open `/graphql/ws` with `authorization` and complete the handshake
+async fn connect_as(base_url: &str, authorization: &str) -> WsStream {
+    let (mut ws, _) = tokio_tungstenite::connect_async(ws_request(base_url, authorization))
+        .await
+        .unwrap();
+
+    ws.send(Message::Text(
+        json!({ "type": "connection_init" }).to_string(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(next_json(&mut ws).await["type"], "connection_ack");
+    ws
+}
+
+#[tokio::test]
+async fn test_transaction_status_subscription_receives_update() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "10.00").await;
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let mut ws = connect(&base_url).await;
+
+    let query = format!(
+        "subscription {{ transactionStatus(id: \"{}\") {{ transactionId status message }} }}",
+        tx.id
+    );
+    ws.send(Message::Text(
+        json!({ "id": "1", "type": "subscribe", "payload": { "query": query } }).to_string(),
+    ))
+    .await
+    .unwrap();
+    // The protocol does not acknowledge a subscribe; give the server a
+    // moment to start the stream before the update is broadcast
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let res = common::client()
+        .patch(format!("{}/admin/transactions/status", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({ "ids": [tx.id], "status": "processing" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success(), "status update: {}", res.status());
+
+    let msg = next_json(&mut ws).await;
+    assert_eq!(msg["type"], "next", "{}", msg);
+    assert_eq!(msg["id"], "1");
+    let update = &msg["payload"]["data"]["transactionStatus"];
+    assert_eq!(update["transactionId"], tx.id.to_string());
+    assert_eq!(update["status"], "processing");
+    assert_eq!(update["message"], "Status updated by admin");
+}
+
+#[tokio::test]
+async fn test_mutation_over_websocket_is_refused() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "10.00").await;
+
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let mut ws = connect(&base_url).await;
+
+    for (id, query) in [
+        (
+            "1",
+            format!(
+                "mutation {{ forceCompleteTransaction(id: \"{}\") {{ id status }} }}",
+                tx.id
+            ),
+        ),
+        ("2", "{ transactions { id } }".to_string()),
+    ] {
+        ws.send(Message::Text(
+            json!({ "id": id, "type": "subscribe", "payload": { "query": query } }).to_string(),
+        ))
+        .await
+        .unwrap();
+        let msg = next_json(&mut ws).await;
+        assert_eq!(msg["type"], "next", "{}", msg);
+        assert_eq!(msg["id"], id);
+        assert_eq!(
+            msg["payload"]["errors"][0]["message"],
+            SUBSCRIPTIONS_ONLY_MESSAGE
+        );
+        assert!(msg["payload"]["data"].is_null());
+        assert_eq!(next_json(&mut ws).await["type"], "complete");
+    }
+
+    let status: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+        .bind(tx.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "pending");
+}
+
+#[tokio::test]
+async fn test_subscription_is_scoped_to_partner() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let keys = ApiKeyService::new(pool.clone());
+    let scopes = [SCOPE_READ.to_string()];
+    let (partner_a, partner_b) = (Uuid::new_v4(), Uuid::new_v4());
+    let (_, key_a) = keys.create(partner_a, &scopes).await.unwrap();
+    let (_, key_callback_only) = keys
+        .create(partner_b, &[SCOPE_CALLBACK.to_string()])
+        .await
+        .unwrap();
+    let theirs = queries::insert_transaction(
+        &pool,
+        &deposit("USD", "10.00").with_partner_id(Some(partner_b)),
+    )
+    .await
+    .unwrap();
+
+    let base_url = spawn_app(&database_url, pool).await;
+
+    // Without the read scope the upgrade itself is refused
+    match tokio_tungstenite::connect_async(ws_request(
+        &base_url,
+        &format!("Api-Key {}", key_callback_only),
+    ))
+    .await
+    {
+        Err(WsError::Http(response)) => assert_eq!(response.status().as_u16(), 403),
+        other => panic!("expected 403, got {:?}", other.map(|(_, r)| r.status())),
+    }
+
+    // Another partner's transaction looks like it does not exist
+    let mut ws = connect_as(&base_url, &format!("Api-Key {}", key_a)).await;
+    let query = format!(
+        "subscription {{ transactionStatus(id: \"{}\") {{ status }} }}",
+        theirs.id
+    );
+    ws.send(Message::Text(
+        json!({ "id": "1", "type": "subscribe", "payload": { "query": query } }).to_string(),
+    ))
+    .await
+    .unwrap();
+    let msg = next_json(&mut ws).await;
+    assert_eq!(msg["type"], "next", "{}", msg);
+    assert_eq!(msg["id"], "1");
+    assert!(msg["payload"]["data"].is_null());
+    assert!(!msg["payload"]["errors"].as_array().unwrap().is_empty());
+    assert_eq!(next_json(&mut ws).await["type"], "complete");
+}
+
+#[tokio::test]
+async fn test_subscriptions_count_against_ws_connection_limit() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let connections = WsConnections::new(1);
+    let app_state = AppState {
+        ws_connections: connections.clone(),
+        ..test_state(&database_url, pool).await
+    };
+    let base_url = serve(create_app(app_state)).await;
+
+    let ws = connect(&base_url).await;
+    assert_eq!(connections.active(), 1);
+    match tokio_tungstenite::connect_async(ws_request(&base_url, ADMIN_AUTH)).await {
+        Err(WsError::Http(response)) => {
+            assert_eq!(response.status().as_u16(), 503)
+        }
+        other => panic!("expected 503, got {:?}", other.map(|(_, r)| r.status())),
+    }
+
+    // Closing the subscription connection frees its slot
+    drop(ws);
+    for _ in 0..50 {
+        if connections.active() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(connections.active(), 0);
+}
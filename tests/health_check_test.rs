@@ -1,5 +1,16 @@
+mod common;
+
+use common::{setup_db, spawn_app, spawn_app_with_pool_manager};
+use reqwest::StatusCode;
+use serde_json::Value;
+use sqlx::migrate::Migrator;
+use sqlx::PgPool;
+use std::borrow::Cow;
+use std::path::Path;
 use std::time::Instant;
+use synapse_core::db::pool_manager::PoolManager;
 use synapse_core::health::*;
+use uuid::Uuid;
 
 #[tokio::test]
 async fn test_health_check_response_structure() {
@@ -84,6 +95,7 @@ fn test_health_response_structure() {
     let response = HealthResponse {
         status: "degraded".to_string(),
         version: "0.1.0".to_string(),
+        git_sha: "abc123".to_string(),
         uptime_seconds: 3600,
         dependencies,
     };
@@ -106,3 +118,332 @@ fn test_health_response_structure() {
     assert!(json.contains("\"redis\""));
     assert!(json.contains("\"horizon\""));
 }
+
+#[tokio::test]
+async fn test_horizon_checker_short_circuits_when_breaker_open() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+        .with_status(503)
+        .expect(1)
+        .create_async()
+        .await;
+
+    // One failure opens the breaker
+    let client = synapse_core::stellar::HorizonClient::with_circuit_breaker(server.url(), 1, 60);
+    let _ = client.get_account("TEST_ACCOUNT").await;
+    assert_eq!(client.circuit_state(), "open");
+
+    let started = Instant::now();
+    let status = HorizonChecker::new(client).check().await;
+    assert!(started.elapsed() < std::time::Duration::from_millis(100));
+
+    match status {
+        DependencyStatus::Unhealthy { error, .. } => assert_eq!(error, "circuit open"),
+        other => panic!("expected unhealthy, got {:?}", other),
+    }
+
+    // Only the request that opened the breaker reached Horizon
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_horizon_checker_uses_short_timeout() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_chunked_body(|w| {
+            // Horizon answering slowly
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            w.write_all(
+                br#"{
+                    "id": "GTEST",
+                    "account_id": "GTEST",
+                    "balances": [],
+                    "sequence": "1",
+                    "subentry_count": 0,
+                    "home_domain": null,
+                    "last_modified_ledger": 1,
+                    "last_modified_time": "2021-01-01T00:00:00Z"
+                }"#,
+            )
+        })
+        .create_async()
+        .await;
+
+    let reconciliation = synapse_core::stellar::HorizonClient::new(server.url())
+        .with_timeout(std::time::Duration::from_secs(10));
+    let checker = HorizonChecker::new(reconciliation.clone())
+        .with_timeout(std::time::Duration::from_millis(100));
+    assert_eq!(checker.timeout(), std::time::Duration::from_millis(100));
+    assert_eq!(reconciliation.timeout(), std::time::Duration::from_secs(10));
+
+    let started = Instant::now();
+    let status = checker.check().await;
+    assert!(started.elapsed() < std::time::Duration::from_millis(400));
+    assert!(matches!(status, DependencyStatus::Unhealthy { .. }));
+
+    // The longer-lived client still waits for the slow answer
+    let account = reconciliation.get_account("GTEST").await.unwrap();
+    assert_eq!(account.account_id, "GTEST");
+}
+
+#[test]
+fn test_horizon_checker_defaults_to_health_timeout() {
+    let client = synapse_core::stellar::HorizonClient::new("http://127.0.0.1:1".to_string());
+    assert_eq!(
+        client.timeout(),
+        synapse_core::stellar::client::DEFAULT_REQUEST_TIMEOUT
+    );
+    assert_eq!(
+        HorizonChecker::new(client).timeout(),
+        synapse_core::health::DEFAULT_HORIZON_HEALTH_TIMEOUT
+    );
+}
+
+#[tokio::test]
+async fn test_redis_checker_fails_fast_on_closed_port() {
+    use std::time::Duration;
+    use synapse_core::utils::redis_connect::RedisConnectPolicy;
+
+    let policy = RedisConnectPolicy {
+        timeout: Duration::from_millis(300),
+        retries: 2,
+        base_delay: Duration::from_millis(20),
+    };
+    let checker = RedisChecker::new("redis://127.0.0.1:1".to_string()).with_connect_policy(policy);
+
+    let start = Instant::now();
+    let status = checker.check().await;
+    assert!(start.elapsed() < Duration::from_millis(900));
+    assert!(matches!(status, DependencyStatus::Unhealthy { .. }));
+}
+
+#[tokio::test]
+async fn test_db_health_reports_downed_replica() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    // The same server doubles as the replica so it starts out healthy
+    let pool_manager = PoolManager::new(&database_url, Some(&database_url))
+        .await
+        .unwrap();
+    let replica = pool_manager.replica().unwrap().clone();
+    let base = spawn_app_with_pool_manager(pool, pool_manager).await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/admin/db/health", base);
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let body: serde_json::Value = client
+        .get(&url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["primary_healthy"], true);
+    assert_eq!(body["replica_count"], 1);
+    assert_eq!(body["replicas"][0]["name"], "replica_1");
+    assert_eq!(body["replicas"][0]["healthy"], true);
+    assert_eq!(body["replicas"][0]["lag_seconds"], 0.0);
+
+    replica.close().await;
+    let res = client
+        .get(&url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["primary_healthy"], true);
+    assert_eq!(body["read_only"], false);
+    assert_eq!(body["replicas"][0]["healthy"], false);
+    assert!(body["replicas"][0]["lag_seconds"].is_null());
+}
+
+async fn shipped_migrator() -> Migrator {
+    Migrator::new(Path::join(
+        Path::new(env!("CARGO_MANIFEST_DIR")),
+        "migrations",
+    ))
+    .await
+    .unwrap()
+}
+
+/// A fresh database, so leaving a migration out cannot affect other tests
+async fn create_database(database_url: &str) -> (String, String) {
+    let admin = PgPool::connect(database_url).await.unwrap();
+    let name = format!("synapse_migrations_{}", Uuid::new_v4().simple());
+    sqlx::query(&format!("CREATE DATABASE {}", name))
+        .execute(&admin)
+        .await
+        .unwrap();
+    let mut url = url::Url::parse(database_url).unwrap();
+    url.set_path(&name);
+    (name, url.to_string())
+}
+
+async fn drop_database(database_url: &str, name: &str) {
+    let admin = PgPool::connect(database_url).await.unwrap();
+    let _ = sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name))
+        .execute(&admin)
+        .await;
+}
+
+#[tokio::test]
+async fn test_ready_is_false_while_a_migration_is_missing() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let (name, url) = create_database(&database_url).await;
+    let pool = PgPool::connect(&url).await.unwrap();
+
+    // Apply everything but the newest migration
+    let full = shipped_migrator().await;
+    let mut partial = shipped_migrator().await;
+    let newest = partial.migrations.last().unwrap().clone();
+    partial.migrations = Cow::Owned(partial.migrations[..partial.migrations.len() - 1].to_vec());
+    partial.run(&pool).await.unwrap();
+
+    let base_url = spawn_app(&url, pool.clone()).await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/ready", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["status"], "not_ready");
+    assert_eq!(body["migrations_current"], false);
+    assert_eq!(
+        body["migration_drift"],
+        serde_json::json!([{ "kind": "pending", "version": newest.version }])
+    );
+
+    let res = client
+        .get(format!("{}/admin/db/migrations", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["up_to_date"], false);
+    assert_eq!(
+        body["applied"].as_array().unwrap().len(),
+        full.migrations.len() - 1
+    );
+    let pending = body["pending"].as_array().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0]["version"], newest.version);
+
+    // Applying the rest makes the service ready
+    full.run(&pool).await.unwrap();
+    let res = client
+        .get(format!("{}/ready", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["migrations_current"], true);
+    assert!(body.get("migration_drift").is_none());
+
+    pool.close().await;
+    drop_database(&database_url, &name).await;
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch_is_reported_as_drift() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let (name, url) = create_database(&database_url).await;
+    let pool = PgPool::connect(&url).await.unwrap();
+    let migrator = shipped_migrator().await;
+    migrator.run(&pool).await.unwrap();
+
+    // As if the file had been edited after it was applied
+    let edited = migrator.migrations.first().unwrap().version;
+    sqlx::query("UPDATE _sqlx_migrations SET checksum = '\\x00' WHERE version = $1")
+        .bind(edited)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let base_url = spawn_app(&url, pool.clone()).await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/ready", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["migrations_current"], false);
+    assert_eq!(
+        body["migration_drift"],
+        serde_json::json!([{ "kind": "checksum_mismatch", "version": edited }])
+    );
+
+    let res = client
+        .get(format!("{}/admin/db/migrations", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["up_to_date"], false);
+    assert!(body["pending"].as_array().unwrap().is_empty());
+    assert_eq!(body["drift"][0]["kind"], "checksum_mismatch");
+
+    pool.close().await;
+    drop_database(&database_url, &name).await;
+}
+
+#[tokio::test]
+async fn test_version_reports_crate_version() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = PgPool::connect(&database_url).await.unwrap();
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/version", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let version: Value = res.json().await.unwrap();
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    for field in ["git_sha", "build_timestamp", "rustc_version"] {
+        assert!(version[field].as_str().is_some_and(|v| !v.is_empty()));
+    }
+
+    // Health reports the same build
+    let health: Value = client
+        .get(format!("{}/health", base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(health["version"], version["version"]);
+    assert_eq!(health["git_sha"], version["git_sha"]);
+}
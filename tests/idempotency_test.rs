@@ -3,6 +3,13 @@ mod idempotency_tests {
     // Note: These tests require a running Redis instance
     // Run with: docker-compose up -d redis
 
+    use std::time::Duration;
+    use synapse_core::middleware::idempotency::{IdempotencyService, IdempotencyStatus};
+
+    fn redis_url() -> String {
+        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string())
+    }
+
     #[tokio::test]
     #[ignore] // Ignore by default since it requires Redis
     async fn test_idempotency_new_request() {
@@ -15,12 +22,176 @@ mod idempotency_tests {
     #[tokio::test]
     #[ignore]
     async fn test_idempotency_duplicate_request() {
-        // TODO: Test that duplicate requests return cached response
+        use axum::{body::Body, http::Request, http::StatusCode, routing::post, Router};
+        use synapse_core::metrics::registry;
+        use synapse_core::middleware::idempotency::{
+            idempotency_middleware, IDEMPOTENCY_METRIC, RESULT_COMPLETED, RESULT_NEW,
+        };
+        use tower::ServiceExt;
+
+        let service = IdempotencyService::new(&redis_url()).unwrap();
+        let app = Router::new()
+            .route("/callback", post(|| async { StatusCode::CREATED }))
+            .layer(axum::middleware::from_fn_with_state(
+                service,
+                idempotency_middleware,
+            ));
+
+        let key = format!("test-duplicate-{}", uuid::Uuid::new_v4());
+        let request = || {
+            Request::builder()
+                .method("POST")
+                .uri("/callback")
+                .header("x-idempotency-key", &key)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let new = [("result", RESULT_NEW)];
+        let completed = [("result", RESULT_COMPLETED)];
+        let new_before = registry().counter(IDEMPOTENCY_METRIC, &new);
+        let completed_before = registry().counter(IDEMPOTENCY_METRIC, &completed);
+
+        let first = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(registry().counter(IDEMPOTENCY_METRIC, &new) > new_before);
+
+        let duplicate = app.oneshot(request()).await.unwrap();
+        assert_eq!(duplicate.status(), StatusCode::CREATED);
+        assert!(duplicate.headers().get("content-type").is_some());
+        assert!(registry().counter(IDEMPOTENCY_METRIC, &completed) > completed_before);
     }
 
     #[tokio::test]
     #[ignore]
     async fn test_idempotency_processing_lock() {
-        // TODO: Test that concurrent requests return 429
+        let service =
+            IdempotencyService::with_lock_ttl(&redis_url(), Duration::from_secs(10)).unwrap();
+        let key = format!("test-processing-{}", uuid::Uuid::new_v4());
+
+        let first = service.check_idempotency(&key, "owner").await.unwrap();
+        assert!(matches!(first, IdempotencyStatus::New));
+
+        let second = service.check_idempotency(&key, "other").await.unwrap();
+        match second {
+            IdempotencyStatus::Processing { retry_after_secs } => {
+                assert!((1..=10).contains(&retry_after_secs))
+            }
+            other => panic!("expected Processing, got {:?}", other),
+        }
+
+        // A non-owner cannot release the lock
+        assert!(!service.release_lock(&key, "other").await.unwrap());
+        assert!(service.release_lock(&key, "owner").await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_idempotency_stale_lock_reclaimed_after_ttl() {
+        let service =
+            IdempotencyService::with_lock_ttl(&redis_url(), Duration::from_millis(500)).unwrap();
+        let key = format!("test-stale-{}", uuid::Uuid::new_v4());
+
+        // The owner acquires the lock and then "crashes" without releasing it
+        let first = service.check_idempotency(&key, "crashed").await.unwrap();
+        assert!(matches!(first, IdempotencyStatus::New));
+
+        tokio::time::sleep(Duration::from_millis(700)).await;
+
+        // After the TTL a retry reclaims the lock
+        let retry = service.check_idempotency(&key, "retry").await.unwrap();
+        assert!(matches!(retry, IdempotencyStatus::New));
+
+        // The crashed owner can no longer overwrite or release the new lock
+        assert!(!service
+            .store_response(&key, "crashed", 200, "{}".to_string())
+            .await
+            .unwrap());
+        assert!(!service.release_lock(&key, "crashed").await.unwrap());
+
+        assert!(service
+            .store_response(&key, "retry", 200, "{}".to_string())
+            .await
+            .unwrap());
+        let cached = service.check_idempotency(&key, "later").await.unwrap();
+        assert!(matches!(cached, IdempotencyStatus::Completed(_)));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_same_key_on_different_paths_does_not_collide() {
+        use axum::{body::Body, http::Request, http::StatusCode, routing::post, Router};
+        use synapse_core::middleware::idempotency::idempotency_middleware;
+        use tower::ServiceExt;
+
+        let service = IdempotencyService::new(&redis_url()).unwrap();
+        let app = Router::new()
+            .route("/callback", post(|| async { StatusCode::CREATED }))
+            .route("/refund", post(|| async { StatusCode::CREATED }))
+            .layer(axum::middleware::from_fn_with_state(
+                service,
+                idempotency_middleware,
+            ));
+
+        let key = format!("test-scope-{}", uuid::Uuid::new_v4());
+        let request = |path: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(path)
+                .header("x-idempotency-key", &key)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let first = app.clone().oneshot(request("/callback")).await.unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+
+        // Cached replays are JSON; the handlers themselves return no body
+        let is_cached =
+            |res: &axum::response::Response| res.headers().get("content-type").is_some();
+        assert!(!is_cached(&first));
+
+        // Same key on another path is processed, not served from the cache
+        let other = app.clone().oneshot(request("/refund")).await.unwrap();
+        assert_eq!(other.status(), StatusCode::CREATED);
+        assert!(!is_cached(&other));
+
+        // Replaying on the original path still hits its own cache entry
+        let replay = app.clone().oneshot(request("/callback")).await.unwrap();
+        assert!(is_cached(&replay));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_concurrent_checks_share_one_connection() {
+        let service = IdempotencyService::new(&redis_url()).unwrap();
+
+        // Clones share the connection manager, so every task talks to Redis
+        // over the same client connection
+        let client_id = |service: IdempotencyService| async move {
+            let mut conn = service.connection().await.unwrap();
+            redis::cmd("CLIENT")
+                .arg("ID")
+                .query_async::<_, i64>(&mut conn)
+                .await
+                .unwrap()
+        };
+        let expected = client_id(service.clone()).await;
+
+        let tasks: Vec<_> = (0..50)
+            .map(|i| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    let key = format!("test-concurrent-{}-{}", i, uuid::Uuid::new_v4());
+                    let status = service.check_idempotency(&key, "owner").await.unwrap();
+                    assert!(matches!(status, IdempotencyStatus::New));
+                    assert!(service.release_lock(&key, "owner").await.unwrap());
+                    client_id(service).await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), expected);
+        }
     }
 }
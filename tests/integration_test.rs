@@ -1,8 +1,8 @@
+mod common;
+
 use reqwest::StatusCode;
 use serde_json::json;
-use sqlx::{migrate::Migrator, PgPool};
-use std::path::Path;
-use synapse_core::{create_app, AppState};
+use sqlx::PgPool;
 use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::postgres::Postgres;
 
@@ -14,76 +14,15 @@ async fn setup_test_app() -> (String, PgPool, impl std::any::Any) {
         host_port
     );
 
-    let pool = PgPool::connect(&database_url).await.unwrap();
-    let migrator = Migrator::new(Path::join(
-        Path::new(env!("CARGO_MANIFEST_DIR")),
-        "migrations",
-    ))
-    .await
-    .unwrap();
-    migrator.run(&pool).await.unwrap();
-
-    // Create partition for current month
-    let _ = sqlx::query(
-        r#"
-        DO $$
-        DECLARE
-            partition_date DATE;
-            partition_name TEXT;
-            start_date TEXT;
-            end_date TEXT;
-        BEGIN
-            partition_date := DATE_TRUNC('month', NOW());
-            partition_name := 'transactions_y' || TO_CHAR(partition_date, 'YYYY') || 'm' || TO_CHAR(partition_date, 'MM');
-            start_date := TO_CHAR(partition_date, 'YYYY-MM-DD');
-            end_date := TO_CHAR(partition_date + INTERVAL '1 month', 'YYYY-MM-DD');
-            
-            IF NOT EXISTS (SELECT 1 FROM pg_class WHERE relname = partition_name) THEN
-                EXECUTE format(
-                    'CREATE TABLE %I PARTITION OF transactions FOR VALUES FROM (%L) TO (%L)',
-                    partition_name, start_date, end_date
-                );
-            END IF;
-        END $$;
-        "#
-    )
-    .execute(&pool)
-    .await;
-
-    let (tx, _rx) = tokio::sync::broadcast::channel(100);
-
-    let app_state = AppState {
-        db: pool.clone(),
-        pool_manager: synapse_core::db::pool_manager::PoolManager::new(&database_url, None)
-            .await
-            .unwrap(),
-        horizon_client: synapse_core::stellar::HorizonClient::new(
-            "https://horizon-testnet.stellar.org".to_string(),
-        ),
-        feature_flags: synapse_core::services::feature_flags::FeatureFlagService::new(pool.clone()),
-        redis_url: "redis://localhost:6379".to_string(),
-        start_time: std::time::Instant::now(),
-        readiness: synapse_core::ReadinessState::new(),
-        tx_broadcast: tx,
-    };
-    let app = create_app(app_state);
-
-    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
-    let actual_addr = server.local_addr();
-
-    tokio::spawn(async move {
-        server.await.unwrap();
-    });
-
-    let base_url = format!("http://{}", actual_addr);
+    let pool = common::setup_db(&database_url).await;
+    let base_url = common::spawn_app(&database_url, pool.clone()).await;
     (base_url, pool, container)
 }
 
 #[tokio::test]
 async fn test_valid_deposit_flow() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let payload = json!({
         "stellar_account": "GABC1234567890",
@@ -122,7 +61,7 @@ async fn test_valid_deposit_flow() {
 #[tokio::test]
 async fn test_callback_with_memo_and_metadata() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let payload = json!({
         "stellar_account": "GDEF9876543210",
@@ -176,13 +115,13 @@ async fn test_callback_with_memo_and_metadata() {
 #[tokio::test]
 async fn test_callback_with_hash_memo_type() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let payload = json!({
         "stellar_account": "GHIJ5555555555",
         "amount": "500.00",
         "asset_code": "USD",
-        "memo": "abc123def456",
+        "memo": "abc123def456abc123def456abc123def456abc123def456abc123def4560000",
         "memo_type": "hash"
     });
 
@@ -196,14 +135,17 @@ async fn test_callback_with_hash_memo_type() {
 
     assert_eq!(res.status(), StatusCode::CREATED);
     let transaction: serde_json::Value = res.json().await.unwrap();
-    assert_eq!(transaction["memo"], "abc123def456");
+    assert_eq!(
+        transaction["memo"],
+        "abc123def456abc123def456abc123def456abc123def456abc123def4560000"
+    );
     assert_eq!(transaction["memo_type"], "hash");
 }
 
 #[tokio::test]
 async fn test_callback_with_invalid_memo_type() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let payload = json!({
         "stellar_account": "GKLM7777777777",
@@ -224,10 +166,46 @@ async fn test_callback_with_invalid_memo_type() {
     assert_eq!(res.status(), StatusCode::BAD_REQUEST);
 }
 
+#[tokio::test]
+async fn test_callback_rejects_memo_not_matching_memo_type() {
+    let (base_url, _pool, _container) = setup_test_app().await;
+    let client = common::client();
+
+    for (memo, memo_type) in [
+        ("this text memo is longer than 28 bytes", "text"),
+        ("not-a-hex-hash", "hash"),
+        ("invoice-7", "id"),
+    ] {
+        let payload = json!({
+            "stellar_account": "GKLM7777777777",
+            "amount": "100.00",
+            "asset_code": "USD",
+            "memo": memo,
+            "memo_type": memo_type
+        });
+
+        let res = client
+            .post(format!("{}/callback", base_url))
+            .header("X-App-Signature", "valid-signature")
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{} memo", memo_type);
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert!(
+            body["error"].as_str().unwrap_or_default().contains("memo"),
+            "{:?}",
+            body
+        );
+    }
+}
+
 #[tokio::test]
 async fn test_callback_with_metadata_only() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let payload = json!({
         "stellar_account": "GNOP3333333333",
@@ -258,7 +236,7 @@ async fn test_callback_with_metadata_only() {
 #[ignore = "Signature validation not implemented"]
 async fn test_invalid_signature_flow() {
     let (base_url, _pool, _container) = setup_test_app().await;
-    let client = reqwest::Client::new();
+    let client = common::client();
 
     let payload = json!({
         "stellar_account": "GABC1234567890",
@@ -0,0 +1,301 @@
+mod common;
+
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use common::{deposit, insert_completed, setup_db};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use synapse_core::create_app_with_jobs;
+use synapse_core::db::audit::AuditLog;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries::{self, ACTION_ANONYMIZED, ANONYMIZED_ACCOUNT};
+use synapse_core::services::jobs::{PiiRetentionJob, SettlementJob};
+use synapse_core::services::reconciliation::ReconciliationWorker;
+use synapse_core::services::settlement::DEFAULT_MAX_BATCH_SIZE;
+use synapse_core::services::transaction::{STATUS_COMPLETED, STATUS_RECONCILIATION_FAILED};
+use synapse_core::services::{Job, JobSchedule, JobScheduler};
+use synapse_core::stellar::HorizonClient;
+use uuid::Uuid;
+
+/// Counts its runs
+struct CountingJob(Arc<AtomicU32>);
+
+#[async_trait]
+impl Job for CountingJob {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    fn schedule(&self) -> JobSchedule {
+        JobSchedule::Every(Duration::from_millis(100))
+    }
+
+    async fn execute(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+async fn spawn_app(database_url: &str, pool: PgPool, jobs: JobScheduler) -> String {
+    let app_state = common::test_state(database_url, pool).await;
+    common::serve(create_app_with_jobs(app_state, jobs)).await
+}
+
+#[tokio::test]
+async fn test_admin_jobs_reports_last_run() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let runs = Arc::new(AtomicU32::new(0));
+    let jobs = JobScheduler::new();
+    jobs.register_job(Box::new(CountingJob(runs.clone())))
+        .await
+        .unwrap();
+    let base_url = spawn_app(&database_url, pool, jobs.clone()).await;
+    let client = reqwest::Client::new();
+
+    let get_jobs = || async {
+        let res = client
+            .get(format!("{}/admin/jobs", base_url))
+            .header("Authorization", "Bearer admin-secret-key")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        res.json::<serde_json::Value>().await.unwrap()
+    };
+
+    let body = get_jobs().await;
+    assert_eq!(body[0]["name"], "counting");
+    assert_eq!(body[0]["run_count"], 0);
+    assert!(body[0]["last_run"].is_null());
+    assert!(body[0]["next_run"].is_string());
+
+    jobs.start().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(350)).await;
+
+    let body = get_jobs().await;
+    assert!(runs.load(Ordering::SeqCst) >= 1);
+    assert!(body[0]["run_count"].as_u64().unwrap() >= 1);
+    assert!(body[0]["last_run"].is_string());
+    assert_eq!(body[0]["last_success"], true);
+    assert_eq!(body[0]["is_active"], true);
+
+    jobs.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_manual_run_of_settlement_job() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let jobs = JobScheduler::new();
+    jobs.register_job(Box::new(SettlementJob::new(
+        pool.clone(),
+        DEFAULT_MAX_BATCH_SIZE,
+    )))
+    .await
+    .unwrap();
+    let base_url = spawn_app(&database_url, pool, jobs.clone()).await;
+    let client = reqwest::Client::new();
+
+    // Not started, so only the manual trigger runs it
+    let res = client
+        .post(format!("{}/admin/jobs/settlement/run", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["name"], "settlement");
+    assert_eq!(body["success"], true, "body: {}", body);
+
+    let status = &jobs.get_job_status().await["settlement"];
+    assert_eq!(status.run_count, 1);
+    assert_eq!(status.last_success, Some(true));
+
+    let res = client
+        .post(format!("{}/admin/jobs/missing/run", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let res = client
+        .post(format!("{}/admin/jobs/settlement/run", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+async fn setup_db_with_old_partition(database_url: &str) -> PgPool {
+    let pool = setup_db(database_url).await;
+    // Old rows land in an earlier month's partition
+    common::create_partition(&pool, "NOW() - INTERVAL '60 days'").await;
+    pool
+}
+
+async fn insert_at(pool: &PgPool, created_at: chrono::DateTime<chrono::Utc>) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        r#"
+        INSERT INTO transactions
+            (id, stellar_account, amount, asset_code, status, memo, memo_type, metadata,
+             created_at, updated_at)
+        VALUES ($1, 'GPIIRETENTION', $2, 'USD', 'completed', 'invoice 42', 'text', $3, $4, $4)
+        "#,
+    )
+    .bind(id)
+    .bind(BigDecimal::from(10))
+    .bind(serde_json::json!({ "customer": "Jane Doe" }))
+    .bind(created_at)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (entity_id, entity_type, action, new_val, actor)
+        VALUES ($1, 'transaction', 'created', $2, 'system')
+        "#,
+    )
+    .bind(id)
+    .bind(serde_json::json!({
+        "stellar_account": "GPIIRETENTION",
+        "amount": "10",
+        "memo": "invoice 42",
+        "metadata": { "customer": "Jane Doe" },
+    }))
+    .execute(pool)
+    .await
+    .unwrap();
+    id
+}
+
+async fn pii(pool: &PgPool, id: Uuid) -> (String, Option<String>, Option<serde_json::Value>) {
+    sqlx::query_as("SELECT stellar_account, memo, metadata FROM transactions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_old_transactions_are_anonymized_and_recent_ones_kept() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db_with_old_partition(&database_url).await;
+    let now = chrono::Utc::now();
+    let old = insert_at(&pool, now - chrono::Duration::days(60)).await;
+    let recent = insert_at(&pool, now - chrono::Duration::minutes(1)).await;
+
+    let job = PiiRetentionJob::new(pool.clone(), 30);
+    job.execute().await.unwrap();
+
+    assert_eq!(
+        pii(&pool, old).await,
+        (ANONYMIZED_ACCOUNT.to_string(), None, None)
+    );
+    assert_eq!(
+        pii(&pool, recent).await,
+        (
+            "GPIIRETENTION".to_string(),
+            Some("invoice 42".to_string()),
+            Some(serde_json::json!({ "customer": "Jane Doe" }))
+        )
+    );
+
+    // The creation entry keeps non-personal fields only, and the change is audited
+    let entries = AuditLog::for_entity(&pool, old).await.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(
+        entries[0].new_val,
+        Some(serde_json::json!({ "amount": "10" }))
+    );
+    assert_eq!(entries[1].action, ACTION_ANONYMIZED);
+    assert_eq!(entries[1].actor, "system");
+    let recent_entries = AuditLog::for_entity(&pool, recent).await.unwrap();
+    assert_eq!(recent_entries.len(), 1);
+    assert_eq!(
+        recent_entries[0].new_val.as_ref().unwrap()["memo"],
+        "invoice 42"
+    );
+
+    // A second run leaves already anonymized rows alone
+    job.execute().await.unwrap();
+    assert_eq!(AuditLog::for_entity(&pool, old).await.unwrap().len(), 2);
+}
+
+async fn completed_with_hash(pool: &PgPool, hash: &str) -> Transaction {
+    let mut tx = deposit("USD", "10.00");
+    tx.anchor_transaction_id = Some(hash.to_string());
+    tx.callback_status = Some("completed".to_string());
+    insert_completed(pool, &tx).await
+}
+
+fn horizon_tx_body(hash: &str, successful: bool) -> String {
+    serde_json::json!({
+        "id": hash,
+        "hash": hash,
+        "successful": successful,
+        "ledger": 123,
+        "created_at": "2026-01-01T00:00:00Z",
+        "memo": null
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_unsuccessful_onchain_transaction_is_flagged() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+
+    let failed_hash = Uuid::new_v4().simple().to_string();
+    let ok_hash = Uuid::new_v4().simple().to_string();
+    let failed_tx = completed_with_hash(&pool, &failed_hash).await;
+    let ok_tx = completed_with_hash(&pool, &ok_hash).await;
+
+    let mut horizon = mockito::Server::new_async().await;
+    let _failed = horizon
+        .mock("GET", format!("/transactions/{}", failed_hash).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(horizon_tx_body(&failed_hash, false))
+        .create_async()
+        .await;
+    let _ok = horizon
+        .mock("GET", format!("/transactions/{}", ok_hash).as_str())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(horizon_tx_body(&ok_hash, true))
+        .create_async()
+        .await;
+
+    // Rows left over from other tests hit unmatched routes; keep the circuit closed
+    let client = HorizonClient::with_endpoints_and_circuit_breaker(vec![horizon.url()], 1000, 60);
+    let worker = ReconciliationWorker::new(pool.clone(), client);
+    let report = worker.run_once().await.unwrap();
+    assert!(report.flagged >= 1, "{:?}", report);
+
+    let flagged = queries::get_transaction(&pool, failed_tx.id).await.unwrap();
+    assert_eq!(flagged.status, STATUS_RECONCILIATION_FAILED);
+    let details = &flagged.metadata.unwrap()["reconciliation"];
+    assert_eq!(details["reason"], "unsuccessful_on_chain");
+    assert_eq!(details["hash"], failed_hash);
+
+    let verified = queries::get_transaction(&pool, ok_tx.id).await.unwrap();
+    assert_eq!(verified.status, STATUS_COMPLETED);
+    assert!(verified.metadata.unwrap().get("reconciled_at").is_some());
+}
@@ -0,0 +1,286 @@
+mod common;
+
+use axum::extract::State;
+use common::{completed_transaction, pending_transaction, setup_db, spawn_app, unique_asset};
+use reqwest::StatusCode;
+use serde_json::json;
+use synapse_core::db::pool_manager::PoolManager;
+use synapse_core::handlers::export::{EXPORTS_METRIC, EXPORT_DURATION_METRIC, EXPORT_ROWS_METRIC};
+use synapse_core::handlers::webhook::{BULK_CALLBACK_THRESHOLD, CALLBACKS_METRIC};
+use synapse_core::metrics::{
+    self, metrics_handler, MetricsHandle, MetricsState, POOL_IDLE_METRIC, POOL_MAX_METRIC,
+};
+use synapse_core::services::{settlement, SettlementService};
+
+#[tokio::test]
+async fn test_metrics_include_pool_stats() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    // Point the replica at the same database so both pools are reported
+    let pool_manager = PoolManager::new(&database_url, Some(&database_url))
+        .await
+        .expect("Failed to create pool manager");
+    sqlx::query("SELECT 1")
+        .execute(pool_manager.primary())
+        .await
+        .unwrap();
+
+    let rendered = metrics_handler(State(MetricsState {
+        handle: MetricsHandle,
+        pool_manager,
+    }))
+    .await
+    .unwrap();
+
+    assert!(rendered.contains("# TYPE db_pool_idle_connections gauge"));
+    assert!(rendered.contains("db_pool_max_connections{pool=\"primary\"} 10"));
+    assert!(rendered.contains("db_pool_max_connections{pool=\"replica_1\"} 10"));
+    assert!(rendered.contains("db_pool_idle_connections{pool=\"replica_1\"}"));
+    assert!(rendered.contains("active_db_connections "));
+
+    let registry = metrics::registry();
+    let idle = registry
+        .labelled_gauge(POOL_IDLE_METRIC, &[("pool", "primary")])
+        .unwrap();
+    let max = registry
+        .labelled_gauge(POOL_MAX_METRIC, &[("pool", "primary")])
+        .unwrap();
+    assert!(idle <= max);
+}
+
+#[tokio::test]
+async fn test_deposit_callback_increments_labelled_counter() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let labels = [
+        ("asset_code", "USD"),
+        ("callback_type", "deposit"),
+        ("status", "completed"),
+    ];
+    let before = metrics::registry().counter(CALLBACKS_METRIC, &labels);
+
+    let res = common::client()
+        .post(format!("{}/callback", base_url))
+        .json(&json!({
+            "stellar_account": format!("G{}", "A".repeat(55)),
+            "amount": "12.00",
+            "asset_code": "USD",
+            "callback_type": "deposit",
+            "callback_status": "completed"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    assert_eq!(
+        metrics::registry().counter(CALLBACKS_METRIC, &labels),
+        before + 1
+    );
+    let rendered = metrics::registry().render();
+    assert!(rendered.contains(
+        "callbacks_total{asset_code=\"USD\",callback_type=\"deposit\",status=\"completed\"}"
+    ));
+}
+
+#[tokio::test]
+async fn test_unlisted_asset_codes_share_the_other_label() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let labels = [
+        ("asset_code", "other"),
+        ("callback_type", "deposit"),
+        ("status", "completed"),
+    ];
+    let before = metrics::registry().counter(CALLBACKS_METRIC, &labels);
+
+    let res = common::client()
+        .post(format!("{}/callback", base_url))
+        .json(&json!({
+            "stellar_account": format!("G{}", "A".repeat(55)),
+            "amount": "12.00",
+            "asset_code": "ZQX",
+            "callback_type": "deposit",
+            "callback_status": "completed"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    assert_eq!(
+        metrics::registry().counter(CALLBACKS_METRIC, &labels),
+        before + 1
+    );
+    assert!(!metrics::registry().render().contains("asset_code=\"ZQX\""));
+}
+
+#[tokio::test]
+async fn test_batch_callback_counts_each_stored_item() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    // Labels no other test in this binary uses, so the counts are exact
+    let labels = [
+        ("asset_code", "USD"),
+        ("callback_type", "withdrawal"),
+        ("status", "completed"),
+    ];
+    let item = |amount: &str| {
+        json!({
+            "stellar_account": format!("G{}", "A".repeat(55)),
+            "amount": amount,
+            "asset_code": "USD",
+            "callback_type": "withdrawal",
+            "callback_status": "completed"
+        })
+    };
+    let send = |query: &'static str, transactions: Vec<serde_json::Value>| {
+        common::client()
+            .post(format!("{}/callback/batch{}", base_url, query))
+            .json(&json!({ "transactions": transactions }))
+            .send()
+    };
+    let invalid = item("not-a-number");
+    let count = || metrics::registry().counter(CALLBACKS_METRIC, &labels);
+
+    // Invalid items are not stored and not counted
+    let before = count();
+    let res = send("", vec![item("1.00"), invalid.clone(), item("2.00")])
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    assert_eq!(count(), before + 2);
+
+    // Bulk-inserted batches count every item
+    let before = count();
+    let res = send(
+        "",
+        (0..BULK_CALLBACK_THRESHOLD).map(|_| item("1.00")).collect(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    assert_eq!(count(), before + BULK_CALLBACK_THRESHOLD as u64);
+
+    // A rolled-back atomic batch stores nothing
+    let before = count();
+    let res = send("?atomic=true", vec![item("1.00"), invalid])
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    assert_eq!(count(), before);
+}
+
+#[tokio::test]
+async fn test_export_records_metrics_by_format() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let asset_code = unique_asset("X");
+    for _ in 0..3 {
+        pending_transaction(&pool, &asset_code, "2.00").await;
+    }
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+    let registry = metrics::registry();
+    let csv = [("format", "csv")];
+    let json = [("format", "json")];
+    let csv_exports = registry.counter(EXPORTS_METRIC, &csv);
+    let csv_rows = registry.counter(EXPORT_ROWS_METRIC, &csv);
+    let json_exports = registry.counter(EXPORTS_METRIC, &json);
+    let json_rows = registry.counter(EXPORT_ROWS_METRIC, &json);
+    let durations = registry.histogram_count(EXPORT_DURATION_METRIC);
+
+    let res = client
+        .get(format!("{}/export?asset_code={}", base_url, asset_code))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.text().await.unwrap();
+
+    assert_eq!(registry.counter(EXPORTS_METRIC, &csv), csv_exports + 1);
+    // The header line is not a data row
+    assert_eq!(registry.counter(EXPORT_ROWS_METRIC, &csv), csv_rows + 3);
+    assert_eq!(registry.counter(EXPORTS_METRIC, &json), json_exports);
+
+    let res = client
+        .get(format!(
+            "{}/export?asset_code={}&format=json",
+            base_url, asset_code
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.text().await.unwrap();
+
+    assert_eq!(registry.counter(EXPORTS_METRIC, &json), json_exports + 1);
+    assert_eq!(registry.counter(EXPORT_ROWS_METRIC, &json), json_rows + 3);
+    assert_eq!(registry.counter(EXPORT_ROWS_METRIC, &csv), csv_rows + 3);
+    assert_eq!(
+        registry.histogram_count(EXPORT_DURATION_METRIC),
+        durations + 2
+    );
+}
+
+#[tokio::test]
+async fn test_settlement_run_records_metrics() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let asset_code = unique_asset("M");
+    for _ in 0..2 {
+        completed_transaction(&pool, &asset_code, "5.00").await;
+    }
+
+    let registry = metrics::registry();
+    let batches_before = registry.histogram_count(settlement::BATCH_TRANSACTIONS_METRIC);
+    let runs_before = registry.histogram_count(settlement::RUN_DURATION_METRIC);
+
+    let settlements = SettlementService::new(pool.clone())
+        .run_settlements()
+        .await
+        .unwrap();
+    assert!(settlements
+        .iter()
+        .any(|s| s.asset_code == asset_code && s.tx_count == 2));
+
+    assert!(registry.histogram_count(settlement::BATCH_TRANSACTIONS_METRIC) > batches_before);
+    assert_eq!(
+        registry.histogram_count(settlement::RUN_DURATION_METRIC),
+        runs_before + 1
+    );
+    assert_eq!(
+        registry.counter(
+            settlement::CREATED_METRIC,
+            &[("asset_code", asset_code.as_str())]
+        ),
+        1
+    );
+
+    let rendered = registry.render();
+    assert!(rendered.contains("# TYPE settlement_batch_transactions histogram"));
+    assert!(rendered.contains("settlement_run_duration_seconds_count"));
+}
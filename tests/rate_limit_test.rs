@@ -0,0 +1,51 @@
+mod common;
+
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::Arc;
+use synapse_core::config::Config;
+use synapse_core::{create_app, AppState};
+
+#[tokio::test]
+async fn test_route_overrides_apply_to_the_app_router() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = common::setup_db(&database_url).await;
+    let app_state = AppState {
+        config: Arc::new(Config {
+            rate_limit_overrides: "/export:1/s,/callback:100/s".to_string(),
+            ..Config::default()
+        }),
+        ..common::test_state(&database_url, pool).await
+    };
+    let base_url = common::serve(create_app(app_state)).await;
+    let client = common::client();
+
+    let export = |client: &reqwest::Client| {
+        client
+            .get(format!("{}/export?asset_code=NOPE", base_url))
+            .header("x-forwarded-for", "203.0.113.5")
+            .send()
+    };
+    assert_ne!(
+        export(&client).await.unwrap().status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    let limited = export(&client).await.unwrap();
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(limited.headers().contains_key("retry-after"));
+
+    // The same client still has room on the callback quota
+    for _ in 0..3 {
+        let res = client
+            .post(format!("{}/callback", base_url))
+            .header("x-forwarded-for", "203.0.113.5")
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_ne!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
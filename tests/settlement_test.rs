@@ -0,0 +1,649 @@
+mod common;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use common::{completed_transaction, deposit, insert_completed, setup_db, spawn_app, unique_asset};
+use reqwest::StatusCode;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::str::FromStr;
+use synapse_core::middleware::auth::SCOPE_READ;
+use synapse_core::services::settlement::SettlementStatus;
+use synapse_core::services::{ApiKeyService, SettlementService};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_backlog_above_cap_is_split_into_settlements() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let asset_code = unique_asset("B");
+    completed_transactions(&pool, &asset_code, 5).await;
+
+    let settlements = SettlementService::new(pool.clone())
+        .with_max_batch_size(2)
+        .settle_asset_batches(&asset_code)
+        .await
+        .unwrap();
+
+    let counts: Vec<i32> = settlements.iter().map(|s| s.tx_count).collect();
+    assert_eq!(counts, vec![2, 2, 1]);
+    let ids: std::collections::HashSet<Uuid> = settlements.iter().map(|s| s.id).collect();
+    assert_eq!(ids.len(), 3);
+
+    let unsettled: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM transactions WHERE asset_code = $1 AND settlement_id IS NULL",
+    )
+    .bind(&asset_code)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(unsettled, 0);
+}
+
+async fn completed_transactions(pool: &PgPool, asset_code: &str, count: usize) {
+    for _ in 0..count {
+        completed_transaction(pool, asset_code, "5.00").await;
+    }
+}
+
+async fn unsettled_count(pool: &PgPool, asset_code: &str) -> i64 {
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM transactions WHERE asset_code = $1 AND settlement_id IS NULL",
+    )
+    .bind(asset_code)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_rerunning_settlement_creates_nothing() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let asset_code = unique_asset("R");
+    completed_transactions(&pool, &asset_code, 2).await;
+
+    let service = SettlementService::new(pool.clone());
+    let first = service.settle_asset_batches(&asset_code).await.unwrap();
+    assert_eq!(first.len(), 1);
+
+    // Every candidate is now in a settlement, so the rerun has nothing to do
+    let rerun = service.settle_asset_batches(&asset_code).await.unwrap();
+    assert!(rerun.is_empty());
+
+    let settlements: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM settlements WHERE asset_code = $1")
+            .bind(&asset_code)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(settlements, 1);
+}
+
+#[tokio::test]
+async fn test_batches_created_at_the_same_instant_all_settle() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // Rows written in one statement share created_at and updated_at, so a
+    // batch cut between them would give two settlements the same period
+    let asset_code = unique_asset("T");
+    let now: DateTime<Utc> = sqlx::query_scalar("SELECT NOW()")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    for _ in 0..5 {
+        sqlx::query(
+            "INSERT INTO transactions (id, stellar_account, amount, asset_code, status, created_at, updated_at)
+             VALUES ($1, $2, 5, $3, 'completed', $4, $4)",
+        )
+        .bind(Uuid::new_v4())
+        .bind("GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA")
+        .bind(&asset_code)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    completed_transactions(&pool, &asset_code, 1).await;
+
+    let settlements = SettlementService::new(pool.clone())
+        .with_max_batch_size(2)
+        .settle_asset_batches(&asset_code)
+        .await
+        .unwrap();
+
+    let counts: Vec<i32> = settlements.iter().map(|s| s.tx_count).collect();
+    assert_eq!(counts, vec![5, 1], "the tie stays in one settlement");
+    assert_eq!(unsettled_count(&pool, &asset_code).await, 0);
+}
+
+#[tokio::test]
+async fn test_voided_settlement_does_not_block_its_period() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    let asset_code = unique_asset("V");
+    completed_transactions(&pool, &asset_code, 2).await;
+
+    let (period_start, period_end): (DateTime<Utc>, DateTime<Utc>) = sqlx::query_as(
+        "SELECT MIN(created_at), MAX(updated_at) FROM transactions WHERE asset_code = $1",
+    )
+    .bind(&asset_code)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO settlements (asset_code, total_amount, tx_count, period_start, period_end, status)
+         VALUES ($1, 10, 2, $2, $3, 'voided')",
+    )
+    .bind(&asset_code)
+    .bind(period_start)
+    .bind(period_end)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let settlements = SettlementService::new(pool.clone())
+        .settle_asset_batches(&asset_code)
+        .await
+        .unwrap();
+    assert_eq!(settlements.len(), 1);
+    assert_eq!(settlements[0].period_start, period_start);
+    assert_eq!(unsettled_count(&pool, &asset_code).await, 0);
+}
+
+#[tokio::test]
+async fn test_preview_matches_real_settlement() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+
+    let asset_code = unique_asset("P");
+    let mut ids = vec![
+        completed_transaction(&pool, &asset_code, "10.25").await.id,
+        completed_transaction(&pool, &asset_code, "4.75").await.id,
+        completed_transaction(&pool, &asset_code, "5").await.id,
+    ];
+    ids.sort();
+
+    let res = client
+        .get(format!(
+            "{}/settlements/preview?asset_code={}",
+            base_url, asset_code
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let preview: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(preview["tx_count"], 3);
+    let assets = preview["assets"].as_array().unwrap();
+    assert_eq!(assets.len(), 1);
+    assert_eq!(assets[0]["asset_code"], asset_code.as_str());
+    assert_eq!(assets[0]["tx_count"], 3);
+    assert_eq!(assets[0]["batches"].as_array().unwrap().len(), 1);
+    let mut previewed_ids: Vec<Uuid> = assets[0]["transaction_ids"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| Uuid::parse_str(id.as_str().unwrap()).unwrap())
+        .collect();
+    previewed_ids.sort();
+    assert_eq!(previewed_ids, ids);
+    let previewed_total =
+        BigDecimal::from_str(assets[0]["total_amount"].as_str().unwrap()).unwrap();
+
+    // Nothing was written by the preview
+    let unsettled: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM transactions WHERE asset_code = $1 AND settlement_id IS NULL",
+    )
+    .bind(&asset_code)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(unsettled, 3);
+
+    let settlement = SettlementService::new(pool.clone())
+        .settle_asset(&asset_code)
+        .await
+        .unwrap()
+        .expect("settlement created");
+    assert_eq!(settlement.tx_count, 3);
+    assert_eq!(settlement.total_amount, previewed_total);
+    assert_eq!(previewed_total, BigDecimal::from(20));
+
+    // Once settled, the preview is empty
+    let res = client
+        .get(format!(
+            "{}/settlements/preview?asset_code={}",
+            base_url, asset_code
+        ))
+        .send()
+        .await
+        .unwrap();
+    let preview: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(preview["tx_count"], 0);
+
+    let res = client
+        .get(format!("{}/settlements/preview?period_end=bogus", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_preview_splits_batches_like_a_run() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // Three transactions sharing created_at, then two more: with a cap of
+    // two the tie stays whole and the rest is split
+    let asset_code = unique_asset("V");
+    let now: DateTime<Utc> = sqlx::query_scalar("SELECT NOW() - INTERVAL '1 minute'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        sqlx::query(
+            "INSERT INTO transactions (id, stellar_account, amount, asset_code, status, created_at, updated_at)
+             VALUES ($1, $2, 5, $3, 'completed', $4, $4)",
+        )
+        .bind(Uuid::new_v4())
+        .bind("GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA")
+        .bind(&asset_code)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    completed_transactions(&pool, &asset_code, 2).await;
+
+    let service = SettlementService::new(pool.clone()).with_max_batch_size(2);
+    let preview = service
+        .preview(Some(&asset_code), Utc::now(), None)
+        .await
+        .unwrap();
+    assert_eq!(preview.tx_count, 5);
+    let previewed: Vec<usize> = preview.assets[0]
+        .batches
+        .iter()
+        .map(|b| b.tx_count)
+        .collect();
+    assert_eq!(previewed, vec![3, 2]);
+
+    let settlements = service.settle_asset_batches(&asset_code).await.unwrap();
+    let settled: Vec<usize> = settlements.iter().map(|s| s.tx_count as usize).collect();
+    assert_eq!(settled, previewed);
+    for (batch, settlement) in preview.assets[0].batches.iter().zip(&settlements) {
+        assert_eq!(batch.total_amount, settlement.total_amount);
+        assert_eq!(batch.period_start, settlement.period_start);
+    }
+}
+
+#[tokio::test]
+async fn test_partner_preview_only_lists_own_transactions() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = reqwest::Client::new();
+
+    let (partner, other) = (Uuid::new_v4(), Uuid::new_v4());
+    let asset_code = unique_asset("W");
+    let own = [
+        partner_transaction(&pool, &asset_code, "3", partner).await,
+        partner_transaction(&pool, &asset_code, "4", partner).await,
+    ];
+    partner_transaction(&pool, &asset_code, "50", other).await;
+
+    let (_, key) = ApiKeyService::new(pool.clone())
+        .create(partner, &[SCOPE_READ.to_string()])
+        .await
+        .unwrap();
+    let url = format!("{}/settlements/preview?asset_code={}", base_url, asset_code);
+    let res = client
+        .get(&url)
+        .header("Authorization", format!("Api-Key {}", key))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let preview: Value = res.json().await.unwrap();
+    assert_eq!(preview["tx_count"], 2);
+    let asset = &preview["assets"][0];
+    assert_eq!(
+        BigDecimal::from_str(asset["total_amount"].as_str().unwrap()).unwrap(),
+        BigDecimal::from(7)
+    );
+    let listed: Vec<Uuid> = asset["transaction_ids"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| Uuid::parse_str(id.as_str().unwrap()).unwrap())
+        .collect();
+    assert_eq!(listed, own);
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+async fn partner_transaction(pool: &PgPool, asset_code: &str, amount: &str, partner: Uuid) -> Uuid {
+    insert_completed(
+        pool,
+        &deposit(asset_code, amount).with_partner_id(Some(partner)),
+    )
+    .await
+    .id
+}
+
+#[tokio::test]
+async fn test_download_settlement_receipt() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+
+    let asset_code = unique_asset("R");
+    let ids = [
+        completed_transaction(&pool, &asset_code, "10.25").await.id,
+        completed_transaction(&pool, &asset_code, "4.75").await.id,
+    ];
+    let settlement = SettlementService::new(pool.clone())
+        .settle_asset(&asset_code)
+        .await
+        .unwrap()
+        .expect("settlement created");
+
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt",
+            base_url, settlement.id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["content-type"], "text/csv");
+    assert_eq!(
+        res.headers()["content-disposition"],
+        format!(
+            "attachment; filename=\"settlement_{}_receipt.csv\"",
+            settlement.id
+        )
+        .as_str()
+    );
+    let body = res.text().await.unwrap();
+    let mut lines = body.lines();
+    assert!(lines.next().unwrap().starts_with("settlement_id,"));
+    let summary = lines.next().unwrap();
+    assert!(summary.starts_with(&format!("{},{},", settlement.id, asset_code)));
+    let total = summary.rsplit(',').next().unwrap();
+    assert_eq!(BigDecimal::from_str(total).unwrap(), BigDecimal::from(15));
+    for id in &ids {
+        assert!(
+            body.contains(&id.to_string()),
+            "{} missing from {}",
+            id,
+            body
+        );
+    }
+
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt?format=json",
+            base_url, settlement.id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .ends_with("_receipt.json\""));
+    let receipt: Value = res.json().await.unwrap();
+    assert_eq!(receipt["tx_count"], 2);
+    assert_eq!(
+        BigDecimal::from_str(receipt["total_amount"].as_str().unwrap()).unwrap(),
+        settlement.total_amount
+    );
+    let listed: Vec<Uuid> = receipt["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| Uuid::parse_str(t["transaction_id"].as_str().unwrap()).unwrap())
+        .collect();
+    assert_eq!(listed, ids);
+
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt",
+            base_url,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt?format=pdf",
+            base_url, settlement.id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_partner_receipt_only_lists_own_transactions() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = reqwest::Client::new();
+
+    let (partner, other) = (Uuid::new_v4(), Uuid::new_v4());
+    let asset_code = unique_asset("R");
+    let own = [
+        partner_transaction(&pool, &asset_code, "3", partner).await,
+        partner_transaction(&pool, &asset_code, "4", partner).await,
+    ];
+    let foreign = partner_transaction(&pool, &asset_code, "50", other).await;
+    let settlement = SettlementService::new(pool.clone())
+        .settle_asset(&asset_code)
+        .await
+        .unwrap()
+        .expect("settlement created");
+
+    let keys = ApiKeyService::new(pool.clone());
+    let (_, key) = keys
+        .create(partner, &[SCOPE_READ.to_string()])
+        .await
+        .unwrap();
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt?format=json",
+            base_url, settlement.id
+        ))
+        .header("Authorization", format!("Api-Key {}", key))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let receipt: Value = res.json().await.unwrap();
+    assert_eq!(receipt["tx_count"], 2);
+    assert_eq!(
+        BigDecimal::from_str(receipt["total_amount"].as_str().unwrap()).unwrap(),
+        BigDecimal::from(7)
+    );
+    let listed: Vec<Uuid> = receipt["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| Uuid::parse_str(t["transaction_id"].as_str().unwrap()).unwrap())
+        .collect();
+    assert_eq!(listed, own);
+    assert!(!listed.contains(&foreign));
+
+    // A partner with nothing in the settlement cannot tell it exists
+    let (_, stranger) = keys
+        .create(Uuid::new_v4(), &[SCOPE_READ.to_string()])
+        .await
+        .unwrap();
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt",
+            base_url, settlement.id
+        ))
+        .header("Authorization", format!("Api-Key {}", stranger))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = client
+        .get(format!(
+            "{}/settlements/{}/receipt",
+            base_url, settlement.id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_valid_settlement_transitions() {
+    use SettlementStatus::*;
+    for (from, to) in [
+        (Pending, Completed),
+        (Pending, Voided),
+        (Completed, Paid),
+        (Completed, Voided),
+    ] {
+        assert_eq!(from.transition(to).unwrap(), to, "{} -> {}", from, to);
+    }
+}
+
+#[test]
+fn test_invalid_settlement_transitions() {
+    use SettlementStatus::*;
+    for (from, to) in [
+        (Pending, Paid),
+        (Completed, Pending),
+        (Paid, Completed),
+        (Paid, Voided),
+        (Voided, Pending),
+        (Voided, Completed),
+        (Paid, Paid),
+    ] {
+        let err = from.transition(to).unwrap_err();
+        assert_eq!(err.code(), "ERR_SETTLEMENT_003", "{} -> {}", from, to);
+    }
+}
+
+#[test]
+fn test_settlement_status_round_trips() {
+    for status in SettlementStatus::ALL {
+        assert_eq!(SettlementStatus::parse(status.as_str()), Some(status));
+    }
+    assert_eq!(SettlementStatus::parse("settled"), None);
+}
+
+async fn insert_settlement(pool: &PgPool, status: &str) -> Uuid {
+    sqlx::query_scalar(
+        "INSERT INTO settlements (asset_code, total_amount, tx_count, period_start, period_end, status)
+         VALUES ('USD', 10, 1, NOW() - INTERVAL '1 day', NOW(), $1)
+         RETURNING id",
+    )
+    .bind(status)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_patch_settlement_status() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let id = insert_settlement(&pool, "completed").await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = reqwest::Client::new();
+    let patch = |id: Uuid, status: &str| {
+        client
+            .patch(format!("{}/admin/settlements/{}/status", base_url, id))
+            .header("Authorization", "Bearer admin-secret-key")
+            .json(&serde_json::json!({ "status": status }))
+            .send()
+    };
+
+    let res = patch(id, "paid").await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["status"], "paid");
+
+    // Paid is terminal
+    let res = patch(id, "voided").await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_SETTLEMENT_003");
+    let status: String = sqlx::query_scalar("SELECT status FROM settlements WHERE id = $1")
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "paid");
+
+    let res = patch(id, "settled").await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = patch(Uuid::new_v4(), "paid").await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = client
+        .patch(format!("{}/admin/settlements/{}/status", base_url, id))
+        .json(&serde_json::json!({ "status": "voided" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // The database rejects statuses outside the lifecycle too
+    let res = sqlx::query("UPDATE settlements SET status = 'settled' WHERE id = $1")
+        .bind(id)
+        .execute(&pool)
+        .await;
+    assert!(res.is_err());
+}
@@ -0,0 +1,973 @@
+mod common;
+
+use common::{deposit, pending_transaction, setup_db, spawn_app, ADMIN_AUTH};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use synapse_core::config::Config;
+use synapse_core::create_app;
+use synapse_core::db::queries;
+use synapse_core::middleware::auth::{SCOPE_CALLBACK, SCOPE_READ};
+use synapse_core::services::ApiKeyService;
+use uuid::Uuid;
+
+async fn get_page(client: &reqwest::Client, base_url: &str, query: &[(&str, &str)]) -> Value {
+    let res = client
+        .get(format!("{}/transactions", base_url))
+        .query(query)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.json().await.unwrap()
+}
+
+/// `(created_at, id)` of every row, the key the listing is ordered by
+fn keys(page: &Value) -> Vec<(String, String)> {
+    page["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tx| {
+            (
+                tx["created_at"].as_str().unwrap().to_string(),
+                tx["id"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn timestamp(raw: &str) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::parse_from_rfc3339(raw).unwrap().into()
+}
+
+fn is_newest_first(keys: &[(String, String)]) -> bool {
+    keys.windows(2)
+        .all(|w| (timestamp(&w[0].0), &w[0].1) > (timestamp(&w[1].0), &w[1].1))
+}
+
+fn is_oldest_first(keys: &[(String, String)]) -> bool {
+    keys.windows(2)
+        .all(|w| (timestamp(&w[0].0), &w[0].1) < (timestamp(&w[1].0), &w[1].1))
+}
+
+#[tokio::test]
+async fn test_list_transactions_with_cursor() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    for amount in ["1", "2", "3"] {
+        pending_transaction(&pool, "USD", amount).await;
+    }
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let first = get_page(&client, &base_url, &[("limit", "2")]).await;
+    let first_keys = keys(&first);
+    assert_eq!(first_keys.len(), 2);
+    assert!(is_newest_first(&first_keys));
+    assert_eq!(first["meta"]["has_more"], true);
+    let cursor = first["meta"]["next_cursor"].as_str().unwrap().to_string();
+
+    let second = get_page(&client, &base_url, &[("limit", "2"), ("cursor", &cursor)]).await;
+    let second_keys = keys(&second);
+    assert!(!second_keys.is_empty());
+    assert!(is_newest_first(&second_keys));
+    // Pages continue without overlap
+    let mut joined = first_keys.clone();
+    joined.extend(second_keys.clone());
+    assert!(is_newest_first(&joined));
+
+    // Paging backward from the second page returns to the end of the first
+    let back_cursor = cursor_of_first(&second);
+    let back = get_page(
+        &client,
+        &base_url,
+        &[
+            ("limit", "1"),
+            ("direction", "backward"),
+            ("cursor", &back_cursor),
+        ],
+    )
+    .await;
+    assert_eq!(keys(&back), vec![first_keys[1].clone()]);
+    assert_eq!(back["meta"]["has_more"], true);
+
+    let res = client
+        .get(format!("{}/transactions?cursor=not-a-cursor", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Cursor pointing at the first row of `page`, as a backward page would return
+fn cursor_of_first(page: &Value) -> String {
+    let first = &page["data"][0];
+    synapse_core::utils::cursor::encode(
+        timestamp(first["created_at"].as_str().unwrap()),
+        first["id"].as_str().unwrap().parse().unwrap(),
+    )
+}
+
+/// Seven transactions for a fresh partner: five sharing one `created_at`,
+/// one older and one newer. Returns the partner's API key and the ids in
+/// listing order, newest first.
+async fn seed_partner_with_ties(pool: &PgPool) -> (String, Vec<String>) {
+    let partner_id = Uuid::new_v4();
+    let (_, key) = ApiKeyService::new(pool.clone())
+        .create(partner_id, &[SCOPE_READ.to_string()])
+        .await
+        .unwrap();
+
+    let tied_at = chrono::DateTime::from_timestamp(chrono::Utc::now().timestamp() - 60, 0).unwrap();
+    let offsets = [-10, 0, 0, 0, 0, 0, 10];
+    for offset in offsets {
+        let mut tx = deposit("USD", "1").with_partner_id(Some(partner_id));
+        tx.created_at = tied_at + chrono::Duration::seconds(offset);
+        queries::insert_transaction(pool, &tx).await.unwrap();
+    }
+
+    let expected: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM transactions WHERE partner_id = $1 ORDER BY created_at DESC, id DESC",
+    )
+    .bind(partner_id)
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    assert_eq!(expected.len(), offsets.len());
+    (key, expected.iter().map(Uuid::to_string).collect())
+}
+
+async fn get_partner_page(
+    client: &reqwest::Client,
+    base_url: &str,
+    key: &str,
+    query: &[(&str, &str)],
+) -> Value {
+    let res = client
+        .get(format!("{}/transactions", base_url))
+        .header("Authorization", format!("Api-Key {}", key))
+        .query(query)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.json().await.unwrap()
+}
+
+fn ids(page: &Value) -> Vec<String> {
+    keys(page).into_iter().map(|(_, id)| id).collect()
+}
+
+/// Follow `next_cursor` in `direction` until `has_more` is false, returning
+/// each page's ids
+async fn walk(
+    client: &reqwest::Client,
+    base_url: &str,
+    key: &str,
+    direction: &str,
+    order: &str,
+    limit: usize,
+) -> Vec<Vec<String>> {
+    let limit = limit.to_string();
+    let mut pages = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![
+            ("limit", limit.as_str()),
+            ("direction", direction),
+            ("order", order),
+        ];
+        if let Some(cursor) = &cursor {
+            query.push(("cursor", cursor));
+        }
+        let page = get_partner_page(client, base_url, key, &query).await;
+        if order == "asc" {
+            assert!(is_oldest_first(&keys(&page)));
+        } else {
+            assert!(is_newest_first(&keys(&page)));
+        }
+        pages.push(ids(&page));
+        if page["meta"]["has_more"] != true {
+            return pages;
+        }
+        cursor = page["meta"]["next_cursor"].as_str().map(str::to_string);
+        assert!(pages.len() <= 10, "pagination did not terminate");
+    }
+}
+
+#[tokio::test]
+async fn test_pagination_walks_tied_rows_without_gaps() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let (key, expected) = seed_partner_with_ties(&pool).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    for limit in 1..=expected.len() + 1 {
+        // Forward from the newest row: every row once, in listing order
+        let pages = walk(&client, &base_url, &key, "forward", "desc", limit).await;
+        assert!(pages[..pages.len() - 1].iter().all(|p| p.len() == limit));
+        assert!(!pages.last().unwrap().is_empty());
+        assert_eq!(pages.concat(), expected, "forward, limit {}", limit);
+        assert_eq!(pages.len(), expected.len().div_ceil(limit));
+
+        // Backward from the oldest row: pages get newer, each newest-first
+        let pages = walk(&client, &base_url, &key, "backward", "desc", limit).await;
+        assert!(pages[..pages.len() - 1].iter().all(|p| p.len() == limit));
+        let mut backward: Vec<String> = pages.into_iter().rev().flatten().collect();
+        assert_eq!(backward, expected, "backward, limit {}", limit);
+        backward.dedup();
+        assert_eq!(backward.len(), expected.len());
+    }
+}
+
+#[tokio::test]
+async fn test_backward_from_tied_cursor_returns_preceding_rows() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let (key, expected) = seed_partner_with_ties(&pool).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    // The first page ends inside the run of tied rows
+    let first = get_partner_page(&client, &base_url, &key, &[("limit", "3")]).await;
+    assert_eq!(ids(&first), expected[..3]);
+    assert_eq!(first["meta"]["has_more"], true);
+    let cursor = first["meta"]["next_cursor"].as_str().unwrap().to_string();
+
+    let second = get_partner_page(
+        &client,
+        &base_url,
+        &key,
+        &[("limit", "3"), ("cursor", &cursor)],
+    )
+    .await;
+    assert_eq!(ids(&second), expected[3..6]);
+
+    // Going back from the start of the second page yields the first page again
+    let back_cursor = cursor_of_first(&second);
+    let back = get_partner_page(
+        &client,
+        &base_url,
+        &key,
+        &[
+            ("limit", "3"),
+            ("direction", "backward"),
+            ("cursor", &back_cursor),
+        ],
+    )
+    .await;
+    assert_eq!(ids(&back), expected[..3]);
+    assert_eq!(back["meta"]["has_more"], false);
+
+    // A page that exactly reaches the end reports nothing more
+    let last = get_partner_page(
+        &client,
+        &base_url,
+        &key,
+        &[("limit", "4"), ("cursor", &cursor)],
+    )
+    .await;
+    assert_eq!(ids(&last), expected[3..]);
+    assert_eq!(last["meta"]["has_more"], false);
+
+    let res = client
+        .get(format!("{}/transactions?direction=sideways", base_url))
+        .header("Authorization", format!("Api-Key {}", key))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_ascending_order_paginates_oldest_first() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let (key, newest_first) = seed_partner_with_ties(&pool).await;
+    let expected: Vec<String> = newest_first.into_iter().rev().collect();
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+
+    for limit in 1..=expected.len() + 1 {
+        let pages = walk(&client, &base_url, &key, "forward", "asc", limit).await;
+        assert!(pages[..pages.len() - 1].iter().all(|p| p.len() == limit));
+        assert_eq!(pages.concat(), expected, "forward asc, limit {}", limit);
+        assert_eq!(pages.len(), expected.len().div_ceil(limit));
+
+        // Backward from the newest row: pages get older, each oldest-first
+        let pages = walk(&client, &base_url, &key, "backward", "asc", limit).await;
+        assert!(pages[..pages.len() - 1].iter().all(|p| p.len() == limit));
+        let backward: Vec<String> = pages.into_iter().rev().flatten().collect();
+        assert_eq!(backward, expected, "backward asc, limit {}", limit);
+    }
+
+    fn asc<'a>(extra: &[(&'a str, &'a str)]) -> Vec<(&'a str, &'a str)> {
+        let mut query = vec![("limit", "3"), ("order", "asc")];
+        query.extend_from_slice(extra);
+        query
+    }
+    let first = get_partner_page(&client, &base_url, &key, &asc(&[])).await;
+    assert_eq!(ids(&first), expected[..3]);
+    let cursor = first["meta"]["next_cursor"].as_str().unwrap().to_string();
+
+    // A row added after the first page does not disturb the pages already
+    // behind the cursor; it shows up at the end
+    let partner_id: Uuid = sqlx::query_scalar("SELECT partner_id FROM transactions WHERE id = $1")
+        .bind(expected[0].parse::<Uuid>().unwrap())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let newer = deposit("USD", "1").with_partner_id(Some(partner_id));
+    queries::insert_transaction(&pool, &newer).await.unwrap();
+
+    let second = get_partner_page(&client, &base_url, &key, &asc(&[("cursor", &cursor)])).await;
+    assert_eq!(ids(&second), expected[3..6]);
+    assert_eq!(second["meta"]["has_more"], true);
+    let next = second["meta"]["next_cursor"].as_str().unwrap().to_string();
+    let third = get_partner_page(&client, &base_url, &key, &asc(&[("cursor", &next)])).await;
+    assert_eq!(ids(&third), [expected[6].clone(), newer.id.to_string()]);
+    assert_eq!(third["meta"]["has_more"], false);
+
+    // Going back from the start of the second page yields the first page again
+    let back_cursor = cursor_of_first(&second);
+    let back = get_partner_page(
+        &client,
+        &base_url,
+        &key,
+        &asc(&[("direction", "backward"), ("cursor", &back_cursor)]),
+    )
+    .await;
+    assert_eq!(ids(&back), expected[..3]);
+    assert_eq!(back["meta"]["has_more"], false);
+
+    let res = client
+        .get(format!("{}/transactions?order=oldest", base_url))
+        .header("Authorization", format!("Api-Key {}", key))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_over_max_limit_is_clamped() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .get(format!("{}/settlements?limit=100000", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["limit"], 100);
+    assert_eq!(body["page"], 1);
+    assert!(body["settlements"].as_array().unwrap().len() <= 100);
+
+    let res = client
+        .get(format!("{}/settlements", base_url))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(
+        body["limit"], 10,
+        "list_settlements has a default page size"
+    );
+
+    let res = client
+        .get(format!("{}/transactions/search?limit=5000", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body["transactions"].as_array().unwrap().len() <= 100);
+}
+
+#[tokio::test]
+async fn test_zero_and_negative_limits_are_rejected() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    for path in [
+        "/settlements?limit=0",
+        "/settlements?page=0",
+        "/transactions/search?limit=0",
+        "/transactions/search?limit=-10",
+    ] {
+        let res = client
+            .get(format!("{}{}", base_url, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_page_links_use_forwarded_scheme_and_host() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    for _ in 0..3 {
+        pending_transaction(&pool, "USD", "1.00").await;
+    }
+
+    let mut app_state = common::test_state(&database_url, pool).await;
+    app_state.config = Arc::new(Config {
+        trusted_proxy_depth: 1,
+        ..Config::default()
+    });
+    let base_url = common::serve(create_app(app_state)).await;
+    let client = common::client();
+    let get = |url: String| {
+        client
+            .get(url)
+            .header("x-forwarded-proto", "https")
+            .header("x-forwarded-host", "api.example.com")
+            .send()
+    };
+
+    let res = get(format!("{}/transactions?limit=1", base_url))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let meta = &body["meta"];
+    assert!(meta["prev"].is_null(), "first page has no prev: {}", meta);
+    let next = meta["next"].as_str().expect("absolute next link");
+    let parsed = url::Url::parse(next).unwrap();
+    assert_eq!(parsed.scheme(), "https");
+    assert_eq!(parsed.host_str(), Some("api.example.com"));
+    assert_eq!(parsed.path(), "/transactions");
+    let query: std::collections::HashMap<_, _> = parsed.query_pairs().into_owned().collect();
+    assert_eq!(query["limit"], "1");
+    assert_eq!(query["direction"], "forward");
+    assert_eq!(query["cursor"], meta["next_cursor"].as_str().unwrap());
+
+    // Following the link through the proxy's address yields a prev link back
+    let res = get(format!(
+        "{}{}",
+        base_url,
+        &next[next.find("/transactions").unwrap()..]
+    ))
+    .await
+    .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let prev = body["meta"]["prev"].as_str().expect("absolute prev link");
+    assert!(
+        prev.starts_with("https://api.example.com/transactions?"),
+        "{}",
+        prev
+    );
+    assert!(prev.contains("direction=backward"), "{}", prev);
+
+    let res = get(format!(
+        "{}/transactions/search?limit=1&asset_code=USD",
+        base_url
+    ))
+    .await
+    .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    let next = body["next"].as_str().expect("absolute search link");
+    assert!(
+        next.starts_with("https://api.example.com/transactions/search?"),
+        "{}",
+        next
+    );
+    assert!(next.contains("asset_code=USD"), "{}", next);
+}
+
+/// A stellar account unique to this test run, so searches only see our rows
+fn unique_account() -> String {
+    let suffix = Uuid::new_v4().simple().to_string().to_uppercase();
+    format!("G{:A<55}", suffix)
+}
+
+async fn insert_for_account(pool: &PgPool, account: &str, count: usize) {
+    for _ in 0..count {
+        let mut tx = deposit("USD", "10.00");
+        tx.stellar_account = account.to_string();
+        queries::insert_transaction(pool, &tx).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_search_without_count_still_paginates() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let account = unique_account();
+    insert_for_account(&pool, &account, 5).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let mut url = format!(
+            "{}/transactions/search?stellar_account={}&limit=2&count=false",
+            base_url, account
+        );
+        if let Some(c) = &cursor {
+            url.push_str(&format!("&cursor={}", urlencode(c)));
+        }
+        let res = client.get(url).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = res.json().await.unwrap();
+
+        assert!(body["total"].is_null());
+        for tx in body["transactions"].as_array().unwrap() {
+            assert!(seen.insert(tx["id"].as_str().unwrap().to_string()));
+        }
+        pages += 1;
+
+        if !body["has_more"].as_bool().unwrap() {
+            assert!(body["next_cursor"].is_null());
+            break;
+        }
+        cursor = Some(body["next_cursor"].as_str().unwrap().to_string());
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(seen.len(), 5);
+
+    // Counting is still the default
+    let res = client
+        .get(format!(
+            "{}/transactions/search?stellar_account={}&limit=2",
+            base_url, account
+        ))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["total"], 5);
+    assert_eq!(body["has_more"], true);
+}
+
+#[tokio::test]
+async fn test_search_accepts_relative_and_month_dates() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let account = unique_account();
+    insert_for_account(&pool, &account, 2).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let this_month = chrono::Utc::now().format("%Y-%m");
+    for (filters, expected) in [
+        ("from=now-1h".to_string(), 2),
+        (format!("from={0}&to={0}", this_month), 2),
+        ("to=now-1d".to_string(), 0),
+    ] {
+        let res = client
+            .get(format!(
+                "{}/transactions/search?stellar_account={}&{}",
+                base_url, account, filters
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK, "{}", filters);
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(body["total"], expected, "{}", filters);
+    }
+
+    let res = client
+        .get(format!("{}/transactions/search?from=last-week", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_filters_by_asset_issuer() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let account = unique_account();
+    let (issuer_a, issuer_b) = (unique_account(), unique_account());
+    for issuer in [&issuer_a, &issuer_b] {
+        let mut tx = deposit("USD", "10.00").with_asset_issuer(Some(issuer.clone()));
+        tx.stellar_account = account.clone();
+        let inserted = queries::insert_transaction(&pool, &tx).await.unwrap();
+        assert_eq!(inserted.asset_issuer.as_ref(), Some(issuer));
+    }
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .get(format!(
+            "{}/transactions/search?stellar_account={}&asset_code=USD",
+            base_url, account
+        ))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["total"], 2);
+
+    let res = client
+        .get(format!(
+            "{}/transactions/search?stellar_account={}&asset_code=USD&asset_issuer={}",
+            base_url, account, issuer_b
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["transactions"][0]["asset_issuer"], issuer_b.as_str());
+}
+
+/// Percent-encode the base64 characters that are not query-safe
+fn urlencode(value: &str) -> String {
+    value
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace('=', "%3D")
+}
+
+#[tokio::test]
+async fn test_count_matches_search_total() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let account = unique_account();
+    insert_for_account(&pool, &account, 4).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    for filters in [
+        format!("stellar_account={}", account),
+        format!("stellar_account={}&min_amount=1&asset_code=USD", account),
+        format!("stellar_account={}&status=completed", account),
+        format!("stellar_account={}&from=now-1h&limit=1", account),
+    ] {
+        let res = client
+            .get(format!("{}/transactions/search?{}", base_url, filters))
+            .send()
+            .await
+            .unwrap();
+        let search: serde_json::Value = res.json().await.unwrap();
+
+        let res = client
+            .get(format!("{}/transactions/count?{}", base_url, filters))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK, "{}", filters);
+        let count: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(count["count"], search["total"], "{}", filters);
+    }
+
+    let res = client
+        .get(format!(
+            "{}/transactions/count?stellar_account={}",
+            base_url, account
+        ))
+        .send()
+        .await
+        .unwrap();
+    let count: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(count, serde_json::json!({ "count": 4 }));
+
+    let res = client
+        .get(format!("{}/transactions/count?to=yesterday-ish", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_and_export_filter_by_settlement_id() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let account = unique_account();
+    insert_for_account(&pool, &account, 3).await;
+    let ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM transactions WHERE stellar_account = $1 ORDER BY id")
+            .bind(&account)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+
+    let settlement_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO settlements (id, asset_code, total_amount, tx_count, period_start, period_end, status) VALUES ($1, 'USD', 20, 2, NOW(), NOW(), 'completed')",
+    )
+    .bind(settlement_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let settled: HashSet<String> = ids[..2].iter().map(|id| id.to_string()).collect();
+    sqlx::query("UPDATE transactions SET settlement_id = $1 WHERE id = ANY($2)")
+        .bind(settlement_id)
+        .bind(&ids[..2])
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .get(format!(
+            "{}/transactions/search?settlement_id={}",
+            base_url, settlement_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["total"], 2);
+    let found: HashSet<String> = body["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tx| tx["id"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(found, settled);
+
+    let res = client
+        .get(format!(
+            "{}/export?format=json&settlement_id={}",
+            base_url, settlement_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.text().await.unwrap();
+    let exported: HashSet<String> = body
+        .lines()
+        .map(|line| {
+            let row: serde_json::Value = serde_json::from_str(line).unwrap();
+            row["id"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(exported, settled);
+}
+
+/// A stellar account unique to this test run, so searches only see our rows
+async fn post_callback(
+    client: &reqwest::Client,
+    base_url: &str,
+    auth: &str,
+    account: &str,
+) -> Value {
+    let res = client
+        .post(format!("{}/callback", base_url))
+        .header("Authorization", auth)
+        .json(&json!({
+            "stellar_account": account,
+            "amount": "10.00",
+            "asset_code": "USD",
+            "callback_type": "deposit"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    res.json().await.unwrap()
+}
+
+async fn search_ids(
+    client: &reqwest::Client,
+    base_url: &str,
+    auth: &str,
+    account: &str,
+) -> Vec<String> {
+    let res = client
+        .get(format!(
+            "{}/transactions/search?stellar_account={}",
+            base_url, account
+        ))
+        .header("Authorization", auth)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().await.unwrap();
+    body["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tx| tx["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_partner_only_sees_own_transactions() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let partner_a = Uuid::new_v4();
+    let partner_b = Uuid::new_v4();
+    let pool = setup_db(&database_url).await;
+    let keys = ApiKeyService::new(pool.clone());
+    let scopes = [SCOPE_CALLBACK.to_string(), SCOPE_READ.to_string()];
+    let (_, key_a) = keys.create(partner_a, &scopes).await.unwrap();
+    let (_, key_b) = keys.create(partner_b, &scopes).await.unwrap();
+    let auth_a = format!("Api-Key {}", key_a);
+    let auth_b = format!("Api-Key {}", key_b);
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+
+    // One account shared by both partners, so only the scope tells them apart
+    let account = unique_account();
+    let tx_a = post_callback(&client, &base_url, &auth_a, &account).await;
+    let tx_b = post_callback(&client, &base_url, &auth_b, &account).await;
+    assert_eq!(tx_a["partner_id"], partner_a.to_string());
+    assert_eq!(tx_b["partner_id"], partner_b.to_string());
+    let id_a = tx_a["id"].as_str().unwrap().to_string();
+    let id_b = tx_b["id"].as_str().unwrap().to_string();
+
+    // Search
+    assert_eq!(
+        search_ids(&client, &base_url, &auth_a, &account).await,
+        vec![id_a.clone()]
+    );
+    assert_eq!(
+        search_ids(&client, &base_url, &auth_b, &account).await,
+        vec![id_b.clone()]
+    );
+    assert_eq!(
+        search_ids(&client, &base_url, ADMIN_AUTH, &account).await,
+        vec![id_b.clone(), id_a.clone()]
+    );
+
+    // List: every row on the page belongs to the caller
+    let res = client
+        .get(format!("{}/transactions?limit=100", base_url))
+        .header("Authorization", &auth_a)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().await.unwrap();
+    let rows = body["data"].as_array().unwrap();
+    assert!(rows.iter().any(|tx| tx["id"] == id_a.as_str()));
+    assert!(rows
+        .iter()
+        .all(|tx| tx["partner_id"] == partner_a.to_string()));
+
+    // Export
+    let export = |key: &str| {
+        client
+            .get(format!("{}/export?format=csv", base_url))
+            .header("Authorization", key)
+            .send()
+    };
+    let csv = export(&auth_a).await.unwrap().text().await.unwrap();
+    assert!(csv.contains(&id_a));
+    assert!(!csv.contains(&id_b));
+    let csv = export(ADMIN_AUTH).await.unwrap().text().await.unwrap();
+    assert!(csv.contains(&id_a) && csv.contains(&id_b));
+
+    // Lookups by id, including the audit timeline, hide other partners' rows
+    for path in ["", "/timeline"] {
+        let get = |key: &str, id: &str| {
+            client
+                .get(format!("{}/transactions/{}{}", base_url, id, path))
+                .header("Authorization", key)
+                .send()
+        };
+        assert_eq!(get(&auth_a, &id_a).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            get(&auth_a, &id_b).await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get(ADMIN_AUTH, &id_b).await.unwrap().status(),
+            StatusCode::OK
+        );
+    }
+
+    // GraphQL lists and lookups apply the same scope
+    let graphql = |key: &str, query: String| {
+        client
+            .post(format!("{}/graphql", base_url))
+            .header("Authorization", key)
+            .json(&json!({ "query": query }))
+            .send()
+    };
+    let body: Value = graphql(
+        &auth_a,
+        "{ transactions(limit: 100) { id partnerId } }".to_string(),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let rows = body["data"]["transactions"].as_array().unwrap();
+    assert!(rows.iter().any(|tx| tx["id"] == id_a.as_str()));
+    assert!(rows
+        .iter()
+        .all(|tx| tx["partnerId"] == partner_a.to_string()));
+    let res = graphql(
+        &auth_a,
+        format!("{{ transaction(id: \"{}\") {{ id }} }}", id_b),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Once keys exist, a caller without one has no scope to apply
+    let res = client
+        .get(format!("{}/transactions", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = client
+        .get(format!("{}/transactions", base_url))
+        .header("Authorization", "Bearer unknown-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
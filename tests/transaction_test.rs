@@ -0,0 +1,635 @@
+mod common;
+
+use bigdecimal::BigDecimal;
+use common::{
+    completed_transaction, deposit, pending_transaction, setup_db, spawn_app, unique_asset,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::str::FromStr;
+use synapse_core::create_app;
+use synapse_core::db::audit::{AuditLog, ENTITY_TRANSACTION_NOTE};
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::error::AppError;
+use synapse_core::handlers::ws::TransactionStatusUpdate;
+use synapse_core::services::transaction::{
+    map_status_violation, transition_status, STATUS_COMPLETED, STATUS_PENDING, STATUS_REFUNDED,
+};
+use synapse_core::services::SettlementService;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn test_valid_transition_is_applied_and_audited() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "10.00").await;
+
+    let updated = transition_status(&pool, tx.id, STATUS_PENDING, STATUS_COMPLETED)
+        .await
+        .unwrap();
+    assert_eq!(updated.status, STATUS_COMPLETED);
+
+    let audited: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1 AND action = 'status_update'",
+    )
+    .bind(tx.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audited, 1);
+}
+
+#[tokio::test]
+async fn test_invalid_transition_is_rejected() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "10.00").await;
+    transition_status(&pool, tx.id, STATUS_PENDING, STATUS_COMPLETED)
+        .await
+        .unwrap();
+    transition_status(&pool, tx.id, STATUS_COMPLETED, STATUS_REFUNDED)
+        .await
+        .unwrap();
+
+    let err = transition_status(&pool, tx.id, STATUS_REFUNDED, STATUS_COMPLETED)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AppError::InvalidStatusTransition(_)));
+
+    let current = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(current.status, STATUS_REFUNDED);
+}
+
+#[tokio::test]
+async fn test_unknown_status_is_rejected() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let mut tx = deposit("USD", "10.00");
+    tx.status = "compleeted".to_string();
+    let err = queries::insert_transaction(&pool, &tx).await.unwrap_err();
+    assert!(matches!(err, AppError::InvalidStatusTransition(_)));
+    assert!(queries::get_transaction(&pool, tx.id).await.is_err());
+
+    // Writes that skip the service layer are stopped by the check constraint
+    let stored = pending_transaction(&pool, "USD", "10.00").await;
+    let err = sqlx::query("UPDATE transactions SET status = 'compleeted' WHERE id = $1")
+        .bind(stored.id)
+        .execute(&pool)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        map_status_violation(err),
+        AppError::InvalidStatusTransition(_)
+    ));
+    let current = queries::get_transaction(&pool, stored.id).await.unwrap();
+    assert_eq!(current.status, STATUS_PENDING);
+}
+
+#[tokio::test]
+async fn test_concurrent_double_complete_only_one_wins() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "10.00").await;
+
+    let (first, second) = tokio::join!(
+        transition_status(&pool, tx.id, STATUS_PENDING, STATUS_COMPLETED),
+        transition_status(&pool, tx.id, STATUS_PENDING, STATUS_COMPLETED),
+    );
+
+    let wins = [&first, &second].iter().filter(|r| r.is_ok()).count();
+    assert_eq!(wins, 1);
+    let loser = if first.is_err() { first } else { second };
+    assert!(matches!(
+        loser.unwrap_err(),
+        AppError::InvalidStatusTransition(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_refund_endpoint_marks_transaction_refunded() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USD", "10.00").await;
+    transition_status(&pool, tx.id, STATUS_PENDING, STATUS_COMPLETED)
+        .await
+        .unwrap();
+
+    let app_state = common::test_state(&database_url, pool.clone()).await;
+    let mut updates = app_state.tx_broadcast.subscribe();
+    let base_url = common::serve(create_app(app_state)).await;
+
+    let client = reqwest::Client::new();
+    let refund_url = format!("{}/transactions/{}/refund", base_url, tx.id);
+    let res = client
+        .post(&refund_url)
+        .json(&serde_json::json!({ "reason": "customer chargeback" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // A body that is there but malformed is rejected rather than ignored
+    let res = client
+        .post(&refund_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .header("Content-Type", "application/json")
+        .body(r#"{"reason": "customer chargeback""#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let res = client
+        .post(&refund_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&serde_json::json!({ "reason": "customer chargeback" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["status"], STATUS_REFUNDED);
+    assert_eq!(body["metadata"]["refund_reason"], "customer chargeback");
+
+    let audited: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1 AND action = 'status_update' AND new_val->>'status' = 'refunded'",
+    )
+    .bind(tx.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audited, 1);
+
+    let update = updates.recv().await.unwrap();
+    assert_eq!(update.transaction_id, tx.id);
+    assert_eq!(update.status, STATUS_REFUNDED);
+
+    // A second refund is a conflict
+    let res = client
+        .post(&refund_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_add_and_list_transaction_notes() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let tx = pending_transaction(&pool, "USDC", "25.00").await;
+
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = reqwest::Client::new();
+    let notes_url = format!("{}/admin/transactions/{}/notes", base_url, tx.id);
+
+    // Admin authentication is required
+    let res = client.get(&notes_url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let mut ids = Vec::new();
+    for (author, note) in [
+        (
+            "alice",
+            "Customer called about the delay.\nEscalated to ops.",
+        ),
+        ("bob", "Ops confirmed the deposit arrived."),
+    ] {
+        let res = client
+            .post(&notes_url)
+            .header("Authorization", "Bearer admin-secret-key")
+            .json(&serde_json::json!({ "author": author, "note": note }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(body["transaction_id"], tx.id.to_string());
+        assert_eq!(body["author"], author);
+        assert_eq!(body["note"], note);
+        ids.push(Uuid::parse_str(body["id"].as_str().unwrap()).unwrap());
+    }
+
+    let res = client
+        .get(&notes_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let notes: Vec<serde_json::Value> = res.json().await.unwrap();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0]["author"], "alice");
+    assert_eq!(
+        notes[0]["note"],
+        "Customer called about the delay.\nEscalated to ops."
+    );
+    assert_eq!(notes[1]["author"], "bob");
+
+    // Each note is audited under its own id with the author as actor
+    let audit = AuditLog::for_entity(&pool, ids[0]).await.unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].entity_type, ENTITY_TRANSACTION_NOTE);
+    assert_eq!(audit[0].action, "created");
+    assert_eq!(audit[0].actor, "alice");
+
+    // The transaction itself is untouched
+    let stored = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(stored.metadata, tx.metadata);
+
+    let res = client
+        .post(&notes_url)
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&serde_json::json!({ "author": "alice", "note": "   " }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = client
+        .get(format!(
+            "{}/admin/transactions/{}/notes",
+            base_url,
+            Uuid::new_v4()
+        ))
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_timeline_includes_settlement_link() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+
+    // A dedicated asset keeps the settlement run limited to this transaction
+    let asset_code = unique_asset("T");
+    let tx = completed_transaction(&pool, &asset_code, "42.00").await;
+    let settlement = SettlementService::new(pool.clone())
+        .settle_asset(&asset_code)
+        .await
+        .unwrap()
+        .expect("settlement created");
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .get(format!("{}/transactions/{}/timeline", base_url, tx.id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+
+    assert_eq!(body["transaction"]["id"], tx.id.to_string());
+    let events: Vec<&str> = body["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(events, ["created", "status_changed", "settlement_linked"]);
+    assert_eq!(
+        body["events"][2]["new_val"]["settlement_id"],
+        settlement.id.to_string()
+    );
+
+    // Unknown transactions are a 404
+    let res = client
+        .get(format!(
+            "{}/transactions/{}/timeline",
+            base_url,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+async fn spawn_app_with_updates(
+    database_url: &str,
+    pool: PgPool,
+) -> (String, broadcast::Receiver<TransactionStatusUpdate>) {
+    let app_state = common::test_state(database_url, pool).await;
+    let rx = app_state.tx_broadcast.subscribe();
+    (common::serve(create_app(app_state)).await, rx)
+}
+
+async fn status_audits(pool: &PgPool, id: Uuid) -> Vec<(serde_json::Value, String)> {
+    sqlx::query_as(
+        "SELECT new_val, actor FROM audit_logs WHERE entity_id = $1 AND action = 'status_update'",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_bulk_status_update_audits_and_broadcasts_each_transaction() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let ids = vec![
+        pending_transaction(&pool, "USD", "10.00").await.id,
+        pending_transaction(&pool, "USD", "10.00").await.id,
+        pending_transaction(&pool, "USD", "10.00").await.id,
+    ];
+    // Already refunded: cannot move to completed
+    let refunded = pending_transaction(&pool, "USD", "10.00").await.id;
+    sqlx::query("UPDATE transactions SET status = 'refunded' WHERE id = $1")
+        .bind(refunded)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let missing = Uuid::new_v4();
+
+    let (base_url, mut rx) = spawn_app_with_updates(&database_url, pool.clone()).await;
+    let client = reqwest::Client::new();
+
+    let mut request_ids = ids.clone();
+    request_ids.extend([refunded, missing]);
+    let res = client
+        .patch(format!("{}/admin/transactions/status", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({ "ids": request_ids, "status": "completed" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["updated"], 3);
+    assert_eq!(body["failed"], 2);
+
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 5);
+    for (result, id) in results.iter().zip(&ids) {
+        assert_eq!(result["id"], id.to_string());
+        assert_eq!(result["updated"], true);
+        assert_eq!(result["previous_status"], "pending");
+        assert_eq!(result["status"], "completed");
+    }
+    assert_eq!(results[3]["updated"], false);
+    assert_eq!(results[3]["status"], "refunded");
+    assert!(results[4]["previous_status"].is_null());
+    assert!(results[4]["error"].as_str().unwrap().contains("not found"));
+
+    for id in &ids {
+        let tx = queries::get_transaction(&pool, *id).await.unwrap();
+        assert_eq!(tx.status, "completed");
+
+        let audits = status_audits(&pool, *id).await;
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].0, json!({ "status": "completed" }));
+        assert_eq!(audits[0].1, "admin");
+
+        let update = rx.recv().await.unwrap();
+        assert_eq!(update.transaction_id, *id);
+        assert_eq!(update.status, "completed");
+    }
+    assert!(rx.try_recv().is_err());
+    assert!(status_audits(&pool, refunded).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_bulk_status_update_rejects_unknown_status() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let id = pending_transaction(&pool, "USD", "10.00").await.id;
+    let (base_url, _rx) = spawn_app_with_updates(&database_url, pool.clone()).await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .patch(format!("{}/admin/transactions/status", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({ "ids": [id], "status": "finished" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        queries::get_transaction(&pool, id).await.unwrap().status,
+        "pending"
+    );
+
+    let res = client
+        .patch(format!("{}/admin/transactions/status", base_url))
+        .json(&json!({ "ids": [id], "status": "completed" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// `count` transactions on an asset unique to this run, with distinct anchor ids
+fn batch(asset_code: &str, count: usize) -> Vec<Transaction> {
+    (0..count)
+        .map(|i| {
+            let mut tx = deposit(asset_code, &format!("{}.25", i + 1));
+            tx.anchor_transaction_id = Some(format!("{}-{}", asset_code, i));
+            tx.callback_status = Some("completed".to_string());
+            tx.memo = Some(format!("memo \"{}\",\nline two", i));
+            tx.memo_type = Some("text".to_string());
+            tx
+        })
+        .collect()
+}
+
+async fn count_for_asset(pool: &PgPool, asset_code: &str) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE asset_code = $1")
+        .bind(asset_code)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_bulk_insert_ten_thousand_rows() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let asset_code = unique_asset("K");
+    let txs = batch(&asset_code, 10_000);
+
+    let inserted = queries::bulk_insert_transactions(&pool, &txs)
+        .await
+        .unwrap();
+    assert_eq!(inserted, 10_000);
+    assert_eq!(count_for_asset(&pool, &asset_code).await, 10_000);
+
+    let expected = &txs[4321];
+    let stored = queries::get_transaction(&pool, expected.id).await.unwrap();
+    assert_eq!(stored.amount, BigDecimal::from_str("4322.25").unwrap());
+    assert_eq!(stored.anchor_transaction_id, expected.anchor_transaction_id);
+    assert_eq!(stored.memo, expected.memo);
+    assert_eq!(stored.status, "pending");
+    assert_eq!(stored.asset_issuer, None);
+    assert_eq!(stored.settlement_id, None);
+}
+
+#[tokio::test]
+async fn test_bulk_insert_small_batch_and_duplicate_anchor() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let asset_code = unique_asset("K");
+    let txs = batch(&asset_code, 3);
+
+    assert_eq!(
+        queries::bulk_insert_transactions(&pool, &txs)
+            .await
+            .unwrap(),
+        3
+    );
+    let audit: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1 AND action = 'created'",
+    )
+    .bind(txs[0].id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audit, 1);
+
+    // Reusing an anchor id already recorded fails the whole batch, on both paths
+    for size in [2, queries::BULK_COPY_THRESHOLD] {
+        let mut retry = batch(&unique_asset("K"), size);
+        retry[1].anchor_transaction_id = txs[1].anchor_transaction_id.clone();
+        let retry_asset = retry[0].asset_code.clone();
+
+        let err = queries::bulk_insert_transactions(&pool, &retry)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::TransactionAlreadyProcessed(_)));
+        assert_eq!(count_for_asset(&pool, &retry_asset).await, 0);
+    }
+}
+
+async fn insert_tagged(pool: &PgPool, tag: Option<&str>) -> Uuid {
+    let metadata = tag.map(|tag| json!({ "compliance_tag": tag, "reference_id": "REF-1" }));
+    let mut tx = deposit("USD", "10.00");
+    tx.metadata = metadata;
+    queries::insert_transaction(pool, &tx).await.unwrap().id
+}
+
+#[tokio::test]
+async fn test_flagged_lists_only_high_risk_transactions() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let mut high_risk = HashSet::new();
+    for _ in 0..3 {
+        high_risk.insert(insert_tagged(&pool, Some("high_risk")).await);
+    }
+    let low_risk = insert_tagged(&pool, Some("low_risk")).await;
+    let untagged = insert_tagged(&pool, None).await;
+
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/admin/transactions/flagged", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // Walk every page; rows from earlier runs may be flagged too
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut request = client
+            .get(format!("{}/admin/transactions/flagged?limit=2", base_url))
+            .header("Authorization", "Bearer admin-secret-key");
+        if let Some(cursor) = &cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+        let res = request.send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let page: Value = res.json().await.unwrap();
+        let rows = page["data"].as_array().unwrap();
+        assert!(rows.len() <= 2);
+        for row in rows {
+            assert_eq!(row["metadata"]["compliance_tag"], "high_risk");
+            seen.insert(Uuid::parse_str(row["id"].as_str().unwrap()).unwrap());
+        }
+        if page["meta"]["has_more"] != true {
+            break;
+        }
+        cursor = page["meta"]["next_cursor"].as_str().map(str::to_string);
+    }
+
+    assert!(high_risk.is_subset(&seen));
+    assert!(!seen.contains(&low_risk));
+    assert!(!seen.contains(&untagged));
+}
+
+#[tokio::test]
+async fn test_malformed_uuid_path_returns_structured_error() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = common::client();
+
+    let res = client
+        .get(format!("{}/transactions/not-a-uuid", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.headers()["content-type"].to_str().unwrap(),
+        "application/json"
+    );
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_BAD_REQUEST_001");
+    assert_eq!(body["status"], 400);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("invalid path parameter"));
+
+    // A well-formed id still reaches the handler
+    let res = client
+        .get(format!("{}/transactions/{}", base_url, Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
@@ -1,5 +1,13 @@
+mod common;
+
+use common::{setup_db, spawn_app};
 use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
 use sha2::Sha256;
+use synapse_core::middleware::auth::{SCOPE_CALLBACK, SCOPE_READ};
+use synapse_core::services::ApiKeyService;
+use uuid::Uuid;
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -69,3 +77,218 @@ fn test_constant_time_comparison() {
     // Verification should fail for different payloads
     assert!(mac2.verify_slice(&sig1).is_err());
 }
+
+async fn post_callback(client: &reqwest::Client, base_url: &str, auth: &str) -> reqwest::Response {
+    client
+        .post(format!("{}/callback", base_url))
+        .header("Authorization", auth)
+        .json(&json!({
+            "stellar_account": format!("G{}", "A".repeat(55)),
+            "amount": "10.00",
+            "asset_code": "USD",
+            "callback_type": "deposit"
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_valid_api_key_records_partner() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+    let partner_id = Uuid::new_v4();
+
+    let res = client
+        .post(format!("{}/admin/api-keys", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .json(&json!({ "partner_id": partner_id, "scopes": ["callback"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created: Value = res.json().await.unwrap();
+    let key = created["key"].as_str().unwrap();
+    assert_eq!(created["partner_id"], partner_id.to_string());
+    assert_eq!(created["scopes"], json!(["callback"]));
+    assert!(created.get("key_hash").is_none());
+
+    let res = post_callback(&client, &base_url, &format!("Api-Key {}", key)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let tx: Value = res.json().await.unwrap();
+    assert_eq!(tx["partner_id"], partner_id.to_string());
+}
+
+#[tokio::test]
+async fn test_revoked_api_key_is_rejected() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let keys = ApiKeyService::new(pool.clone());
+    let (api_key, key) = keys
+        .create(Uuid::new_v4(), &[SCOPE_CALLBACK.to_string()])
+        .await
+        .unwrap();
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+    let auth = format!("Api-Key {}", key);
+
+    assert_eq!(
+        post_callback(&client, &base_url, &auth).await.status(),
+        StatusCode::CREATED
+    );
+
+    let revoke = || {
+        client
+            .delete(format!("{}/admin/api-keys/{}", base_url, api_key.id))
+            .header("Authorization", "Bearer admin-secret-key")
+            .send()
+    };
+    assert_eq!(revoke().await.unwrap().status(), StatusCode::NO_CONTENT);
+    assert_eq!(revoke().await.unwrap().status(), StatusCode::NOT_FOUND);
+
+    assert_eq!(
+        post_callback(&client, &base_url, &auth).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert!(keys.authenticate(&key).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_unknown_api_key_is_rejected() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let keys = ApiKeyService::new(pool.clone());
+    let (_, key) = keys.create(Uuid::new_v4(), &[]).await.unwrap();
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+
+    // Same lookup prefix as a real key, different secret
+    let forged = format!("{}{}", &key[..11], "0".repeat(key.len() - 11));
+    for candidate in [forged.as_str(), "sk_unknown", ""] {
+        let res = post_callback(&client, &base_url, &format!("Api-Key {}", candidate)).await;
+        assert_eq!(
+            res.status(),
+            StatusCode::UNAUTHORIZED,
+            "key {:?}",
+            candidate
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_key_without_scope_is_forbidden() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    let keys = ApiKeyService::new(pool.clone());
+    let (_, read_only) = keys
+        .create(Uuid::new_v4(), &[SCOPE_READ.to_string()])
+        .await
+        .unwrap();
+    let (_, callback_only) = keys
+        .create(Uuid::new_v4(), &[SCOPE_CALLBACK.to_string()])
+        .await
+        .unwrap();
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+    let read_only = format!("Api-Key {}", read_only);
+    let callback_only = format!("Api-Key {}", callback_only);
+
+    let res = post_callback(&client, &base_url, &read_only).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body: Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_AUTH_002");
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .header("Authorization", &read_only)
+        .json(&json!({ "transactions": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    for path in [
+        "/transactions",
+        "/transactions/search",
+        "/transactions/count",
+        "/export",
+    ] {
+        let get = |auth: &str| {
+            client
+                .get(format!("{}{}", base_url, path))
+                .header("Authorization", auth)
+                .send()
+        };
+        assert_eq!(
+            get(&callback_only).await.unwrap().status(),
+            StatusCode::FORBIDDEN,
+            "{}",
+            path
+        );
+        assert_eq!(
+            get(&read_only).await.unwrap().status(),
+            StatusCode::OK,
+            "{}",
+            path
+        );
+    }
+    assert_eq!(
+        post_callback(&client, &base_url, &callback_only)
+            .await
+            .status(),
+        StatusCode::CREATED
+    );
+}
+
+#[tokio::test]
+async fn test_no_key_is_rejected_once_keys_exist() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+    let pool = setup_db(&database_url).await;
+    ApiKeyService::new(pool.clone())
+        .create(Uuid::new_v4(), &[SCOPE_READ.to_string()])
+        .await
+        .unwrap();
+    let base_url = spawn_app(&database_url, pool).await;
+    let client = reqwest::Client::new();
+
+    for path in ["/transactions", "/transactions/search", "/export"] {
+        let res = client
+            .get(format!("{}{}", base_url, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{}", path);
+    }
+    let res = client
+        .post(format!("{}/callback", base_url))
+        .json(&json!({
+            "stellar_account": format!("G{}", "A".repeat(55)),
+            "amount": "10.00",
+            "asset_code": "USD",
+            "callback_type": "deposit"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // The admin key still sees everything
+    let res = client
+        .get(format!("{}/transactions", base_url))
+        .header("Authorization", "Bearer admin-secret-key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
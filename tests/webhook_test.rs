@@ -1,4 +1,13 @@
+mod common;
+
+use common::{deposit, setup_db, spawn_app};
+use reqwest::StatusCode;
 use serde_json::json;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::handlers::webhook::BULK_CALLBACK_THRESHOLD;
+use synapse_core::services::CallbackQueue;
+use uuid::Uuid;
 
 #[tokio::test]
 async fn test_callback_transaction_success() {
@@ -80,3 +89,465 @@ async fn test_callback_validation_invalid_asset_code() {
     let asset_code = payload["asset_code"].as_str().unwrap();
     assert!(asset_code.len() > 12, "Asset code should be too long");
 }
+
+#[tokio::test]
+async fn test_async_callback_returns_202_and_is_stored() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let anchor_id = format!("async-{}", Uuid::new_v4());
+
+    let res = client
+        .post(format!("{}/callback?async=true", base_url))
+        .json(&json!({
+            "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            "amount": "42.00",
+            "asset_code": "USD",
+            "anchor_transaction_id": anchor_id,
+            "callback_type": "deposit",
+            "callback_status": "completed"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["status"], "accepted");
+    let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(body["location"], format!("/transactions/{}", id));
+
+    // The background processor stores it under the tracking id
+    let mut stored = None;
+    for _ in 0..50 {
+        let res = client
+            .get(format!("{}/transactions/{}", base_url, id))
+            .send()
+            .await
+            .unwrap();
+        if res.status() == StatusCode::OK {
+            stored = Some(res.json::<serde_json::Value>().await.unwrap());
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let stored = stored.expect("queued callback was never stored");
+    assert_eq!(stored["id"], id.to_string());
+    assert_eq!(stored["anchor_transaction_id"], anchor_id);
+
+    // Validation still happens before the callback is accepted
+    let res = client
+        .post(format!("{}/callback?async=true", base_url))
+        .json(&json!({
+            "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            "amount": "not-a-number",
+            "asset_code": "USD"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_async_callback_rejects_duplicate_anchor_id_before_queueing() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let payload = json!({
+        "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+        "amount": "42.00",
+        "asset_code": "USD",
+        "anchor_transaction_id": format!("async-dup-{}", Uuid::new_v4()),
+    });
+
+    let res = client
+        .post(format!("{}/callback", base_url))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = client
+        .post(format!("{}/callback?async=true", base_url))
+        .json(&payload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_TRANSACTION_004");
+}
+
+fn idempotent_callback(anchor_id: &str) -> serde_json::Value {
+    json!({
+        "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+        "amount": "42.00",
+        "asset_code": "USD",
+        "anchor_transaction_id": anchor_id,
+    })
+}
+
+#[tokio::test]
+async fn test_callback_routes_check_idempotency_key() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let anchor_id = format!("idem-invalid-{}", Uuid::new_v4());
+
+    // A malformed key is refused by the idempotency layer before Redis or
+    // the handler is reached
+    for path in ["/callback", "/callback/transaction"] {
+        let res = client
+            .post(format!("{}{}", base_url, path))
+            .header("x-idempotency-key", "not a valid key!")
+            .json(&idempotent_callback(&anchor_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", path);
+    }
+
+    let stored: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE anchor_transaction_id = $1")
+            .bind(&anchor_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored, 0);
+}
+
+#[tokio::test]
+#[ignore] // Requires Redis
+async fn test_redelivered_callback_replays_first_response() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let anchor_id = format!("idem-replay-{}", Uuid::new_v4());
+
+    let send = || {
+        client
+            .post(format!("{}/callback", base_url))
+            .header("x-idempotency-key", &anchor_id)
+            .json(&idempotent_callback(&anchor_id))
+            .send()
+    };
+
+    let first = send().await.unwrap();
+    assert_eq!(first.status(), StatusCode::CREATED);
+    let first: serde_json::Value = first.json().await.unwrap();
+
+    // Without the cache the redelivery would hit the duplicate anchor id check
+    let replay = send().await.unwrap();
+    assert_eq!(replay.status(), StatusCode::CREATED);
+    let replay: serde_json::Value = replay.json().await.unwrap();
+    assert_eq!(replay["id"], first["id"]);
+}
+
+fn queued_transaction(anchor_transaction_id: Option<String>) -> Transaction {
+    let mut tx = deposit("USD", "10");
+    tx.anchor_transaction_id = anchor_transaction_id;
+    tx
+}
+
+#[tokio::test]
+async fn test_queue_drains_and_records_failures_when_dropped() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let anchor_id = format!("async-claimed-{}", Uuid::new_v4());
+    queries::insert_transaction(&pool, &queued_transaction(Some(anchor_id.clone())))
+        .await
+        .unwrap();
+
+    let (queue, processor) = CallbackQueue::spawn(pool.clone(), 10);
+    let stored: Vec<Transaction> = (0..5).map(|_| queued_transaction(None)).collect();
+    for tx in &stored {
+        queue.try_enqueue(tx.clone()).unwrap();
+    }
+    // Claimed since it passed the duplicate check, so it fails once dequeued
+    let duplicate = queued_transaction(Some(anchor_id));
+    queue.try_enqueue(duplicate.clone()).unwrap();
+
+    // Dropping the last producer lets the processor finish what is queued
+    drop(queue);
+    processor.await.unwrap();
+
+    for tx in &stored {
+        assert_eq!(
+            queries::get_transaction(&pool, tx.id).await.unwrap().id,
+            tx.id
+        );
+    }
+    let (error_code,): (String,) =
+        sqlx::query_as("SELECT error_code FROM transaction_dlq WHERE transaction_id = $1")
+            .bind(duplicate.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(error_code, "permanent_failure");
+}
+
+fn batch_with_one_invalid(anchor_id: &str) -> serde_json::Value {
+    json!({
+        "transactions": [
+            {
+                "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                "amount": "25.00",
+                "asset_code": "USD",
+                "anchor_transaction_id": anchor_id,
+                "callback_type": "deposit",
+                "callback_status": "completed"
+            },
+            {
+                "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                "amount": "not-a-number",
+                "asset_code": "USD"
+            }
+        ]
+    })
+}
+
+#[tokio::test]
+async fn test_callback_batch_mixed_results() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+
+    // Partial failure keeps the valid item
+    let anchor_id = format!("batch-{}", Uuid::new_v4());
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .json(&batch_with_one_invalid(&anchor_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    let body: serde_json::Value = res.json().await.unwrap();
+
+    assert_eq!(body["summary"]["total"], 2);
+    assert_eq!(body["summary"]["succeeded"], 1);
+    assert_eq!(body["summary"]["failed"], 1);
+    assert_eq!(body["results"][0]["index"], 0);
+    assert!(body["results"][0]["id"].is_string());
+    assert!(body["results"][0].get("error").is_none());
+    assert_eq!(body["results"][1]["index"], 1);
+    assert!(body["results"][1].get("id").is_none());
+    assert!(body["results"][1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("Invalid amount"));
+
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE anchor_transaction_id = $1")
+            .bind(&anchor_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(count, 1);
+
+    // Atomic mode rolls back the valid item too
+    let anchor_id = format!("batch-{}", Uuid::new_v4());
+    let res = client
+        .post(format!("{}/callback/batch?atomic=true", base_url))
+        .json(&batch_with_one_invalid(&anchor_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["summary"]["succeeded"], 0);
+    assert_eq!(body["summary"]["failed"], 2);
+
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE anchor_transaction_id = $1")
+            .bind(&anchor_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(count, 0);
+
+    // Oversized batches are rejected outright
+    let item = json!({
+        "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+        "amount": "1",
+        "asset_code": "USD"
+    });
+    let oversized = json!({ "transactions": vec![item; 501] });
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .json(&oversized)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_duplicate_anchor_transaction_id_returns_409() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+    let callback = |anchor_id: &str| {
+        json!({
+            "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            "amount": "10.00",
+            "asset_code": "USD",
+            "anchor_transaction_id": anchor_id
+        })
+    };
+
+    let anchor_id = format!("dup-{}", Uuid::new_v4());
+    let first = client
+        .post(format!("{}/callback", base_url))
+        .json(&callback(&anchor_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(first.status(), StatusCode::CREATED);
+
+    let second = client
+        .post(format!("{}/callback", base_url))
+        .json(&callback(&anchor_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(second.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(body["code"], "ERR_TRANSACTION_004");
+
+    // Racing inserts: exactly one wins
+    let anchor_id = format!("dup-{}", Uuid::new_v4());
+    let requests = (0..5).map(|_| {
+        client
+            .post(format!("{}/callback", base_url))
+            .json(&callback(&anchor_id))
+            .send()
+    });
+    let statuses: Vec<StatusCode> = futures::future::join_all(requests)
+        .await
+        .into_iter()
+        .map(|res| res.unwrap().status())
+        .collect();
+    assert_eq!(
+        statuses
+            .iter()
+            .filter(|s| **s == StatusCode::CREATED)
+            .count(),
+        1,
+        "{:?}",
+        statuses
+    );
+    assert!(statuses
+        .iter()
+        .all(|s| *s == StatusCode::CREATED || *s == StatusCode::CONFLICT));
+
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE anchor_transaction_id = $1")
+            .bind(&anchor_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn test_large_batch_is_bulk_inserted_and_falls_back_per_item() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let base_url = spawn_app(&database_url, pool.clone()).await;
+    let client = common::client();
+
+    let prefix = format!("bulk-{}", Uuid::new_v4());
+    let item = |anchor_id: String| {
+        json!({
+            "stellar_account": "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            "amount": "3.00",
+            "asset_code": "USD",
+            "anchor_transaction_id": anchor_id,
+            "callback_type": "deposit",
+            "callback_status": "completed"
+        })
+    };
+    let count = |pattern: String| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM transactions WHERE anchor_transaction_id LIKE $1",
+            )
+            .bind(pattern)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        }
+    };
+
+    let size = BULK_CALLBACK_THRESHOLD + 10;
+    let batch: Vec<_> = (0..size)
+        .map(|i| item(format!("{}-{}", prefix, i)))
+        .collect();
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .json(&json!({ "transactions": batch }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["summary"]["succeeded"], size);
+    let indices: Vec<u64> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["index"].as_u64().unwrap())
+        .collect();
+    assert_eq!(indices, (0..size as u64).collect::<Vec<_>>());
+    assert_eq!(count(format!("{}-%", prefix)).await, size as i64);
+
+    // One already-recorded anchor id fails only that item
+    let retry_prefix = format!("bulk-{}", Uuid::new_v4());
+    let mut batch: Vec<_> = (0..size)
+        .map(|i| item(format!("{}-{}", retry_prefix, i)))
+        .collect();
+    batch[3] = item(format!("{}-0", prefix));
+    let res = client
+        .post(format!("{}/callback/batch", base_url))
+        .json(&json!({ "transactions": batch }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["summary"]["succeeded"], size - 1);
+    assert_eq!(body["summary"]["failed"], 1);
+    assert!(body["results"][3]["error"].is_string());
+    assert_eq!(count(format!("{}-%", retry_prefix)).await, size as i64 - 1);
+}
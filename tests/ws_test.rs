@@ -0,0 +1,51 @@
+mod common;
+
+use common::setup_db;
+use sqlx::PgPool;
+use synapse_core::handlers::ws::WsConnections;
+use synapse_core::{create_app, AppState};
+use tokio_tungstenite::tungstenite::{error::Error as WsError, http::StatusCode};
+
+async fn spawn_app(database_url: &str, pool: PgPool, ws_connections: WsConnections) -> String {
+    let app_state = AppState {
+        ws_connections,
+        ..common::test_state(database_url, pool).await
+    };
+    common::serve(create_app(app_state))
+        .await
+        .replace("http://", "ws://")
+}
+
+#[tokio::test]
+async fn test_ws_connection_limit() {
+    let Some(database_url) = common::database_url_or_skip() else {
+        return;
+    };
+
+    let pool = setup_db(&database_url).await;
+    let connections = WsConnections::new(2);
+    let base_url = spawn_app(&database_url, pool, connections.clone()).await;
+    let ws_url = format!("{}/ws", base_url);
+
+    let (first, _) = tokio_tungstenite::connect_async(&ws_url).await.unwrap();
+    let (_second, _) = tokio_tungstenite::connect_async(&ws_url).await.unwrap();
+    assert_eq!(connections.active(), 2);
+
+    match tokio_tungstenite::connect_async(&ws_url).await {
+        Err(WsError::Http(response)) => {
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE)
+        }
+        other => panic!("expected 503, got {:?}", other.map(|(_, r)| r.status())),
+    }
+
+    // Closing a connection frees its slot
+    drop(first);
+    for _ in 0..50 {
+        if connections.active() < 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(connections.active(), 1);
+    assert!(tokio_tungstenite::connect_async(&ws_url).await.is_ok());
+}